@@ -1,12 +1,17 @@
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use axerrno::AxResult;
 use axfs::fops::{Directory, File};
 
 pub use axfs::fops::DirEntry as AxDirEntry;
 pub use axfs::fops::FileAttr as AxFileAttr;
+pub use axfs::fops::FileLock as AxFileLock;
+pub use axfs::fops::FileLocker as AxFileLocker;
 pub use axfs::fops::FilePerm as AxFilePerm;
+pub use axfs::fops::FileSystemInfo as AxFileSystemInfo;
 pub use axfs::fops::FileType as AxFileType;
+pub use axfs::fops::LockKind as AxLockKind;
 pub use axfs::fops::OpenOptions as AxOpenOptions;
+pub use axfs::fops::SetAttr as AxSetAttr;
 pub use axio::SeekFrom as AxSeekFrom;
 
 #[cfg(feature = "myfs")]
@@ -58,6 +63,32 @@ pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr> {
     file.0.get_attr()
 }
 
+pub fn ax_set_file_attr(file: &AxFileHandle, changes: &AxSetAttr) -> AxResult {
+    file.0.set_attr(changes)
+}
+
+pub fn ax_file_locker(file: &AxFileHandle) -> AxFileLocker {
+    file.0.locker()
+}
+
+pub fn ax_flock_file(locker: &AxFileLocker, kind: Option<AxLockKind>, wait: bool) -> AxResult {
+    locker.flock(kind, wait)
+}
+
+pub fn ax_set_file_lock(
+    locker: &AxFileLocker,
+    kind: Option<AxLockKind>,
+    start: u64,
+    len: u64,
+    wait: bool,
+) -> AxResult {
+    locker.set_lock(kind, start, len, wait)
+}
+
+pub fn ax_get_file_lock(locker: &AxFileLocker, lock: &AxFileLock) -> AxResult<Option<AxFileLock>> {
+    locker.get_lock(lock)
+}
+
 pub fn ax_read_dir(dir: &mut AxDirHandle, dirents: &mut [AxDirEntry]) -> AxResult<usize> {
     dir.0.read_dir(dirents)
 }
@@ -78,6 +109,50 @@ pub fn ax_rename(old: &str, new: &str) -> AxResult {
     axfs::api::rename(old, new)
 }
 
+pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr> {
+    axfs::api::symlink_metadata(path).map(|m| *m.raw_metadata())
+}
+
+pub fn ax_set_attr(path: &str, changes: &AxSetAttr) -> AxResult {
+    axfs::api::set_attr(path, changes)
+}
+
+pub fn ax_set_symlink_attr(path: &str, changes: &AxSetAttr) -> AxResult {
+    axfs::api::set_symlink_attr(path, changes)
+}
+
+pub fn ax_read_link(path: &str) -> AxResult<String> {
+    axfs::api::read_link(path)
+}
+
+pub fn ax_symlink(original: &str, link: &str) -> AxResult {
+    axfs::api::symlink(original, link)
+}
+
+pub fn ax_hard_link(original: &str, link: &str) -> AxResult {
+    axfs::api::hard_link(original, link)
+}
+
+pub fn ax_statfs(path: &str) -> AxResult<AxFileSystemInfo> {
+    axfs::api::statfs(path)
+}
+
+pub fn ax_mount_points() -> Vec<String> {
+    axfs::api::mount_points()
+}
+
+pub fn ax_mount(source: &str, target: &str, fs_type: &str) -> AxResult {
+    axfs::api::mount(source, target, fs_type)
+}
+
+pub fn ax_bind_mount(source: &str, target: &str) -> AxResult {
+    axfs::api::bind_mount(source, target)
+}
+
+pub fn ax_umount(target: &str) -> AxResult {
+    axfs::api::umount(target)
+}
+
 pub fn ax_current_dir() -> AxResult<String> {
     axfs::api::current_dir()
 }
@@ -3,6 +3,9 @@ use axerrno::AxResult;
 use axnet::{UdpSocket, TcpSocket};
 use core::net::{IpAddr, SocketAddr};
 
+pub use axnet::NetIfaceInfo as AxNetIfaceInfo;
+pub use axnet::RouteEntry as AxRouteEntry;
+
 /// A handle to a TCP socket.
 pub struct AxTcpSocketHandle(TcpSocket);
 
@@ -129,3 +132,27 @@ pub fn ax_poll_interfaces() -> AxResult {
     axnet::poll_interfaces();
     Ok(())
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// Network interfaces
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn ax_net_interfaces() -> alloc::vec::Vec<AxNetIfaceInfo> {
+    axnet::interfaces()
+}
+
+pub fn ax_net_set_addrs(name: &str, addrs: &[(IpAddr, u8)]) -> AxResult {
+    axnet::set_interface_addrs(name, addrs)
+}
+
+pub fn ax_net_routes() -> alloc::vec::Vec<AxRouteEntry> {
+    axnet::routes()
+}
+
+pub fn ax_net_add_route(dest: IpAddr, prefix_len: u8, gateway: IpAddr, iface: &str) -> AxResult {
+    axnet::add_route(dest, prefix_len, gateway, iface)
+}
+
+pub fn ax_net_del_route(dest: IpAddr, prefix_len: u8) -> AxResult {
+    axnet::del_route(dest, prefix_len)
+}
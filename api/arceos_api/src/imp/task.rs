@@ -39,6 +39,12 @@ cfg_task! {
         }
     }
 
+    /// A snapshot of the statistics of a task.
+    pub type AxTaskStats = axtask::TaskStats;
+
+    /// The scheduling policy of a real-time task.
+    pub type AxSchedPolicy = axtask::RTPolicy;
+
     /// A handle to a wait queue.
     ///
     /// A wait queue is used to store sleeping tasks waiting for a certain event
@@ -82,6 +88,21 @@ cfg_task! {
         }
     }
 
+    pub fn ax_task_stats() -> alloc::vec::Vec<AxTaskStats> {
+        axtask::tasks().map(|t| t.stats()).collect()
+    }
+
+    pub fn ax_set_current_policy(policy: AxSchedPolicy) -> crate::AxResult {
+        if axtask::set_current_policy(policy) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                Unsupported,
+                "ax_set_current_policy: not supported by the scheduler"
+            )
+        }
+    }
+
     pub fn ax_wait_queue_wait(
         wq: &AxWaitQueueHandle,
         until_condition: impl Fn() -> bool,
@@ -30,6 +30,16 @@ pub mod config {
     pub use axconfig::*;
 }
 
+/// Synchronization primitives.
+///
+/// They block the current task if the `multitask` feature is enabled,
+/// otherwise they spin.
+pub mod sync {
+    pub use axsync::{Barrier, BarrierWaitResult, Condvar, WaitTimeoutResult};
+    pub use axsync::{Mutex, MutexGuard, Semaphore, SemaphoreGuard};
+    pub use axsync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+}
+
 /// System operations.
 pub mod sys {
     define_api! {
@@ -86,6 +96,8 @@ pub mod task {
         @cfg "multitask";
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
+        pub type AxTaskStats;
+        pub type AxSchedPolicy;
     }
 
     define_api! {
@@ -120,6 +132,11 @@ pub mod task {
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Returns the statistics of all alive tasks, in order of their IDs.
+        pub fn ax_task_stats() -> alloc::vec::Vec<AxTaskStats>;
+        /// Sets the scheduling policy of the current task, only supported by
+        /// the real-time scheduler.
+        pub fn ax_set_current_policy(policy: AxSchedPolicy) -> crate::AxResult;
 
         /// Blocks the current task and put it into the wait queue, until the
         /// given condition becomes true, or the the given duration has elapsed
@@ -149,8 +166,13 @@ pub mod fs {
         pub type AxFileAttr;
         pub type AxFileType;
         pub type AxFilePerm;
+        pub type AxSetAttr;
+        pub type AxFileSystemInfo;
         pub type AxDirEntry;
         pub type AxSeekFrom;
+        pub type AxFileLock;
+        pub type AxFileLocker;
+        pub type AxLockKind;
         #[cfg(feature = "myfs")]
         pub type AxDisk;
         #[cfg(feature = "myfs")]
@@ -195,6 +217,23 @@ pub mod fs {
         pub fn ax_seek_file(file: &mut AxFileHandle, pos: AxSeekFrom) -> AxResult<u64>;
         /// Returns attributes of the file.
         pub fn ax_file_attr(file: &AxFileHandle) -> AxResult<AxFileAttr>;
+        /// Changes the permission mode, owner or timestamps of the file.
+        pub fn ax_set_file_attr(file: &AxFileHandle, changes: &AxSetAttr) -> AxResult;
+        /// Returns a handle to set the advisory locks of the file, which
+        /// doesn't borrow it, so the file can be used while waiting for a
+        /// lock.
+        pub fn ax_file_locker(file: &AxFileHandle) -> AxFileLocker;
+        /// Sets a whole-file advisory lock like `flock`, or releases it if
+        /// `kind` is `None`. If the file is locked by others incompatibly,
+        /// waits for them to unlock if `wait` is `true`.
+        pub fn ax_flock_file(locker: &AxFileLocker, kind: Option<AxLockKind>, wait: bool) -> AxResult;
+        /// Sets a byte-range advisory lock on `len` bytes from `start` (`0`
+        /// for up to the end of the file), or releases the range if `kind`
+        /// is `None`. It waits like [`ax_flock_file`].
+        pub fn ax_set_file_lock(locker: &AxFileLocker, kind: Option<AxLockKind>, start: u64, len: u64, wait: bool) -> AxResult;
+        /// Returns the first byte-range lock of other files that prevents
+        /// setting `lock`, or `None` if it can be set.
+        pub fn ax_get_file_lock(locker: &AxFileLocker, lock: &AxFileLock) -> AxResult<Option<AxFileLock>>;
 
         /// Reads directory entries starts from the current position into the
         /// given buffer, returns the number of entries read.
@@ -215,6 +254,35 @@ pub mod fs {
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
 
+        /// Returns attributes of the file at the given path, without following
+        /// the symbolic link at the last component.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
+        /// Changes the permission mode, owner or timestamps of the file at the
+        /// given path.
+        pub fn ax_set_attr(path: &str, changes: &AxSetAttr) -> AxResult;
+        /// Like [`ax_set_attr`], but does not follow the symbolic link at the
+        /// last component.
+        pub fn ax_set_symlink_attr(path: &str, changes: &AxSetAttr) -> AxResult;
+        /// Reads the target of the symbolic link at the given path.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Creates a symbolic link at `link` which points to `original`.
+        pub fn ax_symlink(original: &str, link: &str) -> AxResult;
+        /// Creates a hard link at `link` which refers to the file `original`.
+        ///
+        /// Both paths must be in the same mounted filesystem.
+        pub fn ax_hard_link(original: &str, link: &str) -> AxResult;
+
+        /// Returns the attributes of the filesystem containing the given path.
+        pub fn ax_statfs(path: &str) -> AxResult<AxFileSystemInfo>;
+        /// Returns the paths of all mount points, starting with the root `/`.
+        pub fn ax_mount_points() -> alloc::vec::Vec<alloc::string::String>;
+        /// Mounts a new filesystem of `fs_type` on the directory `target`.
+        pub fn ax_mount(source: &str, target: &str, fs_type: &str) -> AxResult;
+        /// Mounts the directory `source` on the directory `target` as well.
+        pub fn ax_bind_mount(source: &str, target: &str) -> AxResult;
+        /// Unmounts the filesystem mounted on the directory `target`.
+        pub fn ax_umount(target: &str) -> AxResult;
+
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
@@ -231,6 +299,8 @@ pub mod net {
         @cfg "net";
         pub type AxTcpSocketHandle;
         pub type AxUdpSocketHandle;
+        pub type AxNetIfaceInfo;
+        pub type AxRouteEntry;
     }
 
     define_api! {
@@ -314,6 +384,21 @@ pub mod net {
         /// It may receive packets from the NIC and process them, and transmit queued
         /// packets to the NIC.
         pub fn ax_poll_interfaces() -> AxResult;
+
+        // Network interfaces
+
+        /// Returns the configuration of all network interfaces.
+        pub fn ax_net_interfaces() -> alloc::vec::Vec<AxNetIfaceInfo>;
+        /// Replaces the IP addresses of the network interface `name`, given
+        /// with the prefix lengths of their networks.
+        pub fn ax_net_set_addrs(name: &str, addrs: &[(IpAddr, u8)]) -> AxResult;
+        /// Returns the routing table, including the directly connected networks.
+        pub fn ax_net_routes() -> alloc::vec::Vec<AxRouteEntry>;
+        /// Adds a route to the network `dest`/`prefix_len` via `gateway` on the
+        /// network interface `iface`. The prefix length 0 makes a default route.
+        pub fn ax_net_add_route(dest: IpAddr, prefix_len: u8, gateway: IpAddr, iface: &str) -> AxResult;
+        /// Removes the route to the network `dest`/`prefix_len`.
+        pub fn ax_net_del_route(dest: IpAddr, prefix_len: u8) -> AxResult;
     }
 }
 
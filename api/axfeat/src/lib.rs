@@ -18,6 +18,9 @@
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `sched_rt`: Use the fixed-priority real-time preemptive scheduler.
+//!     - `sched_edf`: Use the Earliest Deadline First (EDF) preemptive scheduler.
+//!     - `stack_stats`: Estimate the maximum stack usage of each task.
 //! - Upperlayer stacks (fs, net, display)
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
@@ -1,9 +1,10 @@
 use std::fs::{self, File, FileType};
 use std::io::{self, prelude::*};
+use std::time::Duration;
 use std::{string::String, vec::Vec};
 
 #[cfg(all(not(feature = "axstd"), unix))]
-use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
 
 macro_rules! print_err {
     ($cmd: literal, $msg: expr) => {
@@ -19,13 +20,16 @@ type CmdHandler = fn(&str);
 const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("cat", do_cat),
     ("cd", do_cd),
+    ("df", do_df),
     ("echo", do_echo),
     ("exit", do_exit),
     ("help", do_help),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    ("mount", do_mount),
     ("pwd", do_pwd),
     ("rm", do_rm),
+    ("umount", do_umount),
     ("uname", do_uname),
 ];
 
@@ -66,6 +70,37 @@ const fn file_perm_to_rwx(mode: u32) -> [u8; 9] {
     perm
 }
 
+#[cfg(feature = "axstd")]
+fn file_mtime(metadata: &fs::Metadata) -> Duration {
+    metadata.modified()
+}
+
+#[cfg(not(feature = "axstd"))]
+fn file_mtime(metadata: &fs::Metadata) -> Duration {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .unwrap_or_default()
+}
+
+/// Splits seconds since the epoch into `(year, month, day, hour, minute)`.
+fn split_time(time: Duration) -> (u64, u64, u64, u64, u64) {
+    let secs = time.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month, day, rem / 3600, rem % 3600 / 60)
+}
+
 fn do_ls(args: &str) {
     let current_dir = std::env::current_dir().unwrap();
     let args = if args.is_empty() {
@@ -82,7 +117,22 @@ fn do_ls(args: &str) {
         let file_type_char = file_type_to_char(file_type);
         let rwx = file_perm_to_rwx(metadata.permissions().mode());
         let rwx = unsafe { core::str::from_utf8_unchecked(&rwx) };
-        println!("{}{} {:>8} {}", file_type_char, rwx, size, entry);
+        let (year, month, day, hour, min) = split_time(file_mtime(&metadata));
+        println!(
+            "{}{} {:>2} {:>4} {:>4} {:>8} {:04}-{:02}-{:02} {:02}:{:02} {}",
+            file_type_char,
+            rwx,
+            metadata.nlink(),
+            metadata.uid(),
+            metadata.gid(),
+            size,
+            year,
+            month,
+            day,
+            hour,
+            min,
+            entry
+        );
         Ok(())
     }
 
@@ -243,6 +293,111 @@ fn do_pwd(_args: &str) {
     println!("{}", path_to_str!(pwd));
 }
 
+#[cfg(feature = "axstd")]
+fn do_df(args: &str) {
+    let paths = if args.is_empty() {
+        fs::mount_points()
+    } else {
+        args.split_whitespace().map(String::from).collect()
+    };
+
+    println!(
+        "{:>10} {:>10} {:>10} {:>4}  Mounted on",
+        "1K-blocks", "Used", "Available", "Use%"
+    );
+    for path in paths {
+        match fs::statfs(&path) {
+            Ok(info) => {
+                let kb = |blocks: u64| blocks * info.block_size / 1024;
+                let used = info.blocks.saturating_sub(info.blocks_free);
+                print!(
+                    "{:>10} {:>10} {:>10} ",
+                    kb(info.blocks),
+                    kb(used),
+                    kb(info.blocks_avail)
+                );
+                if info.blocks == 0 {
+                    print!("{:>4}", "-");
+                } else {
+                    // round up like df does
+                    print!(
+                        "{:>3}%",
+                        (used * 100).div_ceil((used + info.blocks_avail).max(1))
+                    );
+                }
+                println!("  {}", path);
+            }
+            Err(e) => print_err!("df", path, e),
+        }
+    }
+}
+
+#[cfg(not(feature = "axstd"))]
+fn do_df(_args: &str) {
+    print_err!("df", "not supported on this platform");
+}
+
+#[cfg(feature = "axstd")]
+fn do_mount(args: &str) {
+    let mut fs_type = None;
+    let mut bind = false;
+    let mut operands = Vec::new();
+    let mut iter = args.split_whitespace();
+    while let Some(arg) = iter.next() {
+        match arg {
+            "-t" => fs_type = iter.next(),
+            "-B" | "--bind" => bind = true,
+            _ => operands.push(arg),
+        }
+    }
+
+    let res = match (operands.as_slice(), fs_type) {
+        ([], None) if !bind => {
+            match fs::read_to_string("/proc/mounts") {
+                Ok(mounts) => print!("{}", mounts),
+                // no procfs, list the mount points only
+                Err(_) => fs::mount_points().iter().for_each(|p| println!("{}", p)),
+            }
+            return;
+        }
+        ([source, target], None) if bind => fs::bind_mount(source, target),
+        ([source, target], Some(fs_type)) if !bind => fs::mount(source, target, fs_type),
+        _ => {
+            print_err!(
+                "mount",
+                "usage: mount [-t TYPE SOURCE DIR | --bind OLDDIR DIR]"
+            );
+            return;
+        }
+    };
+    if let Err(e) = res {
+        print_err!("mount", operands[1], e);
+    }
+}
+
+#[cfg(not(feature = "axstd"))]
+fn do_mount(_args: &str) {
+    print_err!("mount", "not supported on this platform");
+}
+
+#[cfg(feature = "axstd")]
+fn do_umount(args: &str) {
+    if args.is_empty() {
+        print_err!("umount", "missing operand");
+        return;
+    }
+    for target in args.split_whitespace() {
+        if let Err(e) = fs::umount(target) {
+            print_err!("umount", target, e);
+        }
+    }
+}
+
+#[cfg(not(feature = "axstd"))]
+fn do_umount(_args: &str) {
+    print_err!("umount", "not supported on this platform");
+}
+
 fn do_uname(_args: &str) {
     let arch = option_env!("AX_ARCH").unwrap_or("");
     let platform = option_env!("AX_PLATFORM").unwrap_or("");
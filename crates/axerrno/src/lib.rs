@@ -41,8 +41,16 @@ pub enum AxError {
     ConnectionRefused,
     /// The connection was reset by the remote server.
     ConnectionReset,
+    /// Cross-device or cross-filesystem (hard) link or rename.
+    CrossesDevices,
+    /// Waiting for a resource would never end, e.g., for a file lock held by
+    /// an owner which is waiting for the current one.
+    Deadlock,
     /// A non-empty directory was specified where an empty directory was expected.
     DirectoryNotEmpty,
+    /// Loop in the filesystem or IO subsystem; often, too many levels of
+    /// symbolic links.
+    FilesystemLoop,
     /// Data not valid for the operation were encountered.
     ///
     /// Unlike [`InvalidInput`], this typically means that the operation
@@ -60,6 +68,8 @@ pub enum AxError {
     Io,
     /// The filesystem object is, unexpectedly, a directory.
     IsADirectory,
+    /// The network containing the remote host is not reachable.
+    NetworkUnreachable,
     /// Not enough space/cannot allocate memory.
     NoMemory,
     /// A filesystem object is, unexpectedly, not a directory.
@@ -194,11 +204,15 @@ impl AxError {
             AlreadyExists => "Entity already exists",
             ConnectionRefused => "Connection refused",
             ConnectionReset => "Connection reset",
+            CrossesDevices => "Cross-device link or rename",
+            Deadlock => "Resource deadlock would occur",
             DirectoryNotEmpty => "Directory not empty",
+            FilesystemLoop => "Filesystem loop or indirection limit",
             InvalidData => "Invalid data",
             InvalidInput => "Invalid input parameter",
             Io => "I/O error",
             IsADirectory => "Is a directory",
+            NetworkUnreachable => "Network unreachable",
             NoMemory => "Out of memory",
             NotADirectory => "Not a directory",
             NotConnected => "Not connected",
@@ -247,10 +261,14 @@ impl From<AxError> for LinuxError {
             BadAddress | BadState => LinuxError::EFAULT,
             ConnectionRefused => LinuxError::ECONNREFUSED,
             ConnectionReset => LinuxError::ECONNRESET,
+            CrossesDevices => LinuxError::EXDEV,
+            Deadlock => LinuxError::EDEADLK,
             DirectoryNotEmpty => LinuxError::ENOTEMPTY,
+            FilesystemLoop => LinuxError::ELOOP,
             InvalidInput | InvalidData => LinuxError::EINVAL,
             Io => LinuxError::EIO,
             IsADirectory => LinuxError::EISDIR,
+            NetworkUnreachable => LinuxError::ENETUNREACH,
             NoMemory => LinuxError::ENOMEM,
             NotADirectory => LinuxError::ENOTDIR,
             NotConnected => LinuxError::ENOTCONN,
@@ -283,7 +301,7 @@ mod tests {
     #[test]
     fn test_try_from() {
         let max_code = core::mem::variant_count::<AxError>() as i32;
-        assert_eq!(max_code, 22);
+        assert_eq!(max_code, 26);
         assert_eq!(max_code, AxError::WriteZero.code());
 
         assert_eq!(AxError::AddrInUse.code(), 1);
@@ -0,0 +1,119 @@
+use alloc::vec;
+use axfs_vfs::VfsSetAttr;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use driver_block::{BlockDriverOps, DevError};
+use spin::{Mutex, RwLock};
+
+/// A block device node, such as `/dev/vda` or `/dev/vda1`.
+///
+/// It reads and writes the raw data of the device at any offset and of any
+/// length. The partial blocks at both ends are read, modified, and written
+/// back. Writing beyond the end of the device fails with
+/// [`VfsError::StorageFull`].
+pub struct BlockDev<D> {
+    attr: RwLock<VfsNodeAttr>,
+    dev: Mutex<D>,
+}
+
+impl<D: BlockDriverOps> BlockDev<D> {
+    /// Creates a new `BlockDev` on the block device `dev`.
+    pub fn new(dev: D) -> Self {
+        let size = dev.num_blocks() * dev.block_size() as u64;
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o660),
+                VfsNodeType::BlockDevice,
+                size,
+                0,
+            ))),
+            dev: Mutex::new(dev),
+        }
+    }
+}
+
+const fn as_vfs_err(err: DevError) -> VfsError {
+    match err {
+        DevError::AlreadyExists => VfsError::AlreadyExists,
+        DevError::Again => VfsError::WouldBlock,
+        DevError::InvalidParam => VfsError::InvalidInput,
+        DevError::NoMemory => VfsError::NoMemory,
+        DevError::ResourceBusy => VfsError::ResourceBusy,
+        DevError::Unsupported => VfsError::Unsupported,
+        DevError::BadState | DevError::Io => VfsError::Io,
+    }
+}
+
+impl<D: BlockDriverOps + 'static> VfsNodeOps for BlockDev<D> {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let size = dev.num_blocks() * block_size as u64;
+        let len = buf.len().min(size.saturating_sub(offset) as usize);
+        let mut block = vec![0; block_size];
+        let mut pos = 0;
+        while pos < len {
+            let block_id = (offset + pos as u64) / block_size as u64;
+            let start = ((offset + pos as u64) % block_size as u64) as usize;
+            let count = (len - pos).min(block_size - start);
+            if count == block_size {
+                // whole block
+                dev.read_block(block_id, &mut buf[pos..pos + count])
+                    .map_err(as_vfs_err)?;
+            } else {
+                // partial block
+                dev.read_block(block_id, &mut block).map_err(as_vfs_err)?;
+                buf[pos..pos + count].copy_from_slice(&block[start..start + count]);
+            }
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let size = dev.num_blocks() * block_size as u64;
+        if !buf.is_empty() && offset >= size {
+            return Err(VfsError::StorageFull);
+        }
+        let len = buf.len().min(size.saturating_sub(offset) as usize);
+        let mut block = vec![0; block_size];
+        let mut pos = 0;
+        while pos < len {
+            let block_id = (offset + pos as u64) / block_size as u64;
+            let start = ((offset + pos as u64) % block_size as u64) as usize;
+            let count = (len - pos).min(block_size - start);
+            if count == block_size {
+                // whole block
+                dev.write_block(block_id, &buf[pos..pos + count])
+                    .map_err(as_vfs_err)?;
+            } else {
+                // partial block
+                dev.read_block(block_id, &mut block).map_err(as_vfs_err)?;
+                block[start..start + count].copy_from_slice(&buf[pos..pos + count]);
+                dev.write_block(block_id, &block).map_err(as_vfs_err)?;
+            }
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.dev.lock().flush().map_err(as_vfs_err)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
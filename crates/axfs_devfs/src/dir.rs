@@ -1,6 +1,6 @@
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsSetAttr};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
@@ -10,6 +10,7 @@ use spin::RwLock;
 pub struct DirNode {
     parent: RwLock<Weak<dyn VfsNodeOps>>,
     children: RwLock<BTreeMap<&'static str, VfsNodeRef>>,
+    attr: RwLock<VfsNodeAttr>,
 }
 
 impl DirNode {
@@ -18,6 +19,7 @@ impl DirNode {
         Arc::new(Self {
             parent: RwLock::new(parent),
             children: RwLock::new(BTreeMap::new()),
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new_dir(4096, 0))),
         })
     }
 
@@ -37,11 +39,37 @@ impl DirNode {
     pub fn add(&self, name: &'static str, node: VfsNodeRef) {
         self.children.write().insert(name, node);
     }
+
+    /// Returns the number of nodes in this subtree, including this directory.
+    pub(crate) fn node_count(&self) -> u64 {
+        1 + self
+            .children
+            .read()
+            .values()
+            .map(|node| match node.as_any().downcast_ref::<DirNode>() {
+                Some(dir) => dir.node_count(),
+                None => 1,
+            })
+            .sum::<u64>()
+    }
 }
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        let subdirs = self
+            .children
+            .read()
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>())
+            .count();
+        let mut attr = *self.attr.read();
+        attr.set_nlink(2 + subdirs as u64);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
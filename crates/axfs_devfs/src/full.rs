@@ -0,0 +1,61 @@
+use axfs_vfs::{
+    VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr,
+};
+use spin::RwLock;
+
+/// A full device behaves like `/dev/full`.
+///
+/// It always returns a chunk of `\0` bytes when read, and all writes fail
+/// with [`VfsError::StorageFull`].
+pub struct FullDev {
+    attr: RwLock<VfsNodeAttr>,
+}
+
+impl FullDev {
+    /// Creates a new `FullDev`.
+    pub fn new() -> Self {
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::default_file(),
+                VfsNodeType::CharDevice,
+                0,
+                0,
+            ))),
+        }
+    }
+}
+
+impl Default for FullDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsNodeOps for FullDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
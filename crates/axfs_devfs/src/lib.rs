@@ -6,21 +6,43 @@
 
 extern crate alloc;
 
+mod block;
 mod dir;
+mod full;
 mod null;
+mod random;
+mod tty;
 mod zero;
 
 #[cfg(test)]
 mod tests;
 
+pub use self::block::BlockDev;
 pub use self::dir::DirNode;
+pub use self::full::FullDev;
 pub use self::null::NullDev;
+pub use self::random::RandomDev;
+pub use self::tty::TtyDev;
 pub use self::zero::ZeroDev;
 
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use axfs_vfs::{FileSystemInfo, VfsNodeAttr, VfsNodeRef, VfsOps, VfsResult};
+use core::sync::atomic::{AtomicU64, Ordering};
 use spin::once::Once;
 
+/// The filesystem type magic number of devfs, the same as Linux's
+/// `TMPFS_MAGIC` reported by `devtmpfs`.
+pub const DEVFS_MAGIC: u64 = 0x0102_1994;
+
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+/// Assigns a new inode number to `attr` and stamps it with the current time.
+fn new_attr(mut attr: VfsNodeAttr) -> VfsNodeAttr {
+    attr.set_ino(NEXT_INO.fetch_add(1, Ordering::Relaxed));
+    attr.touch();
+    attr
+}
+
 /// A device filesystem that implements [`axfs_vfs::VfsOps`].
 pub struct DeviceFileSystem {
     parent: Once<VfsNodeRef>,
@@ -59,6 +81,18 @@ impl VfsOps for DeviceFileSystem {
         Ok(())
     }
 
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        // Device nodes do not occupy any storage, and no more nodes can be
+        // created at runtime.
+        Ok(FileSystemInfo {
+            fs_type: DEVFS_MAGIC,
+            block_size: 4096,
+            files: self.root.node_count(),
+            name_len: 255,
+            ..Default::default()
+        })
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
         self.root.clone()
     }
@@ -1,18 +1,41 @@
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+use spin::RwLock;
 
 /// A null device behaves like `/dev/null`.
 ///
 /// Nothing can be read and all writes are discarded.
-pub struct NullDev;
+pub struct NullDev {
+    attr: RwLock<VfsNodeAttr>,
+}
+
+impl NullDev {
+    /// Creates a new `NullDev`.
+    pub fn new() -> Self {
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::default_file(),
+                VfsNodeType::CharDevice,
+                0,
+                0,
+            ))),
+        }
+    }
+}
+
+impl Default for NullDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl VfsNodeOps for NullDev {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::default_file(),
-            VfsNodeType::CharDevice,
-            0,
-            0,
-        ))
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
@@ -0,0 +1,85 @@
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+use spin::{Mutex, RwLock};
+
+/// The seed used by [`RandomDev::default`].
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// A random device behaves like `/dev/random` and `/dev/urandom`.
+///
+/// It returns pseudo-random bytes generated by xorshift64* when read, and
+/// the written bytes are mixed into its state. It is **not** suitable for
+/// cryptographic use.
+pub struct RandomDev {
+    attr: RwLock<VfsNodeAttr>,
+    state: Mutex<u64>,
+}
+
+impl RandomDev {
+    /// Creates a new `RandomDev` seeded by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::default_file(),
+                VfsNodeType::CharDevice,
+                0,
+                0,
+            ))),
+            // the state of xorshift must not be zero
+            state: Mutex::new(if seed == 0 { DEFAULT_SEED } else { seed }),
+        }
+    }
+}
+
+impl Default for RandomDev {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+/// Advances the xorshift64* generator and returns the next number.
+fn next(state: &mut u64) -> u64 {
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut state = self.state.lock();
+        for chunk in buf.chunks_mut(8) {
+            let bytes = next(&mut state).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut state = self.state.lock();
+        for chunk in buf.chunks(8) {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            let mixed = *state ^ u64::from_le_bytes(bytes);
+            *state = if mixed == 0 { DEFAULT_SEED } else { mixed };
+            next(&mut state);
+        }
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
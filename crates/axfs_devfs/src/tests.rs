@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+
+use driver_block::ramdisk::RamDisk;
 
 use crate::*;
 
@@ -91,6 +93,28 @@ fn test_get_parent(devfs: &DeviceFileSystem) -> VfsResult {
     Ok(())
 }
 
+fn test_devfs_attrs(devfs: &DeviceFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    assert_eq!(root.get_attr()?.nlink(), 3);
+
+    let null = root.clone().lookup("null")?;
+    let zero = root.lookup("zero")?;
+    assert_ne!(null.get_attr()?.ino(), zero.get_attr()?.ino());
+
+    null.set_attr(&VfsSetAttr {
+        mode: Some(VfsNodePerm::from_bits_truncate(0o600)),
+        uid: Some(1000),
+        ..Default::default()
+    })?;
+    let attr = null.get_attr()?;
+    assert_eq!(attr.file_type(), VfsNodeType::CharDevice);
+    assert_eq!(attr.perm().mode(), 0o600);
+    assert_eq!((attr.uid(), attr.gid()), (1000, 0));
+    assert_eq!(zero.get_attr()?.perm().mode(), 0o666);
+
+    Ok(())
+}
+
 #[test]
 fn test_devfs() {
     // .
@@ -102,14 +126,88 @@ fn test_devfs() {
     // └── zero
 
     let devfs = DeviceFileSystem::new();
-    devfs.add("null", Arc::new(NullDev));
-    devfs.add("zero", Arc::new(ZeroDev));
+    devfs.add("null", Arc::new(NullDev::new()));
+    devfs.add("zero", Arc::new(ZeroDev::new()));
 
     let dir_foo = devfs.mkdir("foo");
-    dir_foo.add("f2", Arc::new(ZeroDev));
+    dir_foo.add("f2", Arc::new(ZeroDev::new()));
     let dir_bar = dir_foo.mkdir("bar");
-    dir_bar.add("f1", Arc::new(NullDev));
+    dir_bar.add("f1", Arc::new(NullDev::new()));
 
     test_devfs_ops(&devfs).unwrap();
     test_get_parent(&devfs).unwrap();
+    test_devfs_attrs(&devfs).unwrap();
+
+    let info = devfs.statfs().unwrap();
+    assert_eq!(info.fs_type, DEVFS_MAGIC);
+    assert_eq!(info.files, 7);
+    assert_eq!((info.blocks, info.files_free), (0, 0));
+}
+
+#[test]
+fn test_devices() {
+    let mut buf = [1u8; 600];
+
+    let full = FullDev::new();
+    assert_eq!(full.read_at(0, &mut buf).unwrap(), 600);
+    assert_eq!(buf, [0; 600]);
+    assert_eq!(full.write_at(0, &buf).err(), Some(VfsError::StorageFull));
+    assert_eq!(full.write_at(0, &[]).unwrap(), 0);
+
+    let random = RandomDev::new(42);
+    let mut other = [0u8; 600];
+    assert_eq!(random.read_at(0, &mut buf[..13]).unwrap(), 13);
+    assert_eq!(random.read_at(0, &mut other[..13]).unwrap(), 13);
+    assert_ne!(buf[..13], other[..13]);
+    assert_eq!(random.write_at(0, b"entropy").unwrap(), 7);
+    assert_ne!(RandomDev::new(42).get_attr().unwrap().ino(), 0);
+
+    static OUTPUT: spin::Mutex<Vec<u8>> = spin::Mutex::new(Vec::new());
+    let tty = TtyDev::new(
+        |buf| {
+            buf[0] = b'y';
+            1
+        },
+        |buf| OUTPUT.lock().extend_from_slice(buf),
+    );
+    assert_eq!(tty.get_attr().unwrap().file_type(), VfsNodeType::CharDevice);
+    assert_eq!(tty.read_at(0, &mut buf).unwrap(), 1);
+    assert_eq!(buf[0], b'y');
+    assert_eq!(tty.write_at(100, b"hello").unwrap(), 5);
+    assert_eq!(OUTPUT.lock().as_slice(), b"hello");
+}
+
+#[test]
+fn test_block_dev() {
+    const SIZE: usize = 4 * 512;
+    let dev = BlockDev::new(RamDisk::new(SIZE));
+    let attr = dev.get_attr().unwrap();
+    assert_eq!(attr.file_type(), VfsNodeType::BlockDevice);
+    assert_eq!(attr.size(), SIZE as u64);
+
+    // aligned
+    let data: Vec<u8> = (0..SIZE).map(|i| (i % 251) as u8).collect();
+    assert_eq!(dev.write_at(0, &data).unwrap(), SIZE);
+    let mut buf = vec![0; SIZE];
+    assert_eq!(dev.read_at(0, &mut buf).unwrap(), SIZE);
+    assert_eq!(buf, data);
+
+    // unaligned, across the blocks
+    assert_eq!(dev.write_at(500, &[0xff; 30]).unwrap(), 30);
+    let mut buf = [0; 40];
+    assert_eq!(dev.read_at(495, &mut buf).unwrap(), 40);
+    assert_eq!(buf[..5], data[495..500]);
+    assert_eq!(buf[5..35], [0xff; 30]);
+    assert_eq!(buf[35..], data[530..535]);
+
+    // beyond the end
+    assert_eq!(dev.read_at(SIZE as u64 - 10, &mut buf).unwrap(), 10);
+    assert_eq!(buf[..10], data[SIZE - 10..]);
+    assert_eq!(dev.read_at(SIZE as u64, &mut buf).unwrap(), 0);
+    assert_eq!(dev.write_at(SIZE as u64 - 10, &[7; 40]).unwrap(), 10);
+    assert_eq!(
+        dev.write_at(SIZE as u64, &[7; 40]).err(),
+        Some(VfsError::StorageFull)
+    );
+    dev.fsync().unwrap();
 }
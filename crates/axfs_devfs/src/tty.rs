@@ -0,0 +1,57 @@
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+use spin::RwLock;
+
+/// A terminal device behaves like `/dev/tty` or `/dev/ttyS0`.
+///
+/// The reads and writes are forwarded to the console functions given when
+/// it is created, regardless of the offset.
+pub struct TtyDev {
+    attr: RwLock<VfsNodeAttr>,
+    read: fn(&mut [u8]) -> usize,
+    write: fn(&[u8]),
+}
+
+impl TtyDev {
+    /// Creates a new `TtyDev`.
+    ///
+    /// `read` fills the buffer with the console input and returns the number
+    /// of bytes read, `write` writes all the bytes to the console.
+    pub fn new(read: fn(&mut [u8]) -> usize, write: fn(&[u8])) -> Self {
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o620),
+                VfsNodeType::CharDevice,
+                0,
+                0,
+            ))),
+            read,
+            write,
+        }
+    }
+}
+
+impl VfsNodeOps for TtyDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        Ok((self.read)(buf))
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        (self.write)(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
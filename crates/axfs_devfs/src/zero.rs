@@ -1,18 +1,41 @@
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+use spin::RwLock;
 
 /// A zero device behaves like `/dev/zero`.
 ///
 /// It always returns a chunk of `\0` bytes when read, and all writes are discarded.
-pub struct ZeroDev;
+pub struct ZeroDev {
+    attr: RwLock<VfsNodeAttr>,
+}
+
+impl ZeroDev {
+    /// Creates a new `ZeroDev`.
+    pub fn new() -> Self {
+        Self {
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new(
+                VfsNodePerm::default_file(),
+                VfsNodeType::CharDevice,
+                0,
+                0,
+            ))),
+        }
+    }
+}
+
+impl Default for ZeroDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl VfsNodeOps for ZeroDev {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::default_file(),
-            VfsNodeType::CharDevice,
-            0,
-            0,
-        ))
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
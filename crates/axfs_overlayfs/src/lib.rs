@@ -0,0 +1,408 @@
+//! Overlay filesystem used by [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! It merges two directory trees: a read-only **lower** layer, e.g., a FAT
+//! image or an initramfs, and a writable **upper** layer, e.g., a RAM
+//! filesystem. Lookups see the upper nodes first, and the directories of both
+//! layers are merged. The lower layer is never modified:
+//!
+//! - A lower file is **copied up** to the upper layer when it is first
+//!   written, truncated or its attributes are changed, with its parent
+//!   directories.
+//! - Deleting a lower node leaves a **whiteout** in the upper layer, an empty
+//!   file named `.wh.<name>` which hides the lower one.
+//! - A directory created in place of a deleted one is **opaque**, marked by an
+//!   empty file `.wh..wh..opq`, which hides the lower directory of the same
+//!   path.
+//!
+//! Names starting with `.wh.` are reserved and never listed. Directories only
+//! in the lower layer cannot be renamed, as with Linux's overlayfs without
+//! `redirect_dir`.
+//!
+//! The implementation is based on [`axfs_vfs`].
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod node;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::node::OverlayNode;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{FileSystemInfo, VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps};
+use axfs_vfs::{VfsResult, VfsSetAttr};
+use spin::{once::Once, Mutex};
+
+/// The filesystem type magic number of overlayfs, the same as Linux's
+/// `OVERLAYFS_SUPER_MAGIC`.
+pub const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+
+/// The prefix of the names of whiteouts.
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// The name of the marker of opaque directories.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// An overlay filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct OverlayFileSystem {
+    layers: Arc<Layers>,
+    root: Arc<OverlayNode>,
+}
+
+/// The two layers shared by the nodes of an overlay.
+pub(crate) struct Layers {
+    lower_fs: Arc<dyn VfsOps>,
+    upper_fs: Arc<dyn VfsOps>,
+    lower: VfsNodeRef,
+    upper: VfsNodeRef,
+    parent: Once<VfsNodeRef>,
+    /// Serializes copy-ups, so that a node is copied up only once.
+    copy_up_lock: Mutex<()>,
+}
+
+/// The nodes of both layers at a path, after the whiteouts and opaque
+/// directories are applied.
+#[derive(Clone)]
+pub(crate) struct Resolved {
+    pub upper: Option<VfsNodeRef>,
+    pub lower: Option<VfsNodeRef>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay of the read-only `lower` and the writable `upper`
+    /// filesystems.
+    pub fn new(lower: Arc<dyn VfsOps>, upper: Arc<dyn VfsOps>) -> Self {
+        let layers = Arc::new(Layers {
+            lower: lower.root_dir(),
+            upper: upper.root_dir(),
+            lower_fs: lower,
+            upper_fs: upper,
+            parent: Once::new(),
+            copy_up_lock: Mutex::new(()),
+        });
+        let resolved = Resolved {
+            upper: Some(layers.upper.clone()),
+            lower: Some(layers.lower.clone()),
+        };
+        Self {
+            root: OverlayNode::new(layers.clone(), String::new(), resolved),
+            layers,
+        }
+    }
+
+    /// Returns the root directory node in [`Arc<OverlayNode>`](OverlayNode).
+    pub fn root_dir_node(&self) -> Arc<OverlayNode> {
+        self.root.clone()
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            self.layers.parent.call_once(|| parent);
+        }
+        Ok(())
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.layers.upper_fs.umount()?;
+        self.layers.lower_fs.umount()
+    }
+
+    /// Reports the capacity of the upper layer, where the changes are stored.
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        Ok(FileSystemInfo {
+            fs_type: OVERLAYFS_SUPER_MAGIC,
+            ..self.layers.upper_fs.statfs()?
+        })
+    }
+
+    fn sync(&self) -> VfsResult {
+        self.layers.upper_fs.sync()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+/// Returns the child `name` of the directory `dir`, or `None` if it does not
+/// exist.
+fn child(dir: &VfsNodeRef, name: &str) -> VfsResult<Option<VfsNodeRef>> {
+    match dir.clone().lookup(name) {
+        Ok(node) => Ok(Some(node)),
+        Err(VfsError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_dir(node: &VfsNodeRef) -> VfsResult<bool> {
+    Ok(node.get_attr()?.is_dir())
+}
+
+/// Joins a path relative to the root of the overlay and a name.
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        alloc::format!("{}/{}", path, name)
+    }
+}
+
+/// Splits a path relative to the root of the overlay into the parent and the
+/// last component.
+fn split_parent(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some((parent, name)) => (parent, name),
+        None => ("", path),
+    }
+}
+
+fn whiteout_name(name: &str) -> String {
+    alloc::format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+/// Reads all entries of the directory `dir`, except `.` and `..`.
+fn read_entries(dir: &VfsNodeRef) -> VfsResult<Vec<(String, VfsNodeType)>> {
+    let mut dirents: [VfsDirEntry; 16] = core::array::from_fn(|_| VfsDirEntry::default());
+    let mut entries = Vec::new();
+    let mut start = 0;
+    loop {
+        let n = dir.read_dir(start, &mut dirents)?;
+        for ent in &dirents[..n] {
+            let name =
+                core::str::from_utf8(ent.name_as_bytes()).map_err(|_| VfsError::InvalidData)?;
+            if name != "." && name != ".." {
+                entries.push((name.into(), ent.entry_type()));
+            }
+        }
+        if n < dirents.len() {
+            return Ok(entries);
+        }
+        start += n;
+    }
+}
+
+impl Layers {
+    /// Resolves `path` relative to the root of the overlay, whose components
+    /// are all normal names.
+    pub fn resolve(&self, path: &str) -> VfsResult<Resolved> {
+        let mut upper = Some(self.upper.clone());
+        let mut lower = match self.is_opaque(&self.upper)? {
+            true => None,
+            false => Some(self.lower.clone()),
+        };
+        for name in path.split('/').filter(|c| !c.is_empty()) {
+            if name.starts_with(WHITEOUT_PREFIX) {
+                return Err(VfsError::NotFound);
+            }
+            let (upper_dir, lower_dir) = (upper.take(), lower.take());
+            let mut whiteout = false;
+            if let Some(dir) = &upper_dir {
+                if !is_dir(dir)? {
+                    return Err(VfsError::NotADirectory);
+                }
+                upper = child(dir, name)?;
+                whiteout = child(dir, &whiteout_name(name))?.is_some();
+            }
+            if let Some(dir) = lower_dir.filter(|_| !whiteout) {
+                if upper_dir.is_none() && !is_dir(&dir)? {
+                    return Err(VfsError::NotADirectory);
+                }
+                lower = child(&dir, name)?;
+            }
+            // an upper directory is merged with a lower directory only,
+            // unless it is opaque
+            if let Some(node) = &upper {
+                let merged = match &lower {
+                    Some(lower) => is_dir(node)? && is_dir(lower)? && !self.is_opaque(node)?,
+                    None => false,
+                };
+                if !merged {
+                    lower = None;
+                }
+            }
+            if upper.is_none() && lower.is_none() {
+                return Err(VfsError::NotFound);
+            }
+        }
+        Ok(Resolved { upper, lower })
+    }
+
+    /// Returns whether there is a lower node at `path`, visible or shadowed
+    /// by an upper one, which needs a whiteout to be deleted.
+    pub fn in_lower(&self, path: &str) -> VfsResult<bool> {
+        let (parent, name) = split_parent(path);
+        match self.resolve(parent)?.lower {
+            Some(dir) => Ok(child(&dir, name)?.is_some()),
+            None => Ok(false),
+        }
+    }
+
+    fn is_opaque(&self, upper_dir: &VfsNodeRef) -> VfsResult<bool> {
+        Ok(child(upper_dir, OPAQUE_MARKER)?.is_some())
+    }
+
+    /// Returns the upper node at `path` if it exists.
+    pub fn upper_node(&self, path: &str) -> VfsResult<Option<VfsNodeRef>> {
+        child(&self.upper, path)
+    }
+
+    /// Returns the upper directory at `path`, copying up it and its ancestors
+    /// from the lower layer if needed.
+    pub fn upper_dir(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let _guard = self.copy_up_lock.lock();
+        self.copy_up_dirs(path)
+    }
+
+    fn copy_up_dirs(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let mut dir = self.upper.clone();
+        let mut prefix = String::new();
+        for name in path.split('/').filter(|c| !c.is_empty()) {
+            prefix = join(&prefix, name);
+            dir = match child(&dir, name)? {
+                Some(node) => node,
+                None => {
+                    let lower = self.resolve(&prefix)?.lower.ok_or(VfsError::NotFound)?;
+                    dir.create(name, VfsNodeType::Dir)?;
+                    let node = dir.clone().lookup(name)?;
+                    copy_attr(&lower, &node)?;
+                    node
+                }
+            };
+        }
+        Ok(dir)
+    }
+
+    /// Copies up the lower node at `path` to the upper layer, returns the upper
+    /// node.
+    pub fn copy_up(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let _guard = self.copy_up_lock.lock();
+        let resolved = self.resolve(path)?;
+        if let Some(upper) = resolved.upper {
+            return Ok(upper);
+        }
+        let lower = resolved.lower.ok_or(VfsError::NotFound)?;
+        let (parent, name) = split_parent(path);
+        let dir = self.copy_up_dirs(parent)?;
+        let attr = lower.get_attr()?;
+        match attr.file_type() {
+            VfsNodeType::Dir => dir.create(name, VfsNodeType::Dir)?,
+            VfsNodeType::File => {
+                dir.create(name, VfsNodeType::File)?;
+                let node = dir.clone().lookup(name)?;
+                copy_data(&lower, &node, attr.size())?;
+            }
+            VfsNodeType::SymLink => {
+                let mut target = alloc::vec![0; attr.size() as usize];
+                let len = lower.readlink(&mut target)?;
+                let target =
+                    core::str::from_utf8(&target[..len]).map_err(|_| VfsError::InvalidData)?;
+                dir.symlink(name, target)?;
+            }
+            _ => return Err(VfsError::Unsupported),
+        }
+        let node = dir.clone().lookup(name)?;
+        copy_attr(&lower, &node)?;
+        log::debug!("copy up at overlayfs: {}", path);
+        Ok(node)
+    }
+
+    /// Hides the lower node of `name` in the upper directory `dir`.
+    pub fn add_whiteout(&self, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        let whiteout = whiteout_name(name);
+        if child(dir, &whiteout)?.is_none() {
+            dir.create(&whiteout, VfsNodeType::File)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the whiteout of `name` in the upper directory `dir`, returns
+    /// whether it existed.
+    pub fn remove_whiteout(&self, dir: &VfsNodeRef, name: &str) -> VfsResult<bool> {
+        let whiteout = whiteout_name(name);
+        if child(dir, &whiteout)?.is_some() {
+            dir.remove(&whiteout)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Marks the upper directory `dir` as opaque.
+    pub fn set_opaque(&self, dir: &VfsNodeRef) -> VfsResult {
+        dir.create(OPAQUE_MARKER, VfsNodeType::File)
+    }
+
+    /// Removes the whiteouts and the opaque marker in the upper directory
+    /// `dir`, before it is removed.
+    pub fn clear_whiteouts(&self, dir: &VfsNodeRef) -> VfsResult {
+        for (name, _) in read_entries(dir)? {
+            if name.starts_with(WHITEOUT_PREFIX) {
+                dir.remove(&name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the merged entries of a resolved directory, except `.` and
+    /// `..`.
+    pub fn merged_entries(&self, resolved: &Resolved) -> VfsResult<Vec<(String, VfsNodeType)>> {
+        let mut entries = Vec::new();
+        let mut hidden = Vec::new();
+        if let Some(upper) = &resolved.upper {
+            for (name, ty) in read_entries(upper)? {
+                match name.strip_prefix(WHITEOUT_PREFIX) {
+                    Some(hidden_name) => hidden.push(String::from(hidden_name)),
+                    None => entries.push((name, ty)),
+                }
+            }
+        }
+        if let Some(lower) = &resolved.lower {
+            for (name, ty) in read_entries(lower)? {
+                if !hidden.contains(&name) && !entries.iter().any(|(n, _)| *n == name) {
+                    entries.push((name, ty));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns the parent of the root, i.e., of the mount point.
+    pub fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.get().cloned()
+    }
+}
+
+/// Copies the mode, owner and timestamps of `src` to `dst`.
+fn copy_attr(src: &VfsNodeRef, dst: &VfsNodeRef) -> VfsResult {
+    let attr = src.get_attr()?;
+    dst.set_attr(&VfsSetAttr {
+        mode: Some(attr.perm()),
+        uid: Some(attr.uid()),
+        gid: Some(attr.gid()),
+        atime: Some(attr.atime()),
+        mtime: Some(attr.mtime()),
+    })
+}
+
+/// Copies the first `size` bytes of the file `src` to `dst`.
+fn copy_data(src: &VfsNodeRef, dst: &VfsNodeRef, size: u64) -> VfsResult {
+    let mut buf = alloc::vec![0; 4096];
+    let mut offset = 0;
+    while offset < size {
+        let n = src.read_at(offset, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let mut written = 0;
+        while written < n {
+            written += dst.write_at(offset + written as u64, &buf[written..n])?;
+        }
+        offset += n as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsSetAttr};
+use axfs_vfs::{VfsError, VfsResult};
+use spin::RwLock;
+
+use crate::{join, split_parent, Layers, Resolved, WHITEOUT_PREFIX};
+
+/// The node of a file, directory or symbolic link in an overlay.
+///
+/// Directory operations are dispatched by the path of the node, so they always
+/// see the current state of both layers. File operations go to the upper node
+/// if it exists, or the lower one until the file is copied up.
+pub struct OverlayNode {
+    layers: Arc<Layers>,
+    /// The path from the root of the overlay, empty for the root.
+    path: String,
+    upper: RwLock<Option<VfsNodeRef>>,
+    lower: Option<VfsNodeRef>,
+}
+
+/// The result of joining a path to the path of a directory.
+enum FullPath {
+    /// The path from the root of the overlay.
+    Inner(String),
+    /// The rest of the path after leaving the root through `..`.
+    Outer(String),
+}
+
+impl OverlayNode {
+    pub(crate) fn new(layers: Arc<Layers>, path: String, resolved: Resolved) -> Arc<Self> {
+        Arc::new(Self {
+            layers,
+            path,
+            upper: RwLock::new(resolved.upper),
+            lower: resolved.lower,
+        })
+    }
+
+    /// Returns the node at the path from the root of the overlay.
+    fn node_at(&self, path: String) -> VfsResult<VfsNodeRef> {
+        let resolved = self.layers.resolve(&path)?;
+        Ok(Self::new(self.layers.clone(), path, resolved))
+    }
+
+    /// Returns the upper node, which may have been copied up through another
+    /// node of the same path.
+    fn upper(&self) -> Option<VfsNodeRef> {
+        if let Some(upper) = self.upper.read().clone() {
+            return Some(upper);
+        }
+        let upper = self.layers.upper_node(&self.path).ok().flatten()?;
+        *self.upper.write() = Some(upper.clone());
+        Some(upper)
+    }
+
+    fn current(&self) -> VfsResult<VfsNodeRef> {
+        self.upper()
+            .or_else(|| self.lower.clone())
+            .ok_or(VfsError::NotFound)
+    }
+
+    /// Returns the upper node, copying up the lower one if needed.
+    fn copy_up(&self) -> VfsResult<VfsNodeRef> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        let upper = self.layers.copy_up(&self.path)?;
+        *self.upper.write() = Some(upper.clone());
+        Ok(upper)
+    }
+
+    fn full_path(&self, path: &str) -> FullPath {
+        let mut comps: Vec<&str> = self.path.split('/').filter(|c| !c.is_empty()).collect();
+        let mut rest = path.split('/');
+        while let Some(comp) = rest.next() {
+            match comp {
+                "" | "." => {}
+                ".." => {
+                    if comps.pop().is_none() {
+                        return FullPath::Outer(rest.collect::<Vec<_>>().join("/"));
+                    }
+                }
+                _ => comps.push(comp),
+            }
+        }
+        FullPath::Inner(comps.join("/"))
+    }
+
+    /// Returns the path from the root of the overlay of a new node at `path`,
+    /// which must not be the root or have a reserved name.
+    fn new_path(&self, path: &str) -> VfsResult<String> {
+        match self.full_path(path) {
+            FullPath::Inner(full) if !full.is_empty() => {
+                if split_parent(&full).1.starts_with(WHITEOUT_PREFIX) {
+                    Err(VfsError::InvalidInput)
+                } else {
+                    Ok(full)
+                }
+            }
+            _ => Err(VfsError::InvalidInput),
+        }
+    }
+
+    /// Returns the node type at `path` from the root of the overlay, or `None`
+    /// if it does not exist.
+    fn type_at(&self, path: &str) -> VfsResult<Option<(Resolved, VfsNodeType)>> {
+        match self.layers.resolve(path) {
+            Ok(resolved) => {
+                let node = resolved.upper.as_ref().or(resolved.lower.as_ref());
+                let ty = node.ok_or(VfsError::NotFound)?.get_attr()?.file_type();
+                Ok(Some((resolved, ty)))
+            }
+            Err(VfsError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates a node at `path` from the root of the overlay by `create` in
+    /// the upper parent directory, with the name.
+    fn create_with<F>(&self, path: &str, is_dir: bool, create: F) -> VfsResult
+    where
+        F: FnOnce(&VfsNodeRef, &str) -> VfsResult,
+    {
+        let (parent, name) = split_parent(path);
+        if self.type_at(parent)?.ok_or(VfsError::NotFound)?.1 != VfsNodeType::Dir {
+            return Err(VfsError::NotADirectory);
+        }
+        let dir = self.layers.upper_dir(parent)?;
+        create(&dir, name)?;
+        if self.layers.remove_whiteout(&dir, name)? && is_dir {
+            // hide the deleted lower directory
+            self.layers.set_opaque(&dir.clone().lookup(name)?)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the node at `path` from the root of the overlay, leaving a
+    /// whiteout if it exists in the lower layer.
+    fn remove_at(&self, path: &str) -> VfsResult {
+        let (resolved, ty) = self.type_at(path)?.ok_or(VfsError::NotFound)?;
+        if ty == VfsNodeType::Dir && !self.layers.merged_entries(&resolved)?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        let in_lower = self.layers.in_lower(path)?;
+        let (parent, name) = split_parent(path);
+        let dir = self.layers.upper_dir(parent)?;
+        if let Some(upper) = &resolved.upper {
+            if ty == VfsNodeType::Dir {
+                self.layers.clear_whiteouts(upper)?;
+            }
+            dir.remove(name)?;
+        }
+        if in_lower {
+            self.layers.add_whiteout(&dir, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.current()?.get_attr()
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.copy_up()?.set_attr(changes)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.current()?.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.copy_up()?.write_at(offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match self.upper() {
+            Some(upper) => upper.fsync(),
+            None => self.current()?.fsync(),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.copy_up()?.truncate(size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.path.is_empty() {
+            self.layers.parent()
+        } else {
+            self.node_at(split_parent(&self.path).0.into()).ok()
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        match self.full_path(path) {
+            FullPath::Inner(full) if full == self.path => {
+                if !path.is_empty() && !self.get_attr()?.is_dir() {
+                    return Err(VfsError::NotADirectory);
+                }
+                Ok(self)
+            }
+            FullPath::Inner(full) => self.node_at(full),
+            FullPath::Outer(rest) => self
+                .layers
+                .parent()
+                .ok_or(VfsError::NotFound)?
+                .lookup(&rest),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        log::debug!("create {:?} at overlayfs: {}", ty, path);
+        let path = match self.full_path(path) {
+            FullPath::Inner(full) if full.is_empty() => return Ok(()), // the root
+            _ => self.new_path(path)?,
+        };
+        match self.type_at(&path)? {
+            Some((_, old_ty)) if old_ty == ty => Ok(()), // already exists
+            Some(_) => Err(VfsError::AlreadyExists),
+            None => self.create_with(&path, ty == VfsNodeType::Dir, |dir, name| {
+                dir.create(name, ty)
+            }),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        log::debug!("remove at overlayfs: {}", path);
+        self.remove_at(&self.new_path(path)?)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let resolved = self.layers.resolve(&self.path)?;
+        let entries = self.layers.merged_entries(&resolved)?;
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = entries.next() {
+                        *ent = VfsDirEntry::new(name, *ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("rename at overlayfs: {} -> {}", src_path, dst_path);
+        let src = self.new_path(src_path)?;
+        let dst = self.new_path(dst_path)?;
+        let (src_resolved, src_ty) = self.type_at(&src)?.ok_or(VfsError::NotFound)?;
+        if src == dst {
+            return Ok(());
+        }
+        let is_dir = src_ty == VfsNodeType::Dir;
+        if is_dir && dst.starts_with(&join(&src, "")) {
+            return Err(VfsError::InvalidInput);
+        } else if is_dir && src_resolved.lower.is_some() {
+            // lower directories cannot be moved without copying up their trees
+            return Err(VfsError::CrossesDevices);
+        }
+        match self.type_at(&dst)? {
+            Some((_, VfsNodeType::Dir)) if !is_dir => return Err(VfsError::IsADirectory),
+            Some((_, ty)) if is_dir && ty != VfsNodeType::Dir => {
+                return Err(VfsError::NotADirectory)
+            }
+            Some(_) => self.remove_at(&dst)?,
+            None => {}
+        }
+        let src_in_lower = self.layers.in_lower(&src)?;
+        let dst_in_lower = self.layers.in_lower(&dst)?;
+
+        let upper = self.layers.copy_up(&src)?;
+        let (src_parent, src_name) = split_parent(&src);
+        let (dst_parent, dst_name) = split_parent(&dst);
+        if self.type_at(dst_parent)?.ok_or(VfsError::NotFound)?.1 != VfsNodeType::Dir {
+            return Err(VfsError::NotADirectory);
+        }
+        let dst_dir = self.layers.upper_dir(dst_parent)?;
+        match self.layers.upper.rename(&src, &dst) {
+            Err(VfsError::Unsupported) if !is_dir => {
+                // move by a hard link, e.g., in a RAM filesystem
+                dst_dir.link(dst_name, upper)?;
+                self.layers.upper_dir(src_parent)?.remove(src_name)?;
+            }
+            result => result?,
+        }
+        if self.layers.remove_whiteout(&dst_dir, dst_name)? && is_dir && dst_in_lower {
+            self.layers.set_opaque(&dst_dir.clone().lookup(dst_name)?)?;
+        }
+        if src_in_lower {
+            self.layers
+                .add_whiteout(&self.layers.upper_dir(src_parent)?, src_name)?;
+        }
+        Ok(())
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink {} -> {} at overlayfs", path, target);
+        let path = self.new_path(path)?;
+        if self.type_at(&path)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.create_with(&path, false, |dir, name| dir.symlink(name, target))
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        log::debug!("link at overlayfs: {}", path);
+        let path = self.new_path(path)?;
+        let src = match node.as_any().downcast_ref::<OverlayNode>() {
+            Some(src) if Arc::ptr_eq(&src.layers, &self.layers) => src,
+            _ => return Err(VfsError::CrossesDevices),
+        };
+        if src.get_attr()?.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        if self.type_at(&path)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let upper = src.copy_up()?;
+        self.create_with(&path, false, |dir, name| dir.link(name, upper))
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.current()?.readlink(buf)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps, VfsResult, VfsSetAttr};
+
+use crate::*;
+
+fn read_to_string(node: &VfsNodeRef) -> VfsResult<String> {
+    let mut buf = [0; 64];
+    let len = node.read_at(0, &mut buf)?;
+    Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+}
+
+fn write(root: &VfsNodeRef, path: &str, contents: &str) -> VfsResult {
+    root.create(path, VfsNodeType::File)?;
+    let node = root.clone().lookup(path)?;
+    node.truncate(0)?;
+    node.write_at(0, contents.as_bytes())?;
+    Ok(())
+}
+
+fn dir_entries(node: &VfsNodeRef) -> VfsResult<Vec<String>> {
+    let mut dirents: [VfsDirEntry; 4] = core::array::from_fn(|_| VfsDirEntry::default());
+    let mut names = Vec::new();
+    loop {
+        let n = node.read_dir(names.len(), &mut dirents)?;
+        for ent in &dirents[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+        if n < dirents.len() {
+            names.sort();
+            return Ok(names);
+        }
+    }
+}
+
+/// Creates the lower layer with `a.txt`, `dir/b.txt`, `dir/sub/c.txt` and a
+/// symbolic link `link -> a.txt`.
+fn make_lower() -> VfsResult<Arc<RamFileSystem>> {
+    let fs = Arc::new(RamFileSystem::new());
+    let root = fs.root_dir();
+    write(&root, "a.txt", "lower a")?;
+    root.create("dir", VfsNodeType::Dir)?;
+    write(&root, "dir/b.txt", "lower b")?;
+    root.create("dir/sub", VfsNodeType::Dir)?;
+    write(&root, "dir/sub/c.txt", "lower c")?;
+    root.symlink("link", "a.txt")?;
+    Ok(fs)
+}
+
+fn new_overlay() -> VfsResult<(OverlayFileSystem, VfsNodeRef, VfsNodeRef)> {
+    let lower = make_lower()?;
+    let upper = Arc::new(RamFileSystem::new());
+    let (lower_root, upper_root) = (lower.root_dir(), upper.root_dir());
+    Ok((OverlayFileSystem::new(lower, upper), lower_root, upper_root))
+}
+
+#[test]
+fn test_copy_up() -> VfsResult {
+    let (fs, lower, upper) = new_overlay()?;
+    let root = fs.root_dir();
+
+    // reads go to the lower layer
+    let node = root.clone().lookup("dir/sub/c.txt")?;
+    assert_eq!(read_to_string(&node)?, "lower c");
+    assert_eq!(upper.clone().lookup("dir").err(), Some(VfsError::NotFound));
+
+    // the first write copies up the file with its parents
+    node.write_at(0, b"upper")?;
+    assert_eq!(read_to_string(&node)?, "upper c");
+    assert_eq!(
+        read_to_string(&root.clone().lookup("dir/sub/c.txt")?)?,
+        "upper c"
+    );
+    assert_eq!(
+        read_to_string(&upper.clone().lookup("dir/sub/c.txt")?)?,
+        "upper c"
+    );
+    assert_eq!(
+        read_to_string(&lower.clone().lookup("dir/sub/c.txt")?)?,
+        "lower c"
+    );
+    assert_eq!(
+        upper.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // another node of the same path sees the copy
+    let other = root.clone().lookup("a.txt")?;
+    let node = root.clone().lookup("a.txt")?;
+    node.truncate(2)?;
+    assert_eq!(read_to_string(&other)?, "lo");
+    assert_eq!(read_to_string(&lower.clone().lookup("a.txt")?)?, "lower a");
+
+    // attributes are copied, and changed in the upper layer only
+    let node = root.clone().lookup("dir/b.txt")?;
+    let perm = node.get_attr()?.perm();
+    node.set_attr(&VfsSetAttr {
+        uid: Some(1000),
+        ..Default::default()
+    })?;
+    assert_eq!(node.get_attr()?.uid(), 1000);
+    assert_eq!(node.get_attr()?.perm().bits(), perm.bits());
+    assert_eq!(lower.clone().lookup("dir/b.txt")?.get_attr()?.uid(), 0);
+
+    // symbolic links
+    let link = root.clone().lookup("link")?;
+    assert_eq!(link.get_attr()?.file_type(), VfsNodeType::SymLink);
+    let mut buf = [0; 16];
+    let len = link.readlink(&mut buf)?;
+    assert_eq!(&buf[..len], b"a.txt");
+    Ok(())
+}
+
+#[test]
+fn test_whiteouts() -> VfsResult {
+    let (fs, lower, upper) = new_overlay()?;
+    let root = fs.root_dir();
+
+    // merged listing
+    write(&root, "dir/new.txt", "new")?;
+    assert_eq!(
+        dir_entries(&root.clone().lookup("dir")?)?,
+        [".", "..", "b.txt", "new.txt", "sub"]
+    );
+
+    // deleting a lower file leaves a whiteout
+    root.remove("dir/b.txt")?;
+    assert_eq!(
+        root.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert!(lower.clone().lookup("dir/b.txt").is_ok());
+    assert!(upper.clone().lookup("dir/.wh.b.txt").is_ok());
+    assert_eq!(
+        dir_entries(&root.clone().lookup("dir")?)?,
+        [".", "..", "new.txt", "sub"]
+    );
+    assert_eq!(
+        root.clone().lookup("dir/.wh.b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(
+        root.create("dir/.wh.x", VfsNodeType::File).err(),
+        Some(VfsError::InvalidInput)
+    );
+
+    // recreating it removes the whiteout
+    write(&root, "dir/b.txt", "again")?;
+    assert_eq!(read_to_string(&root.clone().lookup("dir/b.txt")?)?, "again");
+    assert_eq!(
+        upper.clone().lookup("dir/.wh.b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // removing a copied-up file hides the lower one as well
+    root.remove("dir/b.txt")?;
+    assert_eq!(
+        root.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert!(upper.clone().lookup("dir/.wh.b.txt").is_ok());
+
+    // merged directories must be empty to be removed
+    assert_eq!(
+        root.remove("dir/sub").err(),
+        Some(VfsError::DirectoryNotEmpty)
+    );
+    root.remove("dir/sub/c.txt")?;
+    root.remove("dir/sub")?;
+    assert_eq!(
+        root.clone().lookup("dir/sub").err(),
+        Some(VfsError::NotFound)
+    );
+    root.remove("dir/new.txt")?;
+    root.remove("dir")?;
+
+    // a directory recreated in place of a lower one is opaque
+    root.create("dir", VfsNodeType::Dir)?;
+    assert_eq!(dir_entries(&root.clone().lookup("dir")?)?, [".", ".."]);
+    assert_eq!(
+        root.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert!(lower.clone().lookup("dir/sub/c.txt").is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_rename_link() -> VfsResult {
+    let (fs, lower, _upper) = new_overlay()?;
+    let root = fs.root_dir();
+
+    // a lower file is copied up and hidden
+    root.rename("dir/b.txt", "b2.txt")?;
+    assert_eq!(read_to_string(&root.clone().lookup("b2.txt")?)?, "lower b");
+    assert_eq!(
+        root.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert!(lower.clone().lookup("dir/b.txt").is_ok());
+
+    // replace an existing file
+    root.rename("b2.txt", "a.txt")?;
+    assert_eq!(read_to_string(&root.clone().lookup("a.txt")?)?, "lower b");
+    assert_eq!(
+        root.clone().lookup("b2.txt").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // lower directories cannot be renamed, upper ones can
+    assert_eq!(
+        root.rename("dir", "dir2").err(),
+        Some(VfsError::CrossesDevices)
+    );
+    root.create("up", VfsNodeType::Dir)?;
+    assert_eq!(
+        root.rename("up", "up/x").err(),
+        Some(VfsError::InvalidInput)
+    );
+
+    // hard links to lower files copy them up
+    let node = root.clone().lookup("dir/sub/c.txt")?;
+    root.link("c2.txt", node.clone())?;
+    let link = root.clone().lookup("c2.txt")?;
+    link.write_at(0, b"LOWER")?;
+    assert_eq!(read_to_string(&node)?, "LOWER c");
+    assert_eq!(link.get_attr()?.nlink(), 2);
+    assert_eq!(
+        root.link("d", root.clone().lookup("dir")?).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.link("x", lower.clone().lookup("a.txt")?).err(),
+        Some(VfsError::CrossesDevices)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_lookup() -> VfsResult {
+    let (fs, _lower, upper) = new_overlay()?;
+    let root = fs.root_dir();
+
+    let dir = root.clone().lookup("dir")?;
+    assert!(dir.get_attr()?.is_dir());
+    assert_eq!(
+        read_to_string(&dir.clone().lookup("sub/../b.txt")?)?,
+        "lower b"
+    );
+    assert_eq!(read_to_string(&dir.clone().lookup("../a.txt")?)?, "lower a");
+    assert!(dir.parent().is_some());
+    assert_eq!(
+        root.clone().lookup("a.txt/x").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(root.clone().lookup("none").err(), Some(VfsError::NotFound));
+
+    // an upper file shadows a lower directory
+    write(&upper, "dir", "file")?;
+    assert!(root.clone().lookup("dir")?.get_attr()?.is_file());
+    assert_eq!(
+        root.clone().lookup("dir/b.txt").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.create("a.txt", VfsNodeType::Dir).err(),
+        Some(VfsError::AlreadyExists)
+    );
+
+    assert_eq!(fs.statfs()?.fs_type, OVERLAYFS_SUPER_MAGIC);
+    Ok(())
+}
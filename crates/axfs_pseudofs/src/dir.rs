@@ -0,0 +1,177 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsResult};
+use spin::RwLock;
+
+/// Generates the dynamic entries of a [`DirNode`] on demand.
+pub trait DirGenerator: Send + Sync {
+    /// Returns the names and types of all dynamic entries.
+    fn entries(&self) -> Vec<(String, VfsNodeType)>;
+
+    /// Creates the node of the dynamic entry `name` in the directory `dir`,
+    /// or returns `None` if it does not exist.
+    fn lookup(&self, dir: &Arc<DirNode>, name: &str) -> Option<VfsNodeRef>;
+}
+
+/// The directory node in the pseudo filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct DirNode {
+    this: Weak<DirNode>,
+    parent: RwLock<Weak<dyn VfsNodeOps>>,
+    children: RwLock<BTreeMap<&'static str, VfsNodeRef>>,
+    generator: RwLock<Option<Box<dyn DirGenerator>>>,
+}
+
+impl DirNode {
+    pub(super) fn new(parent: Option<&VfsNodeRef>) -> Arc<Self> {
+        let parent = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: RwLock::new(parent),
+            children: RwLock::new(BTreeMap::new()),
+            generator: RwLock::new(None),
+        })
+    }
+
+    pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
+        *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+    }
+
+    /// Create a subdirectory at this directory.
+    pub fn mkdir(self: &Arc<Self>, name: &'static str) -> Arc<Self> {
+        let node = self.new_child_dir();
+        self.children.write().insert(name, node.clone());
+        node
+    }
+
+    /// Create a subdirectory whose parent is this directory, but do not add it
+    /// to this directory. It's used to create dynamic entries.
+    ///
+    /// The subdirectory only holds a weak reference to this directory, so
+    /// this directory must be kept alive while the subdirectory is in use.
+    pub fn new_child_dir(self: &Arc<Self>) -> Arc<Self> {
+        let parent = self.clone() as VfsNodeRef;
+        Self::new(Some(&parent))
+    }
+
+    /// Add a node to this directory.
+    pub fn add(&self, name: &'static str, node: VfsNodeRef) {
+        self.children.write().insert(name, node);
+    }
+
+    /// Set the generator of the dynamic entries of this directory.
+    ///
+    /// The static entries take precedence over the dynamic ones with the same
+    /// name.
+    pub fn set_generator(&self, generator: impl DirGenerator + 'static) {
+        *self.generator.write() = Some(Box::new(generator));
+    }
+
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(node) = self.children.read().get(name) {
+            return Some(node.clone());
+        }
+        let this = self.this.upgrade()?;
+        self.generator.read().as_ref()?.lookup(&this, name)
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_dir(4096, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.lookup_child(name).ok_or(VfsError::NotFound),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let children = self.children.read();
+        let generated = match self.generator.read().as_ref() {
+            Some(generator) => generator.entries(),
+            None => Vec::new(),
+        };
+        let mut entries = children
+            .iter()
+            .map(|(name, node)| (*name, node.get_attr().unwrap().file_type()))
+            .chain(generated.iter().map(|(name, ty)| (name.as_str(), *ty)))
+            .skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = entries.next() {
+                        *ent = VfsDirEntry::new(name, ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        log::debug!("create {:?} at pseudofs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => self
+                    .lookup_child(name)
+                    .ok_or(VfsError::NotFound)?
+                    .create(rest, ty),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            Err(VfsError::PermissionDenied) // do not support to create nodes dynamically
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        log::debug!("remove at pseudofs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => self
+                    .lookup_child(name)
+                    .ok_or(VfsError::NotFound)?
+                    .remove(rest),
+            }
+        } else {
+            Err(VfsError::PermissionDenied) // do not support to remove nodes dynamically
+        }
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
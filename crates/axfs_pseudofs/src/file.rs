@@ -0,0 +1,89 @@
+use alloc::{boxed::Box, string::String};
+use axfs_vfs::{VfsError, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType};
+
+type ReadFn = dyn Fn() -> VfsResult<String> + Send + Sync;
+type WriteFn = dyn Fn(&str) -> VfsResult + Send + Sync;
+
+/// The file node in the pseudo filesystem.
+///
+/// Its content is generated on every read, so it always reflects the current
+/// state of the source. If a write handler is given, the file is writable and
+/// the handler is called with the written string.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct FileNode {
+    read: Box<ReadFn>,
+    write: Option<Box<WriteFn>>,
+}
+
+impl FileNode {
+    /// Create a read-only file, whose content is generated by `read`.
+    pub fn new<F>(read: F) -> Self
+    where
+        F: Fn() -> VfsResult<String> + Send + Sync + 'static,
+    {
+        Self {
+            read: Box::new(read),
+            write: None,
+        }
+    }
+
+    /// Create a read-only file with fixed content.
+    pub fn new_static(content: &'static str) -> Self {
+        Self::new(move || Ok(content.into()))
+    }
+
+    /// Make the file writable. `write` is called with the data of each write,
+    /// regardless of the offset.
+    pub fn with_write<F>(mut self, write: F) -> Self
+    where
+        F: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        self.write = Some(Box::new(write));
+        self
+    }
+
+    fn perm(&self) -> VfsNodePerm {
+        let read = VfsNodePerm::OWNER_READ | VfsNodePerm::GROUP_READ | VfsNodePerm::OTHER_READ;
+        if self.write.is_some() {
+            read | VfsNodePerm::OWNER_WRITE
+        } else {
+            read
+        }
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = (self.read)()?.len() as u64;
+        Ok(VfsNodeAttr::new(self.perm(), VfsNodeType::File, size, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)()?;
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content.as_bytes()[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        let data = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidData)?;
+        write(data)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // Opening with `O_TRUNC` is allowed for writable files.
+        if self.write.is_some() {
+            Ok(())
+        } else {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,108 @@
+//! Pseudo filesystem used by [ArceOS](https://github.com/rcore-os/arceos),
+//! such as procfs and sysfs.
+//!
+//! The contents of files are not stored, but generated on demand by the
+//! closures given when the files are created. Files can also be made writable
+//! to accept commands or tunables. Besides the static entries, a directory
+//! can have dynamic entries generated by a [`DirGenerator`], e.g., one
+//! directory for each running task.
+//!
+//! The implementation is based on [`axfs_vfs`].
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod dir;
+mod file;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::dir::{DirGenerator, DirNode};
+pub use self::file::FileNode;
+
+use alloc::sync::Arc;
+use axfs_vfs::{FileSystemInfo, VfsNodeRef, VfsOps, VfsResult};
+use spin::once::Once;
+
+/// The filesystem type magic number of procfs, the same as Linux's
+/// `PROC_SUPER_MAGIC`.
+pub const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+
+/// The filesystem type magic number of sysfs, the same as Linux's
+/// `SYSFS_MAGIC`.
+pub const SYSFS_MAGIC: u64 = 0x6265_6572;
+
+/// A pseudo filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct PseudoFileSystem {
+    parent: Once<VfsNodeRef>,
+    root: Arc<DirNode>,
+    fs_type: u64,
+}
+
+impl PseudoFileSystem {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self {
+            parent: Once::new(),
+            root: DirNode::new(None),
+            fs_type: 0,
+        }
+    }
+
+    /// Sets the filesystem type magic number reported by
+    /// [`VfsOps::statfs`], e.g., [`PROC_SUPER_MAGIC`].
+    pub fn with_fs_type(mut self, fs_type: u64) -> Self {
+        self.fs_type = fs_type;
+        self
+    }
+
+    /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
+    pub fn root_dir_node(&self) -> Arc<DirNode> {
+        self.root.clone()
+    }
+
+    /// Create a subdirectory at the root directory.
+    pub fn mkdir(&self, name: &'static str) -> Arc<DirNode> {
+        self.root.mkdir(name)
+    }
+
+    /// Add a node to the root directory.
+    ///
+    /// The node must implement [`axfs_vfs::VfsNodeOps`], and be wrapped in [`Arc`].
+    pub fn add(&self, name: &'static str, node: VfsNodeRef) {
+        self.root.add(name, node);
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            self.root.set_parent(Some(self.parent.call_once(|| parent)));
+        } else {
+            self.root.set_parent(None);
+        }
+        Ok(())
+    }
+
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        // Nothing is stored, so there is no capacity to report.
+        Ok(FileSystemInfo {
+            fs_type: self.fs_type,
+            block_size: 4096,
+            name_len: 255,
+            ..Default::default()
+        })
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl Default for PseudoFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use crate::*;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Generates `0`..`n` as subdirectories, each of them has a file `id`.
+struct NumGenerator(usize);
+
+impl DirGenerator for NumGenerator {
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        (0..self.0)
+            .map(|i| (i.to_string(), VfsNodeType::Dir))
+            .collect()
+    }
+
+    fn lookup(&self, dir: &Arc<DirNode>, name: &str) -> Option<VfsNodeRef> {
+        let id: usize = name.parse().ok().filter(|&i| i < self.0)?;
+        let node = dir.new_child_dir();
+        node.add(
+            "id",
+            Arc::new(FileNode::new(move || Ok(format!("{}\n", id)))),
+        );
+        Some(node)
+    }
+}
+
+fn read_to_string(node: &VfsNodeRef) -> VfsResult<String> {
+    let mut buf = [0; 64];
+    let len = node.read_at(0, &mut buf)?;
+    Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+}
+
+fn dir_entries(node: &VfsNodeRef) -> VfsResult<Vec<String>> {
+    let mut dirents: [VfsDirEntry; 4] = core::array::from_fn(|_| VfsDirEntry::default());
+    let mut names = Vec::new();
+    loop {
+        let n = node.read_dir(names.len(), &mut dirents)?;
+        for ent in &dirents[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+        if n < dirents.len() {
+            return Ok(names);
+        }
+    }
+}
+
+fn test_file_ops(fs: &PseudoFileSystem) -> VfsResult {
+    let root = fs.root_dir();
+
+    let node = root.clone().lookup("version")?;
+    assert_eq!(node.get_attr()?.file_type(), VfsNodeType::File);
+    assert_eq!(node.get_attr()?.size(), 6);
+    assert!(!node.get_attr()?.perm().owner_writable());
+    assert_eq!(read_to_string(&node)?, "0.1.0\n");
+    let mut buf = [0; 3];
+    assert_eq!(node.read_at(4, &mut buf)?, 2);
+    assert_eq!(&buf[..2], b"0\n");
+    assert_eq!(node.read_at(10, &mut buf)?, 0);
+    assert_eq!(
+        node.write_at(0, b"1").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(node.truncate(0).err(), Some(VfsError::PermissionDenied));
+
+    let node = root.clone().lookup("./sys/counter")?;
+    assert!(node.get_attr()?.perm().owner_writable());
+    assert_eq!(read_to_string(&node)?, "0\n");
+    node.truncate(0)?;
+    assert_eq!(node.write_at(0, b"42\n")?, 3);
+    assert_eq!(COUNTER.load(Ordering::Relaxed), 42);
+    assert_eq!(read_to_string(&node)?, "42\n");
+    assert_eq!(node.write_at(0, b"foo").err(), Some(VfsError::InvalidInput));
+    assert_eq!(node.write_at(0, &[0xff]).err(), Some(VfsError::InvalidData));
+    assert_eq!(read_to_string(&node)?, "42\n");
+    assert_eq!(node.lookup("/").err(), Some(VfsError::NotADirectory));
+
+    assert_eq!(
+        root.create("sys/foo", VfsNodeType::File).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.remove("sys/counter").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    Ok(())
+}
+
+fn test_generator(fs: &PseudoFileSystem) -> VfsResult {
+    let root = fs.root_dir();
+    assert_eq!(
+        dir_entries(&root)?,
+        [".", "..", "sys", "version", "0", "1", "2"]
+    );
+    assert_eq!(root.clone().lookup("3").err(), Some(VfsError::NotFound));
+
+    let node = root.clone().lookup("2")?;
+    assert!(node.get_attr()?.is_dir());
+    assert_eq!(dir_entries(&node)?, [".", "..", "id"]);
+    assert_eq!(read_to_string(&root.clone().lookup("/1//id")?)?, "1\n");
+    assert!(Arc::ptr_eq(&node.parent().unwrap(), &root));
+    assert!(Arc::ptr_eq(&root.clone().lookup("2/../sys/..")?, &root));
+    Ok(())
+}
+
+#[test]
+fn test_pseudofs() {
+    // .
+    // ├── 0..3 (generated)
+    // │   └── id
+    // ├── sys
+    // │   └── counter (writable)
+    // └── version
+
+    let fs = PseudoFileSystem::new().with_fs_type(PROC_SUPER_MAGIC);
+    fs.add("version", Arc::new(FileNode::new_static("0.1.0\n")));
+    let sys = fs.mkdir("sys");
+    sys.add(
+        "counter",
+        Arc::new(
+            FileNode::new(|| Ok(format!("{}\n", COUNTER.load(Ordering::Relaxed)))).with_write(
+                |s| {
+                    let val = s.trim().parse().map_err(|_| VfsError::InvalidInput)?;
+                    COUNTER.store(val, Ordering::Relaxed);
+                    Ok(())
+                },
+            ),
+        ),
+    );
+    fs.root_dir_node().set_generator(NumGenerator(3));
+
+    test_file_ops(&fs).unwrap();
+    test_generator(&fs).unwrap();
+
+    let info = fs.statfs().unwrap();
+    assert_eq!(info.fs_type, PROC_SUPER_MAGIC);
+    assert_eq!((info.blocks, info.files), (0, 0));
+}
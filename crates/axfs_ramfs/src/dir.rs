@@ -1,12 +1,13 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
 
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsSetAttr};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::symlink::SymlinkNode;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -15,6 +16,7 @@ pub struct DirNode {
     this: Weak<DirNode>,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    attr: RwLock<VfsNodeAttr>,
 }
 
 impl DirNode {
@@ -23,6 +25,7 @@ impl DirNode {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new_dir(4096, 0))),
         })
     }
 
@@ -52,9 +55,59 @@ impl DirNode {
             _ => return Err(VfsError::Unsupported),
         };
         self.children.write().insert(name.into(), node);
+        self.attr.write().touch_modified();
         Ok(())
     }
 
+    /// Creates a new symbolic link with the given name in this directory,
+    /// which points to `target`.
+    pub fn create_symlink(&self, name: &str, target: &str) -> VfsResult {
+        self.insert_node(name, Arc::new(SymlinkNode::new(target)))
+    }
+
+    /// Creates a hard link with the given name in this directory, which
+    /// refers to the existing `node`.
+    ///
+    /// Directories cannot be hard linked, and the `node` must belong to the
+    /// RAM filesystem.
+    pub fn link_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let any = node.as_any();
+        if any.is::<DirNode>() {
+            return Err(VfsError::PermissionDenied);
+        } else if !any.is::<FileNode>() && !any.is::<SymlinkNode>() {
+            return Err(VfsError::CrossesDevices);
+        }
+        self.insert_node(name, node.clone())?;
+        if let Some(attr) = link_attr(&node) {
+            let mut attr = attr.write();
+            let nlink = attr.nlink() + 1;
+            attr.set_nlink(nlink);
+            attr.touch_status_changed();
+        }
+        Ok(())
+    }
+
+    fn insert_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            log::error!("AlreadyExists {}", name);
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
+        self.attr.write().touch_modified();
+        Ok(())
+    }
+
+    /// Returns the node of the intermediate component `name` of a path.
+    fn lookup_dir(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        match name {
+            "" | "." => self.this.upgrade().map(|this| this as _),
+            ".." => self.parent(),
+            _ => self.children.read().get(name).cloned(),
+        }
+        .ok_or(VfsError::NotFound)
+    }
+
     /// Removes a node by the given name in this directory.
     pub fn remove_node(&self, name: &str) -> VfsResult {
         let mut children = self.children.write();
@@ -64,14 +117,68 @@ impl DirNode {
                 return Err(VfsError::DirectoryNotEmpty);
             }
         }
+        if let Some(attr) = link_attr(node) {
+            let mut attr = attr.write();
+            let nlink = attr.nlink().saturating_sub(1);
+            attr.set_nlink(nlink);
+            attr.touch_status_changed();
+        }
         children.remove(name);
+        self.attr.write().touch_modified();
         Ok(())
     }
+
+    /// Returns the number of nodes in this subtree, including this directory,
+    /// and the total size of them in bytes. Hard links are counted once.
+    pub(crate) fn usage(&self) -> (u64, u64) {
+        let mut inos = BTreeSet::new();
+        let mut bytes = 0;
+        self.collect_usage(&mut inos, &mut bytes);
+        (inos.len() as u64, bytes)
+    }
+
+    fn collect_usage(&self, inos: &mut BTreeSet<u64>, bytes: &mut u64) {
+        let attr = *self.attr.read();
+        inos.insert(attr.ino());
+        *bytes += attr.size();
+        for node in self.children.read().values() {
+            if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+                dir.collect_usage(inos, bytes);
+            } else if let Ok(attr) = node.get_attr() {
+                if inos.insert(attr.ino()) {
+                    *bytes += attr.size();
+                }
+            }
+        }
+    }
+}
+
+/// Returns the attributes of a node that can be hard linked.
+fn link_attr(node: &VfsNodeRef) -> Option<&RwLock<VfsNodeAttr>> {
+    let any = node.as_any();
+    if let Some(file) = any.downcast_ref::<FileNode>() {
+        Some(&file.attr)
+    } else {
+        any.downcast_ref::<SymlinkNode>().map(|link| &link.attr)
+    }
 }
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        let subdirs = self
+            .children
+            .read()
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>())
+            .count();
+        let mut attr = *self.attr.read();
+        attr.set_nlink(2 + subdirs as u64);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
@@ -165,6 +272,30 @@ impl VfsNodeOps for DirNode {
         }
     }
 
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink {} -> {} at ramfs", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.symlink(rest, target)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.create_symlink(name, target)
+        }
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        log::debug!("link at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.link(rest, node)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.link_node(name, node)
+        }
+    }
+
     axfs_vfs::impl_vfs_dir_default! {}
 }
 
@@ -1,5 +1,5 @@
 use alloc::vec::Vec;
-use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult, VfsSetAttr};
 use spin::RwLock;
 
 /// The file node in the RAM filesystem.
@@ -7,19 +7,29 @@ use spin::RwLock;
 /// It implements [`axfs_vfs::VfsNodeOps`].
 pub struct FileNode {
     content: RwLock<Vec<u8>>,
+    pub(crate) attr: RwLock<VfsNodeAttr>,
 }
 
 impl FileNode {
-    pub(super) const fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
             content: RwLock::new(Vec::new()),
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new_file(0, 0))),
         }
     }
 }
 
 impl VfsNodeOps for FileNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_file(self.content.read().len() as _, 0))
+        let size = self.content.read().len() as u64;
+        let mut attr = *self.attr.read();
+        attr.set_size(size, size.div_ceil(512));
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
@@ -29,6 +39,7 @@ impl VfsNodeOps for FileNode {
         } else {
             content.resize(size as _, 0);
         }
+        self.attr.write().touch_modified();
         Ok(())
     }
 
@@ -38,6 +49,7 @@ impl VfsNodeOps for FileNode {
         let end = content.len().min(offset as usize + buf.len());
         let src = &content[start..end];
         buf[..src.len()].copy_from_slice(src);
+        self.attr.write().touch_accessed();
         Ok(src.len())
     }
 
@@ -49,8 +61,14 @@ impl VfsNodeOps for FileNode {
         }
         let dst = &mut content[offset..offset + buf.len()];
         dst.copy_from_slice(&buf[..dst.len()]);
+        self.attr.write().touch_modified();
         Ok(buf.len())
     }
 
+    fn fsync(&self) -> VfsResult {
+        // the data only lives in memory
+        Ok(())
+    }
+
     impl_vfs_non_dir_default! {}
 }
@@ -8,21 +8,44 @@ extern crate alloc;
 
 mod dir;
 mod file;
+mod symlink;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::dir::DirNode;
 pub use self::file::FileNode;
+pub use self::symlink::SymlinkNode;
 
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use axfs_vfs::{FileSystemInfo, VfsNodeAttr, VfsNodeRef, VfsOps, VfsResult};
+use core::sync::atomic::{AtomicU64, Ordering};
 use spin::once::Once;
 
+/// The filesystem type magic number of ramfs, the same as Linux's
+/// `RAMFS_MAGIC`.
+pub const RAMFS_MAGIC: u64 = 0x8584_58f6;
+
+/// The block size reported by [`VfsOps::statfs`].
+const BLOCK_SIZE: u64 = 4096;
+
+/// The maximum length of a file name reported by [`VfsOps::statfs`].
+const NAME_MAX: u64 = 255;
+
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+/// Assigns a new inode number to `attr` and stamps it with the current time.
+fn new_attr(mut attr: VfsNodeAttr) -> VfsNodeAttr {
+    attr.set_ino(NEXT_INO.fetch_add(1, Ordering::Relaxed));
+    attr.touch();
+    attr
+}
+
 /// A RAM filesystem that implements [`axfs_vfs::VfsOps`].
 pub struct RamFileSystem {
     parent: Once<VfsNodeRef>,
     root: Arc<DirNode>,
+    mem_stats: Option<fn() -> (usize, usize)>,
 }
 
 impl RamFileSystem {
@@ -31,9 +54,19 @@ impl RamFileSystem {
         Self {
             parent: Once::new(),
             root: DirNode::new(None),
+            mem_stats: None,
         }
     }
 
+    /// Sets the function that returns the total and free bytes of the memory
+    /// backing the filesystem, e.g., from the global allocator.
+    ///
+    /// Without it, [`VfsOps::statfs`] reports the filesystem as exactly full.
+    pub fn with_mem_stats(mut self, mem_stats: fn() -> (usize, usize)) -> Self {
+        self.mem_stats = Some(mem_stats);
+        self
+    }
+
     /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
     pub fn root_dir_node(&self) -> Arc<DirNode> {
         self.root.clone()
@@ -50,6 +83,27 @@ impl VfsOps for RamFileSystem {
         Ok(())
     }
 
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        let (files, used) = self.root.usage();
+        let (blocks, blocks_free) = match self.mem_stats {
+            Some(mem_stats) => {
+                let (total, free) = mem_stats();
+                (total as u64 / BLOCK_SIZE, free as u64 / BLOCK_SIZE)
+            }
+            None => (used.div_ceil(BLOCK_SIZE), 0),
+        };
+        Ok(FileSystemInfo {
+            fs_type: RAMFS_MAGIC,
+            block_size: BLOCK_SIZE,
+            blocks,
+            blocks_free,
+            blocks_avail: blocks_free,
+            files: files + blocks_free,
+            files_free: blocks_free,
+            name_len: NAME_MAX,
+        })
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
         self.root.clone()
     }
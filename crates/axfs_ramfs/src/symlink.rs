@@ -0,0 +1,45 @@
+use alloc::string::String;
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult, VfsSetAttr};
+use spin::RwLock;
+
+/// The symbolic link node in the RAM filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: String,
+    pub(crate) attr: RwLock<VfsNodeAttr>,
+}
+
+impl SymlinkNode {
+    pub(super) fn new(target: &str) -> Self {
+        Self {
+            target: target.into(),
+            attr: RwLock::new(crate::new_attr(VfsNodeAttr::new_symlink(target.len() as _))),
+        }
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(*self.attr.read())
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        // the permission of a symbolic link is always `0o777`
+        let changes = VfsSetAttr {
+            mode: None,
+            ..*changes
+        };
+        self.attr.write().apply(&changes);
+        Ok(())
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let len = self.target.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.target.as_bytes()[..len]);
+        self.attr.write().touch_accessed();
+        Ok(len)
+    }
+
+    impl_vfs_non_dir_default! {}
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeType, VfsResult, VfsSetAttr};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
 
 use crate::*;
 
@@ -134,3 +136,184 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+fn read_link(node: &axfs_vfs::VfsNodeRef) -> VfsResult<String> {
+    let mut buf = [0; 64];
+    let len = node.readlink(&mut buf)?;
+    Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+}
+
+#[test]
+fn test_ramfs_links() {
+    // .
+    // ├── foo
+    // │   ├── f1
+    // │   └── l2 -> f1 (hard link)
+    // ├── l1 -> foo/f1
+    // └── l3 -> l1 (hard link)
+
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+    root.symlink("l1", "foo/f1").unwrap();
+    assert_eq!(
+        root.symlink("l1", "foo").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.symlink("bar/l1", "foo").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // symbolic links are not followed by `lookup()`
+    let l1 = root.clone().lookup("l1").unwrap();
+    let attr = l1.get_attr().unwrap();
+    assert!(attr.is_symlink());
+    assert_eq!(attr.size(), 6);
+    assert_eq!(read_link(&l1).unwrap(), "foo/f1");
+    assert_eq!(
+        l1.read_at(0, &mut [0; 4]).err(),
+        Some(VfsError::InvalidInput)
+    );
+
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    assert_eq!(read_link(&f1).err(), Some(VfsError::InvalidInput));
+    assert_eq!(f1.symlink("x", "y").err(), Some(VfsError::NotADirectory));
+
+    // hard links share the same node
+    root.link("foo/../foo/l2", f1.clone()).unwrap();
+    root.link("l3", l1.clone()).unwrap();
+    let l2 = root.clone().lookup("foo/l2").unwrap();
+    assert!(Arc::ptr_eq(&l2, &f1));
+    assert_eq!(l2.write_at(0, b"hello").unwrap(), 5);
+    assert_eq!(f1.get_attr().unwrap().size(), 5);
+    assert_eq!(f1.get_attr().unwrap().nlink(), 2);
+    assert_eq!(
+        read_link(&root.clone().lookup("l3").unwrap()).unwrap(),
+        "foo/f1"
+    );
+    assert_eq!(
+        root.link("l2", root.clone().lookup("foo").unwrap()).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.link("l2", RamFileSystem::new().root_dir()).err(),
+        Some(VfsError::PermissionDenied)
+    );
+
+    // removing a link does not affect the others
+    assert_eq!(root.remove("foo/f1"), Ok(()));
+    assert_eq!(root.remove("l1"), Ok(()));
+    assert_eq!(
+        read_link(&root.clone().lookup("l3").unwrap()).unwrap(),
+        "foo/f1"
+    );
+    assert_eq!(l2.get_attr().unwrap().size(), 5);
+    assert_eq!(l2.get_attr().unwrap().nlink(), 1);
+    let mut entries = ramfs.root_dir_node().get_entries();
+    entries.sort();
+    assert_eq!(entries, ["foo", "l3"]);
+}
+
+/// A fake clock that advances one second on every reading.
+fn tick() -> Duration {
+    static NOW: AtomicU64 = AtomicU64::new(1);
+    Duration::from_secs(NOW.fetch_add(1, Ordering::Relaxed))
+}
+
+#[test]
+fn test_ramfs_attrs() {
+    axfs_vfs::set_clock(tick);
+
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+    root.create("f2", VfsNodeType::File).unwrap();
+
+    let foo = root.clone().lookup("foo").unwrap();
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    let f2 = root.clone().lookup("f2").unwrap();
+    assert_eq!(root.get_attr().unwrap().nlink(), 3);
+    assert_eq!(foo.get_attr().unwrap().nlink(), 2);
+
+    // inode numbers are unique
+    let (ino1, ino2) = (f1.get_attr().unwrap().ino(), f2.get_attr().unwrap().ino());
+    assert_ne!(ino1, 0);
+    assert_ne!(ino1, ino2);
+    assert_ne!(
+        foo.get_attr().unwrap().ino(),
+        root.get_attr().unwrap().ino()
+    );
+
+    // writing updates mtime and ctime, reading updates atime
+    let before = f1.get_attr().unwrap();
+    assert!(before.mtime() > Duration::ZERO);
+    assert_eq!(f1.write_at(0, b"hello").unwrap(), 5);
+    let after = f1.get_attr().unwrap();
+    assert!(after.mtime() > before.mtime());
+    assert!(after.ctime() > before.ctime());
+    assert_eq!(after.atime(), before.atime());
+    assert_eq!(after.blocks(), 1);
+    f1.read_at(0, &mut [0; 5]).unwrap();
+    assert!(f1.get_attr().unwrap().atime() > after.atime());
+
+    // creating a child modifies the directory
+    let dir_mtime = foo.get_attr().unwrap().mtime();
+    foo.create("f3", VfsNodeType::File).unwrap();
+    assert!(foo.get_attr().unwrap().mtime() > dir_mtime);
+
+    // chmod, chown and utimens
+    f2.set_attr(&VfsSetAttr {
+        mode: Some(VfsNodePerm::from_bits_truncate(0o600)),
+        uid: Some(1000),
+        gid: Some(100),
+        atime: Some(Duration::from_secs(3)),
+        mtime: Some(Duration::from_secs(5)),
+    })
+    .unwrap();
+    let attr = f2.get_attr().unwrap();
+    assert_eq!(attr.perm().mode(), 0o600);
+    assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    assert_eq!(attr.atime(), Duration::from_secs(3));
+    assert_eq!(attr.mtime(), Duration::from_secs(5));
+    assert!(attr.ctime() > Duration::from_secs(5));
+
+    // unchanged fields are kept
+    f2.set_attr(&VfsSetAttr {
+        gid: Some(0),
+        ..Default::default()
+    })
+    .unwrap();
+    let attr = f2.get_attr().unwrap();
+    assert_eq!(attr.perm().mode(), 0o600);
+    assert_eq!((attr.uid(), attr.gid()), (1000, 0));
+    assert_eq!(attr.mtime(), Duration::from_secs(5));
+}
+
+#[test]
+fn test_ramfs_statfs() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    f1.write_at(0, &[1; 5000]).unwrap();
+    root.link("f2", f1).unwrap();
+
+    // without memory statistics, the filesystem is exactly full
+    let info = ramfs.statfs().unwrap();
+    assert_eq!(info.fs_type, RAMFS_MAGIC);
+    assert_eq!(info.block_size, 4096);
+    assert_eq!(info.files, 3);
+    assert_eq!(info.blocks, (4096 * 2 + 5000u64).div_ceil(4096));
+    assert_eq!(info.blocks_free, 0);
+
+    let ramfs = RamFileSystem::new().with_mem_stats(|| (0x10_0000, 0x8000));
+    let info = ramfs.statfs().unwrap();
+    assert_eq!(info.blocks, 256);
+    assert_eq!(info.blocks_free, 8);
+    assert_eq!(info.blocks_avail, 8);
+    assert_eq!(info.files_free, 8);
+}
@@ -1,7 +1,7 @@
 //! Virtual filesystem interfaces used by [ArceOS](https://github.com/rcore-os/arceos).
 //!
-//! A filesystem is a set of files and directories (symbol links are not
-//! supported currently), collectively referred to as **nodes**, which are
+//! A filesystem is a set of files, directories and symbolic links,
+//! collectively referred to as **nodes**, which are
 //! conceptually similar to [inodes] in Linux. A file system needs to implement
 //! the [`VfsOps`] trait, its files and directories need to implement the
 //! [`VfsNodeOps`] trait.
@@ -14,14 +14,15 @@
 //! - [`statfs()`](VfsOps::statfs): Get the attributes of the filesystem.
 //! - [`root_dir()`](VfsOps::root_dir): Get root directory of the filesystem.
 //!
-//! The [`VfsNodeOps`] trait provides the following operations on a file or a
-//! directory:
+//! The [`VfsNodeOps`] trait provides the following operations on a file, a
+//! directory or a symbolic link:
 //!
 //! | Operation | Description | file/directory |
 //! | --- | --- | --- |
 //! | [`open()`](VfsNodeOps::open) | Do something when the node is opened | both |
 //! | [`release()`](VfsNodeOps::release) | Do something when the node is closed | both |
 //! | [`get_attr()`](VfsNodeOps::get_attr) | Get the attributes of the node | both |
+//! | [`set_attr()`](VfsNodeOps::set_attr) | Change the mode, owner or timestamps of the node | both |
 //! | [`read_at()`](VfsNodeOps::read_at) | Read data from the file | file |
 //! | [`write_at()`](VfsNodeOps::write_at) | Write data to the file | file |
 //! | [`fsync()`](VfsNodeOps::fsync) | Synchronize the file data to disk | file |
@@ -31,6 +32,12 @@
 //! | [`create()`](VfsNodeOps::create) | Create a new node with the given path | directory |
 //! | [`remove()`](VfsNodeOps::remove) | Remove the node with the given path | directory |
 //! | [`read_dir()`](VfsNodeOps::read_dir) | Read directory entries | directory |
+//! | [`symlink()`](VfsNodeOps::symlink) | Create a symbolic link with the given path | directory |
+//! | [`link()`](VfsNodeOps::link) | Create a hard link with the given path | directory |
+//! | [`readlink()`](VfsNodeOps::readlink) | Read the target of the symbolic link | symlink |
+//!
+//! Filesystems stamp node times with [`current_time()`], whose source is
+//! installed by the kernel with [`set_clock()`].
 //!
 //! [inodes]: https://en.wikipedia.org/wiki/Inode
 
@@ -45,8 +52,12 @@ pub mod path;
 
 use alloc::sync::Arc;
 use axerrno::{ax_err, AxError, AxResult};
+use core::time::Duration;
+use spin::Once;
 
-pub use self::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+pub use self::structs::{
+    FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsSetAttr,
+};
 
 /// A wrapper of [`Arc<dyn VfsNodeOps>`].
 pub type VfsNodeRef = Arc<dyn VfsNodeOps>;
@@ -57,6 +68,21 @@ pub type VfsError = AxError;
 /// Alias of [`AxResult`].
 pub type VfsResult<T = ()> = AxResult<T>;
 
+static CLOCK: Once<fn() -> Duration> = Once::new();
+
+/// Sets the clock used by filesystems to stamp node times.
+///
+/// Only the first call takes effect.
+pub fn set_clock(clock: fn() -> Duration) {
+    CLOCK.call_once(|| clock);
+}
+
+/// Returns the current time of the clock set by [`set_clock()`], or zero if
+/// no clock has been set.
+pub fn current_time() -> Duration {
+    CLOCK.get().map_or(Duration::ZERO, |clock| clock())
+}
+
 /// Filesystem operations.
 pub trait VfsOps: Send + Sync {
     /// Do something when the filesystem is mounted.
@@ -79,6 +105,11 @@ pub trait VfsOps: Send + Sync {
         ax_err!(Unsupported)
     }
 
+    /// Write the cached data and metadata of the filesystem to its device.
+    fn sync(&self) -> VfsResult {
+        Ok(())
+    }
+
     /// Get the root directory of the filesystem.
     fn root_dir(&self) -> VfsNodeRef;
 }
@@ -100,6 +131,11 @@ pub trait VfsNodeOps: Send + Sync {
         ax_err!(Unsupported)
     }
 
+    /// Change the permission mode, owner or timestamps of the node.
+    fn set_attr(&self, _changes: &VfsSetAttr) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
     // file operations:
 
     /// Read data from the file at the given offset.
@@ -160,6 +196,34 @@ pub trait VfsNodeOps: Send + Sync {
         ax_err!(Unsupported)
     }
 
+    /// Create a symbolic link with the given `path` in the directory, which
+    /// points to `target`.
+    ///
+    /// The `target` is stored as is, it's not required to exist.
+    fn symlink(&self, _path: &str, _target: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Create a hard link with the given `path` in the directory, which
+    /// refers to the existing `node`.
+    ///
+    /// The `node` must be in the same filesystem.
+    fn link(&self, _path: &str, _node: VfsNodeRef) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    // symbolic link operations:
+
+    /// Read the target of the symbolic link into `buf`, returns the number of
+    /// bytes read.
+    ///
+    /// The size of the target is given by the size in [`get_attr()`].
+    ///
+    /// [`get_attr()`]: VfsNodeOps::get_attr
+    fn readlink(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        ax_err!(InvalidInput)
+    }
+
     /// Convert `&self` to [`&dyn Any`][1] that can use
     /// [`Any::downcast_ref`][2].
     ///
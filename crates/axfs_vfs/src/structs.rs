@@ -1,10 +1,33 @@
-/// Filesystem attributes.
-///
-/// Currently not used.
-#[non_exhaustive]
-pub struct FileSystemInfo;
+use core::time::Duration;
+
+use crate::current_time;
+
+/// Filesystem attributes, as returned by [`VfsOps::statfs`](crate::VfsOps::statfs).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileSystemInfo {
+    /// Filesystem type magic number, the same as Linux's `f_type` where one
+    /// exists.
+    pub fs_type: u64,
+    /// Fundamental block size, in bytes.
+    pub block_size: u64,
+    /// Total number of blocks.
+    pub blocks: u64,
+    /// Number of free blocks.
+    pub blocks_free: u64,
+    /// Number of free blocks available to unprivileged users.
+    pub blocks_avail: u64,
+    /// Total number of inodes (file nodes).
+    pub files: u64,
+    /// Number of free inodes.
+    pub files_free: u64,
+    /// Maximum length of a file name.
+    pub name_len: u64,
+}
 
 /// Node (file/directory) attributes.
+///
+/// Timestamps are durations since the epoch of the clock given to
+/// [`set_clock()`](crate::set_clock).
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub struct VfsNodeAttr {
@@ -16,6 +39,39 @@ pub struct VfsNodeAttr {
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// Inode number, unique within the filesystem.
+    ino: u64,
+    /// Number of hard links.
+    nlink: u64,
+    /// User ID of the owner.
+    uid: u32,
+    /// Group ID of the owner.
+    gid: u32,
+    /// Time of last access.
+    atime: Duration,
+    /// Time of last modification of the content.
+    mtime: Duration,
+    /// Time of last status (attributes) change.
+    ctime: Duration,
+}
+
+/// Node attributes to be changed by [`VfsNodeOps::set_attr()`].
+///
+/// Fields that are `None` are left unchanged.
+///
+/// [`VfsNodeOps::set_attr()`]: crate::VfsNodeOps::set_attr
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VfsSetAttr {
+    /// New permission mode (`chmod`).
+    pub mode: Option<VfsNodePerm>,
+    /// New owner user ID (`chown`).
+    pub uid: Option<u32>,
+    /// New owner group ID (`chown`).
+    pub gid: Option<u32>,
+    /// New time of last access (`utimens`).
+    pub atime: Option<Duration>,
+    /// New time of last modification (`utimens`).
+    pub mtime: Option<Duration>,
 }
 
 bitflags::bitflags! {
@@ -199,34 +255,45 @@ impl VfsNodeType {
 impl VfsNodeAttr {
     /// Creates a new `VfsNodeAttr` with the given permission mode, type, size
     /// and number of blocks.
+    ///
+    /// The inode number and timestamps are zero, the owner is root, and the
+    /// node has one link (two for directories).
     pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
         Self {
             mode,
             ty,
             size,
             blocks,
+            ino: 0,
+            nlink: if ty.is_dir() { 2 } else { 1 },
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
     /// Creates a new `VfsNodeAttr` for a file, with the default file permission.
     pub const fn new_file(size: u64, blocks: u64) -> Self {
-        Self {
-            mode: VfsNodePerm::default_file(),
-            ty: VfsNodeType::File,
-            size,
-            blocks,
-        }
+        Self::new(VfsNodePerm::default_file(), VfsNodeType::File, size, blocks)
     }
 
     /// Creates a new `VfsNodeAttr` for a directory, with the default directory
     /// permission.
     pub const fn new_dir(size: u64, blocks: u64) -> Self {
-        Self {
-            mode: VfsNodePerm::default_dir(),
-            ty: VfsNodeType::Dir,
+        Self::new(VfsNodePerm::default_dir(), VfsNodeType::Dir, size, blocks)
+    }
+
+    /// Creates a new `VfsNodeAttr` for a symbolic link whose target has
+    /// `size` bytes.
+    pub const fn new_symlink(size: u64) -> Self {
+        Self::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
             size,
-            blocks,
-        }
+            0,
+        )
     }
 
     /// Returns the size of the node.
@@ -249,6 +316,115 @@ impl VfsNodeAttr {
         self.mode = perm
     }
 
+    /// Sets the size of the node and the number of blocks it occupies.
+    pub fn set_size(&mut self, size: u64, blocks: u64) {
+        self.size = size;
+        self.blocks = blocks;
+    }
+
+    /// Returns the inode number of the node.
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Sets the inode number of the node.
+    pub fn set_ino(&mut self, ino: u64) {
+        self.ino = ino
+    }
+
+    /// Returns the number of hard links to the node.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Sets the number of hard links to the node.
+    pub fn set_nlink(&mut self, nlink: u64) {
+        self.nlink = nlink
+    }
+
+    /// Returns the user ID of the owner.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the owner.
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Sets the owner of the node.
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
+    /// Returns the time of last access.
+    pub const fn atime(&self) -> Duration {
+        self.atime
+    }
+
+    /// Returns the time of last modification of the content.
+    pub const fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    /// Returns the time of last status change.
+    pub const fn ctime(&self) -> Duration {
+        self.ctime
+    }
+
+    /// Sets the access, modification and status change times of the node.
+    pub fn set_times(&mut self, atime: Duration, mtime: Duration, ctime: Duration) {
+        self.atime = atime;
+        self.mtime = mtime;
+        self.ctime = ctime;
+    }
+
+    /// Sets all the timestamps to the current time, used when a node is
+    /// created.
+    pub fn touch(&mut self) {
+        let now = current_time();
+        self.set_times(now, now, now);
+    }
+
+    /// Marks the node as accessed at the current time.
+    pub fn touch_accessed(&mut self) {
+        self.atime = current_time();
+    }
+
+    /// Marks the content as modified at the current time.
+    pub fn touch_modified(&mut self) {
+        let now = current_time();
+        self.mtime = now;
+        self.ctime = now;
+    }
+
+    /// Marks the status as changed at the current time.
+    pub fn touch_status_changed(&mut self) {
+        self.ctime = current_time();
+    }
+
+    /// Applies the changes in `changes`, the status change time is updated
+    /// to the current time.
+    pub fn apply(&mut self, changes: &VfsSetAttr) {
+        if let Some(mode) = changes.mode {
+            self.mode = mode;
+        }
+        if let Some(uid) = changes.uid {
+            self.uid = uid;
+        }
+        if let Some(gid) = changes.gid {
+            self.gid = gid;
+        }
+        if let Some(atime) = changes.atime {
+            self.atime = atime;
+        }
+        if let Some(mtime) = changes.mtime {
+            self.mtime = mtime;
+        }
+        self.ctime = current_time();
+    }
+
     /// Returns the type of the node.
     pub const fn file_type(&self) -> VfsNodeType {
         self.ty
@@ -263,6 +439,11 @@ impl VfsNodeAttr {
     pub const fn is_dir(&self) -> bool {
         self.ty.is_dir()
     }
+
+    /// Whether the node is a symbolic link.
+    pub const fn is_symlink(&self) -> bool {
+        self.ty.is_symlink()
+    }
 }
 
 impl VfsDirEntry {
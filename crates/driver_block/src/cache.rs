@@ -0,0 +1,254 @@
+//! A write-back block cache with least-recently-used replacement.
+//!
+//! [`BlockCache`] wraps any [`BlockDriverOps`] device and implements it
+//! again, so it can sit between a driver and the filesystems on it. Reads are
+//! served from the cached blocks, and writes only mark them dirty, they reach
+//! the device when evicted or when [`BlockCache::sync`] or
+//! [`BlockDriverOps::flush`] is called.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+
+use crate::BlockDriverOps;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+/// The default number of blocks in a cache, 1 MiB of 512-byte blocks.
+pub const DEFAULT_CAPACITY: usize = 2048;
+
+/// A callback with the id and the data of each block written to the device.
+pub type WriteHook = Box<dyn FnMut(u64, &[u8]) + Send + Sync>;
+
+/// Counters of a [`BlockCache`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Block accesses served from the cache.
+    pub hits: u64,
+    /// Block accesses that needed a new cache slot.
+    pub misses: u64,
+    /// Dirty blocks written to the device.
+    pub write_backs: u64,
+}
+
+struct CachedBlock {
+    data: Box<[u8]>,
+    dirty: bool,
+    /// The time of the last access, the key in [`BlockCache::lru`].
+    last_used: u64,
+}
+
+/// A write-back cache of the blocks of a device.
+pub struct BlockCache<D> {
+    dev: D,
+    capacity: usize,
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// The cached block ids by the time of their last access, so the first
+    /// one is the least recently used.
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    stats: CacheStats,
+    hook: Option<WriteHook>,
+}
+
+impl<D: BlockDriverOps> BlockCache<D> {
+    /// Creates a cache of [`DEFAULT_CAPACITY`] blocks over `dev`.
+    pub fn new(dev: D) -> Self {
+        Self::with_capacity(dev, DEFAULT_CAPACITY)
+    }
+
+    /// Creates a cache of at most `capacity` blocks over `dev`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(dev: D, capacity: usize) -> Self {
+        assert!(capacity > 0, "block cache capacity must not be zero");
+        Self {
+            dev,
+            capacity,
+            blocks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+            hook: None,
+        }
+    }
+
+    /// Returns the underlying device.
+    pub fn device(&self) -> &D {
+        &self.dev
+    }
+
+    /// Returns the maximum number of cached blocks.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of blocks not written to the device yet.
+    pub fn dirty_blocks(&self) -> usize {
+        self.blocks.values().filter(|b| b.dirty).count()
+    }
+
+    /// Returns the counters of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Sets the callback invoked for each block written to the device, or
+    /// removes it with `None`.
+    pub fn set_write_hook(&mut self, hook: Option<WriteHook>) {
+        self.hook = hook;
+    }
+
+    /// Reads `buf.len()` bytes at `offset` within the block `block_id`.
+    pub fn read_at(&mut self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        self.check(offset, buf.len())?;
+        let block = self.load(block_id, true)?;
+        buf.copy_from_slice(&block.data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    /// Writes `buf` at `offset` within the block `block_id`. The block is
+    /// only read from the device first if it is partially overwritten.
+    pub fn write_at(&mut self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        self.check(offset, buf.len())?;
+        let whole = buf.len() == self.dev.block_size();
+        let block = self.load(block_id, !whole)?;
+        block.data[offset..offset + buf.len()].copy_from_slice(buf);
+        block.dirty = true;
+        Ok(())
+    }
+
+    /// Writes all dirty blocks to the device in block order, without flushing
+    /// the device itself.
+    pub fn sync(&mut self) -> DevResult {
+        for (&block_id, block) in self.blocks.iter_mut().filter(|(_, b)| b.dirty) {
+            write_back(&mut self.dev, &mut self.hook, block_id, &block.data)?;
+            self.stats.write_backs += 1;
+            block.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Writes all dirty blocks to the device and drops all cached blocks, so
+    /// that the changes made to the device bypassing the cache are seen.
+    pub fn invalidate(&mut self) -> DevResult {
+        self.sync()?;
+        self.blocks.clear();
+        self.lru.clear();
+        Ok(())
+    }
+
+    fn check(&self, offset: usize, len: usize) -> DevResult {
+        if offset + len > self.dev.block_size() {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(())
+    }
+
+    /// Returns the cached block `block_id`, reading it from the device if it
+    /// is not cached and `read` is true, or zeroing it otherwise.
+    fn load(&mut self, block_id: u64, read: bool) -> DevResult<&mut CachedBlock> {
+        if block_id >= self.dev.num_blocks() {
+            return Err(DevError::Io);
+        }
+        self.clock += 1;
+        if let Some(block) = self.blocks.get_mut(&block_id) {
+            self.stats.hits += 1;
+            self.lru.remove(&block.last_used);
+            self.lru.insert(self.clock, block_id);
+            block.last_used = self.clock;
+            return Ok(self.blocks.get_mut(&block_id).unwrap());
+        }
+        self.stats.misses += 1;
+        if self.blocks.len() >= self.capacity {
+            self.evict()?;
+        }
+        let mut data = vec![0u8; self.dev.block_size()].into_boxed_slice();
+        if read {
+            self.dev.read_block(block_id, &mut data)?;
+        }
+        self.lru.insert(self.clock, block_id);
+        let block = CachedBlock {
+            data,
+            dirty: false,
+            last_used: self.clock,
+        };
+        Ok(self.blocks.entry(block_id).or_insert(block))
+    }
+
+    /// Drops the least recently used block, writing it back if dirty.
+    fn evict(&mut self) -> DevResult {
+        let Some((&time, &block_id)) = self.lru.first_key_value() else {
+            return Ok(());
+        };
+        let block = &self.blocks[&block_id];
+        if block.dirty {
+            write_back(&mut self.dev, &mut self.hook, block_id, &block.data)?;
+            self.stats.write_backs += 1;
+        }
+        self.lru.remove(&time);
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+}
+
+fn write_back<D: BlockDriverOps>(
+    dev: &mut D,
+    hook: &mut Option<WriteHook>,
+    block_id: u64,
+    data: &[u8],
+) -> DevResult {
+    dev.write_block(block_id, data)?;
+    if let Some(hook) = hook {
+        hook(block_id, data);
+    }
+    Ok(())
+}
+
+impl<D: BlockDriverOps> BaseDriverOps for BlockCache<D> {
+    fn device_name(&self) -> &str {
+        self.dev.device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl<D: BlockDriverOps> BlockDriverOps for BlockCache<D> {
+    fn num_blocks(&self) -> u64 {
+        self.dev.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let block_size = self.dev.block_size();
+        if !buf.len().is_multiple_of(block_size) {
+            return Err(DevError::InvalidParam);
+        }
+        for (i, chunk) in buf.chunks_exact_mut(block_size).enumerate() {
+            self.read_at(block_id + i as u64, 0, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let block_size = self.dev.block_size();
+        if !buf.len().is_multiple_of(block_size) {
+            return Err(DevError::InvalidParam);
+        }
+        for (i, chunk) in buf.chunks_exact(block_size).enumerate() {
+            self.write_at(block_id + i as u64, 0, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.sync()?;
+        self.dev.flush()
+    }
+}
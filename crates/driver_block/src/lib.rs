@@ -1,15 +1,27 @@
 //! Common traits and types for block storage device drivers (i.e. disk).
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(doc_auto_cfg)]
 #![feature(const_trait_impl)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
+#[cfg(feature = "partition")]
+pub mod partition;
+
 #[cfg(feature = "ramdisk")]
 pub mod ramdisk;
 
 #[cfg(feature = "bcm2835-sdhci")]
 pub mod bcm2835sdhci;
 
+#[cfg(all(test, feature = "cache", feature = "partition"))]
+mod tests;
+
 #[doc(no_inline)]
 pub use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
 
@@ -37,3 +49,37 @@ pub trait BlockDriverOps: BaseDriverOps {
     /// Flushes the device to write all pending data to the storage.
     fn flush(&mut self) -> DevResult;
 }
+
+#[cfg(feature = "alloc")]
+impl BaseDriverOps for alloc::boxed::Box<dyn BlockDriverOps> {
+    fn device_name(&self) -> &str {
+        (**self).device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        (**self).device_type()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl BlockDriverOps for alloc::boxed::Box<dyn BlockDriverOps> {
+    fn num_blocks(&self) -> u64 {
+        (**self).num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        (**self).block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        (**self).read_block(block_id, buf)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        (**self).write_block(block_id, buf)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        (**self).flush()
+    }
+}
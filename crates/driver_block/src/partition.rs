@@ -0,0 +1,417 @@
+//! MBR and GPT partition tables.
+//!
+//! [`read_partitions`] parses the partition table of a device, and each
+//! partition found can be wrapped in a [`Partition`], which implements
+//! [`BlockDriverOps`] again with the blocks relative to its start.
+//!
+//! Partitions are numbered as in Linux: the four primary MBR entries are 1 to
+//! 4 by their slot, the logical partitions in an extended one from 5, and the
+//! GPT entries from 1 by their index in the table.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::BlockDriverOps;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+/// The MBR partition type of the protective entry of a GPT disk.
+const MBR_TYPE_GPT: u8 = 0xee;
+/// The MBR partition type of an EFI system partition.
+const MBR_TYPE_EFI: u8 = 0xef;
+/// The MBR partition types of extended partitions (CHS, LBA and Linux).
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// The maximum number of logical partitions followed in an extended one,
+/// against loops in the chain.
+const MAX_LOGICAL: u32 = 64;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_HEADER_SIZE: usize = 92;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+/// The maximum number of GPT entries read, 128 is the usual size of the table.
+const GPT_MAX_ENTRIES: usize = 256;
+/// The GPT attribute bit of a partition bootable by legacy BIOS.
+const GPT_ATTR_BOOTABLE: u64 = 1 << 2;
+
+/// A GUID, as stored on disk with the first three fields little-endian.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// The partition type of an EFI system partition.
+    pub const EFI_SYSTEM: Self = Self([
+        0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9,
+        0x3b,
+    ]);
+
+    /// Returns true if all bytes are zero, i.e., an unused GPT entry.
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9]
+        )?;
+        b[10..].iter().try_for_each(|x| write!(f, "{x:02X}"))
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// The type of a partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// An MBR partition with its type byte.
+    Mbr(u8),
+    /// A GPT partition with its type GUID.
+    Gpt(Guid),
+}
+
+impl PartitionKind {
+    /// Returns true for an EFI system partition, which holds the boot loader.
+    pub fn is_efi_system(&self) -> bool {
+        match self {
+            Self::Mbr(ty) => *ty == MBR_TYPE_EFI,
+            Self::Gpt(guid) => *guid == Guid::EFI_SYSTEM,
+        }
+    }
+}
+
+/// A partition found in a partition table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The number of the partition, from 1.
+    pub number: u32,
+    /// The first block of the partition.
+    pub start: u64,
+    /// The number of blocks in the partition.
+    pub num_blocks: u64,
+    /// The type of the partition.
+    pub kind: PartitionKind,
+    /// The MBR active flag, or the GPT legacy BIOS bootable attribute.
+    pub bootable: bool,
+    /// The GPT partition name, empty for MBR.
+    pub name: String,
+}
+
+impl PartitionInfo {
+    /// Returns true if the partition holds the boot loader rather than data,
+    /// i.e., it is bootable or an EFI system partition.
+    pub fn is_boot(&self) -> bool {
+        self.bootable || self.kind.is_efi_system()
+    }
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// The CRC32 (IEEE 802.3) of `data`, used by the GPT checksums.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// Reads the partition table of `dev`.
+///
+/// A GPT disk is recognized by the protective entry of its MBR, and the
+/// backup GPT header in the last block is used if the primary one is
+/// corrupted. Returns an empty list if the device has no valid partition
+/// table, e.g., it holds a filesystem directly.
+pub fn read_partitions<D: BlockDriverOps + ?Sized>(dev: &mut D) -> DevResult<Vec<PartitionInfo>> {
+    let block_size = dev.block_size();
+    if block_size < 512 || dev.num_blocks() < 2 {
+        return Ok(Vec::new());
+    }
+    let mut mbr = vec![0u8; block_size];
+    dev.read_block(0, &mut mbr)?;
+    let Some(entries) = parse_mbr(&mbr, dev.num_blocks()) else {
+        return Ok(Vec::new());
+    };
+    if entries
+        .iter()
+        .any(|(_, e)| e.kind == PartitionKind::Mbr(MBR_TYPE_GPT))
+    {
+        let last = dev.num_blocks() - 1;
+        return match read_gpt(dev, 1)? {
+            Some(parts) => Ok(parts),
+            None => Ok(read_gpt(dev, last)?.unwrap_or_default()),
+        };
+    }
+
+    let mut parts = Vec::new();
+    for (_, entry) in entries {
+        match entry.kind {
+            PartitionKind::Mbr(ty) if MBR_TYPES_EXTENDED.contains(&ty) => {
+                read_logical(dev, entry.start, entry.num_blocks, &mut parts)?;
+            }
+            _ => parts.push(entry),
+        }
+    }
+    parts.sort_by_key(|p| p.number);
+    Ok(parts)
+}
+
+/// Parses an MBR or EBR, returns its used entries with their slots, or `None`
+/// if it is not valid. The start of an entry is relative to the block.
+fn parse_mbr(block: &[u8], num_blocks: u64) -> Option<Vec<(usize, PartitionInfo)>> {
+    if block[510..512] != MBR_SIGNATURE {
+        return None;
+    }
+    let mut entries = Vec::new();
+    for slot in 0..4 {
+        let entry = &block[MBR_ENTRIES_OFFSET + slot * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        let (status, ty) = (entry[0], entry[4]);
+        if status != 0 && status != 0x80 {
+            // boot code of a volume without a partition table
+            return None;
+        }
+        let start = read_u32(entry, 8) as u64;
+        let len = read_u32(entry, 12) as u64;
+        if ty == 0 || len == 0 {
+            continue;
+        }
+        // the protective entry of a GPT disk may cover more than the disk
+        if ty != MBR_TYPE_GPT && (start == 0 || start + len > num_blocks) {
+            return None;
+        }
+        entries.push((
+            slot,
+            PartitionInfo {
+                number: slot as u32 + 1,
+                start,
+                num_blocks: len,
+                kind: PartitionKind::Mbr(ty),
+                bootable: status == 0x80,
+                name: String::new(),
+            },
+        ));
+    }
+    (!entries.is_empty()).then_some(entries)
+}
+
+/// Follows the chain of EBRs in the extended partition at `ext_start`, and
+/// appends the logical partitions to `parts`.
+fn read_logical<D: BlockDriverOps + ?Sized>(
+    dev: &mut D,
+    ext_start: u64,
+    ext_len: u64,
+    parts: &mut Vec<PartitionInfo>,
+) -> DevResult {
+    let mut block = vec![0u8; dev.block_size()];
+    let mut ebr = ext_start;
+    for number in 5..5 + MAX_LOGICAL {
+        dev.read_block(ebr, &mut block)?;
+        let Some(entries) = parse_mbr(&block, dev.num_blocks()) else {
+            break;
+        };
+        let mut next = None;
+        for (slot, mut entry) in entries {
+            match (slot, entry.kind) {
+                (0, _) => {
+                    // relative to this EBR
+                    entry.number = number;
+                    entry.start += ebr;
+                    if entry.start + entry.num_blocks <= ext_start + ext_len {
+                        parts.push(entry);
+                    }
+                }
+                (1, PartitionKind::Mbr(ty)) if MBR_TYPES_EXTENDED.contains(&ty) => {
+                    // relative to the extended partition
+                    next = Some(ext_start + entry.start);
+                }
+                _ => {}
+            }
+        }
+        match next {
+            Some(block_id) if block_id > ebr && block_id < ext_start + ext_len => ebr = block_id,
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// Reads the GPT with the header at block `header_lba`, returns `None` if
+/// the header or the entries are not valid.
+fn read_gpt<D: BlockDriverOps + ?Sized>(
+    dev: &mut D,
+    header_lba: u64,
+) -> DevResult<Option<Vec<PartitionInfo>>> {
+    let block_size = dev.block_size();
+    let mut header = vec![0u8; block_size];
+    dev.read_block(header_lba, &mut header)?;
+    let header_size = read_u32(&header, 12) as usize;
+    if &header[..8] != GPT_SIGNATURE || !(GPT_HEADER_SIZE..=block_size).contains(&header_size) {
+        return Ok(None);
+    }
+    let checksum = read_u32(&header, 16);
+    header[16..20].fill(0);
+    if crc32(&header[..header_size]) != checksum || read_u64(&header, 24) != header_lba {
+        return Ok(None);
+    }
+
+    let (first_usable, last_usable) = (read_u64(&header, 40), read_u64(&header, 48));
+    let entries_lba = read_u64(&header, 72);
+    let count = read_u32(&header, 80) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    if count > GPT_MAX_ENTRIES
+        || entry_size < GPT_MIN_ENTRY_SIZE
+        || !entry_size.is_multiple_of(8)
+        || !block_size.is_multiple_of(entry_size)
+    {
+        return Ok(None);
+    }
+    let blocks = (count * entry_size).div_ceil(block_size);
+    if entries_lba + blocks as u64 > dev.num_blocks() {
+        return Ok(None);
+    }
+    let mut table = vec![0u8; blocks * block_size];
+    dev.read_block(entries_lba, &mut table)?;
+    if crc32(&table[..count * entry_size]) != read_u32(&header, 88) {
+        return Ok(None);
+    }
+
+    let mut parts = Vec::new();
+    for (i, entry) in table.chunks_exact(entry_size).take(count).enumerate() {
+        let kind = Guid(entry[..16].try_into().unwrap());
+        let (first, last) = (read_u64(entry, 32), read_u64(entry, 40));
+        if kind.is_zero() || first < first_usable || last > last_usable || first > last {
+            continue;
+        }
+        let name = char::decode_utf16(
+            entry[56..128]
+                .as_chunks()
+                .0
+                .iter()
+                .map(|&c| u16::from_le_bytes(c))
+                .take_while(|&c| c != 0),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+        parts.push(PartitionInfo {
+            number: i as u32 + 1,
+            start: first,
+            num_blocks: last - first + 1,
+            kind: PartitionKind::Gpt(kind),
+            bootable: read_u64(entry, 48) & GPT_ATTR_BOOTABLE != 0,
+            name,
+        });
+    }
+    Ok(Some(parts))
+}
+
+/// A range of blocks of a device, e.g., a partition or the whole disk.
+pub struct Partition<D> {
+    dev: D,
+    start: u64,
+    num_blocks: u64,
+}
+
+impl<D: BlockDriverOps> Partition<D> {
+    /// Creates a partition of `num_blocks` blocks from the block `start` of
+    /// `dev`.
+    ///
+    /// Returns [`DevError::InvalidParam`] if it goes beyond the device.
+    pub fn new(dev: D, start: u64, num_blocks: u64) -> DevResult<Self> {
+        if start
+            .checked_add(num_blocks)
+            .is_none_or(|end| end > dev.num_blocks())
+        {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(Self {
+            dev,
+            start,
+            num_blocks,
+        })
+    }
+
+    /// Creates a partition covering the whole device.
+    pub fn whole(dev: D) -> Self {
+        let num_blocks = dev.num_blocks();
+        Self {
+            dev,
+            start: 0,
+            num_blocks,
+        }
+    }
+
+    /// Returns the first block of the partition on the device.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the underlying device.
+    pub fn device(&self) -> &D {
+        &self.dev
+    }
+
+    /// Returns the block on the device of `block_id` of the partition, if the
+    /// `len` bytes from it are in the partition.
+    fn translate(&self, block_id: u64, len: usize) -> DevResult<u64> {
+        let blocks = len.div_ceil(self.dev.block_size()) as u64;
+        match block_id.checked_add(blocks) {
+            Some(end) if end <= self.num_blocks => Ok(self.start + block_id),
+            _ => Err(DevError::Io),
+        }
+    }
+}
+
+impl<D: BlockDriverOps> BaseDriverOps for Partition<D> {
+    fn device_name(&self) -> &str {
+        self.dev.device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl<D: BlockDriverOps> BlockDriverOps for Partition<D> {
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let block_id = self.translate(block_id, buf.len())?;
+        self.dev.read_block(block_id, buf)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let block_id = self.translate(block_id, buf.len())?;
+        self.dev.write_block(block_id, buf)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.dev.flush()
+    }
+}
@@ -0,0 +1,303 @@
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::cache::BlockCache;
+use crate::partition::{self, Guid, Partition, PartitionKind};
+use crate::*;
+
+const BLOCK_SIZE: usize = 512;
+
+/// A RAM disk that counts the accesses to it.
+#[derive(Default)]
+struct MockDisk {
+    data: Vec<u8>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    flushes: usize,
+}
+
+impl MockDisk {
+    fn new(blocks: usize) -> Self {
+        let data = (0..blocks * BLOCK_SIZE)
+            .map(|i| (i / BLOCK_SIZE) as u8)
+            .collect();
+        Self {
+            data,
+            ..Default::default()
+        }
+    }
+}
+
+impl BaseDriverOps for MockDisk {
+    fn device_name(&self) -> &str {
+        "mock"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for MockDisk {
+    fn num_blocks(&self) -> u64 {
+        (self.data.len() / BLOCK_SIZE) as u64
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let start = block_id as usize * BLOCK_SIZE;
+        buf.copy_from_slice(&self.data[start..start + buf.len()]);
+        self.reads.push(block_id);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let start = block_id as usize * BLOCK_SIZE;
+        self.data[start..start + buf.len()].copy_from_slice(buf);
+        self.writes.push(block_id);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_read_write_back() {
+    let mut cache = BlockCache::with_capacity(MockDisk::new(16), 4);
+    let mut buf = [0u8; 4];
+    cache.read_at(3, 100, &mut buf).unwrap();
+    assert_eq!(buf, [3; 4]);
+    cache.read_at(3, 0, &mut buf).unwrap();
+    assert_eq!(cache.device().reads, [3]);
+
+    // a partial write reads the block, a whole one does not
+    cache.write_at(5, 10, &[0xaa; 4]).unwrap();
+    cache.write_block(6, &[0xbb; BLOCK_SIZE]).unwrap();
+    assert_eq!(cache.device().reads, [3, 5]);
+    assert!(cache.device().writes.is_empty());
+    assert_eq!(cache.dirty_blocks(), 2);
+
+    let mut block = [0u8; BLOCK_SIZE];
+    cache.read_block(5, &mut block).unwrap();
+    assert_eq!(block[9..15], [5, 0xaa, 0xaa, 0xaa, 0xaa, 5]);
+
+    cache.flush().unwrap();
+    assert_eq!(cache.device().writes, [5, 6]);
+    assert_eq!(cache.device().flushes, 1);
+    assert_eq!(cache.dirty_blocks(), 0);
+    assert_eq!(cache.device().data[6 * BLOCK_SIZE], 0xbb);
+    // clean blocks are not written again
+    cache.flush().unwrap();
+    assert_eq!(cache.device().writes.len(), 2);
+
+    assert!(matches!(
+        cache.read_at(3, 510, &mut buf),
+        Err(DevError::InvalidParam)
+    ));
+    assert!(matches!(cache.read_at(16, 0, &mut buf), Err(DevError::Io)));
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.write_backs), (2, 3, 2));
+}
+
+#[test]
+fn test_lru_eviction() {
+    let mut cache = BlockCache::with_capacity(MockDisk::new(16), 3);
+    let mut buf = [0u8; 1];
+    cache.write_at(0, 0, &[0xff]).unwrap();
+    cache.read_at(1, 0, &mut buf).unwrap();
+    cache.read_at(2, 0, &mut buf).unwrap();
+    // block 0 is used again, so block 1 is the least recently used
+    cache.read_at(0, 0, &mut buf).unwrap();
+    cache.read_at(3, 0, &mut buf).unwrap();
+    assert!(cache.device().writes.is_empty());
+    cache.read_at(1, 0, &mut buf).unwrap();
+    assert_eq!(cache.device().reads, [0, 1, 2, 3, 1]);
+
+    // block 2 made room for block 1, the dirty block 0 is next and written
+    // back when evicted
+    cache.read_at(4, 0, &mut buf).unwrap();
+    assert_eq!(cache.device().writes, [0]);
+    assert_eq!(cache.device().data[0], 0xff);
+    cache.read_at(0, 0, &mut buf).unwrap();
+    assert_eq!(buf, [0xff]);
+}
+
+#[test]
+fn test_invalidate() {
+    let mut cache = BlockCache::with_capacity(MockDisk::new(16), 4);
+    let mut buf = [0u8; 1];
+    cache.read_at(1, 0, &mut buf).unwrap();
+    cache.write_at(2, 0, &[0xff]).unwrap();
+    cache.invalidate().unwrap();
+    assert_eq!(cache.device().writes, [2]);
+    assert_eq!(cache.dirty_blocks(), 0);
+
+    // the blocks are read from the device again
+    cache.read_at(1, 0, &mut buf).unwrap();
+    cache.read_at(2, 0, &mut buf).unwrap();
+    assert_eq!(buf, [0xff]);
+    assert_eq!(cache.device().reads, [1, 2, 1, 2]);
+}
+
+#[test]
+fn test_write_hook() {
+    let mut cache = BlockCache::with_capacity(MockDisk::new(16), 8);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let hook_log = log.clone();
+    cache.set_write_hook(Some(Box::new(move |block_id, data: &[u8]| {
+        hook_log.lock().unwrap().push((block_id, data[0]));
+    })));
+    cache.write_at(7, 0, &[1]).unwrap();
+    cache.write_at(2, 0, &[2]).unwrap();
+    cache.write_at(7, 0, &[3]).unwrap();
+    assert!(log.lock().unwrap().is_empty());
+    cache.sync().unwrap();
+    assert_eq!(*log.lock().unwrap(), [(2, 2), (7, 3)]);
+    assert_eq!(cache.device().flushes, 0);
+}
+
+/// Writes an MBR entry in `slot` of the block at `block_id`.
+fn mbr_entry(disk: &mut MockDisk, block_id: u64, slot: usize, ty: u8, start: u32, len: u32) {
+    let block = &mut disk.data[block_id as usize * BLOCK_SIZE..][..BLOCK_SIZE];
+    let entry = &mut block[446 + slot * 16..][..16];
+    entry[0] = if slot == 0 && block_id == 0 { 0x80 } else { 0 };
+    entry[1..4].fill(0);
+    entry[4] = ty;
+    entry[5..8].fill(0);
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&len.to_le_bytes());
+    block[510..512].copy_from_slice(&[0x55, 0xaa]);
+}
+
+#[test]
+fn test_mbr() {
+    let mut disk = MockDisk::new(256);
+    // the boot code of a volume with a FAT boot sector is not a table
+    disk.data[510..512].copy_from_slice(&[0x55, 0xaa]);
+    assert!(partition::read_partitions(&mut disk).unwrap().is_empty());
+
+    let mut disk = MockDisk::new(256);
+    disk.data.fill(0);
+    mbr_entry(&mut disk, 0, 0, 0x0c, 8, 56);
+    mbr_entry(&mut disk, 0, 2, 0x05, 64, 192);
+    // two logical partitions, the second EBR is relative to the extended one
+    mbr_entry(&mut disk, 64, 0, 0x83, 2, 62);
+    mbr_entry(&mut disk, 64, 1, 0x05, 64, 128);
+    mbr_entry(&mut disk, 128, 0, 0x83, 4, 124);
+    let parts = partition::read_partitions(&mut disk).unwrap();
+    let summary: Vec<_> = parts
+        .iter()
+        .map(|p| (p.number, p.start, p.num_blocks, p.kind, p.bootable))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (1, 8, 56, PartitionKind::Mbr(0x0c), true),
+            (5, 66, 62, PartitionKind::Mbr(0x83), false),
+            (6, 132, 124, PartitionKind::Mbr(0x83), false),
+        ]
+    );
+    assert!(parts[0].is_boot() && !parts[1].is_boot());
+
+    // an entry beyond the disk makes the table invalid
+    mbr_entry(&mut disk, 0, 1, 0x83, 200, 100);
+    assert!(partition::read_partitions(&mut disk).unwrap().is_empty());
+}
+
+/// Writes a GPT header at `lba` with its entries at `entries_lba`.
+fn gpt_header(disk: &mut MockDisk, lba: u64, entries_lba: u64, entries: &[u8]) {
+    let mut header = [0u8; BLOCK_SIZE];
+    header[..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x10000u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92u32.to_le_bytes());
+    header[24..32].copy_from_slice(&lba.to_le_bytes());
+    header[40..48].copy_from_slice(&34u64.to_le_bytes());
+    header[48..56].copy_from_slice(&(disk.num_blocks() - 34).to_le_bytes());
+    header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+    header[80..84].copy_from_slice(&128u32.to_le_bytes());
+    header[84..88].copy_from_slice(&128u32.to_le_bytes());
+    header[88..92].copy_from_slice(&partition::crc32(entries).to_le_bytes());
+    let crc = partition::crc32(&header[..92]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+    let start = lba as usize * BLOCK_SIZE;
+    disk.data[start..start + BLOCK_SIZE].copy_from_slice(&header);
+    let start = entries_lba as usize * BLOCK_SIZE;
+    disk.data[start..start + entries.len()].copy_from_slice(entries);
+}
+
+#[test]
+fn test_gpt() {
+    assert_eq!(partition::crc32(b"123456789"), 0xcbf4_3926);
+
+    let mut disk = MockDisk::new(256);
+    mbr_entry(&mut disk, 0, 0, 0xee, 1, u32::MAX);
+    let mut entries = vec![0u8; 128 * 128];
+    let linux = Guid([0xaf; 16]);
+    let mut entry = |i: usize, ty: Guid, first: u64, last: u64, attrs: u64, name: &str| {
+        let entry = &mut entries[i * 128..][..128];
+        entry[..16].copy_from_slice(&ty.0);
+        entry[32..40].copy_from_slice(&first.to_le_bytes());
+        entry[40..48].copy_from_slice(&last.to_le_bytes());
+        entry[48..56].copy_from_slice(&attrs.to_le_bytes());
+        for (j, c) in name.encode_utf16().enumerate() {
+            entry[56 + j * 2..58 + j * 2].copy_from_slice(&c.to_le_bytes());
+        }
+    };
+    entry(0, Guid::EFI_SYSTEM, 34, 99, 0, "boot");
+    entry(2, linux, 100, 221, 0, "data");
+    gpt_header(&mut disk, 1, 2, &entries);
+
+    let parts = partition::read_partitions(&mut disk).unwrap();
+    let summary: Vec<_> = parts
+        .iter()
+        .map(|p| {
+            (
+                p.number,
+                p.start,
+                p.num_blocks,
+                p.name.as_str(),
+                p.is_boot(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [(1, 34, 66, "boot", true), (3, 100, 122, "data", false)]
+    );
+    assert_eq!(
+        Guid::EFI_SYSTEM.to_string(),
+        "C12A7328-F81F-11D2-BA4B-00A0C93EC93B"
+    );
+
+    // the backup header is used if the primary one is corrupted
+    gpt_header(&mut disk, 255, 223, &entries);
+    disk.data[BLOCK_SIZE + 40] ^= 1;
+    assert_eq!(partition::read_partitions(&mut disk).unwrap(), parts);
+    disk.data[255 * BLOCK_SIZE] = 0;
+    assert!(partition::read_partitions(&mut disk).unwrap().is_empty());
+}
+
+#[test]
+fn test_partition_dev() {
+    assert!(matches!(
+        Partition::new(MockDisk::new(16), 8, 9),
+        Err(DevError::InvalidParam)
+    ));
+    let mut part = Partition::new(MockDisk::new(16), 8, 4).unwrap();
+    assert_eq!(part.num_blocks(), 4);
+    let mut buf = [0u8; 2 * BLOCK_SIZE];
+    part.read_block(2, &mut buf).unwrap();
+    assert_eq!((buf[0], buf[BLOCK_SIZE]), (10, 11));
+    assert!(matches!(part.read_block(3, &mut buf), Err(DevError::Io)));
+    part.write_block(0, &[0xcc; BLOCK_SIZE]).unwrap();
+    assert_eq!(part.device().writes, [8]);
+
+    let whole = Partition::whole(MockDisk::new(16));
+    assert_eq!((whole.start(), whole.num_blocks()), (0, 16));
+}
@@ -64,6 +64,18 @@ pub trait NetDriverOps: BaseDriverOps {
     /// Allocate a memory buffer of a specified size for network transmission,
     /// returns [`DevResult`]
     fn alloc_tx_buffer(&mut self, size: usize) -> DevResult<NetBufPtr>;
+
+    /// The IRQ number that the NIC raises when packets are received or
+    /// transmitted, or `None` if it must be polled.
+    fn irq_num(&self) -> Option<usize> {
+        None
+    }
+
+    /// Acknowledges the interrupt of the NIC, so that it stops asserting the
+    /// IRQ line. Returns whether there was an interrupt.
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
 }
 
 /// A raw buffer struct for network device.
@@ -99,4 +99,14 @@ fn test_percpu() {
         assert_eq!(s.foo, 0x2333);
         assert_eq!(s.bar, 100);
     });
+
+    #[cfg(not(feature = "sp-naive"))]
+    unsafe {
+        assert_eq!(base + USIZE.offset(), USIZE.remote_ptr(0) as usize);
+        assert_eq!(
+            percpu_area_base(1) + USIZE.offset(),
+            USIZE.remote_ptr(1) as usize
+        );
+        assert_eq!(STRUCT.remote_ref_raw(0).foo, 0x2333);
+    }
 }
@@ -63,6 +63,12 @@ pub fn gen_current_ptr(symbol: &Ident, ty: &Type) -> proc_macro2::TokenStream {
     })
 }
 
+pub fn gen_remote_ptr(_symbol: &Ident, ty: &Type) -> proc_macro2::TokenStream {
+    macos_unimplemented(quote! {
+        (percpu::percpu_area_base(cpu_id) + self.offset()) as *const #ty
+    })
+}
+
 pub fn gen_read_current_raw(symbol: &Ident, ty: &Type) -> proc_macro2::TokenStream {
     let ty_str = quote!(#ty).to_string();
     let rv64_op = match ty_str.as_str() {
@@ -64,6 +64,7 @@ impl<T> CFSTask<T> {
 
     fn set_vruntime(&self, v: isize) {
         self.init_vruntime.store(v, Ordering::Release);
+        self.delta.store(0, Ordering::Release);
     }
 
     // Simple Implementation: no change in vruntime.
@@ -0,0 +1,288 @@
+use alloc::{collections::BTreeMap, collections::VecDeque, sync::Arc};
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::BaseScheduler;
+
+/// The fixed-point scale of the CPU bandwidth, `BW_UNIT` means 100%.
+const BW_UNIT: u64 = 1 << 20;
+
+/// Timing parameters of a deadline task, in timer ticks.
+///
+/// The task is given `runtime` ticks of CPU time in every `period` ticks, and
+/// each of these runtimes should be completed within `deadline` ticks after
+/// the start of the period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EDFParams {
+    /// The worst-case execution time in each period.
+    pub runtime: u64,
+    /// The relative deadline.
+    pub deadline: u64,
+    /// The period.
+    pub period: u64,
+}
+
+impl EDFParams {
+    /// Whether the parameters are valid, i.e., `0 < runtime <= deadline <= period`.
+    pub const fn is_valid(&self) -> bool {
+        0 < self.runtime && self.runtime <= self.deadline && self.deadline <= self.period
+    }
+
+    /// The CPU bandwidth used by the task, in units of `BW_UNIT`.
+    const fn bandwidth(&self) -> u64 {
+        self.runtime * BW_UNIT / self.period
+    }
+}
+
+/// A task wrapper for the [`EDFScheduler`].
+///
+/// It adds the timing parameters and the state of the current job.
+pub struct EDFTask<T> {
+    inner: T,
+    /// Zero for background tasks.
+    runtime: AtomicU64,
+    deadline: AtomicU64,
+    period: AtomicU64,
+    /// The absolute deadline of the current job.
+    abs_deadline: AtomicU64,
+    /// The remaining runtime of the current job.
+    budget: AtomicU64,
+    /// Distinguishes the tasks with the same deadline in the ready queue.
+    seq: AtomicU64,
+}
+
+impl<T> EDFTask<T> {
+    /// Creates a new [`EDFTask`] from the inner task struct. It's a background
+    /// task without timing parameters.
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            runtime: AtomicU64::new(0),
+            deadline: AtomicU64::new(0),
+            period: AtomicU64::new(0),
+            abs_deadline: AtomicU64::new(0),
+            budget: AtomicU64::new(0),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the timing parameters of the task, or [`None`] if it's a
+    /// background task.
+    pub fn params(&self) -> Option<EDFParams> {
+        let runtime = self.runtime.load(Ordering::Acquire);
+        if runtime == 0 {
+            return None;
+        }
+        Some(EDFParams {
+            runtime,
+            deadline: self.deadline.load(Ordering::Acquire),
+            period: self.period.load(Ordering::Acquire),
+        })
+    }
+
+    /// Returns the absolute deadline of the current job, or [`None`] if it's
+    /// a background task.
+    pub fn deadline(&self) -> Option<u64> {
+        self.params()
+            .map(|_| self.abs_deadline.load(Ordering::Acquire))
+    }
+
+    fn set_params(&self, params: Option<EDFParams>) {
+        let p = params.unwrap_or(EDFParams {
+            runtime: 0,
+            deadline: 0,
+            period: 0,
+        });
+        self.runtime.store(p.runtime, Ordering::Release);
+        self.deadline.store(p.deadline, Ordering::Release);
+        self.period.store(p.period, Ordering::Release);
+    }
+
+    /// Starts a new job at `now`.
+    fn new_job(&self, now: u64, params: &EDFParams) {
+        self.abs_deadline
+            .store(now + params.deadline, Ordering::Release);
+        self.budget.store(params.runtime, Ordering::Release);
+    }
+
+    fn key(&self) -> (u64, u64) {
+        (
+            self.abs_deadline.load(Ordering::Acquire),
+            self.seq.load(Ordering::Acquire),
+        )
+    }
+
+    /// Returns a reference to the inner task struct.
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Deref for EDFTask<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// An [Earliest Deadline First][1] (EDF) preemptive scheduler.
+///
+/// Deadline tasks, i.e., the tasks with [`EDFParams`] set by
+/// [`EDFScheduler::set_params`], are always picked before background tasks,
+/// in order of their absolute deadlines. Background tasks are scheduled in
+/// FIFO order when there are no ready deadline tasks.
+///
+/// New deadline tasks are only admitted if the total CPU bandwidth does not
+/// exceed 100%, so that all deadlines can be met. A job that overruns its
+/// runtime is throttled by postponing its deadline to the next period, to
+/// protect other tasks.
+///
+/// Time is measured in timer ticks counted by [`BaseScheduler::task_tick`],
+/// and by [`EDFScheduler::idle_tick`] while no task is running.
+///
+/// [1]: https://en.wikipedia.org/wiki/Earliest_deadline_first_scheduling
+pub struct EDFScheduler<T> {
+    deadline_queue: BTreeMap<(u64, u64), Arc<EDFTask<T>>>, // (deadline, seq)
+    background_queue: VecDeque<Arc<EDFTask<T>>>,
+    /// The total bandwidth of the admitted deadline tasks.
+    bandwidth: u64,
+    /// The current time in ticks.
+    now: u64,
+    next_seq: u64,
+}
+
+impl<T> EDFScheduler<T> {
+    /// Creates a new empty [`EDFScheduler`].
+    pub const fn new() -> Self {
+        Self {
+            deadline_queue: BTreeMap::new(),
+            background_queue: VecDeque::new(),
+            bandwidth: 0,
+            now: 0,
+            next_seq: 0,
+        }
+    }
+    /// get the name of scheduler
+    pub fn scheduler_name() -> &'static str {
+        "Earliest Deadline First"
+    }
+
+    /// Returns the current time of the scheduler in ticks.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Advances the clock by one tick while the CPU is idle, i.e., the idle
+    /// task not managed by the scheduler is running. Otherwise the deadlines
+    /// of the sleeping tasks would not expire.
+    pub fn idle_tick(&mut self) {
+        self.now += 1;
+    }
+
+    /// Returns the total CPU bandwidth of the admitted deadline tasks, where
+    /// `1 << 20` means 100%.
+    pub fn bandwidth(&self) -> u64 {
+        self.bandwidth
+    }
+
+    /// Sets the timing parameters of the task, or turns it into a background
+    /// task if `params` is [`None`].
+    ///
+    /// Returns `false` if the parameters are invalid, or the task can not be
+    /// admitted as the total bandwidth would exceed 100%. The task must not
+    /// be in the ready queue.
+    pub fn set_params(&mut self, task: &Arc<EDFTask<T>>, params: Option<EDFParams>) -> bool {
+        let old_bw = task.params().map_or(0, |p| p.bandwidth());
+        let new_bw = match params {
+            Some(p) if !p.is_valid() => return false,
+            Some(p) => p.bandwidth(),
+            None => 0,
+        };
+        if self.bandwidth - old_bw + new_bw > BW_UNIT {
+            return false;
+        }
+        self.bandwidth = self.bandwidth - old_bw + new_bw;
+        task.set_params(params);
+        if let Some(p) = params {
+            task.new_job(self.now, &p);
+        }
+        true
+    }
+
+    fn enqueue(&mut self, task: Arc<EDFTask<T>>, front: bool) {
+        if task.params().is_none() {
+            if front {
+                self.background_queue.push_front(task);
+            } else {
+                self.background_queue.push_back(task);
+            }
+            return;
+        }
+        task.seq.store(self.next_seq, Ordering::Release);
+        self.next_seq += 1;
+        self.deadline_queue.insert(task.key(), task);
+    }
+}
+
+impl<T> BaseScheduler for EDFScheduler<T> {
+    type SchedItem = Arc<EDFTask<T>>;
+
+    fn init(&mut self) {}
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        if let Some(p) = task.params() {
+            // The task wakes up after its deadline, start a new job.
+            if task.abs_deadline.load(Ordering::Acquire) <= self.now {
+                task.new_job(self.now, &p);
+            }
+        }
+        self.enqueue(task, false);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        if task.params().is_some() {
+            self.deadline_queue.remove(&task.key())
+        } else {
+            self.background_queue
+                .iter()
+                .position(|t| Arc::ptr_eq(t, task))
+                .and_then(|idx| self.background_queue.remove(idx))
+        }
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        if let Some((_, task)) = self.deadline_queue.pop_first() {
+            Some(task)
+        } else {
+            self.background_queue.pop_front()
+        }
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        self.enqueue(prev, preempt);
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        self.now += 1;
+        let Some(p) = current.params() else {
+            // Background tasks are preempted by any deadline task.
+            return !self.deadline_queue.is_empty();
+        };
+        if current.budget.fetch_sub(1, Ordering::AcqRel) <= 1 {
+            // The runtime of this period is used up, throttle it to the next
+            // period.
+            current.abs_deadline.fetch_add(p.period, Ordering::AcqRel);
+            current.budget.store(p.runtime, Ordering::Release);
+            return true;
+        }
+        let deadline = current.abs_deadline.load(Ordering::Acquire);
+        self.deadline_queue
+            .first_key_value()
+            .is_some_and(|(&(d, _), _)| d < deadline)
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
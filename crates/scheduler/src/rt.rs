@@ -0,0 +1,207 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
+
+use crate::BaseScheduler;
+
+/// The number of priority levels of the [`RTScheduler`].
+pub const RT_PRIO_LEVELS: usize = 100;
+
+/// The default priority of tasks in the [`RTScheduler`], which is the lowest
+/// one.
+pub const RT_DEFAULT_PRIO: usize = RT_PRIO_LEVELS - 1;
+
+/// The scheduling policy of tasks with the same priority in the
+/// [`RTScheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RTPolicy {
+    /// Runs until it blocks, yields, or is preempted by a higher-priority task
+    /// (like `SCHED_FIFO`).
+    Fifo,
+    /// Like [`RTPolicy::Fifo`], but also gives up the CPU to the tasks of the
+    /// same priority when its time slice runs out (like `SCHED_RR`).
+    RoundRobin,
+}
+
+/// A task wrapper for the [`RTScheduler`].
+///
+/// It adds a priority, a scheduling policy and a time slice counter.
+pub struct RTTask<T, const MAX_TIME_SLICE: usize> {
+    inner: T,
+    prio: AtomicUsize,
+    round_robin: AtomicBool,
+    time_slice: AtomicIsize,
+}
+
+impl<T, const S: usize> RTTask<T, S> {
+    /// Creates a new [`RTTask`] from the inner task struct, with the default
+    /// priority and the [`RTPolicy::RoundRobin`] policy.
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            prio: AtomicUsize::new(RT_DEFAULT_PRIO),
+            round_robin: AtomicBool::new(true),
+            time_slice: AtomicIsize::new(S as isize),
+        }
+    }
+
+    /// Returns the priority of the task. The smaller the value, the higher
+    /// the priority.
+    pub fn rt_priority(&self) -> usize {
+        self.prio.load(Ordering::Acquire)
+    }
+
+    /// Returns the scheduling policy of the task.
+    pub fn policy(&self) -> RTPolicy {
+        if self.round_robin.load(Ordering::Acquire) {
+            RTPolicy::RoundRobin
+        } else {
+            RTPolicy::Fifo
+        }
+    }
+
+    /// Sets the scheduling policy of the task.
+    pub fn set_policy(&self, policy: RTPolicy) {
+        self.round_robin
+            .store(policy == RTPolicy::RoundRobin, Ordering::Release);
+    }
+
+    fn time_slice(&self) -> isize {
+        self.time_slice.load(Ordering::Acquire)
+    }
+
+    fn reset_time_slice(&self) {
+        self.time_slice.store(S as isize, Ordering::Release);
+    }
+
+    /// Returns a reference to the inner task struct.
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T, const S: usize> Deref for RTTask<T, S> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// A fixed-priority preemptive real-time scheduler.
+///
+/// There are [`RT_PRIO_LEVELS`] priority levels, 0 is the highest and
+/// [`RT_DEFAULT_PRIO`] is the lowest. The highest-priority ready task is
+/// always picked, and the current task is preempted at the next timer tick
+/// once a higher-priority task becomes ready.
+///
+/// Tasks with the same priority are scheduled by their [`RTPolicy`], similar
+/// to `SCHED_FIFO` and `SCHED_RR` in Linux.
+pub struct RTScheduler<T, const MAX_TIME_SLICE: usize> {
+    ready_queues: [VecDeque<Arc<RTTask<T, MAX_TIME_SLICE>>>; RT_PRIO_LEVELS],
+    /// Bit `i` is set if `ready_queues[i]` is not empty.
+    ready_mask: u128,
+}
+
+impl<T, const S: usize> RTScheduler<T, S> {
+    const EMPTY_QUEUE: VecDeque<Arc<RTTask<T, S>>> = VecDeque::new();
+
+    /// Creates a new empty [`RTScheduler`].
+    pub const fn new() -> Self {
+        Self {
+            ready_queues: [Self::EMPTY_QUEUE; RT_PRIO_LEVELS],
+            ready_mask: 0,
+        }
+    }
+    /// get the name of scheduler
+    pub fn scheduler_name() -> &'static str {
+        "Real-time"
+    }
+
+    /// Returns the highest priority of the ready tasks.
+    fn highest_ready_prio(&self) -> Option<usize> {
+        if self.ready_mask == 0 {
+            None
+        } else {
+            Some(self.ready_mask.trailing_zeros() as usize)
+        }
+    }
+
+    fn push(&mut self, task: Arc<RTTask<T, S>>, front: bool) {
+        let prio = task.rt_priority();
+        if front {
+            self.ready_queues[prio].push_front(task);
+        } else {
+            self.ready_queues[prio].push_back(task);
+        }
+        self.ready_mask |= 1 << prio;
+    }
+
+    fn update_mask(&mut self, prio: usize) {
+        if self.ready_queues[prio].is_empty() {
+            self.ready_mask &= !(1 << prio);
+        }
+    }
+}
+
+impl<T, const S: usize> BaseScheduler for RTScheduler<T, S> {
+    type SchedItem = Arc<RTTask<T, S>>;
+
+    fn init(&mut self) {}
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        self.push(task, false);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let prio = task.rt_priority();
+        let queue = &mut self.ready_queues[prio];
+        let task = queue
+            .iter()
+            .position(|t| Arc::ptr_eq(t, task))
+            .and_then(|idx| queue.remove(idx));
+        self.update_mask(prio);
+        task
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let prio = self.highest_ready_prio()?;
+        let task = self.ready_queues[prio].pop_front();
+        self.update_mask(prio);
+        task
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        // A preempted task keeps its place at the head of its priority level,
+        // unless it has used up its time slice.
+        if preempt && (prev.policy() == RTPolicy::Fifo || prev.time_slice() > 0) {
+            self.push(prev, true);
+        } else {
+            prev.reset_time_slice();
+            self.push(prev, false);
+        }
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let prio = current.rt_priority();
+        if self.highest_ready_prio().is_some_and(|p| p < prio) {
+            return true;
+        }
+        if current.policy() == RTPolicy::RoundRobin {
+            let old_slice = current.time_slice.fetch_sub(1, Ordering::Release);
+            // Only yield to the tasks of the same priority.
+            old_slice <= 1 && self.ready_mask & (1 << prio) != 0
+        } else {
+            false
+        }
+    }
+
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
+        if (0..RT_PRIO_LEVELS as isize).contains(&prio) {
+            task.prio.store(prio as usize, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -82,3 +82,199 @@ macro_rules! def_test_sched {
 def_test_sched!(fifo, FifoScheduler::<usize>, FifoTask::<usize>);
 def_test_sched!(rr, RRScheduler::<usize, 5>, RRTask::<usize, 5>);
 def_test_sched!(cfs, CFScheduler::<usize>, CFSTask::<usize>);
+def_test_sched!(rt, RTScheduler::<usize, 5>, RTTask::<usize, 5>);
+def_test_sched!(edf, EDFScheduler::<usize>, EDFTask::<usize>);
+
+mod rt_prio {
+    use crate::*;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_priority_order() {
+        let mut scheduler = RTScheduler::<usize, 5>::new();
+        let tasks: Vec<_> = (0..6).map(|i| Arc::new(RTTask::new(i))).collect();
+        for (i, t) in tasks.iter().enumerate() {
+            // tasks 0, 3 at priority 20; 1, 4 at priority 10; 2, 5 at default.
+            let prio = [20, 10, RT_DEFAULT_PRIO as isize][i % 3];
+            assert!(scheduler.set_priority(t, prio));
+            scheduler.add_task(t.clone());
+        }
+        assert!(!scheduler.set_priority(&tasks[0], -1));
+        assert!(!scheduler.set_priority(&tasks[0], RT_PRIO_LEVELS as isize));
+
+        let order: Vec<_> = core::iter::from_fn(|| scheduler.pick_next_task())
+            .map(|t| *t.inner())
+            .collect();
+        assert_eq!(order, [1, 4, 0, 3, 2, 5]);
+    }
+
+    #[test]
+    fn test_preempt() {
+        let mut scheduler = RTScheduler::<usize, 5>::new();
+        let low = Arc::new(RTTask::new(0));
+        let high = Arc::new(RTTask::new(1));
+        assert!(scheduler.set_priority(&high, 0));
+        low.set_policy(RTPolicy::Fifo);
+
+        scheduler.add_task(low.clone());
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(Arc::ptr_eq(&curr, &low));
+        // a FIFO task without competitors is never preempted by ticks.
+        for _ in 0..10 {
+            assert!(!scheduler.task_tick(&curr));
+        }
+        // but is preempted once a higher-priority task is ready.
+        scheduler.add_task(high.clone());
+        assert!(scheduler.task_tick(&curr));
+        scheduler.put_prev_task(curr, true);
+        assert!(Arc::ptr_eq(&scheduler.pick_next_task().unwrap(), &high));
+        assert!(Arc::ptr_eq(&scheduler.pick_next_task().unwrap(), &low));
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut scheduler = RTScheduler::<usize, 5>::new();
+        let t0 = Arc::new(RTTask::new(0));
+        let t1 = Arc::new(RTTask::new(1));
+        scheduler.add_task(t0.clone());
+        scheduler.add_task(t1.clone());
+
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(Arc::ptr_eq(&curr, &t0));
+        for _ in 0..4 {
+            assert!(!scheduler.task_tick(&curr));
+        }
+        assert!(scheduler.task_tick(&curr));
+        scheduler.put_prev_task(curr, true);
+        assert!(Arc::ptr_eq(&scheduler.pick_next_task().unwrap(), &t1));
+    }
+}
+
+mod edf_deadline {
+    use crate::*;
+    use alloc::sync::Arc;
+
+    fn params(runtime: u64, deadline: u64, period: u64) -> Option<EDFParams> {
+        Some(EDFParams {
+            runtime,
+            deadline,
+            period,
+        })
+    }
+
+    #[test]
+    fn test_admission() {
+        let mut scheduler = EDFScheduler::<usize>::new();
+        let tasks: Vec<_> = (0..4).map(|i| Arc::new(EDFTask::new(i))).collect();
+
+        // invalid parameters
+        assert!(!scheduler.set_params(&tasks[0], params(0, 10, 10)));
+        assert!(!scheduler.set_params(&tasks[0], params(5, 4, 10)));
+        assert!(!scheduler.set_params(&tasks[0], params(5, 20, 10)));
+
+        // 50% + 25% + 25% = 100%
+        assert!(scheduler.set_params(&tasks[0], params(5, 10, 10)));
+        assert!(scheduler.set_params(&tasks[1], params(1, 4, 4)));
+        assert!(scheduler.set_params(&tasks[2], params(5, 20, 20)));
+        assert_eq!(scheduler.bandwidth(), 1 << 20);
+        // exceeds 100%
+        assert!(!scheduler.set_params(&tasks[3], params(1, 100, 100)));
+        assert_eq!(tasks[3].params(), None);
+
+        // frees the bandwidth
+        assert!(scheduler.set_params(&tasks[0], None));
+        assert!(scheduler.set_params(&tasks[3], params(1, 100, 100)));
+        // changes the parameters of an admitted task
+        assert!(!scheduler.set_params(&tasks[1], params(3, 4, 4)));
+        assert!(scheduler.set_params(&tasks[1], params(2, 4, 4)));
+        assert_eq!(tasks[1].params(), params(2, 4, 4));
+    }
+
+    #[test]
+    fn test_deadline_order() {
+        let mut scheduler = EDFScheduler::<usize>::new();
+        let background = Arc::new(EDFTask::new(0));
+        let t1 = Arc::new(EDFTask::new(1));
+        let t2 = Arc::new(EDFTask::new(2));
+        let t3 = Arc::new(EDFTask::new(3));
+        assert!(scheduler.set_params(&t1, params(1, 30, 30)));
+        assert!(scheduler.set_params(&t2, params(1, 10, 10)));
+        assert!(scheduler.set_params(&t3, params(1, 20, 20)));
+        assert_eq!(t2.deadline(), Some(10));
+        assert_eq!(background.deadline(), None);
+
+        for t in [&background, &t1, &t2, &t3] {
+            scheduler.add_task(t.clone());
+        }
+        let order: Vec<_> = core::iter::from_fn(|| scheduler.pick_next_task())
+            .map(|t| *t.inner())
+            .collect();
+        assert_eq!(order, [2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn test_preempt_and_throttle() {
+        let mut scheduler = EDFScheduler::<usize>::new();
+        let background = Arc::new(EDFTask::new(0));
+        let t1 = Arc::new(EDFTask::new(1));
+        let t2 = Arc::new(EDFTask::new(2));
+        assert!(scheduler.set_params(&t1, params(2, 10, 10)));
+        assert!(scheduler.set_params(&t2, params(1, 5, 5)));
+
+        // background tasks are preempted by deadline tasks.
+        scheduler.add_task(background.clone());
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(!scheduler.task_tick(&curr));
+        scheduler.add_task(t1.clone());
+        assert!(scheduler.task_tick(&curr));
+        scheduler.put_prev_task(curr, true);
+
+        // t1 runs out of its runtime after 2 ticks, and is throttled to the
+        // next period.
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(Arc::ptr_eq(&curr, &t1));
+        assert_eq!(t1.deadline(), Some(10));
+        assert!(!scheduler.task_tick(&curr));
+        assert!(scheduler.task_tick(&curr));
+        assert_eq!(t1.deadline(), Some(20));
+        scheduler.put_prev_task(curr, true);
+
+        // t2 with an earlier deadline preempts t1.
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(Arc::ptr_eq(&curr, &t1));
+        scheduler.add_task(t2.clone());
+        assert!(scheduler.task_tick(&curr));
+        scheduler.put_prev_task(curr, true);
+        assert!(Arc::ptr_eq(&scheduler.pick_next_task().unwrap(), &t2));
+        assert!(Arc::ptr_eq(&scheduler.pick_next_task().unwrap(), &t1));
+        assert!(Arc::ptr_eq(
+            &scheduler.pick_next_task().unwrap(),
+            &background
+        ));
+    }
+    #[test]
+    fn test_sleep_across_period() {
+        let mut scheduler = EDFScheduler::<usize>::new();
+        let t = Arc::new(EDFTask::new(0));
+        assert!(scheduler.set_params(&t, params(2, 5, 10)));
+
+        scheduler.add_task(t.clone());
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(!scheduler.task_tick(&curr));
+        assert_eq!(t.deadline(), Some(5));
+
+        // the task sleeps while the CPU is idle past its deadline and period.
+        for _ in 0..20 {
+            scheduler.idle_tick();
+        }
+        assert_eq!(scheduler.now(), 21);
+
+        // a new job with the full runtime is started when it wakes up.
+        scheduler.add_task(curr);
+        assert_eq!(t.deadline(), Some(26));
+        let curr = scheduler.pick_next_task().unwrap();
+        assert!(!scheduler.task_tick(&curr));
+        assert!(scheduler.task_tick(&curr));
+        assert_eq!(t.deadline(), Some(36));
+    }
+}
@@ -192,6 +192,37 @@ impl<G: BaseGuard, T: ?Sized + fmt::Debug> fmt::Debug for BaseSpinLock<G, T> {
     }
 }
 
+impl<'a, G: BaseGuard, T: ?Sized> BaseSpinLockGuard<'a, G, T> {
+    /// Temporarily unlocks the lock to execute the given function, and
+    /// re-locks it before returning.
+    ///
+    /// This is safe because `&mut` guarantees that there exist no other
+    /// references to the data protected by the lock.
+    pub fn unlocked<F, U>(s: &mut Self, f: F) -> U
+    where
+        F: FnOnce() -> U,
+    {
+        #[cfg(feature = "smp")]
+        s.lock.store(false, Ordering::Release);
+        G::release(s.irq_state);
+
+        let ret = f();
+
+        s.irq_state = G::acquire();
+        #[cfg(feature = "smp")]
+        while s
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while s.lock.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
+        ret
+    }
+}
+
 impl<'a, G: BaseGuard, T: ?Sized> Deref for BaseSpinLockGuard<'a, G, T> {
     type Target = T;
     #[inline(always)]
@@ -245,6 +276,16 @@ mod tests {
         drop(m.lock());
     }
 
+    #[test]
+    fn unlocked() {
+        let m = SpinMutex::<_>::new(NonCopy(10));
+        let mut guard = m.lock();
+        crate::BaseSpinLockGuard::unlocked(&mut guard, || {
+            *m.lock() = NonCopy(20);
+        });
+        assert_eq!(*guard, NonCopy(20));
+    }
+
     #[test]
     #[cfg(feature = "smp")]
     fn lots_and_lots() {
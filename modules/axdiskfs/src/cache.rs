@@ -0,0 +1,142 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+
+/// The size of a cached page in bytes.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The default number of pages cached for a file, 64 KiB.
+pub const DEFAULT_PAGES: usize = 16;
+
+/// A cached page of a file.
+struct Page {
+    data: Box<[u8]>,
+    /// The time of the last access, the key in [`PageCache::lru`].
+    last_used: u64,
+}
+
+/// The cached pages of a file, the least recently used one is dropped when
+/// it is full.
+///
+/// Pages are filled from the file on a read miss. Writes go to the file and
+/// update the cached pages they cover, so the pages are never dirty, the
+/// block cache below takes care of writing back.
+pub struct PageCache {
+    capacity: usize,
+    /// The cached pages by the index of the page in the file.
+    pages: BTreeMap<u64, Page>,
+    /// The cached page indices by the time of their last access.
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl PageCache {
+    /// Create an empty page cache of at most `capacity` pages.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "page cache capacity must not be zero");
+        Self {
+            capacity,
+            pages: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Return the number of cached pages.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Return true if no page is cached.
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Read `buf.len()` bytes at the byte offset `offset` of the file. A page
+    /// not cached is filled by `fill` with the data at the given offset, the
+    /// caller must not read beyond the end of the file.
+    pub fn read<E>(
+        &mut self,
+        mut offset: u64,
+        mut buf: &mut [u8],
+        mut fill: impl FnMut(u64, &mut [u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        while !buf.is_empty() {
+            let index = offset / PAGE_SIZE as u64;
+            let start = (offset % PAGE_SIZE as u64) as usize;
+            let len = buf.len().min(PAGE_SIZE - start);
+            if !self.pages.contains_key(&index) {
+                let mut data = vec![0u8; PAGE_SIZE].into_boxed_slice();
+                fill(index * PAGE_SIZE as u64, &mut data)?;
+                self.insert(index, data);
+            }
+            let page = self.touch(index).unwrap();
+            buf[..len].copy_from_slice(&page[start..start + len]);
+            buf = &mut buf[len..];
+            offset += len as u64;
+        }
+        Ok(())
+    }
+
+    /// Update the cached pages covered by the data written at the byte
+    /// offset `offset` of the file.
+    pub fn write(&mut self, mut offset: u64, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            let index = offset / PAGE_SIZE as u64;
+            let start = (offset % PAGE_SIZE as u64) as usize;
+            let len = buf.len().min(PAGE_SIZE - start);
+            if let Some(page) = self.pages.get_mut(&index) {
+                page.data[start..start + len].copy_from_slice(&buf[..len]);
+            }
+            buf = &buf[len..];
+            offset += len as u64;
+        }
+    }
+
+    /// Drop the pages from the one holding the byte offset `offset` to the
+    /// end of the file, as they no longer match it.
+    pub fn invalidate(&mut self, offset: u64) {
+        let dropped = self.pages.split_off(&(offset / PAGE_SIZE as u64));
+        for page in dropped.values() {
+            self.lru.remove(&page.last_used);
+        }
+    }
+
+    /// Drop all cached pages.
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.lru.clear();
+    }
+
+    /// Return the data of a cached page and mark it as recently used.
+    fn touch(&mut self, index: u64) -> Option<&[u8]> {
+        let page = self.pages.get_mut(&index)?;
+        self.clock += 1;
+        self.lru.remove(&page.last_used);
+        self.lru.insert(self.clock, index);
+        page.last_used = self.clock;
+        Some(&page.data)
+    }
+
+    /// Cache a new page, dropping the least recently used one if full.
+    fn insert(&mut self, index: u64, data: Box<[u8]>) {
+        if self.pages.len() >= self.capacity {
+            if let Some((_, oldest)) = self.lru.pop_first() {
+                self.pages.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.lru.insert(self.clock, index);
+        let page = Page {
+            data,
+            last_used: self.clock,
+        };
+        self.pages.insert(index, page);
+    }
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGES)
+    }
+}
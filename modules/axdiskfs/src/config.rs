@@ -1,4 +1,2 @@
 /// Default max directory number of a directory.
 pub const DIRECTORY_MAX_ENTRIES_NUM: usize = 65535;
-/// Default directory entry byte size.
-pub const DIRECTORY_ENTRY_SIZE: usize = 32;
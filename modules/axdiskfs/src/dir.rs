@@ -1,6 +1,7 @@
 use alloc::collections::BTreeMap;
 use alloc::{
     string::{String, ToString},
+    vec,
     vec::Vec,
 };
 
@@ -11,9 +12,10 @@ use spin::RwLock;
 use super::config::*;
 use crate::file::{File, FileNode};
 use crate::layout::{convert_to_u8_array, DirEntry, DirEntryAttr};
-use crate::size_of_struct;
 use crate::FS;
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{
+    VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsSetAttr,
+};
 use axfs_vfs::{VfsError, VfsResult};
 use driver_block::DevError;
 
@@ -57,7 +59,7 @@ impl Dir {
     }
 
     /// Calculate the size of Dir via all entries that are valid.
-    fn size(&self) -> u32 {
+    fn size(&self) -> u64 {
         self.entries.iter().fold(0, |acc, entry| {
             acc + if entry.is_valid() { entry.file_size } else { 0 }
         })
@@ -101,87 +103,87 @@ impl Dir {
     }
 
     /// Find entry by name, if not found, return Err, else update file_size and return Ok.
-    pub fn update_file_size(&mut self, file_name: &str, file_size: u32) -> Result<(), DevError> {
+    pub fn update_file_size(&mut self, file_name: &str, file_size: u64) -> Result<(), DevError> {
         let start_index = if self.is_root { 0 } else { 2 };
-        if let Some(entry) = self
+        let index = self
             .entries
-            .iter_mut()
+            .iter()
+            .enumerate()
             .skip(start_index)
-            .find(|e| e.name().unwrap_or_default() == file_name)
-        {
-            entry.file_size = file_size;
-            Ok(())
-        } else {
-            Err(DevError::Unsupported)
-        }
+            .find(|(_, e)| e.name().unwrap_or_default() == file_name)
+            .map(|(index, _)| index)
+            .ok_or(DevError::Unsupported)?;
+        self.entries[index].file_size = file_size;
+        self.write_entry(index)
     }
 
     /// Read all entries from disk and update entries.
     pub fn update_entries_from_disk(&mut self) -> Result<(), DevError> {
         let mut new_entries = Vec::new();
         let mut curr_cluster = self.get_entry_by_index(0).unwrap().first_cluster;
-        let dir_entry_size = size_of_struct!(DirEntry);
         let fs_arc = FS.try_get().expect("FS not initialized");
+        let version = fs_arc.version();
 
-        while fs_arc.is_end(curr_cluster) {
+        while !fs_arc.is_end(curr_cluster) {
             // if curr_cluster is Bad cluster
             if fs_arc.is_bad_cluster(curr_cluster) {
                 return Err(DevError::Unsupported);
             }
             let cluster_data = fs_arc.read_cluster(curr_cluster)?;
-            let mut cluster_entries = Vec::new();
-            for i in 0..cluster_data.len() / dir_entry_size {
-                let entry = DirEntry::new(&cluster_data[i * dir_entry_size..]);
-                cluster_entries.push(entry);
-            }
-            new_entries.append(&mut cluster_entries);
+            new_entries.extend(
+                cluster_data
+                    .chunks_exact(version.entry_size())
+                    .map(|buf| DirEntry::from_bytes(buf, version)),
+            );
             curr_cluster = fs_arc.get_fat_entry(curr_cluster)?;
         }
         self.set_entries(new_entries);
         Ok(())
     }
 
-    /// Write all entries of current memory to disk.
+    /// Stage all entries of current memory, they are written to disk at the
+    /// next commit.
     pub fn write_entries_to_disk(&mut self) -> Result<(), DevError> {
-        let mut curr_cluster = self.get_entry_by_index(0).unwrap().first_cluster;
-        let dir_entry_size = size_of_struct!(DirEntry);
-        let mut entries_idx = 0;
-        let fs_arc = FS.try_get().expect("FS not initialized");
-
-        while fs_arc.is_end(curr_cluster) {
-            if fs_arc.is_bad_cluster(curr_cluster) {
-                return Err(DevError::Unsupported);
-            }
-
-            let mut cluster_data = fs_arc.read_cluster(curr_cluster)?;
-
-            for i in 0..cluster_data.len() / dir_entry_size {
-                cluster_data[i * dir_entry_size..(i + 1) * dir_entry_size]
-                    .copy_from_slice(self.entries[entries_idx].as_bytes());
-            }
-
-            entries_idx += 1;
-
-            fs_arc.write_cluster(curr_cluster, &cluster_data[..])?;
-            curr_cluster = fs_arc.get_fat_entry(curr_cluster)?;
+        for index in 0..self.entries.len() {
+            self.write_entry(index)?;
         }
-
         Ok(())
     }
 
+    /// Stage the entry by index, it is written to disk at the next commit.
+    fn write_entry(&self, index: usize) -> Result<(), DevError> {
+        let fs_arc = FS.try_get().expect("FS not initialized");
+        let first_cluster = if self.is_root {
+            fs_arc.root_cluster()
+        } else {
+            self.get_self_first_cluster()
+        };
+        let offset = fs_arc.dir_entry_offset(first_cluster, index)?;
+        fs_arc.stage(offset, &self.entries[index].to_bytes(fs_arc.version()))
+    }
+
     /// Add a entry to entries, find a entry that name\[0\] == 0xE5 or name\[0\] == 0x00, if can't find then create a new one after the last entry
     pub fn add_entry(&mut self, entry: DirEntry) -> Result<(), DevError> {
-        let index = self.find_next_free_entry();
-        match index {
-            Some(index) => {
-                self.entries[index] = entry;
-            }
+        let (index, old) = match self.find_next_free_entry() {
+            Some(index) => (index, Some(self.entries[index])),
             None => {
                 if self.entries.len() >= DIRECTORY_MAX_ENTRIES_NUM {
                     return Err(DevError::Unsupported);
                 }
                 self.entries.push(entry);
+                (self.entries.len() - 1, None)
+            }
+        };
+        self.entries[index] = entry;
+        // fails if the root directory is full, then the entry is taken back
+        if let Err(e) = self.write_entry(index) {
+            match old {
+                Some(old) => self.entries[index] = old,
+                None => {
+                    self.entries.pop();
+                }
             }
+            return Err(e);
         }
         Ok(())
     }
@@ -193,6 +195,7 @@ impl Dir {
         match index {
             Some(index) => {
                 self.entries[index].name[0] = 0xE5;
+                self.write_entry(index)?;
                 // free cluster
                 let mut curr_cluster = self.entries[index].first_cluster;
                 while !fs_arc.is_end(curr_cluster) {
@@ -226,7 +229,16 @@ impl Dir {
             return Err(DevError::Unsupported);
         }
         self.entries[index as usize] = entry;
-        Ok(())
+        self.write_entry(index as usize)
+    }
+
+    /// Set or clear the read-only flag of the entry by name.
+    pub fn set_entry_read_only(&mut self, name: &str, read_only: bool) -> Result<(), DevError> {
+        let index = self.get_entry_by_name(name).ok_or(DevError::Unsupported)?;
+        self.entries[index]
+            .attr
+            .set(DirEntryAttr::ReadOnly, read_only);
+        self.write_entry(index)
     }
 
     /// Update entry's name, if can't find the entry, return Err
@@ -245,13 +257,16 @@ impl Dir {
             let mut entry = self.entries[index];
             entry.name = convert_to_u8_array(target_name).unwrap();
             self.entries[index] = entry;
-            Ok(())
+            self.write_entry(index)
         } else {
             Err(DevError::Unsupported)
         }
     }
 }
 
+/// The inode number of the root directory.
+const ROOT_INO: u64 = 1;
+
 /// DirNode: a struct that can represent a dir in VFS, this struct is a higher level of Dir.
 pub struct DirNode {
     this: Weak<DirNode>,
@@ -260,12 +275,29 @@ pub struct DirNode {
     parent: RwLock<Weak<DirNode>>,
     file_children: RwLock<BTreeMap<String, Arc<FileNode>>>,
     dir_children: RwLock<BTreeMap<String, Arc<DirNode>>>,
+    /// The attributes of DirNode, only the permission is saved in the dir
+    /// entry (as the read-only flag).
+    attr: RwLock<VfsNodeAttr>,
 }
 
 impl DirNode {
     /// Create a new DirNode.
     pub fn new(dir: Dir, name: String, parent: Option<Weak<DirNode>>) -> Arc<Self> {
+        let mut attr = VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o755),
+            VfsNodeType::Dir,
+            512,
+            0,
+        );
+        // clusters 0 and 1 are reserved, so the root can take 1 as its inode number
+        attr.set_ino(if dir.is_root {
+            ROOT_INO
+        } else {
+            dir.get_self_first_cluster() as u64
+        });
+        attr.touch();
         Arc::new_cyclic(|this| Self {
+            attr: RwLock::new(attr),
             this: this.clone(),
             dir: RwLock::new(dir),
             name: RwLock::new(name),
@@ -275,13 +307,21 @@ impl DirNode {
         })
     }
 
+    /// Set or clear the read-only flag in the dir entry of a child by name.
+    pub fn set_child_read_only(&self, name: &str, read_only: bool) -> Result<(), DevError> {
+        let fs_arc = FS.try_get().expect("FS not initialized");
+        fs_arc.transaction(|| self.dir.write().set_entry_read_only(name, read_only))?;
+        self.attr.write().touch_modified();
+        Ok(())
+    }
+
     /// Return the name of DirNode
     pub fn get_name(&self) -> String {
         self.name.read().clone()
     }
 
     /// Return the total size of DirNode, virtually this function is a wrapper of Dir.size().
-    pub fn get_total_size(&self) -> u32 {
+    pub fn get_total_size(&self) -> u64 {
         self.dir.read().size()
     }
 
@@ -289,7 +329,7 @@ impl DirNode {
     pub fn update_child_file_size(
         &self,
         child_file_name: &str,
-        file_size: u32,
+        file_size: u64,
     ) -> Result<(), DevError> {
         self.dir
             .write()
@@ -350,21 +390,36 @@ impl DirNode {
             let entry = self_dir.get_entry_by_index(i).unwrap();
             if entry.is_valid() {
                 let name = entry.name().unwrap();
+                let perm = if entry.attr.contains(DirEntryAttr::ReadOnly) {
+                    VfsNodePerm::from_bits_truncate(0o555)
+                } else {
+                    VfsNodePerm::from_bits_truncate(0o755)
+                };
                 match entry.is_dir() {
                     true => {
-                        let child = DirNode::new(
-                            Dir::new(entry.first_cluster, self_dir.get_self_first_cluster()),
-                            name.to_string(),
-                            Some(self.this.clone()),
-                        );
+                        let mut dir =
+                            Dir::new(entry.first_cluster, self_dir.get_self_first_cluster());
+                        let mut on_disk = dir.clone();
+                        // a directory written by mkfs starts with its own "." entry
+                        if on_disk.update_entries_from_disk().is_ok()
+                            && on_disk.entries.len() >= 2
+                            && Some(on_disk.entries[0].name) == convert_to_u8_array(".")
+                            && on_disk.entries[0].first_cluster == entry.first_cluster
+                        {
+                            dir = on_disk;
+                        }
+                        let child = DirNode::new(dir, name.to_string(), Some(self.this.clone()));
+                        child.attr.write().set_perm(perm);
+                        child.update_children()?;
                         self.add_dir_child(&name, child)?;
                     }
                     false => {
                         let child = FileNode::new(
-                            File::new(entry.first_cluster),
+                            File::with_size(entry.first_cluster, entry.file_size),
                             name.to_string(),
                             Some(self.this.clone()),
                         );
+                        child.set_perm(perm);
                         self.add_file_child(&name, Arc::new(child))?;
                     }
                 };
@@ -376,73 +431,96 @@ impl DirNode {
     /// Add a dir child to the current DirNode.
     fn add_dir_child(&self, name: &str, child: Arc<DirNode>) -> Result<(), DevError> {
         self.dir_children.write().insert(name.to_string(), child);
+        self.attr.write().touch_modified();
         Ok(())
     }
 
     /// Add a file child to the current DirNode.
     fn add_file_child(&self, name: &str, child: Arc<FileNode>) -> Result<(), DevError> {
         self.file_children.write().insert(name.to_string(), child);
+        self.attr.write().touch_modified();
         Ok(())
     }
 
     /// Create a dir child to the current DirNode.
     pub fn create_dir_child(&self, name: &str) -> Result<(), DevError> {
         let fs_arc = FS.try_get().expect("FS not initialized");
-        let entry = DirEntry {
-            name: convert_to_u8_array(name).ok_or(DevError::Unsupported)?,
-            attr: DirEntryAttr::Directory,
-            first_cluster: fs_arc
+        if !fs_arc.version().is_valid_name(name, true) {
+            return Err(DevError::InvalidParam);
+        }
+        let name_bytes = convert_to_u8_array(name).unwrap();
+        fs_arc.transaction(|| {
+            let first_cluster = fs_arc
                 .allocate_cluster_at_start()
-                .ok_or(DevError::Unsupported)?,
-            file_size: 0u32,
-        };
-
-        self.dir.write().add_entry(entry)?;
-        let child = DirNode::new(
-            Dir::new(
-                entry.first_cluster,
-                self.dir.read().get_self_first_cluster(),
-            ),
-            name.to_string(),
-            Some(self.this.clone()),
-        );
-        self.add_dir_child(name, child)?;
-        Ok(())
+                .ok_or(DevError::Unsupported)?;
+            // stale entries of a freed cluster must not show up in the new dir
+            let zeros = vec![0u8; fs_arc.bytes_per_cluster() as usize];
+            let entry = DirEntry {
+                name: name_bytes,
+                attr: DirEntryAttr::Directory,
+                first_cluster,
+                file_size: 0,
+            };
+            if let Err(e) = fs_arc
+                .write_cluster(first_cluster, &zeros)
+                .and_then(|_| self.dir.write().add_entry(entry))
+            {
+                fs_arc.free_cluster(first_cluster)?;
+                return Err(e);
+            }
+            let dir = Dir::new(first_cluster, self.dir.read().get_self_first_cluster());
+            dir.write_entry(0)?;
+            dir.write_entry(1)?;
+            let child = DirNode::new(dir, name.to_string(), Some(self.this.clone()));
+            self.add_dir_child(name, child)
+        })
     }
 
     /// Create a new empty file child to the current DirNode by name.
     pub fn create_file_child(&self, name: &str) -> Result<(), DevError> {
         let fs_arc = FS.try_get().expect("FS not initialized");
-        let entry = DirEntry {
-            name: convert_to_u8_array(name).ok_or(DevError::Unsupported)?,
-            attr: DirEntryAttr::Archive,
-            first_cluster: fs_arc
-                .allocate_cluster_at_start()
-                .ok_or(DevError::Unsupported)?,
-            file_size: 0,
-        };
-        self.dir.write().add_entry(entry)?;
-        let child = FileNode::new(
-            File::new(entry.first_cluster),
-            name.to_string(),
-            Some(self.this.clone()),
-        );
-        self.add_file_child(name, Arc::new(child))?;
-        Ok(())
+        if !fs_arc.version().is_valid_name(name, false) {
+            return Err(DevError::InvalidParam);
+        }
+        let name_bytes = convert_to_u8_array(name).unwrap();
+        fs_arc.transaction(|| {
+            let entry = DirEntry {
+                name: name_bytes,
+                attr: DirEntryAttr::Archive,
+                first_cluster: fs_arc
+                    .allocate_cluster_at_start()
+                    .ok_or(DevError::Unsupported)?,
+                file_size: 0,
+            };
+            if let Err(e) = self.dir.write().add_entry(entry) {
+                fs_arc.free_cluster(entry.first_cluster)?;
+                return Err(e);
+            }
+            let child = FileNode::new(
+                File::new(entry.first_cluster),
+                name.to_string(),
+                Some(self.this.clone()),
+            );
+            self.add_file_child(name, Arc::new(child))
+        })
     }
 
     /// Remove a file child of the current DirNode by name if it exists.
     fn remove_file_child(&self, name: &str) -> Result<(), DevError> {
         // find name's location in DirNode's entries, set name[0] = 0xE5, then update children
-        self.dir.write().delete_entry(name)?;
+        let fs_arc = FS.try_get().expect("FS not initialized");
+        fs_arc.transaction(|| self.dir.write().delete_entry(name))?;
         self.file_children.write().remove(name);
+        self.attr.write().touch_modified();
         Ok(())
     }
 
     /// Remove a dir child of the current DirNode if it exists.
     fn remove_dir_child(&self, name: &str) -> Result<(), DevError> {
-        self.dir.write().delete_entry(name)?;
+        let fs_arc = FS.try_get().expect("FS not initialized");
+        fs_arc.transaction(|| self.dir.write().delete_entry(name))?;
         self.dir_children.write().remove(name);
+        self.attr.write().touch_modified();
         Ok(())
     }
 
@@ -472,11 +550,20 @@ impl DirNode {
 
     /// Rename a child of the current DirNode, this function is a combination of rename_file_child and rename_dir_child.
     pub fn rename_child(&self, original_name: &str, target_name: &str) -> Result<(), DevError> {
-        if self.dir.read().is_entry_dir(original_name)? {
-            self.rename_dir_child(original_name, target_name)
-        } else {
-            self.rename_file_child(original_name, target_name)
+        let fs_arc = FS.try_get().expect("FS not initialized");
+        let is_dir = self.dir.read().is_entry_dir(original_name)?;
+        if !fs_arc.version().is_valid_name(target_name, is_dir) {
+            return Err(DevError::InvalidParam);
         }
+        fs_arc.transaction(|| {
+            if is_dir {
+                self.rename_dir_child(original_name, target_name)
+            } else {
+                self.rename_file_child(original_name, target_name)
+            }
+        })?;
+        self.attr.write().touch_modified();
+        Ok(())
     }
 
     /// Return the reference of parent of the current DirNode.
@@ -503,12 +590,19 @@ impl DirNode {
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::from_bits_truncate(0o755),
-            VfsNodeType::Dir,
-            512,
-            0,
-        ))
+        let mut attr = *self.attr.read();
+        attr.set_nlink(2 + self.dir_children.read().len() as u64);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        if let (Some(mode), Some(parent)) = (changes.mode, self.inner_parent()) {
+            parent
+                .set_child_read_only(&self.get_name(), !mode.owner_writable())
+                .map_err(|_| VfsError::NotFound)?;
+        }
+        self.attr.write().apply(changes);
+        Ok(())
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
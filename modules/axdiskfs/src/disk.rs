@@ -1,4 +1,6 @@
-use axdriver::{prelude::*, AxBlockDevice};
+use alloc::boxed::Box;
+use axdriver::prelude::*;
+use driver_block::cache::{BlockCache, CacheStats, WriteHook};
 
 /// The block size of the disk. Default to 512 bytes.
 const BLOCK_SIZE: usize = 512;
@@ -14,27 +16,42 @@ pub enum SeekFrom {
     Current(i64),
 }
 
-/// A disk device with a cursor.
+/// A disk device with a cursor, accessed through a block cache.
 pub struct Disk {
     /// The current block id.
     block_id: u64,
     /// The current offset within the block.
     offset: usize,
-    /// The low-level block device.
-    dev: AxBlockDevice,
+    /// The cache of the low-level block device.
+    cache: BlockCache<Box<dyn BlockDriverOps>>,
 }
 
 impl Disk {
-    /// Create a new disk.
-    pub fn new(dev: AxBlockDevice) -> Self {
+    /// Create a new disk on a block device, e.g., a whole disk or a partition.
+    pub fn new(dev: impl BlockDriverOps + 'static) -> Self {
         assert_eq!(BLOCK_SIZE, dev.block_size());
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            cache: BlockCache::new(Box::new(dev)),
         }
     }
 
+    /// Write the dirty cached blocks to the device and flush it.
+    pub fn flush(&mut self) -> DevResult {
+        self.cache.flush()
+    }
+
+    /// Return the counters of the block cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Set the callback invoked with each block written to the device.
+    pub fn set_write_hook(&mut self, hook: Option<WriteHook>) {
+        self.cache.set_write_hook(hook);
+    }
+
     /// Get the block size of the disk.
     pub fn block_size(&self) -> usize {
         BLOCK_SIZE
@@ -42,7 +59,7 @@ impl Disk {
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.cache.num_blocks() * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -60,18 +77,15 @@ impl Disk {
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            self.dev
+            self.cache
                 .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
             // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
+            self.cache
+                .read_at(self.block_id, self.offset, &mut buf[..count])?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -87,18 +101,14 @@ impl Disk {
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
         let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+            self.cache.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
             // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            self.cache
+                .write_at(self.block_id, self.offset, &buf[..count])?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
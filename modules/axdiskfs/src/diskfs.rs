@@ -1,15 +1,23 @@
+use alloc::collections::btree_map::Entry;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
 use axdriver::prelude::*;
 
 use alloc::sync::Arc;
 use core::slice::from_raw_parts;
-use spin::RwLock;
+use spin::{Mutex, RwLock};
 
 use crate::dir::{Dir, DirNode};
-use crate::layout::{BootSector, DirEntry, FSInfoSector, FatMarker};
+use crate::journal::{self, Transaction};
+use crate::layout::{BootSector, DirEntry, FSInfoSector, FatMarker, Version};
+use crate::mkfs::{self, FormatOptions};
 use crate::sector::SectorManager;
-use axfs_vfs::{VfsNodeRef, VfsOps};
+use axfs_vfs::{FileSystemInfo, VfsError, VfsNodeRef, VfsOps, VfsResult};
+
+/// The filesystem type magic number of CCFileSystem, "CCFS" in ASCII.
+pub const CCFS_MAGIC: u64 = 0x4343_4653;
 
 /// A abstraction Struct of a FAT32 filesystem.
 pub struct CCFileSystem {
@@ -18,6 +26,12 @@ pub struct CCFileSystem {
     boot_sector: RwLock<BootSector>,
     fs_info_sector: RwLock<FSInfoSector>,
     fat: RwLock<Vec<u32>>,
+    /// The FAT sectors changed since the last commit, as indices into a copy.
+    dirty_fat: Mutex<BTreeSet<u32>>,
+    /// The directory sectors changed since the last commit.
+    staged: Mutex<Transaction>,
+    /// Held while a transaction runs, so that transactions do not mix.
+    tx: Mutex<()>,
 }
 
 impl CCFileSystem {
@@ -29,6 +43,9 @@ impl CCFileSystem {
             boot_sector: RwLock::new(BootSector::default()),
             fs_info_sector: RwLock::new(FSInfoSector::default()),
             fat: RwLock::new(Vec::new()),
+            dirty_fat: Mutex::new(BTreeSet::new()),
+            staged: Mutex::new(BTreeMap::new()),
+            tx: Mutex::new(()),
         }
     }
 
@@ -37,59 +54,19 @@ impl CCFileSystem {
         self.boot_sector.read().bytes_per_cluster()
     }
 
+    /// Return the format version of the filesystem.
+    pub fn version(&self) -> Version {
+        self.boot_sector.read().format_version()
+    }
+
     /// Init the filesystem by reading boot sector and sector manager, then initialize the root dir and the fat table.
     pub fn init(&self) -> Result<(), DevError> {
-        self.sector_manager.write().set_position(0);
-        let mut boot_sector = self.boot_sector.write();
-        *boot_sector = BootSector {
-            bytes_per_sector: self
-                .sector_manager
-                .read()
-                .read_16_seq()
-                .expect("read sector manager bytes_per_sector failed"),
-            sectors_per_cluster: self
-                .sector_manager
-                .read()
-                .read_8_seq()
-                .expect("read sector manager sectors_per_cluster failed"),
-            reserved_sectors_count: self
-                .sector_manager
-                .read()
-                .read_16_seq()
-                .expect("read sector manager reserved_sectors_count failed"),
-            total_sectors32: self
-                .sector_manager
-                .read()
-                .read_32_seq()
-                .expect("read sector manager total_sectors32 failed"),
-            fat_count: self
-                .sector_manager
-                .read()
-                .read_8_seq()
-                .expect("read sector manager fat_count failed"),
-            sectors_per_fat32: self
-                .sector_manager
-                .read()
-                .read_32_seq()
-                .expect("read sector manager sectors_per_fat32 failed"),
-            root_cluster: self
-                .sector_manager
-                .read()
-                .read_32_seq()
-                .expect("read sector manager root_cluster failed"),
-            root_dir_sectors_count: self
-                .sector_manager
-                .read()
-                .read_32_seq()
-                .expect("read sector manager root_dir_sectors_count failed"),
-            fsinfo_sector: self
-                .sector_manager
-                .read()
-                .read_16_seq()
-                .expect("read sector manager fs info sector failed"),
-            reserved: [0u8; 488],
-        };
-        drop(boot_sector);
+        let mut buf = [0u8; 512];
+        self.sector_manager.read().read_sector_at(0, &mut buf)?;
+        let boot_sector = BootSector::from_bytes(&buf);
+        // finish the last transaction before anything is read
+        journal::replay(&self.sector_manager.read(), &boot_sector)?;
+        *self.boot_sector.write() = boot_sector;
         self.sector_manager.write().set_position(512);
         let mut fs_info_sector = self.fs_info_sector.write();
         *fs_info_sector = FSInfoSector {
@@ -112,6 +89,23 @@ impl CCFileSystem {
         self.fs_info_sector.read().next_free_cluster
     }
 
+    /// Return the number of free clusters. The count in the FSINFO sector is
+    /// used unless it is out of range, in which case the FAT is scanned.
+    pub fn free_clusters_count(&self) -> u32 {
+        let total = self.boot_sector.read().clusters_count();
+        let free = self.fs_info_sector.read().free_cluster_count;
+        if free <= total {
+            return free;
+        }
+        self.fat
+            .read()
+            .iter()
+            .skip(2)
+            .take(total as usize)
+            .filter(|&&entry| entry == 0x00000000)
+            .count() as u32
+    }
+
     /// Init the root directory.
     fn init_root(&self) -> DevResult<()> {
         let root_dir_start_sector = self.boot_sector.read().root_dir_start_sector() as u64;
@@ -130,7 +124,7 @@ impl CCFileSystem {
 
         for cluster in root_first_cluster..=root_last_cluster {
             fat[cluster as usize] = if cluster == root_last_cluster {
-                0xFFFFFFFF
+                0x0FFFFFFF
             } else {
                 (cluster + 1) as u32
             };
@@ -138,16 +132,18 @@ impl CCFileSystem {
         drop(fat);
         self.sector_manager
             .write()
-            .set_position(root_dir_start_sector);
+            .set_position(root_dir_start_sector * 512);
         let mut clusters = Vec::new();
         for _ in 0..root_dir_sector_count {
             clusters.append(&mut self.sector_manager.read().read_sector_seq()?);
         }
 
         let mut new_entries = Vec::new();
-        for i in 0..clusters.len() / 32 {
-            let entry = DirEntry::new(&clusters[i * 32..(i + 1) * 32]);
-            if !entry.is_valid() {
+        let version = self.version();
+        for buf in clusters.chunks_exact(version.entry_size()) {
+            let entry = DirEntry::from_bytes(buf, version);
+            // deleted entries are kept, so that the ones after them are found
+            if entry.name[0] == 0x00 {
                 break;
             }
             new_entries.push(entry);
@@ -204,13 +200,10 @@ impl CCFileSystem {
     pub fn read_cluster(&self, cluster_id: u32) -> Result<Vec<u8>, DevError> {
         let cluster_start_sector =
             self.boot_sector.read().cluster_to_sector(cluster_id) as u64 * 512;
-        let mut cluster = Vec::new();
+        let mut cluster = vec![0u8; self.bytes_per_cluster() as usize];
         self.sector_manager
-            .write()
-            .set_position(cluster_start_sector);
-        for _ in 0..self.boot_sector.read().sectors_per_cluster as u64 {
-            cluster.append(&mut self.sector_manager.read().read_sector_seq()?);
-        }
+            .read()
+            .read_sector_at(cluster_start_sector, &mut cluster)?;
         Ok(cluster)
     }
 
@@ -228,14 +221,10 @@ impl CCFileSystem {
     pub fn write_cluster(&self, cluster_id: u32, data: &[u8]) -> Result<(), DevError> {
         let cluster_start_sector =
             self.boot_sector.read().cluster_to_sector(cluster_id) as u64 * 512;
+        let len = self.bytes_per_cluster() as usize;
         self.sector_manager
-            .write()
-            .set_position(cluster_start_sector);
-        for i in 0..self.boot_sector.read().sectors_per_cluster as u64 {
-            self.sector_manager
-                .write()
-                .write_sector_seq(&data[i as usize * 512..(i + 1) as usize * 512])?;
-        }
+            .read()
+            .write_sector_at(cluster_start_sector, &data[..len])?;
         Ok(())
     }
 
@@ -296,6 +285,16 @@ impl CCFileSystem {
         Ok(())
     }
 
+    /// Set a FAT entry in memory, the sector holding it is written at the
+    /// next commit.
+    fn set_fat_entry(&self, cluster_id: u32, value: u32) {
+        self.fat.write()[cluster_id as usize] = value;
+        let entries_per_sector = self.boot_sector.read().bytes_per_sector() / 4;
+        self.dirty_fat
+            .lock()
+            .insert(cluster_id / entries_per_sector);
+    }
+
     /// Allocate a cluster between curr_cluster_id and next_cluster_id.
     pub fn allocate_cluster_at_middle(
         &self,
@@ -303,10 +302,8 @@ impl CCFileSystem {
         next_cluster_id: u32,
     ) -> Option<u32> {
         let next_free_cluster = self.find_next_free_cluster()?;
-        let mut fat = self.fat.write();
-        fat[next_free_cluster as usize] = next_cluster_id;
-        fat[curr_cluster_id as usize] = next_free_cluster;
-        drop(fat);
+        self.set_fat_entry(next_free_cluster, next_cluster_id);
+        self.set_fat_entry(curr_cluster_id, next_free_cluster);
         self.fs_info_sector.write().free_cluster_count -= 1;
         self.update_next_free_cluster().ok()?;
         Some(next_free_cluster)
@@ -315,10 +312,8 @@ impl CCFileSystem {
     /// Allocate a cluster at the end of the chain.
     pub fn allocate_cluster_at_end(&self, curr_cluster_id: u32) -> Option<u32> {
         let next_free_cluster = self.find_next_free_cluster()?;
-        let mut fat = self.fat.write();
-        fat[next_free_cluster as usize] = 0x0FFFFFFF;
-        fat[curr_cluster_id as usize] = next_free_cluster;
-        drop(fat);
+        self.set_fat_entry(next_free_cluster, 0x0FFFFFFF);
+        self.set_fat_entry(curr_cluster_id, next_free_cluster);
         self.fs_info_sector.write().free_cluster_count -= 1;
         self.update_next_free_cluster().ok()?;
         Some(next_free_cluster)
@@ -327,36 +322,167 @@ impl CCFileSystem {
     /// Allocate a cluster at the start of the chain.
     pub fn allocate_cluster_at_start(&self) -> Option<u32> {
         let next_free_cluster = self.find_next_free_cluster()?;
-        let mut fat = self.fat.write();
-        fat[next_free_cluster as usize] = 0x0FFFFFFF;
+        self.set_fat_entry(next_free_cluster, 0x0FFFFFFF);
         self.fs_info_sector.write().free_cluster_count -= 1;
-        drop(fat);
         self.update_next_free_cluster().ok()?;
         Some(next_free_cluster)
     }
 
     /// Link the cluster to the end of the chain.
     pub fn link_to_end(&self, curr_cluster_id: u32) -> Result<(), DevError> {
-        let mut fat = self.fat.write();
-        fat[curr_cluster_id as usize] = 0x0FFFFFFF;
-        drop(fat);
+        self.set_fat_entry(curr_cluster_id, 0x0FFFFFFF);
         self.update_next_free_cluster()?;
         Ok(())
     }
 
     /// Free the cluster, then update the free_cluster_count and next_free_cluster.
     pub fn free_cluster(&self, cluster_id: u32) -> Result<(), DevError> {
-        let mut fat = self.fat.write();
-        fat[cluster_id as usize] = 0x00000000;
-        drop(fat);
+        self.set_fat_entry(cluster_id, 0x00000000);
         self.fs_info_sector.write().free_cluster_count += 1;
         self.fs_info_sector.write().next_free_cluster = cluster_id;
-        // self.flush_fs_info_sector()?;
         Ok(())
     }
+
+    /// Return the first cluster of the root directory.
+    pub fn root_cluster(&self) -> u32 {
+        self.boot_sector.read().root_cluster
+    }
+
+    /// Return the byte offset on disk of the entry `index` of the directory
+    /// starting at `first_cluster`.
+    ///
+    /// A directory other than the root grows by a zeroed cluster when the
+    /// entry is past its end, the root has a fixed size.
+    pub fn dir_entry_offset(&self, first_cluster: u32, index: usize) -> DevResult<u64> {
+        let bytes_per_cluster = self.bytes_per_cluster() as usize;
+        let offset = index * self.version().entry_size();
+        if first_cluster == self.root_cluster() {
+            let root_bytes = self.boot_sector.read().root_dir_sectors_count() as usize * 512;
+            if offset >= root_bytes {
+                return Err(DevError::NoMemory);
+            }
+        }
+        let mut cluster = first_cluster;
+        for _ in 0..offset / bytes_per_cluster {
+            let next = self.get_fat_entry(cluster)?;
+            cluster = if self.is_end(next) {
+                let next = self
+                    .allocate_cluster_at_end(cluster)
+                    .ok_or(DevError::NoMemory)?;
+                // the cluster is free on disk until the commit, so it is
+                // safe to write it in place
+                self.write_cluster(next, &vec![0u8; bytes_per_cluster])?;
+                next
+            } else {
+                next
+            };
+        }
+        let sector = self.boot_sector.read().cluster_to_sector(cluster) as u64;
+        Ok(sector * 512 + (offset % bytes_per_cluster) as u64)
+    }
+
+    /// Stage a write of metadata at the byte offset `offset`, it reaches
+    /// the disk at the next commit.
+    pub fn stage(&self, offset: u64, data: &[u8]) -> DevResult {
+        let sector = offset / 512;
+        let start = (offset % 512) as usize;
+        let mut staged = self.staged.lock();
+        let buf = match staged.entry(sector) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut buf = vec![0u8; 512];
+                self.sector_manager
+                    .read()
+                    .read_sector_at(sector * 512, &mut buf)?;
+                entry.insert(buf)
+            }
+        };
+        buf[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Write the staged directory sectors, the changed FAT sectors of both
+    /// copies and FSINFO to disk through the journal.
+    fn commit(&self) -> DevResult {
+        let mut tx = core::mem::take(&mut *self.staged.lock());
+        let dirty_fat = core::mem::take(&mut *self.dirty_fat.lock());
+        if tx.is_empty() && dirty_fat.is_empty() {
+            return Ok(());
+        }
+        let boot = *self.boot_sector.read();
+        let entries_per_sector = boot.bytes_per_sector() as usize / 4;
+        let fat = self.fat.read();
+        for index in dirty_fat {
+            let start = index as usize * entries_per_sector;
+            let data: Vec<u8> = fat[start..start + entries_per_sector]
+                .iter()
+                .flat_map(|entry| entry.to_le_bytes())
+                .collect();
+            for copy in 0..boot.fat_count as u32 {
+                let sector = boot.fat_start_sector() + copy * boot.sectors_per_fat32 + index;
+                tx.insert(sector as u64, data.clone());
+            }
+        }
+        drop(fat);
+        self.update_free_cluster_count();
+        tx.insert(
+            boot.fsinfo_sector as u64,
+            self.fs_info_sector.read().to_bytes().to_vec(),
+        );
+        journal::commit(&self.sector_manager.read(), &boot, &tx)
+    }
+
+    /// Run `f` as one transaction: the metadata it changes is committed
+    /// together when it returns, even if it fails, so that the disk matches
+    /// what is in memory.
+    pub fn transaction<T>(&self, f: impl FnOnce() -> DevResult<T>) -> DevResult<T> {
+        let _tx = self.tx.lock();
+        let res = f();
+        self.commit()?;
+        res
+    }
 }
 
 impl VfsOps for CCFileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        let blocks_free = self.free_clusters_count() as u64;
+        Ok(FileSystemInfo {
+            fs_type: CCFS_MAGIC,
+            block_size: self.bytes_per_cluster() as u64,
+            blocks: self.boot_sector.read().clusters_count() as u64,
+            blocks_free,
+            blocks_avail: blocks_free,
+            name_len: self.version().name_max() as u64,
+            // there are no inodes, like FAT
+            ..Default::default()
+        })
+    }
+
+    fn sync(&self) -> VfsResult {
+        // the metadata is on the disk after each transaction, so flushing
+        // the cache writes back the file data and the cleared journal
+        let _tx = self.tx.lock();
+        self.sector_manager.read().flush().map_err(|_| VfsError::Io)
+    }
+
+    fn format(&self) -> VfsResult {
+        // keep the geometry, unless the disk has never been formatted
+        let options = {
+            let sector = self.sector_manager.read();
+            let boot = self.boot_sector.read();
+            if boot.is_valid(sector.sector_count() / 512) {
+                FormatOptions::from_boot_sector(&boot)
+            } else {
+                FormatOptions::for_disk(&sector)
+            }
+        };
+        let _tx = self.tx.lock();
+        self.staged.lock().clear();
+        self.dirty_fat.lock().clear();
+        mkfs::format(&self.sector_manager.read(), &options).map_err(|_| VfsError::Io)?;
+        self.init().map_err(|_| VfsError::Io)
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
         self.root_dir_node().unwrap().clone()
     }
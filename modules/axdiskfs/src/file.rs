@@ -1,3 +1,4 @@
+use crate::cache::PageCache;
 use crate::dir::DirNode;
 use crate::disk::SeekFrom;
 use crate::FS;
@@ -10,11 +11,11 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use axdriver::prelude::*;
 use axfs_vfs::{
-    impl_vfs_non_dir_default, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType,
-    VfsResult,
+    impl_vfs_non_dir_default, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsOps,
+    VfsResult, VfsSetAttr,
 };
 use driver_block::DevResult;
-use spin::RwLock;
+use spin::{Mutex, RwLock};
 
 use core::cmp::max;
 
@@ -22,7 +23,7 @@ use core::cmp::max;
 #[derive(Clone)]
 pub struct File {
     /// File size in bytes.
-    size: u32,
+    size: u64,
     /// The first cluster of the file.
     first_cluster: u32,
     /// The current cluster of the file.
@@ -42,9 +43,17 @@ impl File {
         }
     }
 
+    /// Create a File Struct for an existing file of `size` bytes.
+    pub fn with_size(first_cluster: u32, size: u64) -> Self {
+        Self {
+            size,
+            ..Self::new(first_cluster)
+        }
+    }
+
     /// Return the size of the file.
     fn size(&self) -> u64 {
-        self.size as u64
+        self.size
     }
 
     /// Return the current byte position of the file.
@@ -156,6 +165,10 @@ impl File {
         }
 
         let fs_arc = FS.try_get().expect("fs is not initialized");
+        // the size must fit in the dir entry
+        if byte_offset + buf.len() as u64 > fs_arc.version().max_file_size() {
+            return Err(DevError::InvalidParam);
+        }
         // set the current_cluster and offset
         let mut offset = byte_offset % fs_arc.bytes_per_cluster() as u64;
         let old_size = self.size();
@@ -199,7 +212,7 @@ impl File {
         self.offset = offset as u32;
 
         // calculate the size of the file after writing
-        self.update_file_size(max(old_size, byte_offset + buf.len() as u64) as u32);
+        self.update_file_size(max(old_size, byte_offset + buf.len() as u64));
 
         // return the read size
         Ok(buf_offset)
@@ -242,7 +255,7 @@ impl File {
         self.current_cluster = cluster;
         self.offset = offset as u32;
 
-        self.update_file_size(max(old_size, self.offset as u64 + buf.len() as u64) as u32);
+        self.update_file_size(max(old_size, self.offset as u64 + buf.len() as u64));
 
         Ok(buf_offset)
     }
@@ -279,7 +292,7 @@ impl File {
         }
         // finally update file's curr_cluster and offset
         self.current_cluster = curr_cluster;
-        self.offset = pos as u32 % fs_mutex.bytes_per_cluster() as u32;
+        self.offset = (pos % fs_mutex.bytes_per_cluster() as u64) as u32;
         Ok(())
     }
 
@@ -304,12 +317,16 @@ impl File {
         // size should be cluster's multiple
         let fs_arc = FS.try_get().expect("fs is not initialized");
 
-        let current_size = self.size() as u64;
+        let current_size = self.size();
         let cluster_size = fs_arc.bytes_per_cluster() as u64;
 
         if size == current_size {
             return Ok(());
         }
+        // the size must fit in the dir entry
+        if size > fs_arc.version().max_file_size() {
+            return Err(DevError::InvalidParam);
+        }
 
         // if size < file size, free the superfluous clusters, and update the file size
         if size < self.size() {
@@ -334,10 +351,18 @@ impl File {
 
             fs_arc.link_to_end(prev_cluster)?;
         } else {
-            let additional_clusters = (size - current_size + cluster_size - 1) / cluster_size;
+            // find the last cluster, the chain may already cover the new size
             let mut last_cluster = self.first_cluster;
-            while !fs_arc.is_end(last_cluster) {
-                last_cluster = fs_arc.get_fat_entry(last_cluster)?;
+            let mut clusters = 1;
+            let mut next_cluster = fs_arc.get_fat_entry(last_cluster)?;
+            while !fs_arc.is_end(next_cluster) {
+                last_cluster = next_cluster;
+                clusters += 1;
+                next_cluster = fs_arc.get_fat_entry(last_cluster)?;
+            }
+            let additional_clusters = size.div_ceil(cluster_size).saturating_sub(clusters);
+            if additional_clusters > fs_arc.free_clusters_count() as u64 {
+                return Err(DevError::NoMemory);
             }
             for _ in 0..additional_clusters {
                 last_cluster = fs_arc
@@ -345,14 +370,14 @@ impl File {
                     .ok_or(DevError::Unsupported)?;
             }
         }
-        self.update_file_size(size as u32);
+        self.update_file_size(size);
         self.current_cluster = self.first_cluster;
         self.offset = 0;
         Ok(())
     }
 
     /// update_file_size: update the file size
-    pub fn update_file_size(&mut self, size: u32) {
+    pub fn update_file_size(&mut self, size: u64) {
         self.size = size;
     }
 }
@@ -365,19 +390,44 @@ pub struct FileNode {
     name: RwLock<String>,
     /// The parent of the fileNode
     parent: RwLock<Weak<DirNode>>,
+    /// The attributes of the fileNode, except for the size.
+    ///
+    /// Only the permission is saved in the dir entry (as the read-only flag),
+    /// the owner and timestamps live in memory until the fileNode is dropped.
+    attr: RwLock<VfsNodeAttr>,
+    /// The cached pages of the file, dropped when it is no longer open.
+    pages: Mutex<PageCache>,
+    /// The number of open handles of the file.
+    opened: Mutex<usize>,
 }
 
 impl FileNode {
     /// Create a new fileNode.
     pub fn new(file: File, name: String, parent: Option<Weak<DirNode>>) -> Self {
+        let mut attr = VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o755),
+            VfsNodeType::File,
+            0,
+            0,
+        );
+        attr.set_ino(file.first_cluster as u64);
+        attr.touch();
         Self {
             file: RwLock::new(file),
             name: RwLock::new(name),
             // parent: Arc::downgrade(&parent.unwrap()),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<DirNode>::new())),
+            attr: RwLock::new(attr),
+            pages: Mutex::new(PageCache::default()),
+            opened: Mutex::new(0),
         }
     }
 
+    /// Set the permission of the fileNode in memory.
+    pub fn set_perm(&self, perm: VfsNodePerm) {
+        self.attr.write().set_perm(perm);
+    }
+
     /// Rename itself.
     pub fn self_rename(&self, new_name: &str) {
         let mut name_lock = self.name.write();
@@ -391,7 +441,7 @@ impl FileNode {
 
     /// Return the size of the fileNode.
     pub fn get_size(&self) -> u64 {
-        self.file.read().size() as u64
+        self.file.read().size()
     }
 
     /// Return the parent of the fileNode.
@@ -401,7 +451,7 @@ impl FileNode {
 
     /// Update the size of the current fileNode.
     pub fn update_size(&self) -> Result<(), DevError> {
-        let new_size = self.file.read().size() as u32;
+        let new_size = self.file.read().size();
         let parent = self.parent.write().upgrade().unwrap();
         parent.update_child_file_size(self.name.read().clone().as_str(), new_size)
     }
@@ -412,16 +462,43 @@ impl FileNode {
         *parent_lock = parent;
     }
 
+    /// Return the number of cached pages of the fileNode.
+    pub fn cached_pages(&self) -> usize {
+        self.pages.lock().len()
+    }
+
     /// Read data from the current fileNode, virtually this function is a inner function of VfsNodeOps read_at().
+    ///
+    /// The data is read through the page cache.
     pub fn read_at_inner(&self, byte_offset: u64, buf: &mut [u8]) -> Result<usize, DevError> {
-        self.file.write().read_at(byte_offset, buf)
+        let size = self.get_size();
+        let len = buf.len().min(size.saturating_sub(byte_offset) as usize);
+        let res = self
+            .pages
+            .lock()
+            .read(byte_offset, &mut buf[..len], |offset, page| {
+                self.file.write().read_at(offset, page).map(|_| ())
+            });
+        self.attr.write().touch_accessed();
+        res.map(|_| len)
     }
 
     /// Write data to the current fileNode, virtually this function is a inner function of VfsNodeOps write_at().
+    ///
+    /// The data is written in place first, the clusters allocated for it and
+    /// the new size are committed after it.
     pub fn write_at_inner(&self, byte_offset: u64, buf: &[u8]) -> Result<usize, DevError> {
-        let res = self.file.write().write_at(byte_offset, buf);
-        self.update_size()?;
-        res
+        let fs_arc = FS.try_get().expect("fs is not initialized");
+        fs_arc.transaction(|| {
+            let res = self.file.write().write_at(byte_offset, buf);
+            match res {
+                Ok(len) => self.pages.lock().write(byte_offset, &buf[..len]),
+                Err(_) => self.pages.lock().invalidate(byte_offset),
+            }
+            self.update_size()?;
+            self.attr.write().touch_modified();
+            res
+        })
     }
 
     /// Read all data of the current fileNode, virtually this function is a inner function of VfsNodeOps read_all().
@@ -431,9 +508,15 @@ impl FileNode {
 
     /// Truncate the fileNode, virtually this function is a inner function of VfsNodeOps truncate().
     pub fn truncate_inner(&self, size: u64) -> Result<(), DevError> {
-        let res = self.file.write().truncate(size);
-        self.update_size()?;
-        res
+        let fs_arc = FS.try_get().expect("fs is not initialized");
+        fs_arc.transaction(|| {
+            let old_size = self.get_size();
+            let res = self.file.write().truncate(size);
+            self.pages.lock().invalidate(old_size.min(size));
+            self.update_size()?;
+            self.attr.write().touch_modified();
+            res
+        })
     }
 
     /// Check if the fileNode is empty via file size.
@@ -443,15 +526,40 @@ impl FileNode {
 }
 
 impl VfsNodeOps for FileNode {
+    fn open(&self) -> VfsResult {
+        *self.opened.lock() += 1;
+        Ok(())
+    }
+
+    fn release(&self) -> VfsResult {
+        let mut opened = self.opened.lock();
+        *opened = opened.saturating_sub(1);
+        if *opened == 0 {
+            self.pages.lock().clear();
+        }
+        Ok(())
+    }
+
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         let file_size = self.file.read().size();
         let blocks = file_size / 512 + if file_size % 512 == 0 { 0 } else { 1 };
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::from_bits_truncate(0o755),
-            VfsNodeType::File,
-            file_size,
-            blocks,
-        ))
+        let mut attr = *self.attr.read();
+        attr.set_size(file_size, blocks);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        if let (Some(mode), Some(parent)) = (changes.mode, self.parent()) {
+            parent
+                .set_child_read_only(&self.get_name(), !mode.owner_writable())
+                .map_err(|_| VfsError::NotFound)?;
+        }
+        self.attr.write().apply(changes);
+        Ok(())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        FS.try_get().expect("fs is not initialized").sync()
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
@@ -0,0 +1,459 @@
+//! Checking and repairing a filesystem.
+//!
+//! [`check`] walks the directory tree from the root, following the FAT chain
+//! of every entry, then compares what it found with the FAT and FSINFO.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use axdriver::prelude::*;
+
+use crate::image::{Image, FAT_BAD, FAT_EOC, FAT_FREE};
+use crate::journal;
+use crate::layout::{convert_to_u8_array, DirEntry, DirEntryAttr};
+use crate::sector::SectorManager;
+
+/// A problem found by [`check`], with the fix applied when repairing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The journal holds a committed transaction, the mount was interrupted
+    /// before writing it home. The transaction is replayed, without repair
+    /// nothing else is checked.
+    PendingJournal,
+    /// The entry name can not be decoded, or is not allowed in the format
+    /// version. The entry is deleted.
+    BadName {
+        /// The path of the parent directory.
+        dir: String,
+    },
+    /// The first cluster of an entry is not on the disk. The entry is deleted.
+    BadFirstCluster {
+        /// The path of the entry.
+        path: String,
+        /// The first cluster in the entry.
+        cluster: u32,
+    },
+    /// A cluster in a chain links to a free, bad or missing cluster. The chain
+    /// is ended at `cluster`.
+    BadLink {
+        /// The path of the chain owner.
+        path: String,
+        /// The last good cluster of the chain.
+        cluster: u32,
+        /// The FAT entry of `cluster`.
+        value: u32,
+    },
+    /// A chain links back to one of its own clusters. The chain is ended at
+    /// `cluster`.
+    Loop {
+        /// The path of the chain owner.
+        path: String,
+        /// The cluster linking backwards.
+        cluster: u32,
+    },
+    /// A chain runs into a cluster owned by another entry. The chain is ended
+    /// before the shared cluster, or the entry is deleted if it starts there.
+    CrossLinked {
+        /// The path of the entry checked later.
+        path: String,
+        /// The path of the entry already owning the cluster.
+        owner: String,
+        /// The shared cluster.
+        cluster: u32,
+    },
+    /// The "." or ".." entry of a directory is wrong. Both are rewritten.
+    BadDotEntries {
+        /// The path of the directory.
+        path: String,
+    },
+    /// A file is larger than its chain. The size is cut to the chain.
+    SizeTooLarge {
+        /// The path of the file.
+        path: String,
+        /// The size in the entry.
+        size: u64,
+        /// The bytes held by the chain.
+        chain_bytes: u64,
+    },
+    /// Clusters are marked used but belong to no entry. They are freed.
+    Orphaned {
+        /// The number of orphaned clusters.
+        clusters: u32,
+    },
+    /// The two FAT copies differ. The second copy is rewritten.
+    FatMismatch {
+        /// The number of different entries.
+        entries: u32,
+    },
+    /// The free cluster count in FSINFO is wrong. It is recomputed.
+    FreeCount {
+        /// The count in FSINFO.
+        found: u32,
+        /// The count of free clusters in the FAT.
+        expected: u32,
+    },
+    /// The next free cluster in FSINFO is not free. It is recomputed.
+    NextFree {
+        /// The cluster in FSINFO.
+        found: u32,
+        /// The first free cluster in the FAT.
+        expected: u32,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PendingJournal => write!(f, "journal has a transaction to replay"),
+            Self::BadName { dir } => write!(f, "{dir}: entry with an invalid name"),
+            Self::BadFirstCluster { path, cluster } => {
+                write!(f, "{path}: invalid first cluster {cluster}")
+            }
+            Self::BadLink {
+                path,
+                cluster,
+                value,
+            } => write!(f, "{path}: cluster {cluster} links to invalid {value:#x}"),
+            Self::Loop { path, cluster } => {
+                write!(f, "{path}: chain loops at cluster {cluster}")
+            }
+            Self::CrossLinked {
+                path,
+                owner,
+                cluster,
+            } => write!(f, "{path}: cross-linked with {owner} at cluster {cluster}"),
+            Self::BadDotEntries { path } => write!(f, "{path}: bad \".\" or \"..\" entry"),
+            Self::SizeTooLarge {
+                path,
+                size,
+                chain_bytes,
+            } => write!(
+                f,
+                "{path}: size {size} exceeds its {chain_bytes} bytes of clusters"
+            ),
+            Self::Orphaned { clusters } => write!(f, "{clusters} orphaned clusters"),
+            Self::FatMismatch { entries } => write!(f, "FAT copies differ in {entries} entries"),
+            Self::FreeCount { found, expected } => {
+                write!(f, "FSINFO free count is {found}, should be {expected}")
+            }
+            Self::NextFree { found, expected } => {
+                write!(
+                    f,
+                    "FSINFO next free cluster is {found}, should be {expected}"
+                )
+            }
+        }
+    }
+}
+
+/// The result of [`check`].
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The problems found, in the order they were found.
+    pub problems: Vec<Problem>,
+    /// The number of files.
+    pub files: usize,
+    /// The number of directories, including the root.
+    pub dirs: usize,
+    /// The number of clusters in use after the fixes.
+    pub used_clusters: u32,
+    /// The number of clusters on the disk.
+    pub total_clusters: u32,
+    /// True if the fixes have been written to the disk.
+    pub repaired: bool,
+}
+
+impl Report {
+    /// Return true if no problem was found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A walk of the directory tree. The fixes are applied to the in-memory FAT
+/// as they are found, so the later checks see a consistent FAT, while entry
+/// fixes are queued until the end.
+struct Checker<'a> {
+    image: Image<'a>,
+    report: Report,
+    /// The index into `paths` of the owner of each cluster.
+    owners: Vec<Option<usize>>,
+    paths: Vec<String>,
+    /// Entries to write back: directory first cluster, slot and new entry.
+    entry_fixes: Vec<(u32, usize, DirEntry)>,
+}
+
+impl Checker<'_> {
+    /// Follow the chain of `path` from `first`, claiming its clusters and
+    /// ending it where it goes wrong. Returns the number of clusters kept.
+    fn claim_chain(&mut self, path: &str, first: u32) -> u32 {
+        let owner = self.paths.len();
+        self.paths.push(path.to_string());
+        let mut cluster = first;
+        let mut count = 1;
+        loop {
+            self.owners[cluster as usize] = Some(owner);
+            let value = self.image.fat()[cluster as usize];
+            if (0x0FFFFFF8..=FAT_EOC).contains(&value) {
+                return count;
+            }
+            let problem = if !self.image.is_link(value) {
+                Problem::BadLink {
+                    path: path.to_string(),
+                    cluster,
+                    value,
+                }
+            } else {
+                match self.owners[value as usize] {
+                    None => {
+                        cluster = value;
+                        count += 1;
+                        continue;
+                    }
+                    Some(o) if o == owner => Problem::Loop {
+                        path: path.to_string(),
+                        cluster,
+                    },
+                    Some(o) => Problem::CrossLinked {
+                        path: path.to_string(),
+                        owner: self.paths[o].clone(),
+                        cluster: value,
+                    },
+                }
+            };
+            self.report.problems.push(problem);
+            self.image.set_fat_entry(cluster, FAT_EOC);
+            return count;
+        }
+    }
+
+    /// Delete the entry in `slot` of the directory starting at `dir`.
+    fn delete_entry(&mut self, dir: u32, slot: usize, mut entry: DirEntry) {
+        entry.name[0] = 0xE5;
+        self.entry_fixes.push((dir, slot, entry));
+    }
+
+    /// Check the root directory and everything below it.
+    fn walk(&mut self) -> DevResult {
+        let root = self.image.root_cluster();
+        let root_clusters = self.image.root_clusters();
+        // the root directory is always contiguous, fix its chain in place
+        for cluster in root_clusters.clone() {
+            let expected = if cluster + 1 == root_clusters.end {
+                FAT_EOC
+            } else {
+                cluster + 1
+            };
+            let value = self.image.fat()[cluster as usize];
+            if value != expected {
+                self.report.problems.push(Problem::BadLink {
+                    path: "/".to_string(),
+                    cluster,
+                    value,
+                });
+                self.image.set_fat_entry(cluster, expected);
+            }
+        }
+        self.claim_chain("/", root);
+
+        let bytes_per_cluster = self.image.boot_sector().bytes_per_cluster() as u64;
+        let version = self.image.version();
+        let mut dirs = vec![(root, String::new())];
+        while let Some((dir, dir_path)) = dirs.pop() {
+            self.report.dirs += 1;
+            let is_root = dir == root;
+            let entries = self.image.read_dir(dir)?;
+            if !is_root && !Self::dot_entries_ok(&entries, dir) {
+                self.report.problems.push(Problem::BadDotEntries {
+                    path: dir_path.clone(),
+                });
+                let parent = entries.get(1).map_or(root, |e| e.first_cluster);
+                let parent = if self.image.is_link(parent) {
+                    parent
+                } else {
+                    root
+                };
+                for (slot, (name, first_cluster)) in [(".", dir), ("..", parent)].iter().enumerate()
+                {
+                    let entry = DirEntry {
+                        name: convert_to_u8_array(name).unwrap(),
+                        attr: DirEntryAttr::Directory,
+                        first_cluster: *first_cluster,
+                        file_size: 0,
+                    };
+                    self.entry_fixes.push((dir, slot, entry));
+                }
+            }
+            let start = if is_root { 0 } else { 2 };
+            for (slot, entry) in entries.into_iter().enumerate().skip(start) {
+                // the kernel stops reading the root directory at the first unused slot
+                if is_root && entry.name[0] == 0 {
+                    break;
+                }
+                if !entry.is_valid() {
+                    continue;
+                }
+                let name = entry.name();
+                let Some(name) = name.filter(|n| version.is_valid_name(n, entry.is_dir())) else {
+                    let path = if is_root { "/" } else { &dir_path };
+                    self.report.problems.push(Problem::BadName {
+                        dir: path.to_string(),
+                    });
+                    self.delete_entry(dir, slot, entry);
+                    continue;
+                };
+                let path = format!("{dir_path}/{name}");
+                let first = entry.first_cluster;
+                if !self.image.is_link(first) {
+                    self.report.problems.push(Problem::BadFirstCluster {
+                        path,
+                        cluster: first,
+                    });
+                    self.delete_entry(dir, slot, entry);
+                    continue;
+                }
+                if let Some(owner) = self.owners[first as usize] {
+                    self.report.problems.push(Problem::CrossLinked {
+                        path,
+                        owner: self.paths[owner].clone(),
+                        cluster: first,
+                    });
+                    self.delete_entry(dir, slot, entry);
+                    continue;
+                }
+                let clusters = self.claim_chain(&path, first);
+                if entry.is_dir() {
+                    dirs.push((first, path));
+                } else {
+                    self.report.files += 1;
+                    let chain_bytes = clusters as u64 * bytes_per_cluster;
+                    if entry.file_size > chain_bytes {
+                        self.report.problems.push(Problem::SizeTooLarge {
+                            path,
+                            size: entry.file_size,
+                            chain_bytes,
+                        });
+                        let mut entry = entry;
+                        entry.file_size = chain_bytes.min(version.max_file_size());
+                        self.entry_fixes.push((dir, slot, entry));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return true if the first two entries of a directory are "." and "..".
+    fn dot_entries_ok(entries: &[DirEntry], dir: u32) -> bool {
+        // the dot names are not valid directory names, so compare raw bytes
+        let is_dot = |entry: Option<&DirEntry>, name: &str| {
+            entry.is_some_and(|e| e.is_dir() && Some(e.name) == convert_to_u8_array(name))
+        };
+        is_dot(entries.first(), ".")
+            && entries[0].first_cluster == dir
+            && is_dot(entries.get(1), "..")
+    }
+
+    /// Free the used clusters that no entry owns.
+    fn free_orphans(&mut self) {
+        let mut orphans = 0;
+        for cluster in self.image.clusters() {
+            let value = self.image.fat()[cluster as usize];
+            if value != FAT_FREE && value != FAT_BAD && self.owners[cluster as usize].is_none() {
+                self.image.set_fat_entry(cluster, FAT_FREE);
+                orphans += 1;
+            }
+        }
+        if orphans > 0 {
+            self.report
+                .problems
+                .push(Problem::Orphaned { clusters: orphans });
+        }
+    }
+}
+
+/// Check the filesystem on the disk, and write the fixes back if `repair` is
+/// true.
+///
+/// The FAT copies and FSINFO are compared with the FAT as it is after fixing
+/// the chains, so a check reports the problems a repair would solve. Fails
+/// if the disk is not formatted.
+pub fn check(sector: &SectorManager, repair: bool) -> DevResult<Report> {
+    let mut image = Image::open(sector)?;
+    let boot = *image.boot_sector();
+    let pending = journal::is_pending(sector, &boot)?;
+    if pending {
+        if !repair {
+            return Ok(Report {
+                problems: vec![Problem::PendingJournal],
+                ..Default::default()
+            });
+        }
+        journal::replay(sector, &boot)?;
+        image = Image::open(sector)?;
+    }
+    let original = image.fat().to_vec();
+    let mut checker = Checker {
+        owners: vec![None; image.fat().len()],
+        report: Report {
+            total_clusters: image.clusters().len() as u32,
+            ..Default::default()
+        },
+        image,
+        paths: Vec::new(),
+        entry_fixes: Vec::new(),
+    };
+    if pending {
+        checker.report.problems.push(Problem::PendingJournal);
+        checker.report.repaired = true;
+    }
+    checker.walk()?;
+    checker.free_orphans();
+
+    let Checker {
+        image,
+        mut report,
+        entry_fixes,
+        ..
+    } = checker;
+    let mismatches = image
+        .read_fat_copy(1)?
+        .iter()
+        .zip(&original)
+        .filter(|(a, b)| a != b)
+        .count();
+    if mismatches > 0 {
+        report.problems.push(Problem::FatMismatch {
+            entries: mismatches as u32,
+        });
+    }
+    let found = image.read_fs_info()?;
+    let expected = image.fs_info();
+    if found.free_cluster_count != expected.free_cluster_count {
+        report.problems.push(Problem::FreeCount {
+            found: found.free_cluster_count,
+            expected: expected.free_cluster_count,
+        });
+    }
+    let next = found.next_free_cluster;
+    let next_is_free = image.is_link(next) && image.fat()[next as usize] == FAT_FREE;
+    if !next_is_free && expected.next_free_cluster != 0 {
+        report.problems.push(Problem::NextFree {
+            found: next,
+            expected: expected.next_free_cluster,
+        });
+    }
+    report.used_clusters = report.total_clusters - expected.free_cluster_count;
+
+    if repair && !report.is_clean() {
+        for (dir, slot, entry) in &entry_fixes {
+            image.write_dir_entry(*dir, *slot, entry)?;
+        }
+        image.flush()?;
+        report.repaired = true;
+    }
+    Ok(report)
+}
@@ -0,0 +1,310 @@
+//! Direct access to a filesystem image, without mounting it.
+//!
+//! [`Image`] works on the on-disk structures only: it keeps the FAT in memory
+//! and reads or writes clusters through a [`SectorManager`]. It is shared by
+//! [`crate::mkfs`], [`crate::fsck`] and the host-side tools, none of which can
+//! use the global [`crate::FS`].
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use axdriver::prelude::*;
+
+use crate::layout::{
+    convert_to_u8_array, BootSector, DirEntry, DirEntryAttr, FSInfoSector, Version,
+};
+use crate::sector::SectorManager;
+
+/// The sector size of the image.
+const SECTOR_SIZE: u64 = 512;
+
+/// The FAT entry of a free cluster.
+pub const FAT_FREE: u32 = 0x00000000;
+/// The FAT entry of a bad cluster.
+///
+/// The entries past the last cluster are marked bad as well, so that the
+/// kernel never allocates a cluster that is not on the disk.
+pub const FAT_BAD: u32 = 0x0FFFFFF7;
+/// The FAT entry of the last cluster of a chain.
+pub const FAT_EOC: u32 = 0x0FFFFFFF;
+/// The FAT entry 0, holding the media type.
+const FAT_MEDIA: u32 = 0x0FFFFFF8;
+
+/// A filesystem image opened for direct access.
+pub struct Image<'a> {
+    sector: &'a SectorManager,
+    boot: BootSector,
+    fat: Vec<u32>,
+}
+
+impl<'a> Image<'a> {
+    /// Create an image with an empty FAT, used by mkfs on a disk whose boot
+    /// sector has just been written.
+    pub(crate) fn new(sector: &'a SectorManager, boot: BootSector) -> Self {
+        let entries = boot.fat_sectors_count() as usize * SECTOR_SIZE as usize / 4;
+        let mut fat = vec![FAT_BAD; entries];
+        fat[0] = FAT_MEDIA;
+        fat[1] = FAT_EOC;
+        let clusters = boot.clusters_count() as usize + 2;
+        fat[2..clusters].fill(FAT_FREE);
+        Self { sector, boot, fat }
+    }
+
+    /// Open a formatted image, reading its boot sector and the first FAT.
+    pub fn open(sector: &'a SectorManager) -> DevResult<Self> {
+        let mut buf = [0u8; SECTOR_SIZE as usize];
+        sector.read_sector_at(0, &mut buf)?;
+        let boot = BootSector::from_bytes(&buf);
+        if !boot.is_valid(sector.sector_count() / SECTOR_SIZE) {
+            return Err(DevError::InvalidParam);
+        }
+        let mut image = Self {
+            sector,
+            boot,
+            fat: Vec::new(),
+        };
+        image.fat = image.read_fat_copy(0)?;
+        Ok(image)
+    }
+
+    /// Return the boot sector of the image.
+    pub fn boot_sector(&self) -> &BootSector {
+        &self.boot
+    }
+
+    /// Return the format version of the image.
+    pub fn version(&self) -> Version {
+        self.boot.format_version()
+    }
+
+    /// Return the in-memory FAT.
+    pub fn fat(&self) -> &[u32] {
+        &self.fat
+    }
+
+    /// Set a FAT entry in memory, written back by [`Image::flush`].
+    pub fn set_fat_entry(&mut self, cluster: u32, value: u32) {
+        self.fat[cluster as usize] = value;
+    }
+
+    /// Return the range of cluster ids that exist on the disk.
+    pub fn clusters(&self) -> Range<u32> {
+        2..self.boot.clusters_count() + 2
+    }
+
+    /// Return the first cluster of the root directory.
+    pub fn root_cluster(&self) -> u32 {
+        self.boot.root_cluster
+    }
+
+    /// Return the clusters of the root directory, which have a fixed size and
+    /// position right at the start of the data area.
+    pub fn root_clusters(&self) -> Range<u32> {
+        let count = self.boot.root_dir_sectors_count() / self.boot.sectors_per_cluster as u32;
+        self.boot.root_cluster..self.boot.root_cluster + count
+    }
+
+    /// Return true if the FAT entry links to another cluster on the disk.
+    pub fn is_link(&self, value: u32) -> bool {
+        self.clusters().contains(&value)
+    }
+
+    /// Read one of the two FAT copies from disk.
+    pub fn read_fat_copy(&self, copy: u32) -> DevResult<Vec<u32>> {
+        let sectors = self.boot.fat_sectors_count() as u64;
+        let start = self.boot.fat_start_sector() as u64 + copy as u64 * sectors;
+        let mut buf = vec![0u8; (sectors * SECTOR_SIZE) as usize];
+        self.sector.read_sector_at(start * SECTOR_SIZE, &mut buf)?;
+        Ok(buf
+            .as_chunks()
+            .0
+            .iter()
+            .map(|b| u32::from_le_bytes(*b))
+            .collect())
+    }
+
+    /// Read the FSINFO sector from disk.
+    pub fn read_fs_info(&self) -> DevResult<FSInfoSector> {
+        let mut buf = [0u8; SECTOR_SIZE as usize];
+        self.sector
+            .read_sector_at(self.boot.fsinfo_sector as u64 * SECTOR_SIZE, &mut buf)?;
+        Ok(FSInfoSector::from_bytes(&buf))
+    }
+
+    /// Build the FSINFO sector matching the in-memory FAT.
+    pub fn fs_info(&self) -> FSInfoSector {
+        let free = self
+            .clusters()
+            .filter(|&c| self.fat[c as usize] == FAT_FREE);
+        FSInfoSector::new(free.clone().count() as u32, free.min().unwrap_or(0))
+    }
+
+    /// Write the in-memory FAT to both copies on disk, then update FSINFO.
+    pub fn flush(&self) -> DevResult {
+        let bytes: Vec<u8> = self.fat.iter().flat_map(|e| e.to_le_bytes()).collect();
+        for copy in 0..self.boot.fat_count as u64 {
+            let start =
+                self.boot.fat_start_sector() as u64 + copy * self.boot.fat_sectors_count() as u64;
+            self.sector.write_sector_at(start * SECTOR_SIZE, &bytes)?;
+        }
+        self.sector.write_sector_at(
+            self.boot.fsinfo_sector as u64 * SECTOR_SIZE,
+            self.fs_info().to_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Read a cluster from disk.
+    pub fn read_cluster(&self, cluster: u32) -> DevResult<Vec<u8>> {
+        let mut buf = vec![0u8; self.boot.bytes_per_cluster() as usize];
+        let sector = self.boot.cluster_to_sector(cluster) as u64;
+        self.sector.read_sector_at(sector * SECTOR_SIZE, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Write a cluster to disk, `data` is padded with zeros to the cluster size.
+    pub fn write_cluster(&self, cluster: u32, data: &[u8]) -> DevResult {
+        let mut buf = vec![0u8; self.boot.bytes_per_cluster() as usize];
+        buf[..data.len()].copy_from_slice(data);
+        let sector = self.boot.cluster_to_sector(cluster) as u64;
+        self.sector.write_sector_at(sector * SECTOR_SIZE, &buf)?;
+        Ok(())
+    }
+
+    /// Follow the chain from `first`, returning all its clusters.
+    ///
+    /// A chain that leaves the disk, runs into a free or bad cluster, or loops
+    /// is reported as [`DevError::BadState`], use [`crate::fsck`] to fix it.
+    pub fn chain(&self, first: u32) -> DevResult<Vec<u32>> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        loop {
+            if !self.is_link(cluster) || chain.len() >= self.clusters().len() {
+                return Err(DevError::BadState);
+            }
+            chain.push(cluster);
+            match self.fat[cluster as usize] {
+                FAT_MEDIA..=FAT_EOC => return Ok(chain),
+                next => cluster = next,
+            }
+        }
+    }
+
+    /// Read all entry slots of the directory starting at `first`, including
+    /// the free ones.
+    pub fn read_dir(&self, first: u32) -> DevResult<Vec<DirEntry>> {
+        let version = self.version();
+        let mut entries = Vec::new();
+        for cluster in self.chain(first)? {
+            let data = self.read_cluster(cluster)?;
+            entries.extend(
+                data.chunks_exact(version.entry_size())
+                    .map(|b| DirEntry::from_bytes(b, version)),
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Write the entry slot `index` of the directory starting at `first`.
+    pub fn write_dir_entry(&self, first: u32, index: usize, entry: &DirEntry) -> DevResult {
+        let entry_size = self.version().entry_size();
+        let per_cluster = self.boot.bytes_per_cluster() as usize / entry_size;
+        let cluster = *self
+            .chain(first)?
+            .get(index / per_cluster)
+            .ok_or(DevError::InvalidParam)?;
+        let offset = self.boot.cluster_to_sector(cluster) as u64 * SECTOR_SIZE
+            + (index % per_cluster * entry_size) as u64;
+        self.sector
+            .write_sector_at(offset, &entry.to_bytes(self.version()))?;
+        Ok(())
+    }
+
+    /// Allocate a zeroed cluster and mark it as the end of a chain.
+    pub fn alloc_cluster(&mut self) -> DevResult<u32> {
+        let cluster = self
+            .clusters()
+            .find(|&c| self.fat[c as usize] == FAT_FREE)
+            .ok_or(DevError::NoMemory)?;
+        self.fat[cluster as usize] = FAT_EOC;
+        self.write_cluster(cluster, &[])?;
+        Ok(cluster)
+    }
+
+    /// Create an empty directory `name` in the directory starting at `parent`,
+    /// returning its first cluster.
+    pub fn create_dir(&mut self, parent: u32, name: &str) -> DevResult<u32> {
+        let slot = self.free_slot(parent, name, true)?;
+        let cluster = self.alloc_cluster()?;
+        let dot = |name, first_cluster| DirEntry {
+            name: convert_to_u8_array(name).unwrap(),
+            attr: DirEntryAttr::Directory,
+            first_cluster,
+            file_size: 0,
+        };
+        self.write_dir_entry(cluster, 0, &dot(".", cluster))?;
+        self.write_dir_entry(cluster, 1, &dot("..", parent))?;
+        self.write_dir_entry(parent, slot, &dot(name, cluster))?;
+        Ok(cluster)
+    }
+
+    /// Create a file `name` holding `data` in the directory starting at
+    /// `parent`, returning its first cluster.
+    pub fn create_file(&mut self, parent: u32, name: &str, data: &[u8]) -> DevResult<u32> {
+        let file_size = data.len() as u64;
+        if file_size > self.version().max_file_size() {
+            return Err(DevError::InvalidParam);
+        }
+        let slot = self.free_slot(parent, name, false)?;
+        // like the kernel, even an empty file owns a cluster
+        let first = self.alloc_cluster()?;
+        let mut cluster = first;
+        let mut chunks = data.chunks(self.boot.bytes_per_cluster() as usize);
+        if let Some(chunk) = chunks.next() {
+            self.write_cluster(cluster, chunk)?;
+        }
+        for chunk in chunks {
+            let next = self.alloc_cluster()?;
+            self.fat[cluster as usize] = next;
+            self.write_cluster(next, chunk)?;
+            cluster = next;
+        }
+        let entry = DirEntry {
+            name: convert_to_u8_array(name).unwrap(),
+            attr: DirEntryAttr::Archive,
+            first_cluster: first,
+            file_size,
+        };
+        self.write_dir_entry(parent, slot, &entry)?;
+        Ok(first)
+    }
+
+    /// Check that `name` can be added to a directory as a file, or a
+    /// directory if `is_dir`, then find the first free entry slot for it,
+    /// growing the directory by a cluster if it is full.
+    /// The root directory has a fixed size.
+    fn free_slot(&mut self, dir: u32, name: &str, is_dir: bool) -> DevResult<usize> {
+        if !self.version().is_valid_name(name, is_dir) {
+            return Err(DevError::InvalidParam);
+        }
+        let entries = self.read_dir(dir)?;
+        if entries
+            .iter()
+            .any(|e| e.is_valid() && e.name().as_deref() == Some(name))
+        {
+            return Err(DevError::AlreadyExists);
+        }
+        let start = if dir == self.root_cluster() { 0 } else { 2 };
+        match entries.iter().skip(start).position(|e| !e.is_valid()) {
+            Some(index) => Ok(index + start),
+            None if dir == self.root_cluster() => Err(DevError::NoMemory),
+            None => {
+                let last = *self.chain(dir)?.last().unwrap();
+                let cluster = self.alloc_cluster()?;
+                self.fat[last as usize] = cluster;
+                Ok(entries.len())
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! A write-ahead journal for metadata sectors.
+//!
+//! A transaction is a set of sector images: FAT sectors of both copies,
+//! directory entry sectors and FSINFO. They are written to the journal area
+//! first, then the first sector of the journal header is written, which
+//! commits the transaction, and only then are they written to their home
+//! locations. The header is cleared at last. The journal is sized by
+//! [`mkfs`](crate::mkfs) to hold the largest transaction, one that is larger
+//! anyway is rejected rather than split. If the power is lost on the way, [`replay`] finds the
+//! committed header on the next mount and writes the sectors home again, so
+//! the metadata is either all old or all new.
+//!
+//! As the disk is accessed through a write-back block cache, the cache is
+//! flushed between these steps, so that each one is on the disk before the
+//! next one starts.
+//!
+//! File data is not journaled, it is written to its clusters before the
+//! transaction allocating them commits, by the first flush.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axdriver::prelude::*;
+
+use crate::layout::BootSector;
+use crate::sector::SectorManager;
+
+/// The sector size of the journal.
+const SECTOR_SIZE: usize = 512;
+
+/// The magic number of a committed header, "CCJL" in ASCII.
+const JOURNAL_MAGIC: u32 = 0x4343_4A4C;
+
+/// The number of 32-bit words in a sector.
+const SECTOR_WORDS: usize = SECTOR_SIZE / 4;
+
+/// The words before the home sector numbers in the header: the magic, the
+/// block count and the checksum.
+const HEADER_WORDS: usize = 3;
+
+/// The sector images of a transaction, by home sector number.
+pub type Transaction = BTreeMap<u64, Vec<u8>>;
+
+/// The journal header, at the start of the journal area.
+///
+/// It takes as many sectors as its home sector numbers need, and the first
+/// one is written last, so that writing it commits the transaction.
+struct Header {
+    /// The home sectors of the journaled blocks, which follow the header.
+    homes: Vec<u32>,
+    /// The checksum of the home sectors and the blocks.
+    checksum: u32,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; header_sectors(self.homes.len()) * SECTOR_SIZE];
+        let words = [JOURNAL_MAGIC, self.homes.len() as u32, self.checksum];
+        for (i, word) in words.iter().chain(&self.homes).enumerate() {
+            buf[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Parse a header of whole sectors, whose first sector is committed.
+    fn from_bytes(buf: &[u8]) -> Self {
+        let word = |i: usize| u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap());
+        Self {
+            homes: (0..word(1) as usize)
+                .map(|i| word(HEADER_WORDS + i))
+                .collect(),
+            checksum: word(2),
+        }
+    }
+}
+
+/// Return the block count of a committed first header sector, `None` if the
+/// journal is clean.
+fn committed_count(buf: &[u8]) -> Option<usize> {
+    let word = |i: usize| u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap());
+    let count = word(1) as usize;
+    (word(0) == JOURNAL_MAGIC && count != 0).then_some(count)
+}
+
+/// Return the number of sectors of the header of `blocks` blocks.
+fn header_sectors(blocks: usize) -> usize {
+    (HEADER_WORDS + blocks).div_ceil(SECTOR_WORDS)
+}
+
+/// Return the size in sectors of a journal holding transactions of up to
+/// `blocks` blocks.
+pub fn journal_sectors(blocks: usize) -> usize {
+    header_sectors(blocks) + blocks
+}
+
+/// FNV-1a over the home sector numbers and the blocks.
+fn checksum<'a>(homes: &[u32], blocks: impl Iterator<Item = &'a [u8]>) -> u32 {
+    let mut hash = 0x811c_9dc5u32;
+    let homes = homes.iter().flat_map(|h| h.to_le_bytes());
+    for byte in homes.chain(blocks.flatten().copied()) {
+        hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+/// Return the number of blocks one journal transaction can hold.
+pub fn capacity(boot: &BootSector) -> usize {
+    let sectors = boot.journal_sectors_count as usize;
+    // the header of all the sectors is at least as large as the one needed
+    let mut blocks = sectors.saturating_sub(header_sectors(sectors));
+    while journal_sectors(blocks + 1) <= sectors {
+        blocks += 1;
+    }
+    blocks
+}
+
+/// Write the sectors of a transaction through the journal.
+///
+/// Without a journal the sectors are written in place. A transaction larger
+/// than the journal can't be committed atomically, it is rejected with
+/// [`DevError::NoMemory`] before anything is written.
+pub fn commit(sector: &SectorManager, boot: &BootSector, tx: &Transaction) -> DevResult {
+    if boot.journal_sectors_count == 0 {
+        for (&home, data) in tx {
+            sector.write_sector_at(home * SECTOR_SIZE as u64, data)?;
+        }
+        return Ok(());
+    }
+    if tx.len() > capacity(boot) {
+        return Err(DevError::NoMemory);
+    }
+    let start = boot.journal_start_sector as u64;
+    let blocks_start = start + header_sectors(tx.len()) as u64;
+    for (i, data) in tx.values().enumerate() {
+        sector.write_sector_at((blocks_start + i as u64) * SECTOR_SIZE as u64, data)?;
+    }
+    let homes: Vec<u32> = tx.keys().map(|&home| home as u32).collect();
+    let header = Header {
+        checksum: checksum(&homes, tx.values().map(Vec::as_slice)),
+        homes,
+    }
+    .to_bytes();
+    // the rest of the header goes with the blocks, its first sector commits
+    if header.len() > SECTOR_SIZE {
+        sector.write_sector_at((start + 1) * SECTOR_SIZE as u64, &header[SECTOR_SIZE..])?;
+    }
+    sector.flush()?;
+    sector.write_sector_at(start * SECTOR_SIZE as u64, &header[..SECTOR_SIZE])?;
+    sector.flush()?;
+    for (&home, data) in tx {
+        sector.write_sector_at(home * SECTOR_SIZE as u64, data)?;
+    }
+    sector.flush()?;
+    clear(sector, boot)
+}
+
+/// Clear the journal header, after the transaction has reached home.
+fn clear(sector: &SectorManager, boot: &BootSector) -> DevResult {
+    let start = boot.journal_start_sector as u64;
+    sector.write_sector_at(start * SECTOR_SIZE as u64, &[0u8; SECTOR_SIZE])?;
+    Ok(())
+}
+
+/// Return true if the journal holds a committed transaction that may not
+/// have reached home.
+pub fn is_pending(sector: &SectorManager, boot: &BootSector) -> DevResult<bool> {
+    if boot.journal_sectors_count == 0 {
+        return Ok(false);
+    }
+    let mut buf = [0u8; SECTOR_SIZE];
+    let start = boot.journal_start_sector as u64;
+    sector.read_sector_at(start * SECTOR_SIZE as u64, &mut buf)?;
+    Ok(committed_count(&buf).is_some())
+}
+
+/// Write a committed transaction home again, then clear the journal. Returns
+/// true if a transaction was replayed.
+///
+/// A header whose checksum does not match was torn while being written, the
+/// transaction never committed and is dropped.
+pub fn replay(sector: &SectorManager, boot: &BootSector) -> DevResult<bool> {
+    if boot.journal_sectors_count == 0 {
+        return Ok(false);
+    }
+    let start = boot.journal_start_sector as u64;
+    let mut buf = vec![0u8; SECTOR_SIZE];
+    sector.read_sector_at(start * SECTOR_SIZE as u64, &mut buf)?;
+    let Some(count) = committed_count(&buf) else {
+        return Ok(false);
+    };
+    if count > capacity(boot) {
+        clear(sector, boot)?;
+        return Ok(false);
+    }
+    buf.resize(header_sectors(count) * SECTOR_SIZE, 0);
+    if buf.len() > SECTOR_SIZE {
+        sector.read_sector_at((start + 1) * SECTOR_SIZE as u64, &mut buf[SECTOR_SIZE..])?;
+    }
+    let header = Header::from_bytes(&buf);
+    let blocks_start = start + header_sectors(count) as u64;
+    let mut blocks = vec![vec![0u8; SECTOR_SIZE]; count];
+    for (i, block) in blocks.iter_mut().enumerate() {
+        sector.read_sector_at((blocks_start + i as u64) * SECTOR_SIZE as u64, block)?;
+    }
+    let replayed = checksum(&header.homes, blocks.iter().map(Vec::as_slice)) == header.checksum;
+    if replayed {
+        for (&home, block) in header.homes.iter().zip(&blocks) {
+            sector.write_sector_at(home as u64 * SECTOR_SIZE as u64, block)?;
+        }
+        sector.flush()?;
+    }
+    clear(sector, boot)?;
+    Ok(replayed)
+}
@@ -1,8 +1,84 @@
 use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The maximum length of a file name in bytes, in any format version.
+pub const NAME_MAX: usize = 240;
+
+/// The on-disk format version of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// 32-byte directory entries with names of up to 23 bytes, directory
+    /// names limited to letters, digits, `_` and `-`, and 32-bit sizes.
+    V1 = 1,
+    /// 256-byte directory entries with UTF-8 names of up to 240 bytes and
+    /// 64-bit sizes.
+    V2 = 2,
+}
+
+impl Version {
+    /// The version written by mkfs.
+    pub const CURRENT: Self = Self::V2;
+
+    /// Convert the version field of the boot sector, volumes created before
+    /// versioning have 0 there.
+    pub fn from_value(value: u16) -> Option<Self> {
+        match value {
+            0 | 1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    /// Return the size of a directory entry in bytes.
+    pub fn entry_size(self) -> usize {
+        match self {
+            Self::V1 => 32,
+            Self::V2 => 256,
+        }
+    }
+
+    /// Return the maximum length of a name in bytes.
+    pub fn name_max(self) -> usize {
+        match self {
+            Self::V1 => 23,
+            Self::V2 => NAME_MAX,
+        }
+    }
+
+    /// Return the maximum size of a file in bytes.
+    pub fn max_file_size(self) -> u64 {
+        match self {
+            Self::V1 => u32::MAX as u64,
+            Self::V2 => u64::MAX,
+        }
+    }
+
+    /// Check that `name` can be given to a new file, or directory if `is_dir`.
+    pub fn is_valid_name(self, name: &str, is_dir: bool) -> bool {
+        if name.is_empty()
+            || name == "."
+            || name == ".."
+            || name.len() > self.name_max()
+            || name.contains(['/', '\0'])
+        {
+            return false;
+        }
+        match self {
+            Self::V1 => {
+                !is_dir
+                    || name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            }
+            Self::V2 => !name.chars().any(char::is_control),
+        }
+    }
+}
 
 /// A Struct representing the boot sector of a FAT32 volume.
 #[repr(packed)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct BootSector {
     /// Bytes per sector, usually set to 512.
     pub bytes_per_sector: u16,
@@ -22,8 +98,14 @@ pub struct BootSector {
     pub root_dir_sectors_count: u32,
     /// Sector number of FSINFO structure, usually set to 1.
     pub fsinfo_sector: u16,
+    /// First sector of the metadata journal, in the reserved area.
+    pub journal_start_sector: u32,
+    /// Size of the metadata journal in sectors, 0 if there is no journal.
+    pub journal_sectors_count: u32,
+    /// The format version, see [`Version`].
+    pub version: u16,
     /// Reserved, fill to 512 bytes.
-    pub reserved: [u8; 488],
+    pub reserved: [u8; 478],
 }
 
 impl BootSector {
@@ -37,6 +119,74 @@ impl BootSector {
         }
     }
 
+    /// Parse a BootSector from the first 512 bytes of a volume.
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        let mut reserved = [0u8; 478];
+        reserved.copy_from_slice(&buf[34..512]);
+        Self {
+            bytes_per_sector: u16_at(0),
+            sectors_per_cluster: buf[2],
+            reserved_sectors_count: u16_at(3),
+            total_sectors32: u32_at(5),
+            fat_count: buf[9],
+            sectors_per_fat32: u32_at(10),
+            root_cluster: u32_at(14),
+            root_dir_sectors_count: u32_at(18),
+            fsinfo_sector: u16_at(22),
+            journal_start_sector: u32_at(24),
+            journal_sectors_count: u32_at(28),
+            version: u16_at(32),
+            reserved,
+        }
+    }
+
+    /// Check that the geometry is usable on a disk of `disk_sectors` sectors.
+    ///
+    /// A zeroed or foreign boot sector fails this check, so it also tells
+    /// whether the disk has been formatted.
+    pub fn is_valid(&self, disk_sectors: u64) -> bool {
+        let spc = self.sectors_per_cluster as u64;
+        let root_sectors = self.root_dir_sectors_count as u64;
+        let total = self.total_sectors32 as u64;
+        let data_start = self.reserved_sectors_count as u64 + self.sectors_per_fat32 as u64 * 2;
+        if self.bytes_per_sector != 512
+            || !self.sectors_per_cluster.is_power_of_two()
+            || self.fat_count != 2
+            || self.root_cluster != 2
+            || self.fsinfo_sector != 1
+            || self.reserved_sectors_count <= self.fsinfo_sector
+            || root_sectors == 0
+            || !root_sectors.is_multiple_of(spc)
+            || total > disk_sectors
+            || total < data_start + root_sectors + spc
+            || !self.journal_is_valid()
+            || Version::from_value(self.version).is_none()
+        {
+            return false;
+        }
+        // the FAT must have an entry for every cluster
+        let clusters = (total - data_start) / spc;
+        self.sectors_per_fat32 as u64 * 128 >= clusters + 2
+    }
+
+    /// Check that the journal, if any, lies between FSINFO and the FAT.
+    fn journal_is_valid(&self) -> bool {
+        let sectors = self.journal_sectors_count as u64;
+        let start = self.journal_start_sector as u64;
+        sectors == 0
+            || (sectors >= 2
+                && start > self.fsinfo_sector as u64
+                && start + sectors <= self.reserved_sectors_count as u64)
+    }
+
+    /// Return the format version, the check in [`Self::is_valid`] makes sure
+    /// that it is known.
+    pub fn format_version(&self) -> Version {
+        Version::from_value(self.version).unwrap_or(Version::V1)
+    }
+
     /// Return the start sector of FAT.
     pub fn fat_start_sector(&self) -> u32 {
         self.reserved_sectors_count as u32
@@ -106,7 +256,10 @@ impl Default for BootSector {
             root_cluster: 2,
             root_dir_sectors_count: 0,
             fsinfo_sector: 1,
-            reserved: [0; 488],
+            journal_start_sector: 0,
+            journal_sectors_count: 0,
+            version: Version::CURRENT as u16,
+            reserved: [0; 478],
         }
     }
 }
@@ -134,6 +287,17 @@ impl FSInfoSector {
         }
     }
 
+    /// Parse a FSInfoSector from a 512-byte sector.
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        let mut reserved = [0u8; 504];
+        reserved.copy_from_slice(&buf[8..512]);
+        Self {
+            free_cluster_count: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            next_free_cluster: u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            reserved,
+        }
+    }
+
     /// Create a new FSInfoSector struct.
     pub fn new(free_cluster_count: u32, next_free_cluster: u32) -> Self {
         Self {
@@ -173,46 +337,79 @@ bitflags::bitflags! {
     }
 }
 
-/// A Struct representing a directory entry
-#[repr(packed)]
+/// A Struct representing a directory entry, the same in memory for all
+/// format versions.
 #[derive(Debug, Copy, Clone)]
 pub struct DirEntry {
-    /// SFN(Short file name) of the object
-    pub name: [u8; 23],
+    /// Name of the object in UTF-8, padded with zeros. As 0xE5 in the first
+    /// byte marks a deleted entry, a name starting with that byte keeps 0x05
+    /// there instead, like in FAT.
+    pub name: [u8; NAME_MAX],
     /// Attribute of the object
     pub attr: DirEntryAttr,
     /// First cluster of the file
     pub first_cluster: u32,
     /// File size in bytes
-    pub file_size: u32,
+    pub file_size: u64,
 }
 
 impl DirEntry {
-    /// Create a new function for DirEntry, function's parameter is a slice of 32 bytes, then return a packed DirEntry struct.
-    pub fn new(buf: &[u8]) -> Self {
+    /// Parse a directory entry of `version` from `buf`, which holds at least
+    /// one entry.
+    ///
+    /// A version 1 entry is the name (23 bytes), the attribute, the first
+    /// cluster and a 32-bit size. A version 2 entry is the name (240 bytes),
+    /// the attribute, 3 reserved bytes, the first cluster and a 64-bit size.
+    pub fn from_bytes(buf: &[u8], version: Version) -> Self {
+        let name_max = version.name_max();
         let mut dir_entry = Self::default();
-        dir_entry.name.copy_from_slice(&buf[0..23]);
-        dir_entry.attr = match buf[23] {
-            0x01 => DirEntryAttr::ReadOnly,
-            0x02 => DirEntryAttr::Hidden,
-            0x04 => DirEntryAttr::System,
-            0x08 => DirEntryAttr::VolumeId,
-            0x10 => DirEntryAttr::Directory,
-            0x20 => DirEntryAttr::Archive,
-            _ => DirEntryAttr::Archive,
+        dir_entry.name[..name_max].copy_from_slice(&buf[..name_max]);
+        // attributes can be combined, e.g. a read-only directory
+        dir_entry.attr = match DirEntryAttr::from_bits_truncate(buf[name_max]) {
+            attr if attr.is_empty() => DirEntryAttr::Archive,
+            attr => attr,
         };
-        dir_entry.first_cluster = u32::from_le_bytes([buf[24], buf[25], buf[26], buf[27]]);
-        dir_entry.file_size = u32::from_le_bytes([buf[28], buf[29], buf[30], buf[31]]);
+        let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+        match version {
+            Version::V1 => {
+                dir_entry.first_cluster = u32_at(24);
+                dir_entry.file_size = u32_at(28) as u64;
+            }
+            Version::V2 => {
+                dir_entry.first_cluster = u32_at(244);
+                dir_entry.file_size = u64::from_le_bytes(buf[248..256].try_into().unwrap());
+            }
+        }
         dir_entry
     }
 
+    /// Convert the entry to its on-disk form in `version`. The name and the
+    /// size must fit in that version.
+    pub fn to_bytes(&self, version: Version) -> Vec<u8> {
+        let name_max = version.name_max();
+        let mut buf = vec![0u8; version.entry_size()];
+        buf[..name_max].copy_from_slice(&self.name[..name_max]);
+        buf[name_max] = self.attr.bits();
+        match version {
+            Version::V1 => {
+                buf[24..28].copy_from_slice(&self.first_cluster.to_le_bytes());
+                buf[28..32].copy_from_slice(&(self.file_size as u32).to_le_bytes());
+            }
+            Version::V2 => {
+                buf[244..248].copy_from_slice(&self.first_cluster.to_le_bytes());
+                buf[248..256].copy_from_slice(&self.file_size.to_le_bytes());
+            }
+        }
+        buf
+    }
+
     /// Increase the file size.
-    pub fn increase_file_size(&mut self, size: u32) {
+    pub fn increase_file_size(&mut self, size: u64) {
         self.file_size += size;
     }
 
     /// Decrease the file size.
-    pub fn decrease_file_size(&mut self, size: u32) {
+    pub fn decrease_file_size(&mut self, size: u64) {
         self.file_size -= size;
     }
 
@@ -231,29 +428,18 @@ impl DirEntry {
         !self.is_dir()
     }
 
-    /// Get the name of the directory entry.
+    /// Get the name of the directory entry, `None` for "." and ".." or if
+    /// the name is not UTF-8.
     pub fn name(&self) -> Option<String> {
-        let name_string = match core::str::from_utf8(&self.name) {
-            Ok(utf8_str) => utf8_str.trim_matches('\0').to_string(),
-            Err(_) => {
-                return None;
-            }
-        };
-        // check if the name string is ascii and if this is a dir, can only use letter and numbers
-        if self.is_dir()
-            && !name_string
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
-        {
-            None
-        } else {
-            Some(name_string)
+        let mut name = self.name;
+        if name[0] == 0x05 {
+            name[0] = 0xE5;
+        }
+        let len = name.iter().position(|&b| b == 0).unwrap_or(NAME_MAX);
+        match core::str::from_utf8(&name[..len]) {
+            Ok("." | "..") | Err(_) => None,
+            Ok(name) => Some(name.to_string()),
         }
-    }
-
-    /// set_name: set the name of the directory entry
-    pub fn as_bytes(&self) -> &[u8] {
-        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, 32) }
     }
 }
 
@@ -266,7 +452,7 @@ impl Default for DirEntryAttr {
 impl Default for DirEntry {
     fn default() -> Self {
         Self {
-            name: [0u8; 23],
+            name: [0u8; NAME_MAX],
             attr: DirEntryAttr::Archive,
             first_cluster: 0,
             file_size: 0,
@@ -302,13 +488,17 @@ impl FatMarker {
     }
 }
 
-/// Convert a u8 array to a string.
-pub fn convert_to_u8_array(s: &str) -> Option<[u8; 23]> {
+/// Convert a name to the name field of a directory entry, `None` if it is
+/// too long for any format version.
+pub fn convert_to_u8_array(s: &str) -> Option<[u8; NAME_MAX]> {
     let bytes = s.as_bytes();
-    if bytes.len() > 23 {
+    if bytes.len() > NAME_MAX {
         return None;
     }
-    let mut array = [0u8; 23];
+    let mut array = [0u8; NAME_MAX];
     array[..bytes.len()].copy_from_slice(bytes);
+    if array[0] == 0xE5 {
+        array[0] = 0x05;
+    }
     Some(array)
 }
@@ -9,6 +9,8 @@ use crate::sector::SectorManager;
 use alloc::sync::Arc;
 use lazy_init::LazyInit;
 
+/// Page cache of file data.
+pub mod cache;
 /// Platform-specific constants and parameters axdiskfs.
 pub mod config;
 /// Directory operations.
@@ -19,10 +21,18 @@ pub mod disk;
 pub mod diskfs;
 /// File operations.
 pub mod file;
+/// Filesystem checking and repairing.
+pub mod fsck;
+/// Direct access to filesystem images.
+pub mod image;
+/// Metadata journaling.
+pub mod journal;
 /// Filesystem layout.
 pub mod layout;
 /// Macros.
 pub mod macros;
+/// Filesystem formatting.
+pub mod mkfs;
 /// Sector operations.
 pub mod sector;
 
@@ -31,7 +41,7 @@ pub static FS: LazyInit<Arc<diskfs::CCFileSystem>> = LazyInit::new();
 
 /// Initializes filesystems by Sector Manager.
 pub fn initialize_fs(sector_manager: SectorManager) {
-    let fs = diskfs::CCFileSystem::new(Some(sector_manager));
-    fs.init().expect("failed to init filesystem");
-    FS.init_by(Arc::new(fs));
+    // the directories loaded by init access the filesystem through FS
+    FS.init_by(Arc::new(diskfs::CCFileSystem::new(Some(sector_manager))));
+    FS.init().expect("failed to init filesystem");
 }
@@ -0,0 +1,170 @@
+//! Formatting a disk with an empty filesystem.
+
+use alloc::vec;
+use axdriver::prelude::*;
+
+use crate::image::{Image, FAT_EOC};
+use crate::journal;
+use crate::layout::{BootSector, Version};
+use crate::sector::SectorManager;
+
+/// The sector size of the filesystem.
+const SECTOR_SIZE: u64 = 512;
+
+/// The first sector of the metadata journal.
+const JOURNAL_START: u32 = 32;
+
+/// The most directory sectors one operation changes: the entry in the parent
+/// directory, and "." and ".." of a new directory.
+const TX_DIR_SECTORS: u32 = 3;
+
+/// Return the size of the metadata journal in sectors, for a transaction
+/// changing all sectors of both FATs, the directory sectors and FSINFO.
+///
+/// The reserved area is limited to 65535 sectors, the journal of a larger
+/// FAT is cut to fit, and the transactions changing too much of the FAT are
+/// rejected.
+fn journal_sectors(sectors_per_fat: u32) -> u32 {
+    let blocks = sectors_per_fat as usize * 2 + TX_DIR_SECTORS as usize + 1;
+    let max = (u16::MAX as u32 - JOURNAL_START) as usize;
+    journal::journal_sectors(blocks).min(max) as u32
+}
+
+/// The default number of entries in the root directory.
+const ROOT_DIR_ENTRIES: u32 = 128;
+
+/// Return the sectors of a root directory with the default number of entries,
+/// rounded up to whole clusters.
+fn root_dir_sectors(version: Version, sectors_per_cluster: u8) -> u32 {
+    let sectors = ROOT_DIR_ENTRIES * version.entry_size() as u32 / SECTOR_SIZE as u32;
+    sectors.next_multiple_of(sectors_per_cluster.max(1) as u32)
+}
+
+/// The geometry of a filesystem to create.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    /// Total sectors of the volume, at most the sectors of the disk.
+    pub total_sectors: u32,
+    /// Sectors per cluster, a power of two.
+    pub sectors_per_cluster: u8,
+    /// Size of the root directory in sectors, a multiple of the cluster size.
+    pub root_dir_sectors: u32,
+    /// The format version.
+    pub version: Version,
+}
+
+impl FormatOptions {
+    /// Options for a volume of `total_sectors` in the current version, with
+    /// 512-byte clusters and a root directory of 128 entries.
+    pub fn new(total_sectors: u32) -> Self {
+        Self {
+            total_sectors,
+            sectors_per_cluster: 1,
+            root_dir_sectors: root_dir_sectors(Version::CURRENT, 1),
+            version: Version::CURRENT,
+        }
+    }
+
+    /// Options for a volume filling the whole disk.
+    pub fn for_disk(sector: &SectorManager) -> Self {
+        let sectors = sector.sector_count() / SECTOR_SIZE;
+        Self::new(sectors.min(u32::MAX as u64) as u32)
+    }
+
+    /// Options keeping the geometry of an existing filesystem.
+    pub fn from_boot_sector(boot: &BootSector) -> Self {
+        Self {
+            total_sectors: boot.total_sectors32,
+            sectors_per_cluster: boot.sectors_per_cluster,
+            root_dir_sectors: boot.root_dir_sectors_count,
+            version: boot.format_version(),
+        }
+    }
+
+    /// Set the cluster size in sectors, the root directory is rounded up to
+    /// whole clusters.
+    pub fn cluster_size(mut self, sectors_per_cluster: u8) -> Self {
+        self.sectors_per_cluster = sectors_per_cluster;
+        self.root_dir_sectors = root_dir_sectors(self.version, sectors_per_cluster);
+        self
+    }
+
+    /// Set the format version, the root directory is resized to hold 128
+    /// entries of that version.
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self.root_dir_sectors = root_dir_sectors(version, self.sectors_per_cluster);
+        self
+    }
+
+    /// Compute the boot sector, the FAT is made just large enough to map all
+    /// the clusters left after it, and the journal large enough for a
+    /// transaction changing all of it.
+    pub fn boot_sector(&self) -> DevResult<BootSector> {
+        let spc = self.sectors_per_cluster as u32;
+        if spc == 0 {
+            return Err(DevError::InvalidParam);
+        }
+        let mut sectors_per_fat = 1;
+        loop {
+            let reserved = JOURNAL_START + journal_sectors(sectors_per_fat);
+            let data_start = reserved + sectors_per_fat * 2;
+            let clusters = self
+                .total_sectors
+                .checked_sub(data_start)
+                .ok_or(DevError::InvalidParam)?
+                / spc;
+            let needed = ((clusters as u64 + 2) * 4).div_ceil(SECTOR_SIZE) as u32;
+            // a larger FAT leaves fewer clusters, so this converges
+            if needed <= sectors_per_fat {
+                break;
+            }
+            sectors_per_fat = needed;
+        }
+        let journal_sectors = journal_sectors(sectors_per_fat);
+        Ok(BootSector {
+            sectors_per_cluster: self.sectors_per_cluster,
+            reserved_sectors_count: (JOURNAL_START + journal_sectors) as u16,
+            total_sectors32: self.total_sectors,
+            sectors_per_fat32: sectors_per_fat,
+            root_dir_sectors_count: self.root_dir_sectors,
+            journal_start_sector: JOURNAL_START,
+            journal_sectors_count: journal_sectors,
+            version: self.version as u16,
+            ..Default::default()
+        })
+    }
+}
+
+/// Return true if the disk holds a filesystem with a valid boot sector.
+pub fn is_formatted(sector: &SectorManager) -> bool {
+    Image::open(sector).is_ok()
+}
+
+/// Create an empty filesystem on the disk, returning its boot sector.
+///
+/// The reserved area with the journal, both FATs and the root directory are
+/// rewritten, the rest of the data area is left as is.
+pub fn format(sector: &SectorManager, options: &FormatOptions) -> DevResult<BootSector> {
+    let boot = options.boot_sector()?;
+    if !boot.is_valid(sector.sector_count() / SECTOR_SIZE) {
+        return Err(DevError::InvalidParam);
+    }
+    let zeros = vec![0u8; boot.reserved_sectors_count as usize * SECTOR_SIZE as usize];
+    sector.write_sector_at(0, &zeros)?;
+    sector.write_sector_at(0, boot.to_bytes())?;
+
+    let mut image = Image::new(sector, boot);
+    let root = image.root_clusters();
+    for cluster in root.clone() {
+        let next = if cluster + 1 == root.end {
+            FAT_EOC
+        } else {
+            cluster + 1
+        };
+        image.set_fat_entry(cluster, next);
+        image.write_cluster(cluster, &[])?;
+    }
+    image.flush()?;
+    Ok(boot)
+}
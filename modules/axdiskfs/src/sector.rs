@@ -1,9 +1,16 @@
 use crate::disk::Disk;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use axdriver::prelude::*;
+use driver_block::cache::CacheStats;
 use spin::Mutex;
 
+/// The writes that reached a disk in order, as byte offset and data. Each
+/// write is one sector, as only sector writes are atomic.
+pub type WriteLog = Arc<Mutex<Vec<(u64, Vec<u8>)>>>;
+
 /// A sector manager warpper for disk.
 pub struct SectorManager {
     inner: Mutex<Disk>,
@@ -17,6 +24,40 @@ impl SectorManager {
         }
     }
 
+    /// Record all later writes to the device into a new log, as the blocks
+    /// leave the cache. Applying a prefix of the log to a copy of the disk
+    /// simulates a power loss at that point.
+    pub fn record_writes(&self) -> WriteLog {
+        let log = WriteLog::default();
+        let hook_log = log.clone();
+        let sector_size = self.sector_size() as u64;
+        self.inner
+            .lock()
+            .set_write_hook(Some(Box::new(move |block_id, data| {
+                hook_log
+                    .lock()
+                    .push((block_id * sector_size, data.to_vec()));
+            })));
+        log
+    }
+
+    /// Write in sequence.
+    fn write_seq(&self, buf: &[u8]) -> DevResult {
+        self.inner.lock().write(buf)?;
+        Ok(())
+    }
+
+    /// Write the cached sectors to the disk and flush it, the sectors written
+    /// before are on the disk when it returns.
+    pub fn flush(&self) -> DevResult {
+        self.inner.lock().flush()
+    }
+
+    /// Return the counters of the block cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.lock().cache_stats()
+    }
+
     /// Return the size of sector.
     pub fn sector_size(&self) -> usize {
         self.inner.lock().block_size()
@@ -119,28 +160,21 @@ impl SectorManager {
 
     /// Write a 8 byte in sequence, not use global_offset, return the number of bytes written.
     pub fn write_8_seq(&self, data: u8) -> DevResult {
-        let buf = [data];
-        self.inner.lock().write(&buf)?;
-        Ok(())
+        self.write_seq(&[data])
     }
 
     /// Write a 16 byte in sequence, not use global_offset, return the number of bytes written.
     pub fn write_16_seq(&self, data: u16) -> DevResult {
-        let buf = data.to_le_bytes();
-        self.inner.lock().write(&buf)?;
-        Ok(())
+        self.write_seq(&data.to_le_bytes())
     }
 
     /// Write a 32 byte in sequence, not use global_offset, return the number of bytes written.
     pub fn write_32_seq(&self, data: u32) -> DevResult {
-        let buf = data.to_le_bytes();
-        self.inner.lock().write(&buf)?;
-        Ok(())
+        self.write_seq(&data.to_le_bytes())
     }
 
     /// Write a sector in sequence, not use global_offset, return the number of bytes written.
     pub fn write_sector_seq(&self, buf: &[u8]) -> DevResult {
-        self.inner.lock().write(buf)?;
-        Ok(())
+        self.write_seq(buf)
     }
 }
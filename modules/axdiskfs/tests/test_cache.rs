@@ -0,0 +1,126 @@
+use axdiskfs::cache::{PageCache, PAGE_SIZE};
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::{disk, initialize_fs, sector, FS};
+use axfs_vfs::{VfsNodeOps, VfsOps};
+use driver_block::ramdisk::RamDisk;
+
+const DISK_SIZE: usize = 2 * 1024 * 1024;
+
+#[test]
+fn test_block_cache() {
+    let sector = sector::SectorManager::new(disk::Disk::new(RamDisk::new(DISK_SIZE)));
+    let log = sector.record_writes();
+    sector.write_32(1024 + 4, 0xdead_beef).unwrap();
+    sector.write_8(1024 + 8, 0x42).unwrap();
+    sector.write_sector_at(4096, &[7u8; 1024]).unwrap();
+    assert_eq!(sector.read_32(1024 + 4).unwrap(), 0xdead_beef);
+    // nothing reaches the disk before a flush
+    assert!(log.lock().is_empty());
+    let stats = sector.cache_stats();
+    assert_eq!((stats.misses, stats.write_backs), (3, 0));
+
+    sector.flush().unwrap();
+    let writes = log.lock().clone();
+    let offsets: Vec<u64> = writes.iter().map(|(offset, _)| *offset).collect();
+    assert_eq!(offsets, [1024, 4096, 4608]);
+    assert_eq!(writes[0].1[4..9], [0xef, 0xbe, 0xad, 0xde, 0x42]);
+    assert_eq!(sector.cache_stats().write_backs, 3);
+    sector.flush().unwrap();
+    assert_eq!(log.lock().len(), 3);
+}
+
+#[test]
+fn test_page_cache() {
+    let data: Vec<u8> = (0..5 * PAGE_SIZE).map(|i| (i / 7) as u8).collect();
+    let mut fills = Vec::new();
+    let mut cache = PageCache::new(2);
+    let read = |cache: &mut PageCache, fills: &mut Vec<u64>, offset: u64, len: usize| {
+        let mut buf = vec![0u8; len];
+        cache
+            .read::<()>(offset, &mut buf, |start, page| {
+                fills.push(start);
+                let start = start as usize;
+                page.copy_from_slice(&data[start..start + PAGE_SIZE]);
+                Ok(())
+            })
+            .unwrap();
+        buf
+    };
+
+    let buf = read(&mut cache, &mut fills, 100, PAGE_SIZE);
+    assert_eq!(buf, data[100..100 + PAGE_SIZE]);
+    assert_eq!(fills, [0, PAGE_SIZE as u64]);
+    read(&mut cache, &mut fills, 10, 10);
+    assert_eq!(fills.len(), 2);
+
+    // page 1 is the least recently used, it is dropped for page 2
+    read(&mut cache, &mut fills, 2 * PAGE_SIZE as u64, 1);
+    read(&mut cache, &mut fills, 0, 1);
+    assert_eq!(fills.len(), 3);
+    read(&mut cache, &mut fills, PAGE_SIZE as u64, 1);
+    assert_eq!(fills.len(), 4);
+    assert_eq!(cache.len(), 2);
+
+    // writes update the cached pages only
+    cache.write(2 * PAGE_SIZE as u64 - 2, &[0xff; 4]);
+    let buf = read(&mut cache, &mut fills, 2 * PAGE_SIZE as u64 - 2, 4);
+    assert_eq!(buf[..2], [0xff; 2]);
+    assert_eq!(buf[2..], data[2 * PAGE_SIZE..2 * PAGE_SIZE + 2]);
+    assert_eq!(fills.len(), 5);
+
+    cache.invalidate(2 * PAGE_SIZE as u64 + 1);
+    assert_eq!(cache.len(), 1);
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_file_pages() {
+    let sector = sector::SectorManager::new(disk::Disk::new(RamDisk::new(DISK_SIZE)));
+    mkfs::format(&sector, &FormatOptions::for_disk(&sector)).unwrap();
+    initialize_fs(sector);
+    let root = FS.try_get().unwrap().root_dir_node().unwrap();
+    root.create_file_child("data").unwrap();
+    let file = root.find_file_child("data").unwrap();
+    let data: Vec<u8> = (0..3 * PAGE_SIZE + 100).map(|i| i as u8).collect();
+    file.write_at_inner(0, &data).unwrap();
+
+    file.open().unwrap();
+    let mut buf = vec![0u8; data.len() + 10];
+    assert_eq!(file.read_at_inner(0, &mut buf).unwrap(), data.len());
+    assert_eq!(buf[..data.len()], data);
+    assert_eq!(file.cached_pages(), 4);
+
+    // a write is seen by later reads, whether it is cached or not
+    file.write_at_inner(PAGE_SIZE as u64 - 1, b"Rust").unwrap();
+    file.write_at_inner(data.len() as u64, b" is cool").unwrap();
+    let mut buf = [0u8; 12];
+    let end = data.len() as u64 + 8;
+    assert_eq!(file.read_at_inner(end - 12, &mut buf).unwrap(), 12);
+    assert_eq!(&buf[4..], b" is cool");
+    assert_eq!(
+        file.read_at_inner(PAGE_SIZE as u64 - 1, &mut buf[..4])
+            .unwrap(),
+        4
+    );
+    assert_eq!(&buf[..4], b"Rust");
+
+    // truncating drops the pages beyond the new end
+    file.truncate_inner(PAGE_SIZE as u64 + 10).unwrap();
+    assert_eq!(file.cached_pages(), 1);
+    assert_eq!(file.read_at_inner(PAGE_SIZE as u64, &mut buf).unwrap(), 10);
+    assert_eq!(&buf[..3], b"ust");
+    assert_eq!(buf[3..10], data[PAGE_SIZE + 3..PAGE_SIZE + 10]);
+    assert_eq!(
+        file.read_at_inner(PAGE_SIZE as u64 + 10, &mut buf).unwrap(),
+        0
+    );
+
+    // the pages are dropped when the last handle is closed
+    file.open().unwrap();
+    file.release().unwrap();
+    assert_eq!(file.cached_pages(), 2);
+    file.release().unwrap();
+    assert_eq!(file.cached_pages(), 0);
+    FS.try_get().unwrap().sync().unwrap();
+}
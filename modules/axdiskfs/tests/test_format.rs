@@ -0,0 +1,157 @@
+use axdiskfs::fsck::{self, Problem};
+use axdiskfs::image::Image;
+use axdiskfs::layout::{convert_to_u8_array, DirEntry, DirEntryAttr, Version};
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::{disk, initialize_fs, sector, FS};
+use driver_block::{ramdisk::RamDisk, DevError};
+
+const DISK_SIZE: usize = 2 * 1024 * 1024;
+
+fn make_sector(version: Version) -> sector::SectorManager {
+    let sector = sector::SectorManager::new(disk::Disk::new(RamDisk::new(DISK_SIZE)));
+    let options = FormatOptions::for_disk(&sector).version(version);
+    mkfs::format(&sector, &options).expect("failed to format");
+    sector
+}
+
+#[test]
+fn test_entry_layout() {
+    let entry = DirEntry {
+        name: convert_to_u8_array("my-config.v2.json").unwrap(),
+        attr: DirEntryAttr::Archive | DirEntryAttr::ReadOnly,
+        first_cluster: 0x1234,
+        file_size: 5 << 30,
+    };
+    let bytes = entry.to_bytes(Version::V2);
+    assert_eq!(bytes.len(), 256);
+    let parsed = DirEntry::from_bytes(&bytes, Version::V2);
+    assert_eq!(parsed.name().as_deref(), Some("my-config.v2.json"));
+    assert_eq!(parsed.first_cluster, 0x1234);
+    assert_eq!(parsed.file_size, 5 << 30);
+    assert!(parsed.attr.contains(DirEntryAttr::ReadOnly));
+
+    // a version 1 entry keeps the layout of the original format
+    let entry = DirEntry {
+        name: convert_to_u8_array("short.txt").unwrap(),
+        file_size: 13,
+        ..entry
+    };
+    let bytes = entry.to_bytes(Version::V1);
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(&bytes[..9], b"short.txt");
+    assert_eq!(bytes[23], entry.attr.bits());
+    assert_eq!(bytes[24..28], 0x1234u32.to_le_bytes());
+    assert_eq!(bytes[28..32], 13u32.to_le_bytes());
+    let parsed = DirEntry::from_bytes(&bytes, Version::V1);
+    assert_eq!(parsed.name().as_deref(), Some("short.txt"));
+    assert_eq!(parsed.file_size, 13);
+
+    // a name starting with the deleted marker byte is not deleted
+    let entry = DirEntry {
+        name: convert_to_u8_array("好.txt").unwrap(),
+        ..entry
+    };
+    assert!(entry.is_valid());
+    let parsed = DirEntry::from_bytes(&entry.to_bytes(Version::V2), Version::V2);
+    assert_eq!(parsed.name().as_deref(), Some("好.txt"));
+
+    assert!(Version::V2.is_valid_name(&"x".repeat(240), true));
+    assert!(!Version::V2.is_valid_name(&"x".repeat(241), false));
+    assert!(!Version::V2.is_valid_name("a\nb", false));
+    assert!(Version::V1.is_valid_name("a.txt", false));
+    assert!(!Version::V1.is_valid_name("a.d", true));
+    assert!(!Version::V1.is_valid_name(&"x".repeat(24), false));
+}
+
+#[test]
+fn test_long_names() {
+    let sector = make_sector(Version::V2);
+    let mut image = Image::open(&sector).unwrap();
+    assert_eq!(image.version(), Version::V2);
+    let root = image.root_cluster();
+    let long = "a directory with a name longer than the 23 bytes of version 1";
+    let dir = image.create_dir(root, long).unwrap();
+    image.create_file(dir, "my-config.v2.json", b"{}").unwrap();
+    image.create_file(dir, "日本語のファイル名", b"").unwrap();
+    // 9 entries of 256 bytes take 5 sectors
+    for i in 0..7 {
+        image.create_file(dir, &format!("file {i}"), b"").unwrap();
+    }
+    assert!(matches!(
+        image.create_file(dir, "my-config.v2.json", b""),
+        Err(DevError::AlreadyExists)
+    ));
+    image.flush().unwrap();
+
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!((report.dirs, report.files), (2, 9));
+    let names: Vec<_> = image
+        .read_dir(dir)
+        .unwrap()
+        .iter()
+        .skip(2)
+        .filter_map(|e| e.name())
+        .collect();
+    assert_eq!(names[..2], ["my-config.v2.json", "日本語のファイル名"]);
+    assert_eq!(image.chain(dir).unwrap().len(), 6);
+}
+
+#[test]
+fn test_mount_v1() {
+    let sector = make_sector(Version::V1);
+    let mut image = Image::open(&sector).unwrap();
+    assert_eq!(image.version(), Version::V1);
+    let root = image.root_cluster();
+    assert!(image.create_dir(root, "bad.name").is_err());
+    assert!(image.create_file(root, &"x".repeat(24), b"").is_err());
+    let dir = image.create_dir(root, "very-long-dir-name").unwrap();
+    image
+        .create_file(dir, "long.txt", "Rust is cool\n".repeat(100).as_bytes())
+        .unwrap();
+    image.flush().unwrap();
+
+    // a name that version 2 allows is a bad name in version 1
+    let mut entry = image.read_dir(dir).unwrap()[2];
+    entry.attr = DirEntryAttr::Directory;
+    image.write_dir_entry(dir, 3, &entry).unwrap();
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report
+        .problems
+        .iter()
+        .any(|p| matches!(p, Problem::BadName { .. })));
+    image.write_dir_entry(dir, 3, &DirEntry::default()).unwrap();
+    assert!(fsck::check(&sector, false).unwrap().is_clean());
+
+    initialize_fs(sector);
+    let fs = FS.try_get().unwrap();
+    assert_eq!(fs.version(), Version::V1);
+    let root = fs.root_dir_node().unwrap();
+    let dir = root.find_dir_child("very-long-dir-name").unwrap();
+    let long = dir.find_file_child("long.txt").unwrap();
+    assert_eq!(long.get_size(), 1300);
+    assert_eq!(
+        long.read_all_inner().unwrap(),
+        "Rust is cool\n".repeat(100).as_bytes()
+    );
+
+    // the limits of version 1 still hold
+    root.create_file_child("my-config.v2.json.bak").unwrap();
+    assert!(matches!(
+        root.create_file_child(&"x".repeat(24)),
+        Err(DevError::InvalidParam)
+    ));
+    assert!(matches!(
+        root.create_dir_child("a.d"),
+        Err(DevError::InvalidParam)
+    ));
+    assert!(matches!(
+        long.truncate_inner(5 << 30),
+        Err(DevError::InvalidParam)
+    ));
+    assert_eq!(long.get_size(), 1300);
+    assert!(matches!(
+        long.truncate_inner(u32::MAX as u64),
+        Err(DevError::NoMemory)
+    ));
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+
+use axdiskfs::image::Image;
+use axdiskfs::layout::DirEntryAttr;
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::sector::SectorManager;
+use axdiskfs::{disk, fsck, initialize_fs, journal, FS};
+use axfs_vfs::{VfsNodeOps, VfsOps};
+use driver_block::ramdisk::RamDisk;
+
+const DISK_SIZE: usize = 2 * 1024 * 1024;
+
+/// The files and directories of an image, as path, size and read-only flag.
+type Tree = BTreeSet<(String, u64, bool)>;
+
+fn load(data: &[u8]) -> SectorManager {
+    SectorManager::new(disk::Disk::new(RamDisk::from(data)))
+}
+
+/// Apply the first `count` writes of the log to a copy of `base`, as if the
+/// power was lost after them.
+fn crash_image(base: &[u8], writes: &[(u64, Vec<u8>)], count: usize) -> SectorManager {
+    let mut data = base.to_vec();
+    for (offset, buf) in &writes[..count] {
+        let offset = *offset as usize;
+        data[offset..offset + buf.len()].copy_from_slice(buf);
+    }
+    load(&data)
+}
+
+fn walk(image: &Image, cluster: u32, path: &str, tree: &mut Tree) {
+    let entries = image.read_dir(cluster).unwrap();
+    // "." and ".." of a subdirectory are not part of the tree
+    let skip = if cluster == image.root_cluster() {
+        0
+    } else {
+        2
+    };
+    for entry in entries.iter().skip(skip).filter(|e| e.is_valid()) {
+        let path = format!("{path}/{}", entry.name().unwrap());
+        let read_only = entry.attr.contains(DirEntryAttr::ReadOnly);
+        tree.insert((path.clone(), entry.file_size, read_only));
+        if entry.is_dir() {
+            walk(image, entry.first_cluster, &path, tree);
+        }
+    }
+}
+
+fn tree(sector: &SectorManager) -> Tree {
+    let image = Image::open(sector).unwrap();
+    let mut tree = Tree::new();
+    walk(&image, image.root_cluster(), "", &mut tree);
+    tree
+}
+
+#[test]
+fn test_journal_replay() {
+    let sector = load(&vec![0u8; DISK_SIZE]);
+    mkfs::format(&sector, &FormatOptions::for_disk(&sector)).unwrap();
+    let mut base = vec![0u8; DISK_SIZE];
+    sector.read_sector_at(0, &mut base).unwrap();
+    let log = sector.record_writes();
+    initialize_fs(sector);
+
+    // the log length after each operation, each is one transaction
+    let mut ends = vec![0];
+    let mut step = |op: &dyn Fn()| {
+        op();
+        ends.push(log.lock().len());
+    };
+    let root = FS.try_get().unwrap().root_dir_node().unwrap();
+    let data = "Rust is cool\n".repeat(200);
+    step(&|| root.create_dir_child("dir").unwrap());
+    step(&|| {
+        let dir = root.find_dir_child("dir").unwrap();
+        dir.create_file_child("a").unwrap();
+    });
+    step(&|| {
+        let a = root
+            .find_dir_child("dir")
+            .unwrap()
+            .find_file_child("a")
+            .unwrap();
+        a.write_at_inner(0, data.as_bytes()).unwrap();
+    });
+    step(&|| root.create_file_child("b").unwrap());
+    step(&|| {
+        let b = root.find_file_child("b").unwrap();
+        b.write_at_inner(0, &data.as_bytes()[..1000]).unwrap();
+    });
+    step(&|| {
+        let a = root
+            .find_dir_child("dir")
+            .unwrap()
+            .find_file_child("a")
+            .unwrap();
+        a.truncate_inner(700).unwrap();
+    });
+    step(&|| {
+        root.find_file_child("b")
+            .unwrap()
+            .truncate_inner(3000)
+            .unwrap()
+    });
+    step(&|| root.rename_child("b", "my-config.v2.json").unwrap());
+    step(&|| root.set_child_read_only("my-config.v2.json", true).unwrap());
+    step(&|| root.find_dir_child("dir").unwrap().remove("a").unwrap());
+    step(&|| root.remove("dir").unwrap());
+    let writes = log.lock().clone();
+
+    let states: Vec<Tree> = ends
+        .iter()
+        .map(|&end| tree(&crash_image(&base, &writes, end)))
+        .collect();
+    assert!(states[3].contains(&("/dir/a".into(), 2600, false)));
+    let config = ("/my-config.v2.json".to_string(), 3000, true);
+    assert!(states[9].contains(&config));
+    assert_eq!(states[11], Tree::from([config]));
+    let boot = *Image::open(&load(&base)).unwrap().boot_sector();
+
+    // a power loss at every write leaves the image consistent after replay,
+    // and the operation under way either done or not at all
+    for op in 0..ends.len() - 1 {
+        for count in ends[op]..=ends[op + 1] {
+            let sector = crash_image(&base, &writes, count);
+            journal::replay(&sector, &boot).unwrap();
+            let report = fsck::check(&sector, false).unwrap();
+            assert!(
+                report.is_clean(),
+                "after {count} writes: {:?}",
+                report.problems
+            );
+            let tree = tree(&sector);
+            assert!(
+                tree == states[op] || tree == states[op + 1],
+                "after {count} writes, in operation {op}: {tree:?}"
+            );
+        }
+    }
+
+    // fsck replays a pending transaction only when repairing, the first one
+    // is pending right after its header is written
+    let journal = boot.journal_start_sector as u64 * 512;
+    let header = writes
+        .iter()
+        .position(|(offset, buf)| *offset == journal && buf.iter().any(|&b| b != 0))
+        .unwrap();
+    let sector = crash_image(&base, &writes, header + 1);
+    let report = fsck::check(&sector, false).unwrap();
+    assert_eq!(report.problems, [fsck::Problem::PendingJournal]);
+    let report = fsck::check(&sector, true).unwrap();
+    assert!(report.repaired);
+    assert!(fsck::check(&sector, false).unwrap().is_clean());
+    assert_eq!(tree(&sector), states[1]);
+
+    // the root is full after 128 entries, and nothing leaks
+    let created = (0..)
+        .map(|i| format!("f{i}"))
+        .take_while(|name| root.create_file_child(name).is_ok())
+        .count();
+    assert_eq!(created, 127);
+    // the journal header is cleared in the cache, write it back
+    FS.try_get().unwrap().sync().unwrap();
+    let writes = log.lock().clone();
+    let sector = crash_image(&base, &writes, writes.len());
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(report.files, 128);
+}
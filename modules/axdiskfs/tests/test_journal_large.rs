@@ -0,0 +1,87 @@
+use axdiskfs::image::Image;
+use axdiskfs::journal::{self, Transaction};
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::sector::SectorManager;
+use axdiskfs::{disk, fsck, initialize_fs, FS};
+use driver_block::{ramdisk::RamDisk, DevError};
+
+const DISK_SIZE: usize = 8 * 1024 * 1024;
+
+fn load(data: &[u8]) -> SectorManager {
+    SectorManager::new(disk::Disk::new(RamDisk::from(data)))
+}
+
+/// Return the size of the file in the root directory, `None` if there is no
+/// such file.
+fn file_size(sector: &SectorManager, name: &str) -> Option<u64> {
+    let image = Image::open(sector).unwrap();
+    let entries = image.read_dir(image.root_cluster()).unwrap();
+    entries
+        .iter()
+        .find(|e| e.is_valid() && e.name().unwrap() == name)
+        .map(|e| e.file_size)
+}
+
+#[test]
+fn test_journal_large_transaction() {
+    let sector = load(&vec![0u8; DISK_SIZE]);
+    let boot = mkfs::format(&sector, &FormatOptions::for_disk(&sector)).unwrap();
+    // the journal holds a transaction changing all of both FATs, more than
+    // one header sector can list
+    let capacity = journal::capacity(&boot);
+    assert!(capacity > boot.sectors_per_fat32 as usize * 2);
+    assert!(capacity > 125);
+    let mut base = vec![0u8; DISK_SIZE];
+    sector.read_sector_at(0, &mut base).unwrap();
+
+    // a transaction larger than the journal is rejected, nothing is written
+    let mut small = boot;
+    small.journal_sectors_count = 8;
+    let tx: Transaction = (0..8).map(|i| (1000 + i, vec![0xaa; 512])).collect();
+    let log = sector.record_writes();
+    assert!(matches!(
+        journal::commit(&sector, &small, &tx),
+        Err(DevError::NoMemory)
+    ));
+    assert!(log.lock().is_empty());
+
+    initialize_fs(sector);
+    let root = FS.try_get().unwrap().root_dir_node().unwrap();
+    root.create_file_child("big").unwrap();
+    let begin = log.lock().len();
+    // 10240 clusters change 80 sectors of each FAT in one transaction
+    let data = vec![0x5a; 5 * 1024 * 1024];
+    let big = root.find_file_child("big").unwrap();
+    big.write_at_inner(0, &data).unwrap();
+    let writes = log.lock().clone();
+
+    let mut image = base.clone();
+    for (offset, buf) in &writes[..begin] {
+        let offset = *offset as usize;
+        image[offset..offset + buf.len()].copy_from_slice(buf);
+    }
+    // a power loss at every metadata write of the operation leaves the image
+    // consistent after replay, with the file either empty or fully written
+    // the reserved area with the journal, the FATs and the root directory
+    let root = boot.root_dir_start_sector() + boot.root_dir_sectors_count();
+    let metadata_end = root as u64 * 512;
+    let mut replayed = false;
+    for (offset, buf) in &writes[begin..] {
+        let start = *offset as usize;
+        image[start..start + buf.len()].copy_from_slice(buf);
+        if *offset >= metadata_end {
+            continue;
+        }
+        let sector = load(&image);
+        replayed |= journal::replay(&sector, &boot).unwrap();
+        let report = fsck::check(&sector, false).unwrap();
+        assert!(report.is_clean(), "at {offset:#x}: {:?}", report.problems);
+        let size = file_size(&sector, "big").unwrap();
+        assert!(
+            size == 0 || size == data.len() as u64,
+            "at {offset:#x}: size {size}"
+        );
+    }
+    assert!(replayed);
+    assert_eq!(file_size(&load(&image), "big"), Some(data.len() as u64));
+}
@@ -0,0 +1,168 @@
+use axdiskfs::fsck::{self, Problem};
+use axdiskfs::image::{Image, FAT_EOC};
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::{disk, initialize_fs, sector, FS};
+use driver_block::{ramdisk::RamDisk, DevError};
+
+const DISK_SIZE: usize = 2 * 1024 * 1024;
+
+fn make_sector() -> sector::SectorManager {
+    sector::SectorManager::new(disk::Disk::new(RamDisk::new(DISK_SIZE)))
+}
+
+/// Format a 2MB disk and populate it with a small tree.
+fn make_image(sector: &sector::SectorManager) {
+    mkfs::format(sector, &FormatOptions::for_disk(sector)).expect("failed to format");
+    let mut image = Image::open(sector).expect("failed to open image");
+    let root = image.root_cluster();
+    image
+        .create_file(root, "short.txt", b"Rust is cool\n")
+        .unwrap();
+    let dir = image.create_dir(root, "very-long-dir-name").unwrap();
+    image
+        .create_file(dir, "long.txt", "Rust is cool\n".repeat(100).as_bytes())
+        .unwrap();
+    let sub = image.create_dir(dir, "sub").unwrap();
+    image.create_file(sub, "empty", b"").unwrap();
+    image.flush().unwrap();
+}
+
+#[test]
+fn test_mkfs() {
+    let sector = make_sector();
+    assert!(!mkfs::is_formatted(&sector));
+
+    // bad geometries
+    let mut options = FormatOptions::for_disk(&sector);
+    options.total_sectors += 1;
+    assert!(mkfs::format(&sector, &options).is_err());
+    let options = FormatOptions::for_disk(&sector).cluster_size(3);
+    assert!(mkfs::format(&sector, &options).is_err());
+    assert!(!mkfs::is_formatted(&sector));
+
+    let options = FormatOptions::for_disk(&sector).cluster_size(4);
+    let boot = mkfs::format(&sector, &options).unwrap();
+    assert!(mkfs::is_formatted(&sector));
+    assert!(boot.sectors_per_fat32 * 128 >= boot.clusters_count() + 2);
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(report.dirs, 1);
+    // the root directory of 128 entries of 256 bytes
+    assert_eq!(report.used_clusters, 16);
+    assert_eq!(report.total_clusters, boot.clusters_count());
+
+    make_image(&sector);
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!((report.dirs, report.files), (3, 3));
+    // 64 clusters of root, short.txt, 2 of the dir, 3 of long.txt, 2 of sub
+    // and empty, the directories take 2 entries of 256 bytes per cluster
+    assert_eq!(report.used_clusters, 73);
+
+    let mut image = Image::open(&sector).unwrap();
+    let root = image.root_cluster();
+    assert!(matches!(
+        image.create_file(root, "short.txt", b""),
+        Err(DevError::AlreadyExists)
+    ));
+    assert!(image.create_dir(root, "bad/name").is_err());
+    assert!(image.create_file(root, &"x".repeat(241), b"").is_err());
+}
+
+#[test]
+fn test_fsck_repair() {
+    let sector = make_sector();
+    make_image(&sector);
+
+    let mut image = Image::open(&sector).unwrap();
+    let root = image.root_cluster();
+    let entries = image.read_dir(root).unwrap();
+    let short = entries[0];
+    let dir = entries[1].first_cluster;
+    let long = image.read_dir(dir).unwrap()[2];
+    let long_chain = image.chain(long.first_cluster).unwrap();
+    assert_eq!(long_chain.len(), 3);
+
+    // long.txt runs into short.txt, an orphan chain of two clusters, a file
+    // larger than its chain and a wrong FSINFO
+    image.set_fat_entry(long_chain[1], short.first_cluster);
+    image.set_fat_entry(100, 101);
+    image.set_fat_entry(101, FAT_EOC);
+    let mut short_entry = short;
+    short_entry.file_size = 4096;
+    image.write_dir_entry(root, 0, &short_entry).unwrap();
+    image.flush().unwrap();
+    let mut info = [0u8; 8];
+    info[..4].copy_from_slice(&7u32.to_le_bytes());
+    sector.write_sector_at(512, &info).unwrap();
+
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(!report.repaired);
+    let has = |f: &dyn Fn(&Problem) -> bool| report.problems.iter().any(f);
+    assert!(has(&|p| matches!(
+        p,
+        Problem::SizeTooLarge { size: 4096, .. }
+    )));
+    assert!(has(&|p| matches!(
+        p,
+        Problem::CrossLinked { path, owner, .. }
+            if path == "/very-long-dir-name/long.txt" && owner == "/short.txt"
+    )));
+    // the third cluster of long.txt is orphaned by the fix
+    assert!(has(&|p| *p == Problem::Orphaned { clusters: 3 }));
+    assert!(has(&|p| matches!(p, Problem::FreeCount { found: 7, .. })));
+    assert!(has(&|p| matches!(p, Problem::NextFree { found: 0, .. })));
+    // a check alone does not write anything
+    assert_eq!(
+        fsck::check(&sector, false).unwrap().problems,
+        report.problems
+    );
+
+    let report = fsck::check(&sector, true).unwrap();
+    assert!(report.repaired);
+    let report = fsck::check(&sector, false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    let image = Image::open(&sector).unwrap();
+    assert_eq!(image.chain(long.first_cluster).unwrap().len(), 2);
+    assert_eq!(image.read_dir(root).unwrap()[0].file_size, 512);
+
+    // the FAT copies differ
+    let mut fat = [0u8; 4];
+    let fat2 = (image.boot_sector().fat_start_sector() + image.boot_sector().sectors_per_fat32)
+        as u64
+        * 512;
+    sector.read_sector_at(fat2 + 4 * 200, &mut fat).unwrap();
+    sector
+        .write_sector_at(fat2 + 4 * 200, &[1, 0, 0, 0])
+        .unwrap();
+    let report = fsck::check(&sector, true).unwrap();
+    assert_eq!(report.problems, [Problem::FatMismatch { entries: 1 }]);
+    assert!(fsck::check(&sector, false).unwrap().is_clean());
+}
+
+#[test]
+fn test_load_image() {
+    let sector = make_sector();
+    make_image(&sector);
+    initialize_fs(sector);
+
+    let fs = FS.try_get().unwrap();
+    let root = fs.root_dir_node().unwrap();
+    let short = root.find_file_child("short.txt").unwrap();
+    assert_eq!(short.read_all_inner().unwrap(), b"Rust is cool\n");
+    let dir = root.find_dir_child("very-long-dir-name").unwrap();
+    let long = dir.find_file_child("long.txt").unwrap();
+    assert_eq!(long.get_size(), 1300);
+    assert_eq!(
+        long.read_all_inner().unwrap(),
+        "Rust is cool\n".repeat(100).as_bytes()
+    );
+    let sub = dir.find_dir_child("sub").unwrap();
+    assert!(sub.find_file_child("empty").unwrap().is_empty());
+
+    // format() empties the disk
+    axfs_vfs::VfsOps::format(fs.as_ref()).unwrap();
+    let root = fs.root_dir_node().unwrap();
+    assert!(root.find_file_child("short.txt").is_err());
+    assert!(root.find_dir_child("very-long-dir-name").is_err());
+}
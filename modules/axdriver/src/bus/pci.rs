@@ -1,11 +1,36 @@
 use crate::{prelude::*, AllDevices};
-use axhal::mem::phys_to_virt;
+use alloc::vec::Vec;
+use axhal::mem::{phys_to_virt, VirtAddr};
 use driver_pci::{
     BarInfo, Cam, Command, DeviceFunction, HeaderType, MemoryBarType, PciRangeAllocator, PciRoot,
 };
 
 const PCI_BAR_NUM: u8 = 6;
 
+/// Offset of the interrupt pin register in the configuration space.
+const PCI_INTERRUPT_PIN: usize = 0x3d;
+
+/// Returns the IRQ number of the legacy INTx interrupt of the device.
+///
+/// The INTx lines of the devices on the root bus are swizzled by their slot
+/// numbers onto the 4 IRQs from `PCI_INTX_IRQ_BASE`. Returns `None` if the
+/// device has no interrupt pin, is behind a bridge, or the platform doesn't
+/// route INTx interrupts (e.g., x86, where the IO APIC is not configured).
+fn intx_irq_num(ecam_base: VirtAddr, bdf: DeviceFunction) -> Option<usize> {
+    if axconfig::PCI_INTX_IRQ_BASE == 0 || bdf.bus != 0 {
+        return None;
+    }
+    let config_space = ecam_base + ((bdf.device as usize) << 15) + ((bdf.function as usize) << 12);
+    let pin = unsafe { (config_space + PCI_INTERRUPT_PIN).as_ptr().read_volatile() };
+    match pin {
+        1..=4 => {
+            let line = (bdf.device as usize + pin as usize - 1) % 4;
+            Some(axconfig::PCI_INTX_IRQ_BASE + line)
+        }
+        _ => None,
+    }
+}
+
 fn config_pci_device(
     root: &mut PciRoot,
     bdf: DeviceFunction,
@@ -92,15 +117,24 @@ impl AllDevices {
             .get(1)
             .map(|range| PciRangeAllocator::new(range.0 as u64, range.1 as u64));
 
+        // a line shared by multiple devices is not used, since the driver of
+        // one device can't clear the interrupts of the others
+        let intx_irqs: Vec<usize> = root
+            .enumerate_bus(0)
+            .filter_map(|(bdf, _)| intx_irq_num(base_vaddr, bdf))
+            .collect();
+        let is_shared = |irq_num: usize| intx_irqs.iter().filter(|&&n| n == irq_num).count() > 1;
+
         for bus in 0..=axconfig::PCI_BUS_END as u8 {
             for (bdf, dev_info) in root.enumerate_bus(bus) {
                 debug!("PCI {}: {}", bdf, dev_info);
                 if dev_info.header_type != HeaderType::Standard {
                     continue;
                 }
+                let irq_num = intx_irq_num(base_vaddr, bdf).filter(|&n| !is_shared(n));
                 match config_pci_device(&mut root, bdf, &mut allocator) {
                     Ok(_) => for_each_drivers!(type Driver, {
-                        if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info) {
+                        if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info, irq_num) {
                             info!(
                                 "registered a new {:?} device at {}: {:?}",
                                 dev.device_type(),
@@ -19,7 +19,11 @@ pub trait DriverProbe {
     }
 
     #[cfg(bus = "mmio")]
-    fn probe_mmio(_mmio_base: usize, _mmio_size: usize) -> Option<AxDeviceEnum> {
+    fn probe_mmio(
+        _mmio_base: usize,
+        _mmio_size: usize,
+        _irq_num: Option<usize>,
+    ) -> Option<AxDeviceEnum> {
         None
     }
 
@@ -28,6 +32,7 @@ pub trait DriverProbe {
         _root: &mut PciRoot,
         _bdf: DeviceFunction,
         _dev_info: &DeviceFunctionInfo,
+        _irq_num: Option<usize>,
     ) -> Option<AxDeviceEnum> {
         None
     }
@@ -92,6 +97,7 @@ cfg_if::cfg_if! {
                     root: &mut driver_pci::PciRoot,
                     bdf: driver_pci::DeviceFunction,
                     dev_info: &driver_pci::DeviceFunctionInfo,
+                    _irq_num: Option<usize>,
                 ) -> Option<crate::AxDeviceEnum> {
                     use crate::ixgbe::IxgbeHalImpl;
                     use driver_net::ixgbe::{INTEL_82599, INTEL_VEND, IxgbeNic};
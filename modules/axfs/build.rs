@@ -0,0 +1,34 @@
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Resolves the path of the initrd archive, relative to the root directory of
+/// ArceOS if it is not absolute.
+fn resolve_initrd_path(initrd: &str) -> PathBuf {
+    let path = PathBuf::from(initrd);
+    if path.is_absolute() {
+        path
+    } else {
+        let mut root_dir = PathBuf::from(std::env!("CARGO_MANIFEST_DIR"));
+        root_dir.extend(["..", ".."]);
+        root_dir.join(path)
+    }
+}
+
+fn main() -> Result<()> {
+    // The archive of the initial RAM disk is copied into `OUT_DIR` and
+    // embedded by `include_bytes!`, or left empty if it is not given.
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let out_path = Path::new(&out_dir).join("initrd");
+    let initrd = std::env::var("AX_INITRD").unwrap_or_default();
+    if std::env::var("CARGO_FEATURE_INITRD").is_ok() && !initrd.is_empty() {
+        let initrd_path = resolve_initrd_path(&initrd);
+        println!("Embedding initrd: {}", initrd_path.display());
+        std::fs::copy(&initrd_path, out_path)?;
+        println!("cargo:rerun-if-changed={}", initrd_path.display());
+    } else {
+        std::fs::write(out_path, [])?;
+    }
+
+    println!("cargo:rerun-if-env-changed=AX_INITRD");
+    Ok(())
+}
@@ -0,0 +1,45 @@
+#!/bin/bash
+
+# Creates the ext2 and ext4 test images with `mke2fs -d`, which copies a
+# directory tree into the new filesystem, so no mount (or root) is needed.
+
+CUR_DIR=`dirname $0`
+
+populate() {
+	local dir=$1
+	rm -rf "$dir"
+	mkdir -p "$dir"
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$dir/long.txt"
+	done
+	echo "Rust is cool!" >>"$dir/short.txt"
+	mkdir -p "$dir/very/long/path"
+	echo "Rust is cool!" >>"$dir/very/long/path/test.txt"
+	mkdir -p "$dir/very-long-dir-name"
+	echo "Rust is cool!" >>"$dir/very-long-dir-name/very-long-file-name.txt"
+	ln -s very/long/path/test.txt "$dir/link"
+	mkdir -p "$dir/many"
+	for i in $(seq 1 300); do
+	  echo "file $i" >"$dir/many/file-$i.txt"
+	done
+}
+
+# ext2 with 1 KiB blocks and block-mapped files, `many` is indexed by
+# `e2fsck -D`
+populate "$CUR_DIR/ext2-root"
+mke2fs -q -F -t ext2 -b 1024 -I 128 -N 512 -L "Test!" -U 12345678-1234-1234-1234-123456789abc \
+	-E root_owner=0:0 -d "$CUR_DIR/ext2-root" "$CUR_DIR/ext2.img" 2048
+e2fsck -fyD "$CUR_DIR/ext2.img" >/dev/null
+rm -rf "$CUR_DIR/ext2-root"
+
+# ext4 with a journal, a sparse file whose extent tree is deeper than the
+# inode, and the mount points as it is read-only
+populate "$CUR_DIR/ext4-root"
+mkdir -p "$CUR_DIR/ext4-root/dev" "$CUR_DIR/ext4-root/tmp" "$CUR_DIR/ext4-root/proc" "$CUR_DIR/ext4-root/sys"
+for i in $(seq 0 7); do
+  printf 'chunk %d\n' $i | dd of="$CUR_DIR/ext4-root/sparse.bin" bs=1 seek=$((i * 65536)) conv=notrunc status=none
+done
+mke2fs -q -F -t ext4 -b 1024 -I 256 -L "Test!" -U 12345678-1234-1234-1234-123456789abc \
+	-E root_owner=0:0 -d "$CUR_DIR/ext4-root" "$CUR_DIR/ext4.img" 4096
+e2fsck -fyD "$CUR_DIR/ext4.img" >/dev/null
+rm -rf "$CUR_DIR/ext4-root"
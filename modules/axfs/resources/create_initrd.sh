@@ -0,0 +1,37 @@
+#!/bin/bash
+
+# Creates the initrd test archives in the newc cpio and ustar formats from
+# the same directory tree, using `bsdtar` (libarchive) and GNU `tar`.
+
+CUR_DIR=`dirname $0`
+ROOT="$CUR_DIR/initrd-root"
+
+rm -rf "$ROOT"
+mkdir -p "$ROOT"
+for i in $(seq 1 1000); do
+  echo "Rust is cool!" >>"$ROOT/long.txt"
+done
+echo "Rust is cool!" >>"$ROOT/short.txt"
+mkdir -p "$ROOT/very/long/path"
+echo "Rust is cool!" >>"$ROOT/very/long/path/test.txt"
+mkdir -p "$ROOT/very-long-dir-name"
+echo "Rust is cool!" >>"$ROOT/very-long-dir-name/very-long-file-name.txt"
+
+# links, modes, an empty directory, and a name longer than 100 bytes
+ln -s very/long/path/test.txt "$ROOT/link"
+mkdir -p "$ROOT/bin" "$ROOT/empty"
+printf '#!/bin/sh\necho hello\n' >"$ROOT/bin/hello"
+chmod 755 "$ROOT/bin/hello"
+ln "$ROOT/bin/hello" "$ROOT/bin/hello2"
+echo "secret" >"$ROOT/secret.txt"
+chmod 600 "$ROOT/secret.txt"
+chmod 700 "$ROOT/empty"
+LONG_DIR="$ROOT/$(printf 'd%.0s' $(seq 1 60))/$(printf 'e%.0s' $(seq 1 60))"
+mkdir -p "$LONG_DIR"
+echo "long name" >"$LONG_DIR/file.txt"
+find "$ROOT" -exec touch -h -d @1700000000 {} +
+
+(cd "$ROOT" && bsdtar --format newc --uid 1000 --gid 100 -cf - .) >"$CUR_DIR/initrd.cpio"
+tar --format=ustar --owner=1000 --group=100 --numeric-owner --sort=name \
+	-cf "$CUR_DIR/initrd.tar" -C "$ROOT" .
+rm -rf "$ROOT"
@@ -8,6 +8,9 @@ pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 
 use alloc::{string::String, vec::Vec};
 use axio::{self as io, prelude::*};
+use core::time::Duration;
+
+use crate::fops::{FileSystemInfo, SetAttr};
 
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
@@ -59,6 +62,141 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Query the metadata about a file without following symlinks.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    let node = crate::root::lookup_no_follow(None, path)?;
+    Ok(Metadata(node.get_attr()?))
+}
+
+/// Reads a symbolic link, returning the file that the link points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(None, path)
+}
+
+/// Creates a new symbolic link on the filesystem.
+///
+/// The `link` path will be a symbolic link pointing to the `original` path.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::symlink(None, original, link)
+}
+
+/// Creates a new hard link on the filesystem.
+///
+/// The `link` path will be a link pointing to the `original` path. Note that
+/// both paths must be in the same mounted fs.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::hard_link(original, link)
+}
+
+/// Changes the permissions found on a file or a directory.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    set_attr(
+        path,
+        &SetAttr {
+            mode: Some(perm),
+            ..Default::default()
+        },
+    )
+}
+
+/// Changes the owner and group of the specified path, `None` leaves the ID
+/// unchanged.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    set_attr(
+        path,
+        &SetAttr {
+            uid,
+            gid,
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [`chown`], but changes the symbolic link itself instead of its
+/// target.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    set_symlink_attr(
+        path,
+        &SetAttr {
+            uid,
+            gid,
+            ..Default::default()
+        },
+    )
+}
+
+/// Changes the last access and modification times of the specified path,
+/// `None` leaves the time unchanged.
+pub fn set_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> io::Result<()> {
+    set_attr(
+        path,
+        &SetAttr {
+            atime: accessed,
+            mtime: modified,
+            ..Default::default()
+        },
+    )
+}
+
+/// Changes the attributes of a file or a directory at once.
+pub fn set_attr(path: &str, changes: &SetAttr) -> io::Result<()> {
+    crate::root::set_attr(None, path, changes, true)
+}
+
+/// Like [`set_attr`], but does not follow the symbolic link at the last
+/// component.
+pub fn set_symlink_attr(path: &str, changes: &SetAttr) -> io::Result<()> {
+    crate::root::set_attr(None, path, changes, false)
+}
+
+/// Returns the attributes of the filesystem containing the given path.
+pub fn statfs(path: &str) -> io::Result<FileSystemInfo> {
+    crate::root::statfs(path)
+}
+
+/// Writes the cached data of all mounted filesystems to their devices, like
+/// `sync(2)`.
+///
+/// It is also done periodically by a background task if the `multitask`
+/// feature is enabled.
+pub fn sync() -> io::Result<()> {
+    crate::root::sync()
+}
+
+/// Returns the paths of all mount points, starting with the root `/`.
+pub fn mount_points() -> Vec<String> {
+    crate::root::mount_points()
+}
+
+/// Mounts a new filesystem of `fs_type` on the existing directory `target`.
+///
+/// `source` is the block device for disk filesystems (e.g., `vdb` for
+/// `vfat`), and is only recorded in `/proc/mounts` for others (`ramfs` or
+/// `tmpfs`, `devfs`, `procfs` and `sysfs`). Filesystems can be mounted under
+/// other mounted ones.
+pub fn mount(source: &str, target: &str, fs_type: &str) -> io::Result<()> {
+    crate::root::mount(source, target, fs_type)
+}
+
+/// Mounts the directory `source` on the directory `target` as well, like
+/// `mount --bind`.
+pub fn bind_mount(source: &str, target: &str) -> io::Result<()> {
+    crate::root::bind_mount(source, target)
+}
+
+/// Unmounts the filesystem mounted on the directory `target`.
+///
+/// Fails with [`ResourceBusy`](io::Error::ResourceBusy) if there are files
+/// opened in it, other filesystems mounted under it, or the current directory
+/// is in it.
+pub fn umount(target: &str) -> io::Result<()> {
+    crate::root::umount(target)
+}
+
 /// Creates a new, empty directory at the provided path.
 pub fn create_dir(path: &str) -> io::Result<()> {
     DirBuilder::new().create(path)
@@ -1,28 +1,359 @@
-use axdriver::prelude::*;
+//! Block devices, their partitions, and the [`Disk`] used by filesystems.
+//!
+//! The disks are named `vda`, `vdb`, etc. in probe order, and their
+//! partitions `vda1`, `vda2`, etc. by partition number, as in Linux.
 
+// the devices are only listed without a disk filesystem
+#![cfg_attr(
+    not(any(
+        feature = "fatfs",
+        feature = "diskfs",
+        feature = "ext4fs",
+        feature = "myfs"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+use alloc::sync::Weak;
+use alloc::{format, sync::Arc, vec::Vec};
+use axdriver::{prelude::*, AxDeviceContainer};
+use axerrno::{ax_err, AxError, AxResult};
+use axsync::Mutex;
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+use driver_block::cache::BlockCache;
+use driver_block::partition::{self, Partition, PartitionInfo};
+
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
 const BLOCK_SIZE: usize = 512;
 
-/// A disk device with a cursor.
+/// The maximum number of disks, named from `vda` to `vdz`.
+const MAX_DISKS: u8 = 26;
+
+/// The block cache of a device, shared by the [`Disk`] handles on it.
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+pub type SharedCache = Arc<Mutex<BlockCache<BlockDev>>>;
+
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+type WeakCache = Weak<Mutex<BlockCache<BlockDev>>>;
+
+/// A block device shared by a disk and its partitions.
+#[derive(Clone)]
+pub struct SharedDev {
+    dev: Arc<Mutex<AxBlockDevice>>,
+    name: Arc<str>,
+    /// The block caches of the [`Disk`]s on the disk or its partitions.
+    #[cfg(any(feature = "fatfs", feature = "myfs"))]
+    caches: Arc<Mutex<Vec<WeakCache>>>,
+}
+
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+impl SharedDev {
+    /// Returns the block caches overlapping `num_blocks` blocks from
+    /// `block_id` of the disk.
+    fn caches_overlapping(&self, block_id: u64, num_blocks: u64) -> Vec<SharedCache> {
+        let mut caches = self.caches.lock();
+        caches.retain(|c| c.strong_count() > 0);
+        caches
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|c| {
+                let cache = c.lock();
+                let start = cache.device().start();
+                start < block_id + num_blocks && block_id < start + cache.num_blocks()
+            })
+            .collect()
+    }
+}
+
+impl BaseDriverOps for SharedDev {
+    fn device_name(&self) -> &str {
+        &self.name
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for SharedDev {
+    fn num_blocks(&self) -> u64 {
+        self.dev.lock().num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.lock().block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.dev.lock().read_block(block_id, buf)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        self.dev.lock().write_block(block_id, buf)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.dev.lock().flush()
+    }
+}
+
+/// A whole disk or one of its partitions.
+pub type BlockDev = Partition<SharedDev>;
+
+/// A named block device.
+struct BlockDevEntry {
+    name: &'static str,
+    /// The index of the disk in [`BLOCK_DEVS`], its own for a whole disk.
+    disk: usize,
+    /// The partition, or `None` for a whole disk.
+    part: Option<PartitionInfo>,
+    dev: SharedDev,
+    start: u64,
+    num_blocks: u64,
+    /// Whether a filesystem has been opened on the device.
+    busy: bool,
+}
+
+static BLOCK_DEVS: Mutex<Vec<BlockDevEntry>> = Mutex::new(Vec::new());
+
+/// Names the block devices and the partitions on them.
+pub(crate) fn init_block_devs(mut devs: AxDeviceContainer<AxBlockDevice>) {
+    let mut table = BLOCK_DEVS.lock();
+    let mut disks = 0;
+    while let Some(dev) = devs.take_one() {
+        if disks == MAX_DISKS {
+            warn!("  too many block devices, {:?} ignored", dev.device_name());
+            continue;
+        }
+        let index = table.len();
+        let name: &'static str = format!("vd{}", (b'a' + disks) as char).leak();
+        disks += 1;
+        info!("  block device {}: {:?}", name, dev.device_name());
+        let mut dev = SharedDev {
+            name: dev.device_name().into(),
+            dev: Arc::new(Mutex::new(dev)),
+            #[cfg(any(feature = "fatfs", feature = "myfs"))]
+            caches: Arc::new(Mutex::new(Vec::new())),
+        };
+        let parts = partition::read_partitions(&mut dev).unwrap_or_else(|e| {
+            warn!("  failed to read the partition table of {}: {:?}", name, e);
+            Vec::new()
+        });
+        let num_blocks = dev.num_blocks();
+        let disk = BlockDevEntry {
+            name,
+            disk: index,
+            part: None,
+            dev: dev.clone(),
+            start: 0,
+            num_blocks,
+            busy: false,
+        };
+        table.push(disk);
+        for part in parts {
+            let name = format!("{}{}", name, part.number).leak();
+            info!(
+                "    partition {}: {} blocks from {}, {:?}",
+                name, part.num_blocks, part.start, part.kind
+            );
+            table.push(BlockDevEntry {
+                name,
+                disk: index,
+                start: part.start,
+                num_blocks: part.num_blocks,
+                part: Some(part),
+                dev: dev.clone(),
+                busy: false,
+            });
+        }
+    }
+}
+
+/// Returns the block devices with their names, bypassing the busy checks of
+/// [`open_block_dev`] to access the raw data through devfs.
+#[cfg_attr(not(feature = "devfs"), allow(dead_code))]
+pub(crate) fn raw_block_devs() -> Vec<(&'static str, RawDev)> {
+    let table = BLOCK_DEVS.lock();
+    table
+        .iter()
+        .filter_map(|e| {
+            let dev = Partition::new(e.dev.clone(), e.start, e.num_blocks).ok()?;
+            Some((e.name, RawDev(dev)))
+        })
+        .collect()
+}
+
+/// Opens the block device named `name`, e.g., `vda1` or `/dev/vda1`, for a
+/// filesystem.
+///
+/// A device can only be opened once, and a disk can not be opened together
+/// with its partitions.
+pub(crate) fn open_block_dev(name: &str) -> AxResult<BlockDev> {
+    let name = name.strip_prefix("/dev/").unwrap_or(name);
+    let mut table = BLOCK_DEVS.lock();
+    let Some(index) = table.iter().position(|e| e.name == name) else {
+        return ax_err!(NotFound, "no such block device");
+    };
+    let disk = table[index].disk;
+    let is_disk = table[index].part.is_none();
+    let overlaps = |i, e: &BlockDevEntry| i == index || e.part.is_none() || is_disk;
+    let mut devs = table.iter().enumerate();
+    if devs.any(|(i, e)| e.busy && e.disk == disk && overlaps(i, e)) {
+        return Err(AxError::ResourceBusy);
+    }
+    let entry = &mut table[index];
+    entry.busy = true;
+    Partition::new(entry.dev.clone(), entry.start, entry.num_blocks)
+        .map_err(|_| AxError::InvalidData)
+}
+
+/// Returns the block device of the root filesystem when none is configured:
+/// the first data partition of the first disk, i.e., skipping the boot
+/// partitions, or the whole disk if it is not partitioned.
+pub(crate) fn default_root_dev() -> Option<&'static str> {
+    let table = BLOCK_DEVS.lock();
+    let first = table.first()?;
+    let data = table
+        .iter()
+        .filter(|e| e.disk == 0)
+        .find(|e| e.part.as_ref().is_some_and(|p| !p.is_boot()));
+    Some(data.unwrap_or(first).name)
+}
+
+/// A block device accessed through devfs, see [`raw_block_devs`].
+///
+/// It is kept coherent with the filesystems on the disk: the blocks are
+/// accessed through the block cache of the `Disk` on the same device if
+/// there is one, and the caches of other overlapping devices are written back
+/// before the access, and also dropped before writes.
+pub(crate) struct RawDev(BlockDev);
+
+impl RawDev {
+    /// Runs `f` on the block cache of the same device if there is one, after
+    /// writing back the other caches overlapping the `num_blocks` blocks from
+    /// `block_id` (and dropping them if `write`), or returns `None`.
+    #[cfg(any(feature = "fatfs", feature = "myfs"))]
+    fn with_cache<R>(
+        &self,
+        block_id: u64,
+        num_blocks: u64,
+        write: bool,
+        f: impl FnOnce(&mut BlockCache<BlockDev>) -> R,
+    ) -> DevResult<Option<R>> {
+        let start = self.0.start();
+        let mut same = None;
+        for c in self
+            .0
+            .device()
+            .caches_overlapping(start + block_id, num_blocks)
+        {
+            let mut cache = c.lock();
+            let dev = cache.device();
+            if dev.start() == start && dev.num_blocks() == self.0.num_blocks() {
+                drop(cache);
+                same = Some(c);
+            } else if write {
+                cache.invalidate()?;
+            } else {
+                cache.sync()?;
+            }
+        }
+        Ok(same.map(|c| f(&mut c.lock())))
+    }
+
+    #[cfg(not(any(feature = "fatfs", feature = "myfs")))]
+    fn with_cache<R>(
+        &self,
+        _block_id: u64,
+        _num_blocks: u64,
+        _write: bool,
+        _f: impl FnOnce(&mut BlockDev) -> R,
+    ) -> DevResult<Option<R>> {
+        Ok(None)
+    }
+}
+
+impl BaseDriverOps for RawDev {
+    fn device_name(&self) -> &str {
+        self.0.device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for RawDev {
+    fn num_blocks(&self) -> u64 {
+        self.0.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.0.block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let num_blocks = buf.len().div_ceil(self.0.block_size()) as u64;
+        match self.with_cache(block_id, num_blocks, false, |c| c.read_block(block_id, buf))? {
+            Some(res) => res,
+            None => self.0.read_block(block_id, buf),
+        }
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let num_blocks = buf.len().div_ceil(self.0.block_size()) as u64;
+        match self.with_cache(block_id, num_blocks, true, |c| c.write_block(block_id, buf))? {
+            Some(res) => res,
+            None => self.0.write_block(block_id, buf),
+        }
+    }
+
+    fn flush(&mut self) -> DevResult {
+        match self.with_cache(0, self.0.num_blocks(), false, |c| c.flush())? {
+            Some(res) => res,
+            None => self.0.flush(),
+        }
+    }
+}
+
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
+/// A disk device with a cursor, accessed through a block cache.
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    cache: SharedCache,
 }
 
+#[cfg(any(feature = "fatfs", feature = "myfs"))]
 impl Disk {
     /// Create a new disk.
-    pub fn new(dev: AxBlockDevice) -> Self {
+    pub fn new(dev: BlockDev) -> Self {
         assert_eq!(BLOCK_SIZE, dev.block_size());
+        let caches = dev.device().caches.clone();
+        let cache = Arc::new(Mutex::new(BlockCache::new(dev)));
+        caches.lock().push(Arc::downgrade(&cache));
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            cache,
         }
     }
 
+    /// Returns the block cache of the disk, to flush it after the disk has
+    /// been handed over to a filesystem.
+    pub fn cache(&self) -> SharedCache {
+        self.cache.clone()
+    }
+
+    /// Writes the dirty cached blocks to the device and flushes it.
+    pub fn flush(&mut self) -> DevResult {
+        self.cache.lock().flush()
+    }
+
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.cache.lock().num_blocks() * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -40,18 +371,17 @@ impl Disk {
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            self.dev
+            self.cache
+                .lock()
                 .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
             // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
+            self.cache
+                .lock()
+                .read_at(self.block_id, self.offset, &mut buf[..count])?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -67,18 +397,17 @@ impl Disk {
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
         let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+            self.cache
+                .lock()
+                .write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
             // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            self.cache
+                .lock()
+                .write_at(self.block_id, self.offset, &buf[..count])?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
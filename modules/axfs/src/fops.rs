@@ -1,11 +1,16 @@
 //! Low-level filesystem operations.
 
+use alloc::sync::Arc;
 use axerrno::{ax_err, ax_err_type, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use capability::{Cap, WithCap};
 use core::fmt;
 
+use crate::lock::LockOwner;
+
+pub use crate::lock::{FileLock, LockKind};
+
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
 #[cfg(feature = "myfs")]
@@ -19,12 +24,30 @@ pub type DirEntry = axfs_vfs::VfsDirEntry;
 pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
+/// Alias of [`axfs_vfs::VfsSetAttr`].
+pub type SetAttr = axfs_vfs::VfsSetAttr;
+/// Alias of [`axfs_vfs::FileSystemInfo`].
+pub type FileSystemInfo = axfs_vfs::FileSystemInfo;
 
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    mount: Option<Arc<u64>>,
+    lock_owner: Arc<LockOwner>,
+}
+
+/// A handle to set and query the advisory locks of an opened [`File`],
+/// obtained by [`File::locker`].
+///
+/// It doesn't borrow the file, so a blocking lock request doesn't need to
+/// hold up other operations on it. The locks are released when the file and
+/// all its lockers are dropped.
+#[derive(Clone)]
+pub struct FileLocker {
+    owner: Arc<LockOwner>,
+    cap: Cap,
 }
 
 /// An opened directory object, with open permissions and a cursor for
@@ -32,6 +55,7 @@ pub struct File {
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
     entry_idx: usize,
+    mount: Option<Arc<u64>>,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -152,10 +176,18 @@ impl File {
         if opts.truncate {
             node.truncate(0)?;
         }
+        // identify the file by its path in the lock table if it has no inode
+        let lock_path = match attr.ino() {
+            0 => crate::root::canonical_path(dir, path),
+            _ => None,
+        };
+        let mount = crate::root::mount_ref(dir, path);
         Ok(Self {
+            lock_owner: Arc::new(LockOwner::new(&node, &attr, lock_path, mount.as_ref())),
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
+            mount,
         })
     }
 
@@ -240,6 +272,74 @@ impl File {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.node.access(Cap::empty())?.get_attr()
     }
+
+    /// Changes the permission mode, owner or timestamps of the file.
+    pub fn set_attr(&self, changes: &SetAttr) -> AxResult {
+        self.node.access(Cap::empty())?.set_attr(changes)
+    }
+
+    /// Returns a [`FileLocker`] to set and query the advisory locks of this
+    /// file.
+    pub fn locker(&self) -> FileLocker {
+        FileLocker {
+            owner: self.lock_owner.clone(),
+            cap: self.node.cap(),
+        }
+    }
+
+    /// Sets a whole-file lock like [`FileLocker::flock`].
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> AxResult {
+        self.locker().flock(kind, wait)
+    }
+
+    /// Sets a byte-range lock like [`FileLocker::set_lock`].
+    pub fn set_lock(&self, kind: Option<LockKind>, start: u64, len: u64, wait: bool) -> AxResult {
+        self.locker().set_lock(kind, start, len, wait)
+    }
+
+    /// Returns the conflicting byte-range lock like [`FileLocker::get_lock`].
+    pub fn get_lock(&self, lock: &FileLock) -> AxResult<Option<FileLock>> {
+        self.locker().get_lock(lock)
+    }
+}
+
+impl FileLocker {
+    /// Sets a whole-file lock of `kind` like `flock`, or releases it if
+    /// `kind` is `None`. An existing lock of this file is converted.
+    ///
+    /// If the file is locked by others incompatibly, waits for them to unlock
+    /// if `wait` is `true`, otherwise returns [`WouldBlock`]. Returns
+    /// [`Deadlock`] if it would wait forever.
+    ///
+    /// [`WouldBlock`]: axerrno::AxError::WouldBlock
+    /// [`Deadlock`]: axerrno::AxError::Deadlock
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> AxResult {
+        self.owner.set_lock(true, kind, 0, 0, wait)
+    }
+
+    /// Sets a byte-range lock of `kind` on `len` bytes from `start` (`0` for
+    /// up to the end of the file) like `fcntl`, or releases the range if
+    /// `kind` is `None`. The locks of this file in the range are replaced.
+    ///
+    /// A shared lock requires read access and an exclusive lock requires
+    /// write access. It waits or fails like [`FileLocker::flock`].
+    pub fn set_lock(&self, kind: Option<LockKind>, start: u64, len: u64, wait: bool) -> AxResult {
+        let cap = match kind {
+            Some(LockKind::Shared) => Cap::READ,
+            Some(LockKind::Exclusive) => Cap::WRITE,
+            None => Cap::empty(),
+        };
+        if !self.cap.contains(cap) {
+            return ax_err!(PermissionDenied);
+        }
+        self.owner.set_lock(false, kind, start, len, wait)
+    }
+
+    /// Returns the first byte-range lock set through other files that
+    /// prevents setting `lock`, or `None` if it can be set.
+    pub fn get_lock(&self, lock: &FileLock) -> AxResult<Option<FileLock>> {
+        self.owner.get_lock(lock)
+    }
 }
 
 impl Directory {
@@ -266,6 +366,7 @@ impl Directory {
         Ok(Self {
             node: WithCap::new(node, access_cap),
             entry_idx: 0,
+            mount: crate::root::mount_ref(dir, path),
         })
     }
 
@@ -286,13 +387,21 @@ impl Directory {
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        let mut dir = Self::_open_dir_at(self.access_at(path)?, path, opts)?;
+        if !path.starts_with('/') {
+            dir.mount.clone_from(&self.mount);
+        }
+        Ok(dir)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        let mut file = File::_open_at(self.access_at(path)?, path, opts)?;
+        if !path.starts_with('/') {
+            file.mount.clone_from(&self.mount);
+        }
+        Ok(file)
     }
 
     /// Creates an empty file at the path relative to this directory.
@@ -0,0 +1,326 @@
+//! Directories: lists of entries in their data blocks, optionally indexed by
+//! a hash tree (htree) which is used for lookups only.
+
+use alloc::{vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use super::hash::{dx_hash, DX_HASH_UNSIGNED_DELTA};
+use super::layout::*;
+use super::volume::Volume;
+
+/// The offset of `dx_root_info` in the root block of an htree, after the
+/// entries of `.` and `..`.
+const DX_ROOT_INFO: usize = 24;
+/// The offset of the index entries in the other nodes of an htree, after an
+/// empty entry covering the block.
+const DX_NODE_ENTRIES: usize = 8;
+/// The maximum number of index levels under the root.
+const DX_MAX_LEVELS: u8 = 3;
+
+/// An entry of a directory.
+pub struct DirEntry {
+    pub ino: u32,
+    pub name: Vec<u8>,
+    /// The file type of the entry, zero if unknown.
+    pub file_type: u8,
+}
+
+/// An entry in a directory block.
+struct RawEntry {
+    offset: usize,
+    ino: u32,
+    rec_len: usize,
+    name_len: usize,
+}
+
+impl RawEntry {
+    fn name<'a>(&self, block: &'a [u8]) -> &'a [u8] {
+        &block[self.offset + 8..self.offset + 8 + self.name_len]
+    }
+
+    /// Returns the bytes used by the entry, zero for an unused one.
+    fn used(&self) -> usize {
+        if self.ino == 0 {
+            0
+        } else {
+            dirent_size(self.name_len)
+        }
+    }
+}
+
+/// Parses and checks the entries of a directory block.
+fn parse_block(block: &[u8]) -> VfsResult<Vec<RawEntry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < block.len() {
+        if offset + 8 > block.len() {
+            return Err(VfsError::InvalidData);
+        }
+        let rec_len = match read_u16(block, offset + 4) as usize {
+            // a whole 64 KiB block
+            0 | 65535 => 65536,
+            len => len,
+        };
+        let entry = RawEntry {
+            offset,
+            ino: read_u32(block, offset),
+            rec_len,
+            name_len: block[offset + 6] as usize,
+        };
+        if rec_len < 8
+            || !rec_len.is_multiple_of(4)
+            || offset + rec_len > block.len()
+            || 8 + entry.name_len > rec_len
+        {
+            return Err(VfsError::InvalidData);
+        }
+        offset += rec_len;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn write_rec_len(block: &mut [u8], offset: usize, rec_len: usize) {
+    write_u16(block, offset + 4, rec_len.min(65535) as u16);
+}
+
+impl Volume {
+    fn dirent_file_type(&self, ty: VfsNodeType) -> u8 {
+        if self.sb.has_incompat(INCOMPAT_FILETYPE) {
+            type_to_dirent(ty)
+        } else {
+            0
+        }
+    }
+
+    fn write_entry(
+        &self,
+        block: &mut [u8],
+        offset: usize,
+        rec_len: usize,
+        ino: u32,
+        name: &[u8],
+        ty: VfsNodeType,
+    ) {
+        write_u32(block, offset, ino);
+        write_rec_len(block, offset, rec_len);
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = self.dirent_file_type(ty);
+        block[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+        block[offset + 8 + name.len()..offset + dirent_size(name.len())].fill(0);
+    }
+
+    /// Returns the number of data blocks of `dir`.
+    fn dir_blocks(&self, dir: &Inode) -> u64 {
+        dir.size() / self.block_size() as u64
+    }
+
+    /// Reads the logical block `lblock` of `dir`, which must not be a hole.
+    fn read_dir_block(&mut self, dir: &Inode, lblock: u64, buf: &mut [u8]) -> VfsResult<u64> {
+        let block = self.map_block(dir, lblock)?.ok_or(VfsError::InvalidData)?;
+        self.read_block(block, buf)?;
+        Ok(block)
+    }
+
+    /// Returns the entries of `dir` in the order of its blocks, `.` and `..`
+    /// first.
+    pub fn dir_entries(&mut self, dir: &Inode) -> VfsResult<Vec<DirEntry>> {
+        let mut buf = vec![0; self.block_size()];
+        let mut entries = Vec::new();
+        for lblock in 0..self.dir_blocks(dir) {
+            self.read_dir_block(dir, lblock, &mut buf)?;
+            let has_filetype = self.sb.has_incompat(INCOMPAT_FILETYPE);
+            for e in parse_block(&buf)?.into_iter().filter(|e| e.ino != 0) {
+                entries.push(DirEntry {
+                    ino: e.ino,
+                    name: e.name(&buf).to_vec(),
+                    file_type: if has_filetype { buf[e.offset + 7] } else { 0 },
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Finds the entry `name` in `dir`.
+    pub fn find_entry(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<DirEntry>> {
+        let blocks = match self.htree_leaves(dir, name)? {
+            Some(leaves) => leaves,
+            None => (0..self.dir_blocks(dir)).collect(),
+        };
+        let mut buf = vec![0; self.block_size()];
+        for lblock in blocks {
+            self.read_dir_block(dir, lblock, &mut buf)?;
+            if let Some(e) = parse_block(&buf)?
+                .into_iter()
+                .find(|e| e.ino != 0 && e.name(&buf) == name)
+            {
+                let file_type = if self.sb.has_incompat(INCOMPAT_FILETYPE) {
+                    buf[e.offset + 7]
+                } else {
+                    0
+                };
+                return Ok(Some(DirEntry {
+                    ino: e.ino,
+                    name: name.to_vec(),
+                    file_type,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Walks the htree of `dir` to the leaf blocks which may hold `name`, or
+    /// returns `None` if `dir` is not indexed or its index is not usable.
+    fn htree_leaves(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<Vec<u64>>> {
+        if dir.flags() & INODE_INDEX_FL == 0 || self.dir_blocks(dir) == 0 {
+            return Ok(None);
+        }
+        let mut node = vec![0; self.block_size()];
+        self.read_dir_block(dir, 0, &mut node)?;
+        let version = node[DX_ROOT_INFO + 4];
+        let info_len = node[DX_ROOT_INFO + 5] as usize;
+        let levels = node[DX_ROOT_INFO + 6];
+        if levels >= DX_MAX_LEVELS {
+            return Ok(None);
+        }
+        let version = if version < DX_HASH_UNSIGNED_DELTA && self.sb.unsigned_hash() {
+            version + DX_HASH_UNSIGNED_DELTA
+        } else {
+            version
+        };
+        let Some(hash) = dx_hash(name, version, self.sb.hash_seed()) else {
+            return Ok(None);
+        };
+        let mut offset = DX_ROOT_INFO + info_len;
+        for level in 0..=levels {
+            let count = read_u16(&node, offset + 2) as usize;
+            if count == 0 || offset + count * 8 > node.len() {
+                return Err(VfsError::InvalidData);
+            }
+            let hash_at = |i: usize| read_u32(&node, offset + i * 8);
+            let block_at = |i: usize| (read_u32(&node, offset + i * 8 + 4) & 0x0fff_ffff) as u64;
+            // the first entry has no hash and covers the smallest ones
+            let i = (1..count).rev().find(|&i| hash_at(i) <= hash).unwrap_or(0);
+            if level == levels {
+                // names of the same hash continue in the next leaves, whose
+                // hashes are marked with the lowest bit
+                let mut leaves = vec![block_at(i)];
+                leaves.extend(
+                    (i + 1..count)
+                        .take_while(|&j| hash_at(j) == hash | 1)
+                        .map(block_at),
+                );
+                return Ok(Some(leaves));
+            }
+            let child = block_at(i);
+            self.read_dir_block(dir, child, &mut node)?;
+            offset = DX_NODE_ENTRIES;
+        }
+        unreachable!()
+    }
+
+    /// Adds the entry `name` of the inode `ino` to the directory `dir_ino`.
+    /// The caller writes `dir` back.
+    ///
+    /// The htree of `dir` is not updated, so it is dropped and the directory
+    /// is searched linearly from then on.
+    pub fn add_entry(
+        &mut self,
+        dir_ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+        ino: u32,
+        ty: VfsNodeType,
+    ) -> VfsResult {
+        dir.set_flags(dir.flags() & !INODE_INDEX_FL);
+        let needed = dirent_size(name.len());
+        let bs = self.block_size();
+        let mut buf = vec![0; bs];
+        for lblock in 0..self.dir_blocks(dir) {
+            let block = self.read_dir_block(dir, lblock, &mut buf)?;
+            let Some(e) = parse_block(&buf)?
+                .into_iter()
+                .find(|e| e.rec_len - e.used() >= needed)
+            else {
+                continue;
+            };
+            let used = e.used();
+            if used > 0 {
+                write_rec_len(&mut buf, e.offset, used);
+            }
+            self.write_entry(&mut buf, e.offset + used, e.rec_len - used, ino, name, ty);
+            return self.write_block(block, &buf);
+        }
+        let lblock = self.dir_blocks(dir);
+        let block = self.map_block_alloc(dir_ino, dir, lblock)?;
+        buf.fill(0);
+        self.write_entry(&mut buf, 0, bs, ino, name, ty);
+        self.write_block(block, &buf)?;
+        dir.set_size(dir.size() + bs as u64);
+        Ok(())
+    }
+
+    /// Removes the entry `name` from `dir`.
+    pub fn remove_entry(&mut self, dir: &Inode, name: &[u8]) -> VfsResult {
+        let mut buf = vec![0; self.block_size()];
+        for lblock in 0..self.dir_blocks(dir) {
+            let block = self.read_dir_block(dir, lblock, &mut buf)?;
+            let entries = parse_block(&buf)?;
+            let Some(i) = entries
+                .iter()
+                .position(|e| e.ino != 0 && e.name(&buf) == name)
+            else {
+                continue;
+            };
+            if i > 0 {
+                // merged into the previous entry
+                let prev = &entries[i - 1];
+                write_rec_len(&mut buf, prev.offset, prev.rec_len + entries[i].rec_len);
+            } else {
+                write_u32(&mut buf, entries[i].offset, 0);
+            }
+            return self.write_block(block, &buf);
+        }
+        Err(VfsError::NotFound)
+    }
+
+    /// Returns whether `dir` has no entries but `.` and `..`.
+    pub fn is_dir_empty(&mut self, dir: &Inode) -> VfsResult<bool> {
+        Ok(self
+            .dir_entries(dir)?
+            .iter()
+            .all(|e| e.name == b"." || e.name == b".."))
+    }
+
+    /// Points the `..` entry of `dir` to `parent`.
+    pub fn set_parent_entry(&mut self, dir: &Inode, parent: u32) -> VfsResult {
+        let mut buf = vec![0; self.block_size()];
+        let block = self.read_dir_block(dir, 0, &mut buf)?;
+        let entries = parse_block(&buf)?;
+        match entries.get(1) {
+            Some(e) if e.name(&buf) == b".." => write_u32(&mut buf, e.offset, parent),
+            _ => return Err(VfsError::InvalidData),
+        }
+        self.write_block(block, &buf)
+    }
+
+    /// Initializes the new directory `ino` with the entries `.` and `..`.
+    pub fn init_dir(&mut self, ino: u32, dir: &mut Inode, parent: u32) -> VfsResult {
+        let bs = self.block_size();
+        let block = self.map_block_alloc(ino, dir, 0)?;
+        let mut buf = vec![0; bs];
+        let dot_len = dirent_size(1);
+        self.write_entry(&mut buf, 0, dot_len, ino, b".", VfsNodeType::Dir);
+        self.write_entry(
+            &mut buf,
+            dot_len,
+            bs - dot_len,
+            parent,
+            b"..",
+            VfsNodeType::Dir,
+        );
+        self.write_block(block, &buf)?;
+        dir.set_size(bs as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! The name hashes of indexed (htree) directories, as Linux's
+//! `fs/ext4/hash.c`.
+
+const DX_HASH_LEGACY: u8 = 0;
+const DX_HASH_HALF_MD4: u8 = 1;
+const DX_HASH_TEA: u8 = 2;
+/// The offset of the unsigned variants of the hashes.
+pub const DX_HASH_UNSIGNED_DELTA: u8 = 3;
+
+/// The hash of the end of a directory, which no name hashes to.
+const HTREE_EOF_32BIT: u32 = 0x7fff_ffff;
+
+/// Returns the major hash of `name` by the hash `version`, or `None` if the
+/// version is unknown.
+pub fn dx_hash(name: &[u8], version: u8, seed: [u32; 4]) -> Option<u32> {
+    let (version, unsigned) = match version {
+        0..=2 => (version, false),
+        3..=5 => (version - DX_HASH_UNSIGNED_DELTA, true),
+        _ => return None,
+    };
+    let mut buf = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    if seed.iter().any(|&x| x != 0) {
+        buf = seed;
+    }
+    let hash = match version {
+        DX_HASH_LEGACY => dx_hack_hash(name, unsigned),
+        DX_HASH_HALF_MD4 => {
+            for start in (0..name.len()).step_by(32) {
+                let input = str_to_hash_buf::<8>(&name[start..], unsigned);
+                half_md4_transform(&mut buf, &input);
+            }
+            buf[1]
+        }
+        DX_HASH_TEA => {
+            for start in (0..name.len()).step_by(16) {
+                let input = str_to_hash_buf::<4>(&name[start..], unsigned);
+                tea_transform(&mut buf, &input);
+            }
+            buf[0]
+        }
+        _ => unreachable!(),
+    };
+    let hash = hash & !1;
+    Some(if hash == HTREE_EOF_32BIT << 1 {
+        (HTREE_EOF_32BIT - 1) << 1
+    } else {
+        hash
+    })
+}
+
+/// Converts a char to an int as C does with `char` of the signedness.
+fn char_value(c: u8, unsigned: bool) -> u32 {
+    if unsigned {
+        c as u32
+    } else {
+        c as i8 as i32 as u32
+    }
+}
+
+fn dx_hack_hash(name: &[u8], unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3_fe2d_u32, 0x37ab_e8f9_u32);
+    for &c in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ char_value(c, unsigned).wrapping_mul(7_152_373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs the first `N * 4` bytes of the rest of a name into `N` words,
+/// padded with the length of the rest.
+fn str_to_hash_buf<const N: usize>(rest: &[u8], unsigned: bool) -> [u32; N] {
+    let len = rest.len() as u32;
+    let mut pad = len | len << 8;
+    pad |= pad << 16;
+    let mut out = [pad; N];
+    let mut val = pad;
+    let mut words = 0;
+    for (i, &c) in rest.iter().take(N * 4).enumerate() {
+        val = char_value(c, unsigned).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            out[words] = val;
+            words += 1;
+            val = pad;
+        }
+    }
+    if words < N {
+        out[words] = val;
+    }
+    out
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K2: u32 = 0x5a82_7999;
+    const K3: u32 = 0x6ed9_eba1;
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let [mut a, mut b, mut c, mut d] = *buf;
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s)
+        };
+    }
+
+    round!(f, a, b, c, d, input[0], 3);
+    round!(f, d, a, b, c, input[1], 7);
+    round!(f, c, d, a, b, input[2], 11);
+    round!(f, b, c, d, a, input[3], 19);
+    round!(f, a, b, c, d, input[4], 3);
+    round!(f, d, a, b, c, input[5], 7);
+    round!(f, c, d, a, b, input[6], 11);
+    round!(f, b, c, d, a, input[7], 19);
+
+    round!(g, a, b, c, d, input[1].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[3].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[5].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[7].wrapping_add(K2), 13);
+    round!(g, a, b, c, d, input[0].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[2].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[4].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round!(h, a, b, c, d, input[3].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[7].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[2].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[6].wrapping_add(K3), 15);
+    round!(h, a, b, c, d, input[1].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[5].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[0].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    for (x, y) in buf.iter_mut().zip([a, b, c, d]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e37_79b9;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum = 0u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            ((b1 << 4).wrapping_add(a)) ^ b1.wrapping_add(sum) ^ ((b1 >> 5).wrapping_add(b)),
+        );
+        b1 = b1.wrapping_add(
+            ((b0 << 4).wrapping_add(c)) ^ b0.wrapping_add(sum) ^ ((b0 >> 5).wrapping_add(d)),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
@@ -0,0 +1,359 @@
+//! The data of inodes, mapped by the block maps of ext2/3 or the extent trees
+//! of ext4.
+
+use alloc::{vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::layout::*;
+use super::volume::Volume;
+
+/// The magic of the header of an extent tree node.
+const EXTENT_MAGIC: u16 = 0xf30a;
+/// The maximum length of an initialized extent, longer ones are uninitialized.
+const EXTENT_MAX_INIT_LEN: u16 = 32768;
+/// The maximum depth of an extent tree.
+const EXTENT_MAX_DEPTH: usize = 5;
+/// The magic of an extended attribute block.
+const XATTR_MAGIC: u32 = 0xea02_0000;
+
+impl Volume {
+    /// Returns the number of 512-byte sectors allocated to `inode`.
+    pub fn inode_sectors(&self, inode: &Inode) -> u64 {
+        inode.sectors(
+            self.sb.has_ro_compat(RO_COMPAT_HUGE_FILE),
+            self.block_size(),
+        )
+    }
+
+    /// Returns the block of the logical block `lblock` of `inode`, or `None`
+    /// for a hole.
+    pub fn map_block(&mut self, inode: &Inode, lblock: u64) -> VfsResult<Option<u64>> {
+        if inode.flags() & INODE_EXTENTS_FL != 0 {
+            self.map_extent(inode, lblock)
+        } else {
+            self.map_indirect(inode, lblock)
+        }
+    }
+
+    fn map_extent(&mut self, inode: &Inode, lblock: u64) -> VfsResult<Option<u64>> {
+        let Ok(lblock) = u32::try_from(lblock) else {
+            return Ok(None);
+        };
+        let mut node = inode.block_bytes().to_vec();
+        for _ in 0..=EXTENT_MAX_DEPTH {
+            let entries = read_u16(&node, 2) as usize;
+            let depth = read_u16(&node, 6);
+            if read_u16(&node, 0) != EXTENT_MAGIC || 12 + entries * 12 > node.len() {
+                return Err(VfsError::InvalidData);
+            }
+            let entry = |i: usize| &node[12 + i * 12..24 + i * 12];
+            if depth == 0 {
+                for e in (0..entries).map(entry) {
+                    let start = read_u32(e, 0);
+                    let (len, init) = match read_u16(e, 4) {
+                        len if len > EXTENT_MAX_INIT_LEN => (len - EXTENT_MAX_INIT_LEN, false),
+                        len => (len, true),
+                    };
+                    if lblock >= start && lblock - start < len as u32 {
+                        // uninitialized extents read as zeros
+                        let block = (read_u16(e, 6) as u64) << 32 | read_u32(e, 8) as u64;
+                        return Ok(init.then_some(block + (lblock - start) as u64));
+                    }
+                }
+                return Ok(None);
+            }
+            // the last index covering `lblock`
+            let Some(e) = (0..entries)
+                .rev()
+                .map(entry)
+                .find(|e| read_u32(e, 0) <= lblock)
+            else {
+                return Ok(None);
+            };
+            let child = (read_u16(e, 8) as u64) << 32 | read_u32(e, 4) as u64;
+            node = vec![0; self.block_size()];
+            self.read_block(child, &mut node)?;
+        }
+        Err(VfsError::InvalidData)
+    }
+
+    /// Returns the indices of the pointers leading to the logical block
+    /// `lblock`: in `i_block`, then in each level of indirect blocks.
+    fn indirect_path(&self, lblock: u64) -> Option<Vec<usize>> {
+        if lblock < N_DIRECT as u64 {
+            return Some(vec![lblock as usize]);
+        }
+        let per_block = (self.block_size() / 4) as u64;
+        let mut rest = lblock - N_DIRECT as u64;
+        let mut span = per_block;
+        for level in 1..=3 {
+            if rest < span {
+                let mut path = vec![N_DIRECT + level - 1];
+                let mut sub = span;
+                for _ in 0..level {
+                    sub /= per_block;
+                    path.push((rest / sub) as usize);
+                    rest %= sub;
+                }
+                return Some(path);
+            }
+            rest -= span;
+            span *= per_block;
+        }
+        None
+    }
+
+    fn read_ptr(&mut self, block: u64, index: usize) -> VfsResult<u32> {
+        let mut buf = [0; 4];
+        self.read_bytes(
+            block * self.block_size() as u64 + index as u64 * 4,
+            &mut buf,
+        )?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_ptr(&mut self, block: u64, index: usize, value: u32) -> VfsResult {
+        let offset = block * self.block_size() as u64 + index as u64 * 4;
+        self.write_bytes(offset, &value.to_le_bytes())
+    }
+
+    fn map_indirect(&mut self, inode: &Inode, lblock: u64) -> VfsResult<Option<u64>> {
+        let Some(path) = self.indirect_path(lblock) else {
+            return Ok(None);
+        };
+        let mut block = inode.block(path[0]);
+        for &index in &path[1..] {
+            if block == 0 {
+                return Ok(None);
+            }
+            block = self.read_ptr(block as u64, index)?;
+        }
+        Ok((block != 0).then_some(block as u64))
+    }
+
+    /// Returns the block of the logical block `lblock` of the inode `ino`,
+    /// allocating it and the indirect blocks leading to it if needed.
+    ///
+    /// Only for the block-mapped inodes of the writable filesystems, whose
+    /// block numbers are 32-bit.
+    pub fn map_block_alloc(&mut self, ino: u32, inode: &mut Inode, lblock: u64) -> VfsResult<u64> {
+        let path = self.indirect_path(lblock).ok_or(VfsError::StorageFull)?;
+        let goal = self.inode_group(ino);
+        let mut block = inode.block(path[0]) as u64;
+        if block == 0 {
+            block = self.alloc_data_block(inode, goal)?;
+            inode.set_block(path[0], block as u32);
+        }
+        for &index in &path[1..] {
+            let mut next = self.read_ptr(block, index)? as u64;
+            if next == 0 {
+                next = self.alloc_data_block(inode, goal)?;
+                self.write_ptr(block, index, next as u32)?;
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    fn alloc_data_block(&mut self, inode: &mut Inode, goal_group: usize) -> VfsResult<u64> {
+        let block = self.alloc_block(goal_group)?;
+        let sectors = self.inode_sectors(inode) + (self.block_size() / 512) as u64;
+        inode.set_sectors(sectors);
+        Ok(block)
+    }
+
+    fn free_data_block(&mut self, inode: &mut Inode, block: u64) -> VfsResult {
+        self.free_block(block)?;
+        let sectors = self.inode_sectors(inode);
+        inode.set_sectors(sectors.saturating_sub((self.block_size() / 512) as u64));
+        Ok(())
+    }
+
+    /// Reads the data of `inode` at `offset`, holes read as zeros.
+    pub fn read_data(&mut self, inode: &Inode, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        if inode.flags() & INODE_INLINE_DATA_FL != 0 {
+            return Err(VfsError::Unsupported);
+        }
+        let len = (size - offset).min(buf.len() as u64) as usize;
+        let bs = self.block_size();
+        let mut pos = 0;
+        while pos < len {
+            let lblock = (offset + pos as u64) / bs as u64;
+            let start = ((offset + pos as u64) % bs as u64) as usize;
+            let count = (len - pos).min(bs - start);
+            let dst = &mut buf[pos..pos + count];
+            match self.map_block(inode, lblock)? {
+                None => dst.fill(0),
+                Some(block) if count == bs => self.read_block(block, dst)?,
+                Some(block) => self.read_bytes(block * bs as u64 + start as u64, dst)?,
+            }
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    /// Writes `buf` to the data of the inode `ino` at `offset`, extending it
+    /// if needed. The caller writes the inode back.
+    pub fn write_data(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        offset: u64,
+        buf: &[u8],
+    ) -> VfsResult<usize> {
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(VfsError::InvalidInput)?;
+        if end > i32::MAX as u64 && !self.sb.has_ro_compat(RO_COMPAT_LARGE_FILE) {
+            return Err(VfsError::StorageFull);
+        }
+        let bs = self.block_size();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let lblock = (offset + pos as u64) / bs as u64;
+            let start = ((offset + pos as u64) % bs as u64) as usize;
+            let count = (buf.len() - pos).min(bs - start);
+            let block = self.map_block_alloc(ino, inode, lblock)?;
+            let src = &buf[pos..pos + count];
+            if count == bs {
+                self.write_block(block, src)?;
+            } else {
+                self.write_bytes(block * bs as u64 + start as u64, src)?;
+            }
+            pos += count;
+        }
+        if end > inode.size() {
+            inode.set_size(end);
+        }
+        Ok(buf.len())
+    }
+
+    /// Truncates or extends the data of `inode` to `size` bytes, freeing the
+    /// blocks past the end. The caller writes the inode back.
+    pub fn truncate_data(&mut self, inode: &mut Inode, size: u64) -> VfsResult {
+        let bs = self.block_size() as u64;
+        if size < inode.size() {
+            self.free_blocks_from(inode, size.div_ceil(bs))?;
+            // a later extension must read zeros
+            if !size.is_multiple_of(bs) {
+                if let Some(block) = self.map_block(inode, size / bs)? {
+                    let start = size % bs;
+                    self.write_bytes(block * bs + start, &vec![0; (bs - start) as usize])?;
+                }
+            }
+        }
+        inode.set_size(size);
+        Ok(())
+    }
+
+    /// Frees the data blocks of a block-mapped `inode` from the logical block
+    /// `from`, with the indirect blocks left empty.
+    pub fn free_blocks_from(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        for i in (from.min(N_DIRECT as u64) as usize)..N_DIRECT {
+            let block = inode.block(i);
+            if block != 0 {
+                self.free_data_block(inode, block as u64)?;
+                inode.set_block(i, 0);
+            }
+        }
+        let per_block = (self.block_size() / 4) as u64;
+        let (mut base, mut span) = (N_DIRECT as u64, per_block);
+        for level in 1..=3 {
+            let i = N_DIRECT + level - 1;
+            let block = inode.block(i) as u64;
+            if block != 0
+                && from < base + span
+                && self.free_tree(inode, block, level, from.saturating_sub(base))?
+            {
+                self.free_data_block(inode, block)?;
+                inode.set_block(i, 0);
+            }
+            base += span;
+            span *= per_block;
+        }
+        Ok(())
+    }
+
+    /// Frees the blocks mapped by the indirect block `block` of `level` from
+    /// its `from`th one, returns whether it maps nothing anymore.
+    fn free_tree(
+        &mut self,
+        inode: &mut Inode,
+        block: u64,
+        level: usize,
+        from: u64,
+    ) -> VfsResult<bool> {
+        let per_block = self.block_size() / 4;
+        let child_span = (per_block as u64).pow(level as u32 - 1);
+        let mut ptrs = vec![0; self.block_size()];
+        self.read_block(block, &mut ptrs)?;
+        let (mut changed, mut empty) = (false, true);
+        for index in 0..per_block {
+            let child = read_u32(&ptrs, index * 4) as u64;
+            if child == 0 {
+                continue;
+            }
+            let start = index as u64 * child_span;
+            let child_empty = start + child_span > from
+                && (level == 1
+                    || self.free_tree(inode, child, level - 1, from.saturating_sub(start))?);
+            if child_empty {
+                self.free_data_block(inode, child)?;
+                write_u32(&mut ptrs, index * 4, 0);
+                changed = true;
+            } else {
+                empty = false;
+            }
+        }
+        if changed && !empty {
+            self.write_block(block, &ptrs)?;
+        }
+        Ok(empty)
+    }
+
+    /// Returns whether the symbolic link `inode` keeps its target in
+    /// `i_block`.
+    pub fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        inode.size() < I_BLOCK_SIZE as u64 && inode.flags() & INODE_INLINE_DATA_FL == 0
+    }
+
+    /// Reads the target of the symbolic link `inode`.
+    pub fn read_link(&mut self, inode: &Inode, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.is_fast_symlink(inode) {
+            let len = (inode.size() as usize).min(buf.len());
+            buf[..len].copy_from_slice(&inode.block_bytes()[..len]);
+            Ok(len)
+        } else {
+            self.read_data(inode, 0, buf)
+        }
+    }
+
+    /// Releases the extended attribute block of `inode`, freed when no other
+    /// inode shares it.
+    pub fn release_xattr_block(&mut self, inode: &mut Inode) -> VfsResult {
+        let block = inode.file_acl();
+        if block == 0 {
+            return Ok(());
+        }
+        let mut buf = vec![0; self.block_size()];
+        self.read_block(block, &mut buf)?;
+        if read_u32(&buf, 0) != XATTR_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let refcount = read_u32(&buf, 4);
+        if refcount > 1 {
+            write_u32(&mut buf, 4, refcount - 1);
+            self.write_block(block, &buf)?;
+            let sectors = self.inode_sectors(inode);
+            inode.set_sectors(sectors.saturating_sub((self.block_size() / 512) as u64));
+        } else {
+            self.free_data_block(inode, block)?;
+        }
+        inode.set_file_acl(0);
+        Ok(())
+    }
+}
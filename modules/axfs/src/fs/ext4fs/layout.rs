@@ -0,0 +1,559 @@
+//! The on-disk structures of ext2/3/4.
+//!
+//! See <https://www.kernel.org/doc/html/latest/filesystems/ext4/index.html>.
+
+use axfs_vfs::VfsNodeType;
+use core::time::Duration;
+
+/// The magic number of the superblock, also the filesystem type reported by
+/// `statfs`, as Linux's `EXT4_SUPER_MAGIC`.
+pub const EXT4_SUPER_MAGIC: u16 = 0xef53;
+/// The byte offset of the primary superblock.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// The inode of the root directory.
+pub const ROOT_INO: u32 = 2;
+/// The maximum length of a name in a directory entry.
+pub const NAME_MAX: usize = 255;
+
+/// The number of block pointers in an inode, `i_block`.
+pub const N_BLOCKS: usize = 15;
+/// The number of direct block pointers.
+pub const N_DIRECT: usize = 12;
+/// The size of `i_block` in bytes, where the targets of fast symbolic links
+/// and the root of extent trees are stored.
+pub const I_BLOCK_SIZE: usize = N_BLOCKS * 4;
+
+pub const COMPAT_HAS_JOURNAL: u32 = 0x4;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+
+/// The incompatible features that can be read.
+pub const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+/// The incompatible features that can be written.
+pub const INCOMPAT_WRITABLE: u32 = INCOMPAT_FILETYPE;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+
+/// The read-only compatible features that can be written, the others make the
+/// filesystem read-only, e.g., the checksums of `metadata_csum`.
+pub const RO_COMPAT_WRITABLE: u32 =
+    RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE | RO_COMPAT_DIR_NLINK | RO_COMPAT_EXTRA_ISIZE;
+
+/// The superblock flag of directory hashes computed with unsigned chars.
+const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+/// The inode flag of directories indexed by an htree.
+pub const INODE_INDEX_FL: u32 = 0x1000;
+/// The inode flag of files with `i_blocks` in filesystem blocks.
+pub const INODE_HUGE_FILE_FL: u32 = 0x40000;
+/// The inode flag of files mapped by an extent tree.
+pub const INODE_EXTENTS_FL: u32 = 0x80000;
+/// The inode flag of files stored in the inode.
+pub const INODE_INLINE_DATA_FL: u32 = 0x1000_0000;
+
+const S_IFMT: u16 = 0o170000;
+
+pub fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+pub fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The superblock, kept as raw bytes to be written back as is.
+#[derive(Clone)]
+pub struct SuperBlock {
+    raw: [u8; SUPERBLOCK_SIZE],
+}
+
+impl SuperBlock {
+    pub fn new(raw: [u8; SUPERBLOCK_SIZE]) -> Self {
+        Self { raw }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn magic(&self) -> u16 {
+        read_u16(&self.raw, 56)
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        self.read_lo_hi(4, 336)
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        self.read_lo_hi(12, 344)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        write_u32(&mut self.raw, 12, count as u32);
+        if self.is_64bit() {
+            write_u32(&mut self.raw, 344, (count >> 32) as u32);
+        }
+    }
+
+    pub fn reserved_blocks_count(&self) -> u64 {
+        self.read_lo_hi(8, 340)
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 16)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        write_u32(&mut self.raw, 16, count);
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        read_u32(&self.raw, 20)
+    }
+
+    /// Returns the block size, or `None` if it is not supported.
+    pub fn block_size(&self) -> Option<usize> {
+        match read_u32(&self.raw, 24) {
+            log @ 0..=6 => Some(1024 << log),
+            _ => None,
+        }
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        read_u32(&self.raw, 32)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        read_u32(&self.raw, 40)
+    }
+
+    pub fn set_mount_time(&mut self, time: u32) {
+        write_u32(&mut self.raw, 44, time);
+        let count = read_u16(&self.raw, 52);
+        write_u16(&mut self.raw, 52, count.wrapping_add(1));
+    }
+
+    pub fn set_write_time(&mut self, time: u32) {
+        write_u32(&mut self.raw, 48, time);
+    }
+
+    pub fn rev_level(&self) -> u32 {
+        read_u32(&self.raw, 76)
+    }
+
+    /// Returns the first inode for regular files.
+    pub fn first_ino(&self) -> u32 {
+        match self.rev_level() {
+            0 => 11,
+            _ => read_u32(&self.raw, 84),
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        match self.rev_level() {
+            0 => 128,
+            _ => read_u16(&self.raw, 88) as usize,
+        }
+    }
+
+    pub fn feature_compat(&self) -> u32 {
+        read_u32(&self.raw, 92)
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        read_u32(&self.raw, 96)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        read_u32(&self.raw, 100)
+    }
+
+    pub fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat() & feature != 0
+    }
+
+    pub fn has_ro_compat(&self, feature: u32) -> bool {
+        self.feature_ro_compat() & feature != 0
+    }
+
+    pub fn is_64bit(&self) -> bool {
+        self.has_incompat(INCOMPAT_64BIT)
+    }
+
+    /// Returns the seed of the directory hashes.
+    pub fn hash_seed(&self) -> [u32; 4] {
+        core::array::from_fn(|i| read_u32(&self.raw, 236 + i * 4))
+    }
+
+    /// Returns whether the directory hashes are computed with unsigned chars.
+    pub fn unsigned_hash(&self) -> bool {
+        read_u32(&self.raw, 352) & FLAGS_UNSIGNED_HASH != 0
+    }
+
+    /// Returns the size of a group descriptor.
+    pub fn desc_size(&self) -> usize {
+        if self.is_64bit() {
+            read_u16(&self.raw, 254) as usize
+        } else {
+            32
+        }
+    }
+
+    fn read_lo_hi(&self, lo: usize, hi: usize) -> u64 {
+        let hi = if self.is_64bit() {
+            read_u32(&self.raw, hi)
+        } else {
+            0
+        };
+        (hi as u64) << 32 | read_u32(&self.raw, lo) as u64
+    }
+}
+
+/// A block group descriptor.
+#[derive(Clone)]
+pub struct GroupDesc {
+    raw: [u8; 64],
+    is_64bit: bool,
+}
+
+impl GroupDesc {
+    pub fn new(bytes: &[u8], is_64bit: bool) -> Self {
+        let mut raw = [0; 64];
+        let len = bytes.len().min(64);
+        raw[..len].copy_from_slice(&bytes[..len]);
+        Self { raw, is_64bit }
+    }
+
+    /// Returns the bytes to be written back, of `desc_size` bytes.
+    pub fn as_bytes(&self, desc_size: usize) -> &[u8] {
+        &self.raw[..desc_size.min(64)]
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.read_lo_hi32(0, 32)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.read_lo_hi32(4, 36)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.read_lo_hi32(8, 40)
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.read_lo_hi16(12, 44)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u32) {
+        self.write_lo_hi16(12, 44, count);
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.read_lo_hi16(14, 46)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        self.write_lo_hi16(14, 46, count);
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.read_lo_hi16(16, 48)
+    }
+
+    pub fn set_used_dirs_count(&mut self, count: u32) {
+        self.write_lo_hi16(16, 48, count);
+    }
+
+    fn read_lo_hi32(&self, lo: usize, hi: usize) -> u64 {
+        let hi = if self.is_64bit {
+            read_u32(&self.raw, hi)
+        } else {
+            0
+        };
+        (hi as u64) << 32 | read_u32(&self.raw, lo) as u64
+    }
+
+    fn read_lo_hi16(&self, lo: usize, hi: usize) -> u32 {
+        let hi = if self.is_64bit {
+            read_u16(&self.raw, hi)
+        } else {
+            0
+        };
+        (hi as u32) << 16 | read_u16(&self.raw, lo) as u32
+    }
+
+    fn write_lo_hi16(&mut self, lo: usize, hi: usize, value: u32) {
+        write_u16(&mut self.raw, lo, value as u16);
+        if self.is_64bit {
+            write_u16(&mut self.raw, hi, (value >> 16) as u16);
+        }
+    }
+}
+
+/// An inode, kept as the raw bytes of its slot in the inode table to be
+/// written back as is.
+#[derive(Clone)]
+pub struct Inode {
+    raw: [u8; 256],
+    /// The size of the inode slot, at most 256 bytes are used.
+    size: usize,
+}
+
+impl Inode {
+    pub fn new(bytes: &[u8]) -> Self {
+        let mut raw = [0; 256];
+        let size = bytes.len().min(256);
+        raw[..size].copy_from_slice(&bytes[..size]);
+        Self { raw, size }
+    }
+
+    /// Creates a new inode of `mode`, with the current time, in a slot of
+    /// `size` bytes.
+    pub fn create(mode: u16, size: usize) -> Self {
+        let mut inode = Self::new(&[0; 256][..size.min(256)]);
+        if size > 128 {
+            // the extra fields up to `i_crtime_extra`
+            write_u16(&mut inode.raw, 128, 32);
+        }
+        write_u16(&mut inode.raw, 0, mode);
+        let now = axfs_vfs::current_time();
+        inode.set_times(Some(now), Some(now), Some(now));
+        inode
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw[..self.size]
+    }
+
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.raw, 0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        write_u16(&mut self.raw, 0, mode);
+    }
+
+    pub fn file_type(&self) -> Option<VfsNodeType> {
+        mode_to_type(self.mode())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == Some(VfsNodeType::Dir)
+    }
+
+    pub fn uid(&self) -> u32 {
+        (read_u16(&self.raw, 120) as u32) << 16 | read_u16(&self.raw, 2) as u32
+    }
+
+    pub fn gid(&self) -> u32 {
+        (read_u16(&self.raw, 122) as u32) << 16 | read_u16(&self.raw, 24) as u32
+    }
+
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        write_u16(&mut self.raw, 2, uid as u16);
+        write_u16(&mut self.raw, 120, (uid >> 16) as u16);
+        write_u16(&mut self.raw, 24, gid as u16);
+        write_u16(&mut self.raw, 122, (gid >> 16) as u16);
+    }
+
+    pub fn size(&self) -> u64 {
+        (read_u32(&self.raw, 108) as u64) << 32 | read_u32(&self.raw, 4) as u64
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        write_u32(&mut self.raw, 4, size as u32);
+        write_u32(&mut self.raw, 108, (size >> 32) as u32);
+    }
+
+    pub fn links_count(&self) -> u16 {
+        read_u16(&self.raw, 26)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        write_u16(&mut self.raw, 26, count);
+    }
+
+    /// Returns the number of 512-byte sectors allocated to the inode, counted
+    /// in `i_blocks` in the units given by `huge_file` and the flags.
+    pub fn sectors(&self, huge_file: bool, block_size: usize) -> u64 {
+        let mut blocks = read_u32(&self.raw, 28) as u64;
+        if huge_file {
+            blocks |= (read_u16(&self.raw, 116) as u64) << 32;
+            if self.flags() & INODE_HUGE_FILE_FL != 0 {
+                blocks *= block_size as u64 / 512;
+            }
+        }
+        blocks
+    }
+
+    /// Sets the number of 512-byte sectors allocated, only for the writable
+    /// filesystems without `huge_file`.
+    pub fn set_sectors(&mut self, sectors: u64) {
+        write_u32(&mut self.raw, 28, sectors as u32);
+    }
+
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.raw, 32)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        write_u32(&mut self.raw, 32, flags);
+    }
+
+    pub fn set_dtime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 20, time);
+    }
+
+    /// Returns the extended attribute block.
+    pub fn file_acl(&self) -> u64 {
+        (read_u16(&self.raw, 118) as u64) << 32 | read_u32(&self.raw, 104) as u64
+    }
+
+    pub fn set_file_acl(&mut self, block: u64) {
+        write_u32(&mut self.raw, 104, block as u32);
+        write_u16(&mut self.raw, 118, (block >> 32) as u16);
+    }
+
+    pub fn block(&self, index: usize) -> u32 {
+        read_u32(&self.raw, 40 + index * 4)
+    }
+
+    pub fn set_block(&mut self, index: usize, block: u32) {
+        write_u32(&mut self.raw, 40 + index * 4, block);
+    }
+
+    /// Returns `i_block` as bytes.
+    pub fn block_bytes(&self) -> &[u8] {
+        &self.raw[40..40 + I_BLOCK_SIZE]
+    }
+
+    pub fn block_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[40..40 + I_BLOCK_SIZE]
+    }
+
+    /// Returns `(atime, mtime, ctime)`.
+    pub fn times(&self) -> (Duration, Duration, Duration) {
+        (self.time(8, 140), self.time(16, 136), self.time(12, 132))
+    }
+
+    /// Sets the times that are `Some`.
+    pub fn set_times(
+        &mut self,
+        atime: Option<Duration>,
+        mtime: Option<Duration>,
+        ctime: Option<Duration>,
+    ) {
+        for (time, offset, extra) in [(atime, 8, 140), (mtime, 16, 136), (ctime, 12, 132)] {
+            if let Some(time) = time {
+                self.set_time(offset, extra, time);
+            }
+        }
+    }
+
+    /// Returns whether the extra field at `offset` is present.
+    fn has_extra(&self, offset: usize) -> bool {
+        self.size > 128 && 128 + read_u16(&self.raw, 128) as usize >= offset + 4
+    }
+
+    /// Reads a time of seconds at `offset`, with the nanoseconds and the
+    /// epoch bits at `extra` if the inode is large enough.
+    fn time(&self, offset: usize, extra: usize) -> Duration {
+        let secs = read_u32(&self.raw, offset) as i32 as i64;
+        if !self.has_extra(extra) {
+            return Duration::from_secs(secs.max(0) as u64);
+        }
+        let extra = read_u32(&self.raw, extra);
+        let secs = secs + ((extra as i64 & 3) << 32);
+        Duration::new(secs.max(0) as u64, (extra >> 2).min(999_999_999))
+    }
+
+    fn set_time(&mut self, offset: usize, extra: usize, time: Duration) {
+        let secs = time.as_secs();
+        write_u32(&mut self.raw, offset, secs as u32);
+        if self.has_extra(extra) {
+            let epoch = ((secs >> 32) & 3) as u32;
+            write_u32(&mut self.raw, extra, time.subsec_nanos() << 2 | epoch);
+        }
+    }
+}
+
+/// Converts the mode of an inode to the node type.
+pub fn mode_to_type(mode: u16) -> Option<VfsNodeType> {
+    Some(match mode & S_IFMT {
+        0o010000 => VfsNodeType::Fifo,
+        0o020000 => VfsNodeType::CharDevice,
+        0o040000 => VfsNodeType::Dir,
+        0o060000 => VfsNodeType::BlockDevice,
+        0o100000 => VfsNodeType::File,
+        0o120000 => VfsNodeType::SymLink,
+        0o140000 => VfsNodeType::Socket,
+        _ => return None,
+    })
+}
+
+/// Returns the format bits of the mode of an inode of `ty`.
+pub fn type_to_mode(ty: VfsNodeType) -> u16 {
+    (ty as u16) << 12
+}
+
+/// Converts the file type of a directory entry to the node type.
+pub fn dirent_type(file_type: u8) -> Option<VfsNodeType> {
+    Some(match file_type {
+        1 => VfsNodeType::File,
+        2 => VfsNodeType::Dir,
+        3 => VfsNodeType::CharDevice,
+        4 => VfsNodeType::BlockDevice,
+        5 => VfsNodeType::Fifo,
+        6 => VfsNodeType::Socket,
+        7 => VfsNodeType::SymLink,
+        _ => return None,
+    })
+}
+
+/// Returns the file type of a directory entry of a node of `ty`.
+pub fn type_to_dirent(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+/// The size of a directory entry with a name of `name_len` bytes, the
+/// 8-byte header and the name rounded up to 4 bytes.
+pub const fn dirent_size(name_len: usize) -> usize {
+    (8 + name_len).next_multiple_of(4)
+}
@@ -0,0 +1,668 @@
+//! An ext2/3/4 filesystem on a block device.
+//!
+//! Filesystems with only the features of ext2 (and the `dir_index` hash trees
+//! of ext3) are read-write, those of ext4, e.g., extents, 64-bit block
+//! numbers, flexible block groups, journals and metadata checksums, are
+//! mounted read-only. A journal must be empty, i.e., the filesystem cleanly
+//! unmounted, as it is never replayed.
+//!
+//! Nodes are looked up by inode numbers and read the inodes from the disk on
+//! each operation. The inode of a removed file is only freed after its last
+//! node is dropped, so that it stays usable while open. It is leaked if the
+//! filesystem is not unmounted cleanly before that, as it is not added to the
+//! orphan list.
+
+mod dir;
+mod hash;
+mod inode;
+mod layout;
+mod volume;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use axfs_vfs::{FileSystemInfo, VfsDirEntry, VfsError, VfsNodePerm, VfsResult, VfsSetAttr};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axsync::Mutex;
+use lazy_init::LazyInit;
+
+use self::layout::*;
+use self::volume::Volume;
+use crate::dev::BlockDev;
+
+pub struct Ext4FileSystem {
+    this: Weak<Self>,
+    volume: Mutex<Volume>,
+    /// The directory the filesystem is mounted on.
+    parent: LazyInit<VfsNodeRef>,
+    read_only: bool,
+    /// The number of [`FileNode`]s alive for each inode.
+    open_files: Mutex<BTreeMap<u32, usize>>,
+}
+
+/// A regular file, symbolic link or special file.
+pub struct FileNode {
+    fs: Arc<Ext4FileSystem>,
+    ino: u32,
+}
+
+pub struct DirNode {
+    fs: Arc<Ext4FileSystem>,
+    ino: u32,
+}
+
+impl Ext4FileSystem {
+    /// Opens the filesystem on `dev`, read-only if it has features which can
+    /// not be written.
+    pub fn open(dev: BlockDev) -> VfsResult<Arc<Self>> {
+        let mut volume = Volume::open(dev)?;
+        let incompat = volume.sb.feature_incompat();
+        if incompat & !INCOMPAT_SUPPORTED != 0 {
+            warn!(
+                "ext4fs: unsupported incompatible features {:#x}",
+                incompat & !INCOMPAT_SUPPORTED
+            );
+            return Err(VfsError::Unsupported);
+        }
+        if incompat & INCOMPAT_RECOVER != 0 {
+            warn!("ext4fs: the journal needs recovery");
+            return Err(VfsError::Unsupported);
+        }
+        let read_only = incompat & !INCOMPAT_WRITABLE != 0
+            || volume.sb.feature_ro_compat() & !RO_COMPAT_WRITABLE != 0
+            || volume.sb.feature_compat() & COMPAT_HAS_JOURNAL != 0;
+        if read_only {
+            warn!("ext4fs: mounted read-only for the ext4 features");
+        } else {
+            volume
+                .sb
+                .set_mount_time(axfs_vfs::current_time().as_secs() as u32);
+            volume.write_superblock()?;
+        }
+        Ok(Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            volume: Mutex::new(volume),
+            parent: LazyInit::new(),
+            read_only,
+            open_files: Mutex::new(BTreeMap::new()),
+        }))
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn new_node(&self, ino: u32, inode: &Inode) -> VfsNodeRef {
+        if inode.is_dir() {
+            Arc::new(DirNode {
+                fs: self.this(),
+                ino,
+            })
+        } else {
+            *self.open_files.lock().entry(ino).or_default() += 1;
+            Arc::new(FileNode {
+                fs: self.this(),
+                ino,
+            })
+        }
+    }
+
+    fn get_attr(&self, ino: u32) -> VfsResult<VfsNodeAttr> {
+        let mut vol = self.volume.lock();
+        let inode = vol.read_inode(ino)?;
+        let ty = inode.file_type().ok_or(VfsError::InvalidData)?;
+        let perm = VfsNodePerm::from_bits_truncate(inode.mode());
+        let mut attr = VfsNodeAttr::new(perm, ty, inode.size(), vol.inode_sectors(&inode));
+        attr.set_ino(ino as u64);
+        attr.set_nlink(inode.links_count() as u64);
+        attr.set_owner(inode.uid(), inode.gid());
+        let (atime, mtime, ctime) = inode.times();
+        attr.set_times(atime, mtime, ctime);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, ino: u32, changes: &VfsSetAttr) -> VfsResult {
+        self.check_writable()?;
+        let mut vol = self.volume.lock();
+        let mut inode = vol.read_inode(ino)?;
+        if let Some(perm) = changes.mode {
+            inode.set_mode(inode.mode() & !0o777 | perm.bits());
+        }
+        inode.set_owner(
+            changes.uid.unwrap_or(inode.uid()),
+            changes.gid.unwrap_or(inode.gid()),
+        );
+        let now = axfs_vfs::current_time();
+        inode.set_times(changes.atime, changes.mtime, Some(now));
+        vol.write_inode(ino, &inode)
+    }
+
+    /// Reads the directory `ino`, which must be a directory.
+    fn read_dir_inode(vol: &mut Volume, ino: u32) -> VfsResult<Inode> {
+        let inode = vol.read_inode(ino)?;
+        if !inode.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok(inode)
+    }
+
+    fn lookup_child(&self, dir_ino: u32, name: &str) -> VfsResult<VfsNodeRef> {
+        let mut vol = self.volume.lock();
+        let dir = Self::read_dir_inode(&mut vol, dir_ino)?;
+        let entry = vol
+            .find_entry(&dir, name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        let inode = vol.read_inode(entry.ino)?;
+        Ok(self.new_node(entry.ino, &inode))
+    }
+
+    /// Returns the parent directory of the directory `ino`.
+    fn parent_of(&self, ino: u32) -> Option<VfsNodeRef> {
+        if ino == ROOT_INO {
+            return self.parent.try_get().cloned();
+        }
+        self.lookup_child(ino, "..").ok()
+    }
+
+    /// Creates an inode of `mode` with a single link and its entry `name` in
+    /// the directory `dir_ino`, then `init` fills it before it is written.
+    fn create_inode(
+        &self,
+        dir_ino: u32,
+        name: &str,
+        mode: u16,
+        init: impl FnOnce(&mut Volume, u32, &mut Inode) -> VfsResult,
+    ) -> VfsResult {
+        self.check_writable()?;
+        if name.len() > NAME_MAX {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut vol = self.volume.lock();
+        let mut dir = Self::read_dir_inode(&mut vol, dir_ino)?;
+        if vol.find_entry(&dir, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let ty = mode_to_type(mode).ok_or(VfsError::InvalidInput)?;
+        let is_dir = ty == VfsNodeType::Dir;
+        let goal = vol.inode_group(dir_ino);
+        let ino = vol.alloc_inode(goal, is_dir)?;
+        let mut inode = Inode::create(mode, vol.sb.inode_size());
+        inode.set_links_count(1);
+        let res = init(&mut vol, ino, &mut inode)
+            .and_then(|_| vol.add_entry(dir_ino, &mut dir, name.as_bytes(), ino, ty));
+        if let Err(e) = res {
+            // give back what has been allocated
+            if ty != VfsNodeType::SymLink || !vol.is_fast_symlink(&inode) {
+                vol.free_blocks_from(&mut inode, 0)?;
+            }
+            vol.free_inode(ino, is_dir)?;
+            return Err(e);
+        }
+        vol.write_inode(ino, &inode)?;
+        if is_dir {
+            dir.set_links_count(dir.links_count().saturating_add(1));
+        }
+        let now = axfs_vfs::current_time();
+        dir.set_times(None, Some(now), Some(now));
+        vol.write_inode(dir_ino, &dir)
+    }
+
+    fn create(&self, dir_ino: u32, name: &str, ty: VfsNodeType) -> VfsResult {
+        // an existing node of the type is kept as in fatfs, so that the mount
+        // points can be in a read-only filesystem
+        match self.lookup_child(dir_ino, name) {
+            Ok(node) if node.get_attr()?.file_type() == ty => return Ok(()),
+            Ok(_) => return Err(VfsError::AlreadyExists),
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        match ty {
+            VfsNodeType::File => {
+                let mode = type_to_mode(ty) | VfsNodePerm::default_file().bits();
+                self.create_inode(dir_ino, name, mode, |_, _, _| Ok(()))
+            }
+            VfsNodeType::Dir => {
+                let mode = type_to_mode(ty) | VfsNodePerm::default_dir().bits();
+                self.create_inode(dir_ino, name, mode, |vol, ino, inode| {
+                    inode.set_links_count(2);
+                    vol.init_dir(ino, inode, dir_ino)
+                })
+            }
+            _ => Err(VfsError::Unsupported),
+        }
+    }
+
+    fn symlink(&self, dir_ino: u32, name: &str, target: &str) -> VfsResult {
+        let mode = type_to_mode(VfsNodeType::SymLink) | 0o777;
+        self.create_inode(dir_ino, name, mode, |vol, ino, inode| {
+            let target = target.as_bytes();
+            if target.len() < I_BLOCK_SIZE {
+                inode.block_bytes_mut()[..target.len()].copy_from_slice(target);
+                inode.set_size(target.len() as u64);
+            } else {
+                vol.write_data(ino, inode, 0, target)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn link(&self, dir_ino: u32, name: &str, node: &VfsNodeRef) -> VfsResult {
+        let any = node.as_any();
+        if any.is::<DirNode>() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let file = any
+            .downcast_ref::<FileNode>()
+            .filter(|file| core::ptr::eq(Arc::as_ptr(&file.fs), self))
+            .ok_or(VfsError::CrossesDevices)?;
+        self.check_writable()?;
+        let mut vol = self.volume.lock();
+        let mut dir = Self::read_dir_inode(&mut vol, dir_ino)?;
+        if vol.find_entry(&dir, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut inode = vol.read_inode(file.ino)?;
+        let ty = inode.file_type().ok_or(VfsError::InvalidData)?;
+        if inode.links_count() == u16::MAX {
+            return Err(VfsError::StorageFull);
+        }
+        vol.add_entry(dir_ino, &mut dir, name.as_bytes(), file.ino, ty)?;
+        let now = axfs_vfs::current_time();
+        inode.set_links_count(inode.links_count() + 1);
+        inode.set_times(None, None, Some(now));
+        vol.write_inode(file.ino, &inode)?;
+        dir.set_times(None, Some(now), Some(now));
+        vol.write_inode(dir_ino, &dir)
+    }
+
+    fn remove(&self, dir_ino: u32, name: &str) -> VfsResult {
+        self.check_writable()?;
+        let mut vol = self.volume.lock();
+        let mut dir = Self::read_dir_inode(&mut vol, dir_ino)?;
+        let entry = vol
+            .find_entry(&dir, name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        let mut inode = vol.read_inode(entry.ino)?;
+        let is_dir = inode.is_dir();
+        if is_dir && !vol.is_dir_empty(&inode)? {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        vol.remove_entry(&dir, name.as_bytes())?;
+        let now = axfs_vfs::current_time();
+        if is_dir {
+            // the entry and `..`
+            inode.set_links_count(0);
+            dir.set_links_count(dir.links_count().saturating_sub(1).max(2));
+        } else {
+            inode.set_links_count(inode.links_count().saturating_sub(1));
+        }
+        // an open file is deleted when its last node is dropped
+        if inode.links_count() == 0 && !self.open_files.lock().contains_key(&entry.ino) {
+            Self::delete_inode(&mut vol, entry.ino, &mut inode)?;
+        } else {
+            inode.set_times(None, None, Some(now));
+            vol.write_inode(entry.ino, &inode)?;
+        }
+        dir.set_times(None, Some(now), Some(now));
+        vol.write_inode(dir_ino, &dir)
+    }
+
+    /// Frees the data and the inode `ino` which has no links left.
+    fn delete_inode(vol: &mut Volume, ino: u32, inode: &mut Inode) -> VfsResult {
+        let is_dir = inode.is_dir();
+        let is_fast_symlink =
+            inode.file_type() == Some(VfsNodeType::SymLink) && vol.is_fast_symlink(inode);
+        if !is_fast_symlink {
+            vol.free_blocks_from(inode, 0)?;
+        }
+        vol.release_xattr_block(inode)?;
+        inode.set_size(0);
+        // fsck takes a `dtime` below the number of inodes for a link of the
+        // orphan list, e.g., with a clock counting from boot
+        let now = axfs_vfs::current_time().as_secs() as u32;
+        inode.set_dtime(now.max(vol.sb.inodes_count()));
+        vol.write_inode(ino, inode)?;
+        vol.free_inode(ino, is_dir)
+    }
+
+    /// Moves the entry `src_name` of the directory `src_dir` to `dst_name` in
+    /// `dst_dir`, which must not exist.
+    fn rename(&self, src_dir: u32, src_name: &str, dst_dir: u32, dst_name: &str) -> VfsResult {
+        self.check_writable()?;
+        if dst_name.len() > NAME_MAX {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut vol = self.volume.lock();
+        let mut sdir = Self::read_dir_inode(&mut vol, src_dir)?;
+        let entry = vol
+            .find_entry(&sdir, src_name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        if src_dir == dst_dir && src_name == dst_name {
+            return Ok(());
+        }
+        let mut inode = vol.read_inode(entry.ino)?;
+        let ty = inode.file_type().ok_or(VfsError::InvalidData)?;
+        let is_dir = ty == VfsNodeType::Dir;
+        if is_dir && src_dir != dst_dir {
+            // a directory can not be moved into itself
+            let mut ino = dst_dir;
+            while ino != ROOT_INO {
+                if ino == entry.ino {
+                    return Err(VfsError::InvalidInput);
+                }
+                let dir = Self::read_dir_inode(&mut vol, ino)?;
+                ino = vol
+                    .find_entry(&dir, b"..")?
+                    .ok_or(VfsError::InvalidData)?
+                    .ino;
+            }
+        }
+
+        let now = axfs_vfs::current_time();
+        if src_dir == dst_dir {
+            if vol.find_entry(&sdir, dst_name.as_bytes())?.is_some() {
+                return Err(VfsError::AlreadyExists);
+            }
+            vol.add_entry(dst_dir, &mut sdir, dst_name.as_bytes(), entry.ino, ty)?;
+            vol.remove_entry(&sdir, src_name.as_bytes())?;
+        } else {
+            let mut ddir = Self::read_dir_inode(&mut vol, dst_dir)?;
+            if vol.find_entry(&ddir, dst_name.as_bytes())?.is_some() {
+                return Err(VfsError::AlreadyExists);
+            }
+            vol.add_entry(dst_dir, &mut ddir, dst_name.as_bytes(), entry.ino, ty)?;
+            vol.remove_entry(&sdir, src_name.as_bytes())?;
+            if is_dir {
+                vol.set_parent_entry(&inode, dst_dir)?;
+                ddir.set_links_count(ddir.links_count().saturating_add(1));
+                sdir.set_links_count(sdir.links_count().saturating_sub(1).max(2));
+            }
+            ddir.set_times(None, Some(now), Some(now));
+            vol.write_inode(dst_dir, &ddir)?;
+        }
+        sdir.set_times(None, Some(now), Some(now));
+        vol.write_inode(src_dir, &sdir)?;
+        inode.set_times(None, None, Some(now));
+        vol.write_inode(entry.ino, &inode)
+    }
+}
+
+impl VfsOps for Ext4FileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            if !self.parent.is_init() {
+                self.parent.init_by(parent);
+            }
+        }
+        Ok(())
+    }
+
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        let vol = self.volume.lock();
+        let free = vol.sb.free_blocks_count();
+        Ok(FileSystemInfo {
+            fs_type: EXT4_SUPER_MAGIC as u64,
+            block_size: vol.block_size() as u64,
+            blocks: vol.sb.blocks_count(),
+            blocks_free: free,
+            blocks_avail: free.saturating_sub(vol.sb.reserved_blocks_count()),
+            files: vol.sb.inodes_count() as u64,
+            files_free: vol.sb.free_inodes_count() as u64,
+            name_len: NAME_MAX as u64,
+        })
+    }
+
+    fn sync(&self) -> VfsResult {
+        let mut vol = self.volume.lock();
+        if !self.read_only {
+            vol.sb
+                .set_write_time(axfs_vfs::current_time().as_secs() as u32);
+            vol.write_superblock()?;
+        }
+        vol.flush()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        Arc::new(DirNode {
+            fs: self.this(),
+            ino: ROOT_INO,
+        })
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.fs.get_attr(self.ino)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.fs.set_attr(self.ino, changes)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut vol = self.fs.volume.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if inode.file_type() != Some(VfsNodeType::File) {
+            return Err(VfsError::InvalidInput);
+        }
+        vol.read_data(&inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.fs.check_writable()?;
+        let mut vol = self.fs.volume.lock();
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.file_type() != Some(VfsNodeType::File) {
+            return Err(VfsError::InvalidInput);
+        }
+        let res = vol.write_data(self.ino, &mut inode, offset, buf);
+        // the blocks allocated before an error are kept
+        let now = axfs_vfs::current_time();
+        inode.set_times(None, Some(now), Some(now));
+        vol.write_inode(self.ino, &inode)?;
+        res
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.fs.check_writable()?;
+        let mut vol = self.fs.volume.lock();
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.file_type() != Some(VfsNodeType::File) {
+            return Err(VfsError::InvalidInput);
+        }
+        vol.truncate_data(&mut inode, size)?;
+        let now = axfs_vfs::current_time();
+        inode.set_times(None, Some(now), Some(now));
+        vol.write_inode(self.ino, &inode)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.fs.volume.lock().flush()
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut vol = self.fs.volume.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if inode.file_type() != Some(VfsNodeType::SymLink) {
+            return Err(VfsError::InvalidInput);
+        }
+        vol.read_link(&inode, buf)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        let mut vol = self.fs.volume.lock();
+        let mut open_files = self.fs.open_files.lock();
+        let count = open_files.get_mut(&self.ino).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        open_files.remove(&self.ino);
+        drop(open_files);
+        if self.fs.read_only {
+            return;
+        }
+        let res = vol.read_inode(self.ino).and_then(|mut inode| {
+            if inode.links_count() == 0 {
+                Ext4FileSystem::delete_inode(&mut vol, self.ino, &mut inode)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = res {
+            warn!("ext4fs: failed to delete inode {}: {:?}", self.ino, e);
+        }
+    }
+}
+
+impl DirNode {
+    fn lookup_dir(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        match name {
+            "" | "." => Ok(Arc::new(DirNode {
+                fs: self.fs.clone(),
+                ino: self.ino,
+            })),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.fs.lookup_child(self.ino, name),
+        }
+    }
+
+    /// Returns the directory of this filesystem holding the last component of
+    /// `path`, and the component.
+    fn split_parent<'a>(&self, path: &'a str) -> VfsResult<(u32, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (self.lookup_dir("")?.lookup(dir)?, name),
+            None => (self.lookup_dir("")?, path),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        match dir.as_any().downcast_ref::<DirNode>() {
+            Some(dir) if Arc::ptr_eq(&dir.fs, &self.fs) => Ok((dir.ino, name)),
+            _ if dir.get_attr()?.is_dir() => Err(VfsError::CrossesDevices),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.fs.get_attr(self.ino)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.fs.set_attr(self.ino, changes)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.fs.parent_of(self.ino)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            _ => self.lookup_dir(name)?,
+        };
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut vol = self.fs.volume.lock();
+        let dir = Ext4FileSystem::read_dir_inode(&mut vol, self.ino)?;
+        let entries = vol.dir_entries(&dir)?;
+        let mut count = 0;
+        for (entry, ent) in entries.iter().skip(start_idx).zip(dirents.iter_mut()) {
+            let ty = match dirent_type(entry.file_type) {
+                Some(ty) => ty,
+                None => vol
+                    .read_inode(entry.ino)?
+                    .file_type()
+                    .ok_or(VfsError::InvalidData)?,
+            };
+            *ent = VfsDirEntry::new(&String::from_utf8_lossy(&entry.name), ty);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext4fs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.create(rest, ty)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            self.fs.create(self.ino, name, ty)
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext4fs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.remove(rest)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::InvalidInput) // remove '.' or '..
+        } else {
+            self.fs.remove(self.ino, name)
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at ext4fs: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.split_parent(src_path)?;
+        let (dst_dir, dst_name) = self.split_parent(dst_path)?;
+        self.fs.rename(src_dir, src_name, dst_dir, dst_name)
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        debug!("symlink {} -> {} at ext4fs", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.symlink(rest, target)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.fs.symlink(self.ino, name, target)
+        }
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        debug!("link at ext4fs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.lookup_dir(name)?.link(rest, node)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.fs.link(self.ino, name, &node)
+        }
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
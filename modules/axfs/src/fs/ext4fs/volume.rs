@@ -0,0 +1,317 @@
+//! The block device of an ext2/3/4 filesystem, with its superblock, group
+//! descriptors, inode table and bitmaps.
+
+use alloc::{vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsResult};
+use driver_block::{cache::BlockCache, BlockDriverOps};
+
+use super::layout::*;
+use crate::dev::BlockDev;
+
+/// The block device of a filesystem, accessed through a block cache.
+///
+/// The superblock and the group descriptors are kept in memory, and written
+/// to the cache as soon as they are changed.
+pub struct Volume {
+    cache: BlockCache<BlockDev>,
+    pub sb: SuperBlock,
+    groups: Vec<GroupDesc>,
+    block_size: usize,
+    /// The number of device blocks in a filesystem block.
+    dev_blocks: u64,
+}
+
+fn dev_err<E>(_: E) -> VfsError {
+    VfsError::Io
+}
+
+impl Volume {
+    /// Reads the superblock and the group descriptors of the filesystem on
+    /// `dev`.
+    pub fn open(dev: BlockDev) -> VfsResult<Self> {
+        let dev_block_size = dev.block_size();
+        let mut vol = Self {
+            cache: BlockCache::new(dev),
+            sb: SuperBlock::new([0; SUPERBLOCK_SIZE]),
+            groups: Vec::new(),
+            block_size: dev_block_size,
+            dev_blocks: 1,
+        };
+        let mut raw = [0; SUPERBLOCK_SIZE];
+        vol.read_bytes(SUPERBLOCK_OFFSET, &mut raw)?;
+        let sb = SuperBlock::new(raw);
+        if sb.magic() != EXT4_SUPER_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let block_size = match sb.block_size() {
+            Some(size) if size.is_multiple_of(dev_block_size) => size,
+            _ => return Err(VfsError::Unsupported),
+        };
+        if sb.blocks_per_group() == 0
+            || sb.inodes_per_group() == 0
+            || sb.inode_size() < 128
+            || sb.desc_size() < 32
+            || sb.first_data_block() as u64 >= sb.blocks_count()
+            || sb.blocks_count() > vol.cache.num_blocks() / (block_size / dev_block_size) as u64
+        {
+            return Err(VfsError::InvalidData);
+        }
+        vol.sb = sb;
+        vol.block_size = block_size;
+        vol.dev_blocks = (block_size / dev_block_size) as u64;
+
+        let data_blocks = vol.sb.blocks_count() - vol.sb.first_data_block() as u64;
+        let count = data_blocks.div_ceil(vol.sb.blocks_per_group() as u64) as usize;
+        let desc_size = vol.sb.desc_size();
+        let table = (vol.sb.first_data_block() as u64 + 1) * block_size as u64;
+        let mut raw = vec![0; count * desc_size];
+        vol.read_bytes(table, &mut raw)?;
+        let is_64bit = vol.sb.is_64bit();
+        vol.groups = raw
+            .chunks_exact(desc_size)
+            .map(|desc| GroupDesc::new(desc, is_64bit))
+            .collect();
+        Ok(vol)
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Reads the filesystem block `block`, of `block_size` bytes.
+    pub fn read_block(&mut self, block: u64, buf: &mut [u8]) -> VfsResult {
+        self.check_block(block)?;
+        self.cache
+            .read_block(block * self.dev_blocks, buf)
+            .map_err(dev_err)
+    }
+
+    /// Writes the filesystem block `block`, of `block_size` bytes.
+    pub fn write_block(&mut self, block: u64, buf: &[u8]) -> VfsResult {
+        self.check_block(block)?;
+        self.cache
+            .write_block(block * self.dev_blocks, buf)
+            .map_err(dev_err)
+    }
+
+    fn check_block(&self, block: u64) -> VfsResult {
+        if block >= self.sb.blocks_count() {
+            return Err(VfsError::InvalidData);
+        }
+        Ok(())
+    }
+
+    /// Reads `buf.len()` bytes at the byte `offset` of the device.
+    pub fn read_bytes(&mut self, offset: u64, buf: &mut [u8]) -> VfsResult {
+        let dev_block_size = self.cache.block_size();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let block = (offset + pos as u64) / dev_block_size as u64;
+            let start = ((offset + pos as u64) % dev_block_size as u64) as usize;
+            let count = (buf.len() - pos).min(dev_block_size - start);
+            self.cache
+                .read_at(block, start, &mut buf[pos..pos + count])
+                .map_err(dev_err)?;
+            pos += count;
+        }
+        Ok(())
+    }
+
+    /// Writes `buf` at the byte `offset` of the device.
+    pub fn write_bytes(&mut self, offset: u64, buf: &[u8]) -> VfsResult {
+        let dev_block_size = self.cache.block_size();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let block = (offset + pos as u64) / dev_block_size as u64;
+            let start = ((offset + pos as u64) % dev_block_size as u64) as usize;
+            let count = (buf.len() - pos).min(dev_block_size - start);
+            self.cache
+                .write_at(block, start, &buf[pos..pos + count])
+                .map_err(dev_err)?;
+            pos += count;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty cached blocks to the device and flushes it.
+    pub fn flush(&mut self) -> VfsResult {
+        self.cache.flush().map_err(dev_err)
+    }
+
+    /// Returns the byte offset of the inode `ino` in the inode table.
+    fn inode_offset(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.sb.inodes_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let ipg = self.sb.inodes_per_group();
+        let group = self
+            .groups
+            .get(((ino - 1) / ipg) as usize)
+            .ok_or(VfsError::InvalidData)?;
+        let index = ((ino - 1) % ipg) as u64;
+        Ok(group.inode_table() * self.block_size as u64 + index * self.sb.inode_size() as u64)
+    }
+
+    pub fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let offset = self.inode_offset(ino)?;
+        let mut raw = [0; 256];
+        let size = self.sb.inode_size().min(raw.len());
+        self.read_bytes(offset, &mut raw[..size])?;
+        Ok(Inode::new(&raw[..size]))
+    }
+
+    pub fn write_inode(&mut self, ino: u32, inode: &Inode) -> VfsResult {
+        let offset = self.inode_offset(ino)?;
+        self.write_bytes(offset, inode.as_bytes())
+    }
+
+    /// Returns the block group of the inode `ino`.
+    pub fn inode_group(&self, ino: u32) -> usize {
+        ((ino - 1) / self.sb.inodes_per_group()) as usize
+    }
+
+    /// Writes the superblock and the descriptor of `group` to the cache.
+    fn write_meta(&mut self, group: usize) -> VfsResult {
+        let desc_size = self.sb.desc_size();
+        let table = (self.sb.first_data_block() as u64 + 1) * self.block_size as u64;
+        let desc = self.groups[group].clone();
+        self.write_bytes(table + (group * desc_size) as u64, desc.as_bytes(desc_size))?;
+        let sb = self.sb.clone();
+        self.write_bytes(SUPERBLOCK_OFFSET, sb.as_bytes())
+    }
+
+    /// Updates the times of the superblock, at mount or sync.
+    pub fn write_superblock(&mut self) -> VfsResult {
+        let sb = self.sb.clone();
+        self.write_bytes(SUPERBLOCK_OFFSET, sb.as_bytes())
+    }
+
+    /// Returns the number of blocks in `group`, the last one may be shorter.
+    fn blocks_in_group(&self, group: usize) -> u32 {
+        let bpg = self.sb.blocks_per_group() as u64;
+        let start = self.sb.first_data_block() as u64 + group as u64 * bpg;
+        (self.sb.blocks_count() - start).min(bpg) as u32
+    }
+
+    /// Finds a zero bit among the first `count` ones of the bitmap block
+    /// `bitmap`, sets it and returns its index.
+    fn take_bit(&mut self, bitmap: u64, count: u32) -> VfsResult<Option<u32>> {
+        let mut buf = vec![0; self.block_size];
+        self.read_block(bitmap, &mut buf)?;
+        let Some(index) = (0..count).find(|&i| buf[i as usize / 8] & (1 << (i % 8)) == 0) else {
+            return Ok(None);
+        };
+        buf[index as usize / 8] |= 1 << (index % 8);
+        self.write_block(bitmap, &buf)?;
+        Ok(Some(index))
+    }
+
+    /// Clears the bit `index` of the bitmap block `bitmap`, returns whether it
+    /// was set.
+    fn clear_bit(&mut self, bitmap: u64, index: u32) -> VfsResult<bool> {
+        let mut buf = vec![0; self.block_size];
+        self.read_block(bitmap, &mut buf)?;
+        let (byte, mask) = (index as usize / 8, 1 << (index % 8));
+        if buf[byte] & mask == 0 {
+            return Ok(false);
+        }
+        buf[byte] &= !mask;
+        self.write_block(bitmap, &buf)?;
+        Ok(true)
+    }
+
+    /// Allocates a block, preferably in `goal_group`, and fills it with
+    /// zeros.
+    pub fn alloc_block(&mut self, goal_group: usize) -> VfsResult<u64> {
+        let count = self.groups.len();
+        for group in (0..count).map(|i| (goal_group + i) % count) {
+            if self.groups[group].free_blocks_count() == 0 {
+                continue;
+            }
+            let bitmap = self.groups[group].block_bitmap();
+            let Some(index) = self.take_bit(bitmap, self.blocks_in_group(group))? else {
+                continue;
+            };
+            let desc = &mut self.groups[group];
+            desc.set_free_blocks_count(desc.free_blocks_count() - 1);
+            let free = self.sb.free_blocks_count();
+            self.sb.set_free_blocks_count(free.saturating_sub(1));
+            self.write_meta(group)?;
+
+            let block = self.sb.first_data_block() as u64
+                + group as u64 * self.sb.blocks_per_group() as u64
+                + index as u64;
+            self.write_block(block, &vec![0; self.block_size])?;
+            return Ok(block);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_block(&mut self, block: u64) -> VfsResult {
+        let first = self.sb.first_data_block() as u64;
+        if block < first || block >= self.sb.blocks_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let bpg = self.sb.blocks_per_group() as u64;
+        let group = ((block - first) / bpg) as usize;
+        let bitmap = self.groups[group].block_bitmap();
+        if !self.clear_bit(bitmap, ((block - first) % bpg) as u32)? {
+            warn!("ext4fs: freeing free block {}", block);
+            return Ok(());
+        }
+        let desc = &mut self.groups[group];
+        desc.set_free_blocks_count(desc.free_blocks_count() + 1);
+        let free = self.sb.free_blocks_count();
+        self.sb.set_free_blocks_count(free + 1);
+        self.write_meta(group)
+    }
+
+    /// Allocates an inode, preferably in `goal_group`.
+    pub fn alloc_inode(&mut self, goal_group: usize, is_dir: bool) -> VfsResult<u32> {
+        let count = self.groups.len();
+        let ipg = self.sb.inodes_per_group();
+        for group in (0..count).map(|i| (goal_group + i) % count) {
+            if self.groups[group].free_inodes_count() == 0 {
+                continue;
+            }
+            let bitmap = self.groups[group].inode_bitmap();
+            let Some(index) = self.take_bit(bitmap, ipg)? else {
+                continue;
+            };
+            let ino = group as u32 * ipg + index + 1;
+            if ino < self.sb.first_ino() {
+                // the reserved inodes should have been marked in use
+                warn!("ext4fs: reserved inode {} is free", ino);
+                continue;
+            }
+            let desc = &mut self.groups[group];
+            desc.set_free_inodes_count(desc.free_inodes_count() - 1);
+            if is_dir {
+                desc.set_used_dirs_count(desc.used_dirs_count() + 1);
+            }
+            let free = self.sb.free_inodes_count();
+            self.sb.set_free_inodes_count(free.saturating_sub(1));
+            self.write_meta(group)?;
+            return Ok(ino);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let ipg = self.sb.inodes_per_group();
+        let group = self.inode_group(ino);
+        let bitmap = self.groups[group].inode_bitmap();
+        if !self.clear_bit(bitmap, (ino - 1) % ipg)? {
+            warn!("ext4fs: freeing free inode {}", ino);
+            return Ok(());
+        }
+        let desc = &mut self.groups[group];
+        desc.set_free_inodes_count(desc.free_inodes_count() + 1);
+        if is_dir {
+            desc.set_used_dirs_count(desc.used_dirs_count().saturating_sub(1));
+        }
+        let free = self.sb.free_inodes_count();
+        self.sb.set_free_inodes_count(free + 1);
+        self.write_meta(group)
+    }
+}
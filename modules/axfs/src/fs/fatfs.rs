@@ -1,22 +1,48 @@
 use alloc::sync::Arc;
 use core::cell::UnsafeCell;
+use core::time::Duration;
 
-use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
+use axfs_vfs::{FileSystemInfo, VfsDirEntry, VfsError, VfsNodePerm, VfsResult, VfsSetAttr};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, DirEntry, File, FileAttributes, LossyOemCpConverter, Time};
+use fatfs::{Read, Seek, SeekFrom, TimeProvider, Write};
 
-use crate::dev::Disk;
+use crate::dev::{Disk, SharedCache};
 
 const BLOCK_SIZE: usize = 512;
 
+/// The filesystem type magic number of FAT, the same as Linux's
+/// `MSDOS_SUPER_MAGIC`.
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+
+/// Seconds from the UNIX epoch to 1980-01-01, the earliest FAT date.
+const FAT_EPOCH_SECS: u64 = 315_532_800;
+/// Seconds from the UNIX epoch to 2107-12-31 23:59:59, the latest FAT date.
+const FAT_MAX_SECS: u64 = 4_354_819_199;
+
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<Disk, AxTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    /// The block cache of the disk owned by `inner`, flushed by `sync`.
+    cache: SharedCache,
+}
+
+pub struct FileWrapper<'a> {
+    file: Mutex<File<'a, Disk, AxTimeProvider, LossyOemCpConverter>>,
+    attr: Mutex<VfsNodeAttr>,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+pub struct DirWrapper<'a> {
+    dir: Dir<'a, Disk, AxTimeProvider, LossyOemCpConverter>,
+    attr: VfsNodeAttr,
+}
+
+/// Stamps new and modified FAT entries with [`axfs_vfs::current_time()`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxTimeProvider;
+
+type FatDirEntry<'a> = DirEntry<'a, Disk, AxTimeProvider, LossyOemCpConverter>;
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -28,37 +54,113 @@ unsafe impl<'a> Sync for DirWrapper<'a> {}
 impl FatFileSystem {
     #[cfg(feature = "use-ramdisk")]
     pub fn new(mut disk: Disk) -> Self {
+        let cache = disk.cache();
         let opts = fatfs::FormatVolumeOptions::new();
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
+        let opts = fatfs::FsOptions::new().time_provider(AxTimeProvider);
+        let inner =
+            fatfs::FileSystem::new(disk, opts).expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
+            cache,
         }
     }
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
+        let cache = disk.cache();
+        let opts = fatfs::FsOptions::new().time_provider(AxTimeProvider);
+        let inner =
+            fatfs::FileSystem::new(disk, opts).expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
+            cache,
         }
     }
 
+    /// Opens the existing FAT filesystem on the disk, used by runtime mounts.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let cache = disk.cache();
+        let opts = fatfs::FsOptions::new().time_provider(AxTimeProvider);
+        let inner = fatfs::FileSystem::new(disk, opts).map_err(as_vfs_err)?;
+        Ok(Self {
+            inner,
+            root_dir: UnsafeCell::new(None),
+            cache,
+        })
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+        let root_attr = VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o755),
+            VfsNodeType::Dir,
+            BLOCK_SIZE as u64,
+            1,
+        );
+        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir(), root_attr)) }
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+    fn new_file(
+        file: File<'_, Disk, AxTimeProvider, LossyOemCpConverter>,
+        attr: VfsNodeAttr,
+    ) -> Arc<FileWrapper> {
+        Arc::new(FileWrapper {
+            file: Mutex::new(file),
+            attr: Mutex::new(attr),
+        })
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn new_dir(
+        dir: Dir<'_, Disk, AxTimeProvider, LossyOemCpConverter>,
+        attr: VfsNodeAttr,
+    ) -> Arc<DirWrapper> {
+        Arc::new(DirWrapper { dir, attr })
+    }
+
+    /// Wraps the file or directory of the entry, with the attributes recorded
+    /// in the entry.
+    fn new_node(entry: FatDirEntry<'static>) -> VfsNodeRef {
+        // FAT fs doesn't support permissions, we just set everything to 755,
+        // or 555 if the entry is read-only. It has no inode numbers either.
+        let perm = if entry.attributes().contains(FileAttributes::READ_ONLY) {
+            VfsNodePerm::from_bits_truncate(0o555)
+        } else {
+            VfsNodePerm::from_bits_truncate(0o755)
+        };
+        let modified = from_fat_date_time(entry.modified());
+        let accessed = from_fat_date_time(DateTime::new(entry.accessed(), Time::new(0, 0, 0, 0)));
+        if entry.is_dir() {
+            let mut attr = VfsNodeAttr::new(perm, VfsNodeType::Dir, BLOCK_SIZE as u64, 1);
+            attr.set_times(accessed, modified, modified);
+            Self::new_dir(entry.to_dir(), attr)
+        } else {
+            let mut attr = VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0);
+            attr.set_times(accessed, modified, modified);
+            Self::new_file(entry.to_file(), attr)
+        }
+    }
+}
+
+impl TimeProvider for AxTimeProvider {
+    fn get_current_date(&self) -> Date {
+        to_fat_date_time(axfs_vfs::current_time()).date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        to_fat_date_time(axfs_vfs::current_time())
+    }
+}
+
+impl FileWrapper<'_> {
+    /// Records a modification of the content, as the FAT entry does.
+    fn touch_modified(&self) {
+        let now = fat_current_time();
+        let mut attr = self.attr.lock();
+        let atime = attr.atime();
+        attr.set_times(atime, now, now);
     }
 }
 
@@ -66,29 +168,79 @@ impl VfsNodeOps for FileWrapper<'static> {
     axfs_vfs::impl_vfs_non_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        let size = self.0.lock().seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
+        let size = self
+            .file
+            .lock()
+            .seek(SeekFrom::End(0))
+            .map_err(as_vfs_err)?;
         let blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
-        // FAT fs doesn't support permissions, we just set everything to 755
-        let perm = VfsNodePerm::from_bits_truncate(0o755);
-        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks))
+        let mut attr = *self.attr.lock();
+        attr.set_size(size, blocks);
+        Ok(attr)
+    }
+
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        // FAT fs has neither permissions nor owners
+        if changes.mode.is_some() || changes.uid.is_some() || changes.gid.is_some() {
+            return Err(VfsError::Unsupported);
+        }
+        let mut file = self.file.lock();
+        let mut attr = self.attr.lock();
+        let (mut atime, mut mtime) = (attr.atime(), attr.mtime());
+        if let Some(time) = changes.atime {
+            // only the date of the last access is recorded
+            let date = to_fat_date_time(time).date;
+            file.set_accessed(date);
+            atime = from_fat_date_time(DateTime::new(date, Time::new(0, 0, 0, 0)));
+        }
+        if let Some(time) = changes.mtime {
+            let date_time = to_fat_date_time(time);
+            file.set_modified(date_time);
+            mtime = from_fat_date_time(date_time);
+        }
+        attr.set_times(atime, mtime, mtime);
+        Ok(())
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        let mut file = self.0.lock();
+        let mut file = self.file.lock();
         file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
         file.read(buf).map_err(as_vfs_err)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let mut file = self.0.lock();
+        let mut file = self.file.lock();
         file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.write(buf).map_err(as_vfs_err)
+        let n = file.write(buf).map_err(as_vfs_err)?;
+        self.touch_modified();
+        Ok(n)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        // writes the dir entry, then flushes the whole disk
+        self.file.lock().flush().map_err(as_vfs_err)
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
-        let mut file = self.0.lock();
+        let mut file = self.file.lock();
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.truncate().map_err(as_vfs_err)
+        file.truncate().map_err(as_vfs_err)?;
+        self.touch_modified();
+        Ok(())
+    }
+}
+
+impl DirWrapper<'static> {
+    /// Finds the entry of the node at `path` in this directory.
+    fn find_entry(&self, path: &str) -> Option<FatDirEntry<'static>> {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (self.dir.open_dir(parent).ok()?, name),
+            None => (self.dir.clone(), path),
+        };
+        dir.iter().filter_map(Result::ok).find(|entry| {
+            entry.file_name().eq_ignore_ascii_case(name)
+                || entry.short_file_name().eq_ignore_ascii_case(name)
+        })
     }
 }
 
@@ -96,19 +248,11 @@ impl VfsNodeOps for DirWrapper<'static> {
     axfs_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        // FAT fs doesn't support permissions, we just set everything to 755
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::from_bits_truncate(0o755),
-            VfsNodeType::Dir,
-            BLOCK_SIZE as u64,
-            1,
-        ))
+        Ok(self.attr)
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
-        self.0
-            .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+        self.find_entry("..").map(FatFileSystem::new_node)
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -122,13 +266,9 @@ impl VfsNodeOps for DirWrapper<'static> {
         }
 
         // TODO: use `fatfs::Dir::find_entry`, but it's not public.
-        if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
-        } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
-        } else {
-            Err(VfsError::NotFound)
-        }
+        self.find_entry(path)
+            .map(FatFileSystem::new_node)
+            .ok_or(VfsError::NotFound)
     }
 
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
@@ -143,11 +283,11 @@ impl VfsNodeOps for DirWrapper<'static> {
 
         match ty {
             VfsNodeType::File => {
-                self.0.create_file(path).map_err(as_vfs_err)?;
+                self.dir.create_file(path).map_err(as_vfs_err)?;
                 Ok(())
             }
             VfsNodeType::Dir => {
-                self.0.create_dir(path).map_err(as_vfs_err)?;
+                self.dir.create_dir(path).map_err(as_vfs_err)?;
                 Ok(())
             }
             _ => Err(VfsError::Unsupported),
@@ -161,11 +301,11 @@ impl VfsNodeOps for DirWrapper<'static> {
         if let Some(rest) = path.strip_prefix("./") {
             return self.remove(rest);
         }
-        self.0.remove(path).map_err(as_vfs_err)
+        self.dir.remove(path).map_err(as_vfs_err)
     }
 
     fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
-        let mut iter = self.0.iter().skip(start_idx);
+        let mut iter = self.dir.iter().skip(start_idx);
         for (i, out_entry) in dirents.iter_mut().enumerate() {
             let x = iter.next();
             match x {
@@ -192,13 +332,31 @@ impl VfsNodeOps for DirWrapper<'static> {
             src_path, dst_path
         );
 
-        self.0
-            .rename(src_path, &self.0, dst_path)
+        self.dir
+            .rename(src_path, &self.dir, dst_path)
             .map_err(as_vfs_err)
     }
 }
 
 impl VfsOps for FatFileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        let stats = self.inner.stats().map_err(as_vfs_err)?;
+        Ok(FileSystemInfo {
+            fs_type: MSDOS_SUPER_MAGIC,
+            block_size: stats.cluster_size() as u64,
+            blocks: stats.total_clusters() as u64,
+            blocks_free: stats.free_clusters() as u64,
+            blocks_avail: stats.free_clusters() as u64,
+            // long file names
+            name_len: 255,
+            ..Default::default()
+        })
+    }
+
+    fn sync(&self) -> VfsResult {
+        self.cache.lock().flush().map_err(|_| VfsError::Io)
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
         let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
         root_dir.clone()
@@ -243,7 +401,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -264,6 +422,71 @@ impl Seek for Disk {
     }
 }
 
+/// Returns the current time, truncated to what a FAT entry can record.
+fn fat_current_time() -> Duration {
+    from_fat_date_time(to_fat_date_time(axfs_vfs::current_time()))
+}
+
+/// Converts a FAT date and time to the duration since the UNIX epoch.
+fn from_fat_date_time(date_time: DateTime) -> Duration {
+    let (date, time) = (date_time.date, date_time.time);
+    let days = days_from_civil(date.year as u64, date.month as u64, date.day as u64);
+    let secs = days * 86400 + time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64;
+    Duration::from_secs(secs) + Duration::from_millis(time.millis as u64)
+}
+
+/// Converts the duration since the UNIX epoch to a FAT date and time, clamped
+/// to the range FAT can represent.
+fn to_fat_date_time(time: Duration) -> DateTime {
+    let secs = time.as_secs().clamp(FAT_EPOCH_SECS, FAT_MAX_SECS);
+    let millis = if secs == time.as_secs() {
+        time.subsec_millis() as u16
+    } else {
+        0
+    };
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days);
+    DateTime::new(
+        Date::new(year as u16, month as u16, day as u16),
+        Time::new(
+            (secs / 3600) as u16,
+            (secs / 60 % 60) as u16,
+            (secs % 60) as u16,
+            millis,
+        ),
+    )
+}
+
+/// Returns the number of days since 1970-01-01 of the given date (not
+/// earlier than 1970-03-01).
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+const fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Returns the date `(year, month, day)` of the given number of days since
+/// 1970-01-01.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+const fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
     use fatfs::Error::*;
     match err {
@@ -1,15 +1,23 @@
-cfg_if::cfg_if! {
-    if #[cfg(feature = "myfs")] {
-        pub mod myfs;
-    } else if #[cfg(feature = "fatfs")] {
-        pub mod fatfs;
-    } else if #[cfg(feature = "diskfs")] {
-        pub use axdiskfs;
-    }
-}
+#[cfg(feature = "myfs")]
+pub mod myfs;
+
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+pub mod fatfs;
+
+#[cfg(feature = "diskfs")]
+pub use axdiskfs;
+
+#[cfg(feature = "ext4fs")]
+pub mod ext4fs;
 
 #[cfg(feature = "devfs")]
 pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+#[cfg(feature = "overlayfs")]
+pub use axfs_overlayfs as overlayfs;
+
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+pub use axfs_pseudofs as pseudofs;
@@ -0,0 +1,436 @@
+//! The initial RAM disk: a newc cpio or ustar archive unpacked into a ramfs
+//! which is mounted on `/`, so no block device is needed to boot.
+//!
+//! The archive is embedded in the kernel at build time from the file given by
+//! the `AX_INITRD` environment variable (or the `INITRD` variable of `make`),
+//! or set by [`set_initrd`] if it is loaded by the bootloader.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::time::Duration;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axfs_vfs::{VfsNodePerm, VfsNodeRef, VfsNodeType, VfsSetAttr};
+use lazy_init::LazyInit;
+
+/// The archive embedded at build time, empty if `AX_INITRD` is not set.
+static EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initrd"));
+
+static INITRD: LazyInit<&'static [u8]> = LazyInit::new();
+
+/// Sets the archive of the initial RAM disk loaded by the bootloader, which
+/// overrides the embedded one.
+///
+/// It must be called before [`init_filesystems`](crate::init_filesystems).
+pub fn set_initrd(data: &'static [u8]) {
+    INITRD.init_by(data);
+}
+
+/// Returns the archive of the initial RAM disk.
+pub(crate) fn archive() -> &'static [u8] {
+    INITRD.try_get().copied().unwrap_or(EMBEDDED)
+}
+
+/// Unpacks the newc cpio or ustar archive `data` into the directory `root`,
+/// keeping the modes, owners and modification times of the entries.
+///
+/// Directories, regular files, symbolic links and hard links are created,
+/// while device nodes, FIFOs and sockets are skipped. An empty archive leaves
+/// `root` unchanged.
+pub fn unpack(data: &[u8], root: &VfsNodeRef) -> AxResult {
+    let mut unpacker = Unpacker {
+        root: root.clone(),
+        links: BTreeMap::new(),
+    };
+    if data.iter().all(|&b| b == 0) {
+        Ok(())
+    } else if data.starts_with(CPIO_MAGIC) || data.starts_with(CPIO_CRC_MAGIC) {
+        unpack_cpio(data, &mut unpacker)
+    } else if data.len() >= TAR_BLOCK && &data[257..262] == b"ustar" {
+        unpack_tar(data, &mut unpacker)
+    } else {
+        ax_err!(InvalidData, "initrd: unknown archive format")
+    }
+}
+
+/// The kind of an archive entry.
+enum EntryKind {
+    File,
+    Dir,
+    /// A symbolic link to the target.
+    SymLink(String),
+    /// A hard link to the earlier entry of the path.
+    HardLink(String),
+    /// A device node, FIFO or socket.
+    Other,
+}
+
+/// An entry of an archive.
+struct Entry<'a> {
+    path: String,
+    kind: EntryKind,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    data: &'a [u8],
+    /// The device and inode numbers of a cpio entry with more than one link,
+    /// which are shared by the entries of the hard links.
+    link_id: Option<(u64, u64)>,
+}
+
+/// Creates the entries of an archive in a directory tree.
+struct Unpacker {
+    root: VfsNodeRef,
+    /// The first path of each cpio inode with hard links.
+    links: BTreeMap<(u64, u64), String>,
+}
+
+impl Unpacker {
+    fn add(&mut self, entry: Entry) -> AxResult {
+        let path = normalize(&entry.path);
+        if path.is_empty() {
+            // the root itself
+            return self.set_attr(&self.root, &entry);
+        }
+        self.create_parents(&path)?;
+        let node = match &entry.kind {
+            EntryKind::Dir => self.create(&path, VfsNodeType::Dir)?,
+            EntryKind::File => {
+                let first = entry.link_id.and_then(|id| self.links.get(&id).cloned());
+                let node = match &first {
+                    Some(first) => self.link(&path, first)?,
+                    None => self.create(&path, VfsNodeType::File)?,
+                };
+                if let Some(id) = entry.link_id {
+                    self.links.entry(id).or_insert_with(|| path.clone());
+                }
+                // the data of cpio hard links is in one of the entries only
+                if first.is_none() || !entry.data.is_empty() {
+                    node.truncate(0)?;
+                    write_all(&node, entry.data)?;
+                }
+                node
+            }
+            EntryKind::SymLink(target) => {
+                self.remove_existing(&path)?;
+                self.root.symlink(&path, target)?;
+                self.root.clone().lookup(&path)?
+            }
+            EntryKind::HardLink(target) => {
+                self.link(&path, &normalize(target))?;
+                return Ok(());
+            }
+            EntryKind::Other => {
+                warn!("initrd: skip special file {}", path);
+                return Ok(());
+            }
+        };
+        self.set_attr(&node, &entry)
+    }
+
+    /// Returns the node at `path`, creating it if it does not exist.
+    fn create(&self, path: &str, ty: VfsNodeType) -> AxResult<VfsNodeRef> {
+        if let Ok(node) = self.root.clone().lookup(path) {
+            if node.get_attr()?.file_type() == ty {
+                return Ok(node);
+            }
+            self.root.remove(path)?;
+        }
+        self.root.create(path, ty)?;
+        self.root.clone().lookup(path)
+    }
+
+    /// Creates the missing ancestors of `path`, which may be absent from
+    /// archives listing files only.
+    fn create_parents(&self, path: &str) -> AxResult {
+        for (i, _) in path.match_indices('/') {
+            let parent = &path[..i];
+            if self.root.clone().lookup(parent).is_err() {
+                self.root.create(parent, VfsNodeType::Dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Links `path` to the node at `target`.
+    fn link(&self, path: &str, target: &str) -> AxResult<VfsNodeRef> {
+        let node = self.root.clone().lookup(target)?;
+        self.remove_existing(path)?;
+        self.root.link(path, node.clone())?;
+        Ok(node)
+    }
+
+    fn remove_existing(&self, path: &str) -> AxResult {
+        match self.root.clone().lookup(path) {
+            Ok(_) => self.root.remove(path),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn set_attr(&self, node: &VfsNodeRef, entry: &Entry) -> AxResult {
+        let mtime = Duration::from_secs(entry.mtime);
+        node.set_attr(&VfsSetAttr {
+            mode: Some(VfsNodePerm::from_bits_truncate(entry.mode as u16)),
+            uid: Some(entry.uid),
+            gid: Some(entry.gid),
+            atime: Some(mtime),
+            mtime: Some(mtime),
+        })?;
+        Ok(())
+    }
+}
+
+/// Removes the leading `/` and `./`, the trailing `/` and the `.` components
+/// of a path in an archive.
+fn normalize(path: &str) -> String {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn write_all(node: &VfsNodeRef, data: &[u8]) -> AxResult {
+    let mut offset = 0;
+    while offset < data.len() {
+        match node.write_at(offset as u64, &data[offset..])? {
+            0 => return ax_err!(StorageFull),
+            n => offset += n,
+        }
+    }
+    Ok(())
+}
+
+fn kind_of(mode: u32, data: &[u8]) -> AxResult<EntryKind> {
+    Ok(match mode >> 12 {
+        0o10 => EntryKind::File,
+        0o04 => EntryKind::Dir,
+        0o12 => EntryKind::SymLink(utf8(data)?),
+        _ => EntryKind::Other,
+    })
+}
+
+fn utf8(bytes: &[u8]) -> AxResult<String> {
+    let bytes = match bytes.iter().position(|&b| b == 0) {
+        Some(end) => &bytes[..end],
+        None => bytes,
+    };
+    core::str::from_utf8(bytes)
+        .map(String::from)
+        .map_err(|_| AxError::InvalidData)
+}
+
+const CPIO_MAGIC: &[u8] = b"070701";
+const CPIO_CRC_MAGIC: &[u8] = b"070702";
+const CPIO_HEADER: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+/// Unpacks the newc cpio archives concatenated in `data`, as the Linux
+/// initramfs does.
+fn unpack_cpio(data: &[u8], unpacker: &mut Unpacker) -> AxResult {
+    let mut offset = 0;
+    loop {
+        // archives may be padded by zeros between them and at the end
+        while offset < data.len() && data[offset] == 0 {
+            offset += 1;
+        }
+        if offset >= data.len() {
+            return Ok(());
+        }
+        let header = data
+            .get(offset..offset + CPIO_HEADER)
+            .ok_or(AxError::InvalidData)?;
+        if !header.starts_with(CPIO_MAGIC) && !header.starts_with(CPIO_CRC_MAGIC) {
+            return ax_err!(InvalidData, "initrd: bad cpio magic");
+        }
+        let field = |i: usize| parse_hex(&header[6 + i * 8..14 + i * 8]);
+        let (ino, mode, uid, gid, nlink, mtime) = (
+            field(0)?,
+            field(1)?,
+            field(2)?,
+            field(3)?,
+            field(4)?,
+            field(5)?,
+        );
+        let size = field(6)? as usize;
+        let dev = field(7)? << 32 | field(8)?;
+        let name_size = field(11)? as usize;
+
+        let name_start = offset + CPIO_HEADER;
+        let name = data
+            .get(name_start..name_start + name_size)
+            .ok_or(AxError::InvalidData)?;
+        let name = utf8(name)?;
+        let data_start = align_up(name_start + name_size, 4);
+        let file = data
+            .get(data_start..data_start + size)
+            .ok_or(AxError::InvalidData)?;
+        offset = align_up(data_start + size, 4);
+
+        if name == CPIO_TRAILER {
+            // hard links do not span archives
+            unpacker.links.clear();
+            continue;
+        }
+        let kind = kind_of(mode as u32, file)?;
+        let is_file = matches!(kind, EntryKind::File);
+        unpacker.add(Entry {
+            path: name,
+            kind,
+            mode: mode as u32 & 0o7777,
+            uid: uid as u32,
+            gid: gid as u32,
+            mtime,
+            data: if is_file { file } else { &[] },
+            link_id: (is_file && nlink > 1).then_some((dev, ino)),
+        })?;
+    }
+}
+
+fn parse_hex(field: &[u8]) -> AxResult<u64> {
+    let s = core::str::from_utf8(field).map_err(|_| AxError::InvalidData)?;
+    u64::from_str_radix(s, 16).map_err(|_| AxError::InvalidData)
+}
+
+const fn align_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+
+const TAR_BLOCK: usize = 512;
+
+/// Unpacks the ustar archive `data`, with the long names of GNU tar and the
+/// paths of pax extended headers.
+fn unpack_tar(data: &[u8], unpacker: &mut Unpacker) -> AxResult {
+    let mut offset = 0;
+    let mut long_name = None;
+    let mut long_link = None;
+    while let Some(header) = data.get(offset..offset + TAR_BLOCK) {
+        if header.iter().all(|&b| b == 0) {
+            // the end of the archive
+            return Ok(());
+        }
+        if !tar_checksum_ok(header) {
+            return ax_err!(InvalidData, "initrd: bad tar checksum");
+        }
+        let size = parse_octal(&header[124..136])? as usize;
+        let body_start = offset + TAR_BLOCK;
+        let body = data
+            .get(body_start..body_start + size)
+            .ok_or(AxError::InvalidData)?;
+        offset = body_start + align_up(size, TAR_BLOCK);
+
+        let type_flag = header[156];
+        match type_flag {
+            // GNU long name and long link target of the next entry
+            b'L' => {
+                long_name = Some(utf8(body)?);
+                continue;
+            }
+            b'K' => {
+                long_link = Some(utf8(body)?);
+                continue;
+            }
+            // pax extended header of the next entry
+            b'x' => {
+                for (key, value) in pax_records(body)? {
+                    match key {
+                        "path" => long_name = Some(value.into()),
+                        "linkpath" => long_link = Some(value.into()),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            // pax global header
+            b'g' => continue,
+            _ => {}
+        }
+
+        let path = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let name = utf8(&header[0..100])?;
+                let prefix = utf8(&header[345..500])?;
+                if prefix.is_empty() {
+                    name
+                } else {
+                    prefix + "/" + &name
+                }
+            }
+        };
+        let link = match long_link.take() {
+            Some(link) => link,
+            None => utf8(&header[157..257])?,
+        };
+        let kind = match type_flag {
+            b'0' | 0 | b'7' => EntryKind::File,
+            b'1' => EntryKind::HardLink(link),
+            b'2' => EntryKind::SymLink(link),
+            b'5' => EntryKind::Dir,
+            _ => EntryKind::Other,
+        };
+        let is_file = matches!(kind, EntryKind::File);
+        unpacker.add(Entry {
+            path,
+            kind,
+            mode: parse_octal(&header[100..108])? as u32 & 0o7777,
+            uid: parse_octal(&header[108..116])? as u32,
+            gid: parse_octal(&header[116..124])? as u32,
+            mtime: parse_octal(&header[136..148])?,
+            data: if is_file { body } else { &[] },
+            link_id: None,
+        })?;
+    }
+    // archives truncated after the last entry are accepted
+    Ok(())
+}
+
+/// Checks the sum of the header bytes, with the checksum field as spaces.
+fn tar_checksum_ok(header: &[u8]) -> bool {
+    let Ok(checksum) = parse_octal(&header[148..156]) else {
+        return false;
+    };
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum();
+    sum == checksum
+}
+
+/// Parses a numeric field of a tar header, which is octal terminated by a
+/// space or NUL, or base-256 if the highest bit is set (GNU).
+fn parse_octal(field: &[u8]) -> AxResult<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7f) as u64, |n, &b| n << 8 | b as u64));
+    }
+    let s = core::str::from_utf8(field).map_err(|_| AxError::InvalidData)?;
+    let s = s.trim_matches(|c| c == ' ' || c == '\0');
+    if s.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(s, 8).map_err(|_| AxError::InvalidData)
+}
+
+/// Parses the records `<length> <key>=<value>\n` of a pax extended header.
+fn pax_records(mut body: &[u8]) -> AxResult<Vec<(&str, &str)>> {
+    let mut records = Vec::new();
+    while !body.is_empty() && body[0] != 0 {
+        let space = body
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or(AxError::InvalidData)?;
+        let len: usize = core::str::from_utf8(&body[..space])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&len| len > space + 1 && len <= body.len())
+            .ok_or(AxError::InvalidData)?;
+        let record =
+            core::str::from_utf8(&body[space + 1..len - 1]).map_err(|_| AxError::InvalidData)?;
+        let (key, value) = record.split_once('=').ok_or(AxError::InvalidData)?;
+        records.push((key, value));
+        body = &body[len..];
+    }
+    Ok(records)
+}
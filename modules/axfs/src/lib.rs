@@ -4,20 +4,82 @@
 //!
 //! # Cargo Features
 //!
-//! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
-//!    is **enabled** by default.
-//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
-//!    **enabled** by default.
+//! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. The other
+//!    block devices can be mounted as FAT at runtime by [`api::mount`]. This
+//!    feature is **enabled** by default.
+//! - `ext4fs`: Support [ext2/3/4] filesystems, as `/` or mounted at runtime.
+//!    Those with only ext2 features are read-write, and those with journals or
+//!    ext4 features (extents, 64-bit block numbers, etc.) are read-only.
+//! - `initrd`: Use a ramfs populated from a newc cpio or ustar archive as `/`,
+//!    which is embedded at build time from the file of `AX_INITRD` (or the
+//!    `INITRD` variable of `make`) or loaded by the bootloader and passed to
+//!    [`initrd::set_initrd`]. It requires no block device.
+//! - `overlayfs`: Mount an [`axfs_overlayfs::OverlayFileSystem`] on `/`,
+//!    whose read-only lower layer is the selected root filesystem and whose
+//!    upper layer is a ramfs holding all changes, so that the base image is
+//!    never modified.
+//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`, which provides
+//!    `null`, `zero`, `full`, `random`, `urandom`, the console `tty` and
+//!    `ttyS0`, and the block devices. This feature is **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount a [`pseudofs`](fs::pseudofs) on `/proc`, whose contents
+//!    (e.g., `/proc/meminfo`, `/proc/uptime`) are generated from the kernel
+//!    state on every read. If `multitask` is also enabled, it provides
+//!    `/proc/<id>/stat` and `/proc/self/stat` for every task. This feature is
+//!    **enabled** by default.
+//! - `sysfs`: Mount a [`pseudofs`](fs::pseudofs) on `/sys`.
+//! - `multitask`: Expose task information in the procfs, write back the
+//!    block caches of the disks every few seconds in a background task, and
+//!    allow waiting for [file locks](#file-locks).
+//! - `irq`: Provide `/proc/interrupts` in the procfs.
+//! - `net`: Provide `/proc/net/dev` and the writable
+//!    `/proc/sys/net/core/somaxconn` in the procfs.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
 //!    by default, but it will override other filesystem selection features if
 //!    both are enabled.
 //!
+//! # Caching
+//!
+//! Disk filesystems access their devices through a write-back LRU block cache
+//! ([`driver_block::cache::BlockCache`]), so dirty blocks only reach the
+//! device when they are evicted, when a file is flushed (`fsync`), when
+//! [`api::sync`] is called, or by the background task.
+//!
+//! # File locks
+//!
+//! Opened files can set advisory whole-file locks ([`fops::File::flock`]) and
+//! byte-range locks ([`fops::File::set_lock`]), which are independent of each
+//! other. They are owned by the opened file rather than a task, and released
+//! when it's closed. Waiting for a lock held by another file requires the
+//! `multitask` feature, and fails with [`AxError::Deadlock`] if the holder is
+//! waiting for the current file, directly or through other files.
+//!
+//! # Block devices
+//!
+//! The disks are named `vda`, `vdb`, etc. in probe order. Their MBR or GPT
+//! partitions are named `vda1`, `vda2`, etc., and each disk or partition is
+//! listed in `/dev` and can be mounted once by [`api::mount`]. The raw data of
+//! the devices can be read and written at any offset through `/dev`, bypassing
+//! the block caches of the mounted filesystems.
+//!
+//! The root filesystem is selected at build time by the environment variables
+//! (or the `ROOT` and `ROOTFS` variables of `make`):
+//!
+//! - `AX_ROOT`: the block device of `/`, e.g., `vda2`. Defaults to the first
+//!    partition of the first disk which is neither bootable nor an EFI system
+//!    partition, or the whole disk if it is not partitioned.
+//! - `AX_ROOTFS`: the filesystem type of `/`, one of `vfat`, `diskfs`, `ext4`
+//!    (or `ext2`, `ext3`), `initrd`, `myfs` and `ramfs` enabled by the
+//!    features. Defaults to `myfs` if enabled, then `initrd`, `diskfs`,
+//!    `ext4`, `vfat` and `ramfs`.
+//!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext2/3/4]: https://docs.kernel.org/filesystems/ext4/
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
+//! [`AxError::Deadlock`]: axerrno::AxError::Deadlock
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
 #![feature(doc_auto_cfg)]
@@ -28,57 +90,98 @@ extern crate alloc;
 
 mod dev;
 mod fs;
+mod lock;
 mod mounts;
 mod root;
 
 pub mod api;
 pub mod fops;
+#[cfg(feature = "initrd")]
+pub mod initrd;
 
 #[cfg(feature = "diskfs")]
-use axdiskfs::{disk, layout, sector};
+use axdiskfs::{disk, mkfs, sector};
 
-use axdriver::{prelude::*, AxDeviceContainer};
+use axdriver::{AxBlockDevice, AxDeviceContainer};
+
+#[cfg(feature = "diskfs")]
+use axdriver::prelude::DevError;
+
+macro_rules! env_or_default {
+    ($key:literal) => {
+        match option_env!($key) {
+            Some(val) => val,
+            None => "",
+        }
+    };
+}
+
+/// The block device of the root filesystem, the default one if empty.
+const ROOT_DEV: &str = env_or_default!("AX_ROOT");
+/// The type of the root filesystem, the default one if empty.
+const ROOT_FS_TYPE: &str = env_or_default!("AX_ROOTFS");
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "myfs")] {
+        const DEFAULT_ROOT_FS_TYPE: &str = "myfs";
+    } else if #[cfg(feature = "initrd")] {
+        const DEFAULT_ROOT_FS_TYPE: &str = "initrd";
+    } else if #[cfg(feature = "diskfs")] {
+        const DEFAULT_ROOT_FS_TYPE: &str = "diskfs";
+    } else if #[cfg(feature = "ext4fs")] {
+        const DEFAULT_ROOT_FS_TYPE: &str = "ext4";
+    } else if #[cfg(feature = "fatfs")] {
+        const DEFAULT_ROOT_FS_TYPE: &str = "vfat";
+    } else {
+        const DEFAULT_ROOT_FS_TYPE: &str = "ramfs";
+    }
+}
 
 /// Initializes filesystems by block devices.
-pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
+pub fn init_filesystems(blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
+    axfs_vfs::set_clock(axhal::time::current_time);
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
+    self::dev::init_block_devs(blk_devs);
+    let fs_type = match ROOT_FS_TYPE {
+        "" => DEFAULT_ROOT_FS_TYPE,
+        fs_type => fs_type,
+    };
+    let source = match ROOT_DEV {
+        "" if matches!(fs_type, "ramfs" | "initrd") => "none",
+        "" => self::dev::default_root_dev().expect("No block device found!"),
+        dev => dev,
+    };
+    info!("  use {} on {} as the root filesystem", fs_type, source);
+    self::root::init_rootfs(source, fs_type);
+
+    #[cfg(feature = "multitask")]
+    axtask::spawn(writeback);
+}
 
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "myfs")] {
-            self::root::init_rootfs(self::dev::Disk::new(dev));
-        } else if #[cfg(feature = "diskfs")] {
-            self::root::init_my_rootfs(disk::Disk::new(dev));
-        } else if #[cfg(feature = "fatfs")] {
-            self::root::init_rootfs(self::dev::Disk::new(dev));
+/// The interval between two write-backs of the block caches.
+#[cfg(feature = "multitask")]
+const WRITEBACK_INTERVAL: core::time::Duration = core::time::Duration::from_secs(5);
+
+/// The background task writing back the block caches.
+#[cfg(feature = "multitask")]
+fn writeback() {
+    loop {
+        axtask::sleep(WRITEBACK_INTERVAL);
+        if let Err(e) = self::root::sync() {
+            warn!("failed to write back the block caches: {:?}", e);
         }
     }
 }
 
 #[cfg(feature = "diskfs")]
-/// Initializes sector manager by block devices.
+/// Initializes sector manager by block devices, formatting the disk if it
+/// does not hold a filesystem yet.
 pub fn init_sector_manager(disk: disk::Disk) -> Result<sector::SectorManager, DevError> {
     let sector = sector::SectorManager::new(disk);
-    let boot_sector = layout::BootSector {
-        bytes_per_sector: 512,
-        sectors_per_cluster: 1,
-        reserved_sectors_count: 32,
-        total_sectors32: 4096,
-        fat_count: 2,
-        sectors_per_fat32: 31, //
-        root_cluster: 2,
-        root_dir_sectors_count: 1,
-        fsinfo_sector: 1,
-        reserved: [0; 488],
-    };
-    let fs_info_sector = layout::FSInfoSector::new(3967, 3);
-    sector
-        .write_sector_seq(&boot_sector.to_bytes())
-        .expect("failed to write boot sector");
-    sector
-        .write_sector_seq(&fs_info_sector.to_bytes())
-        .expect("failed to write fs info sector");
+    if !mkfs::is_formatted(&sector) {
+        info!("  formatting the disk as diskfs...");
+        mkfs::format(&sector, &mkfs::FormatOptions::for_disk(&sector))?;
+    }
     Ok(sector)
 }
@@ -0,0 +1,343 @@
+//! Advisory file locks.
+//!
+//! Two independent kinds of locks are kept for every file: whole-file locks
+//! set by `flock`, and byte-range locks set by `fcntl`. Both are owned by the
+//! opened [`File`](crate::fops::File) (like the `F_OFD_*` locks of Linux,
+//! since all tasks share one process), so that the tasks opening the same
+//! file separately exclude each other, and are released when it is closed.
+//!
+//! A file is identified by the mount and the inode number of its node,
+//! or by its canonical path if the filesystem has no inode numbers (e.g.,
+//! FAT). A blocking request waits until the conflicting locks are released,
+//! unless it would wait for itself through a chain of other waiting owners,
+//! in which case [`AxError::Deadlock`](axerrno::AxError::Deadlock) is
+//! returned.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeRef};
+use axsync::{Condvar, Mutex};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// The type of an advisory lock.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LockKind {
+    /// A shared (read) lock, which can be held by multiple owners.
+    Shared,
+    /// An exclusive (write) lock, which can be held by only one owner.
+    Exclusive,
+}
+
+/// A byte-range lock of a file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileLock {
+    /// The type of the lock.
+    pub kind: LockKind,
+    /// The offset of the first locked byte.
+    pub start: u64,
+    /// The number of locked bytes, `0` means up to the end of the file, no
+    /// matter how large it grows.
+    pub len: u64,
+}
+
+/// Identifies a file in the lock table.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum LockKey {
+    /// The mount ID (`0` for the root) and the inode number.
+    Inode(u64, u64),
+    /// The canonical absolute path.
+    Path(String),
+    /// The address of the node, if it has neither.
+    Node(usize),
+}
+
+/// The owner of the locks set through an opened file, which releases them
+/// when dropped.
+pub(crate) struct LockOwner {
+    id: u64,
+    key: LockKey,
+    /// Whether any lock has been set, so there may be locks to release.
+    used: AtomicBool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Lock {
+    owner: u64,
+    kind: LockKind,
+    start: u64,
+    /// Exclusive, `u64::MAX` for the end of the file.
+    end: u64,
+}
+
+#[derive(Default)]
+struct FileLocks {
+    flocks: Vec<Lock>,
+    records: Vec<Lock>,
+}
+
+struct Waiter {
+    id: u64,
+    key: LockKey,
+    is_flock: bool,
+    lock: Lock,
+}
+
+struct LockTable {
+    files: BTreeMap<LockKey, FileLocks>,
+    waiters: Vec<Waiter>,
+    next_waiter: u64,
+}
+
+static LOCK_TABLE: Mutex<LockTable> = Mutex::new(LockTable {
+    files: BTreeMap::new(),
+    waiters: Vec::new(),
+    next_waiter: 0,
+});
+
+/// Notified when any lock is released.
+static LOCK_RELEASED: Condvar = Condvar::new();
+
+static NEXT_OWNER_ID: AtomicU64 = AtomicU64::new(1);
+
+impl Lock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts(&self, other: &Lock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.kind == LockKind::Exclusive || other.kind == LockKind::Exclusive)
+    }
+}
+
+impl FileLocks {
+    fn list(&mut self, is_flock: bool) -> &mut Vec<Lock> {
+        if is_flock {
+            &mut self.flocks
+        } else {
+            &mut self.records
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty()
+    }
+}
+
+/// Removes the range `[start, end)` from the locks of `owner`, splitting the
+/// locks partially in it. Returns whether anything is removed.
+fn remove_range(list: &mut Vec<Lock>, owner: u64, start: u64, end: u64) -> bool {
+    let mut removed = false;
+    let mut i = 0;
+    while i < list.len() {
+        let l = list[i];
+        if l.owner != owner || !l.overlaps(start, end) {
+            i += 1;
+            continue;
+        }
+        removed = true;
+        list.remove(i);
+        if l.start < start {
+            list.insert(i, Lock { end: start, ..l });
+            i += 1;
+        }
+        if end < l.end {
+            list.insert(i, Lock { start: end, ..l });
+            i += 1;
+        }
+    }
+    removed
+}
+
+/// Adds a lock, replacing the overlapping locks of the same owner and merging
+/// with its adjacent locks of the same type.
+fn add_lock(list: &mut Vec<Lock>, mut lock: Lock) {
+    remove_range(list, lock.owner, lock.start, lock.end);
+    list.retain(|l| {
+        let mergeable = l.owner == lock.owner
+            && l.kind == lock.kind
+            && (l.end == lock.start || lock.end == l.start);
+        if mergeable {
+            lock.start = lock.start.min(l.start);
+            lock.end = lock.end.max(l.end);
+        }
+        !mergeable
+    });
+    list.push(lock);
+}
+
+impl LockTable {
+    fn conflicts<'a>(
+        &'a self,
+        key: &LockKey,
+        is_flock: bool,
+        lock: &'a Lock,
+    ) -> impl Iterator<Item = &'a Lock> {
+        self.files
+            .get(key)
+            .map(|f| if is_flock { &f.flocks } else { &f.records })
+            .into_iter()
+            .flatten()
+            .filter(move |l| l.conflicts(lock))
+    }
+
+    /// Whether `owner` would wait for itself if it waits for `blockers`, i.e.,
+    /// one of them is waiting for `owner`, directly or through other waiting
+    /// owners.
+    fn would_deadlock(&self, owner: u64, mut blockers: Vec<u64>) -> bool {
+        let mut visited = Vec::new();
+        while let Some(blocker) = blockers.pop() {
+            if blocker == owner {
+                return true;
+            }
+            if visited.contains(&blocker) {
+                continue;
+            }
+            visited.push(blocker);
+            for w in self.waiters.iter().filter(|w| w.lock.owner == blocker) {
+                blockers.extend(self.conflicts(&w.key, w.is_flock, &w.lock).map(|l| l.owner));
+            }
+        }
+        false
+    }
+
+    fn release(&mut self, key: &LockKey, is_flock: bool, owner: u64, start: u64, end: u64) {
+        if let Some(file) = self.files.get_mut(key) {
+            if remove_range(file.list(is_flock), owner, start, end) {
+                LOCK_RELEASED.notify_all();
+            }
+            if file.is_empty() {
+                self.files.remove(key);
+            }
+        }
+    }
+}
+
+fn range(start: u64, len: u64) -> AxResult<(u64, u64)> {
+    if len == 0 {
+        Ok((start, u64::MAX))
+    } else {
+        match start.checked_add(len) {
+            Some(end) if end < u64::MAX => Ok((start, end)),
+            _ => ax_err!(InvalidInput),
+        }
+    }
+}
+
+impl LockOwner {
+    /// Creates a new owner for the file `node`, which is opened in the
+    /// filesystem with the mount reference `mount` (`None` for the root). `path` is its
+    /// canonical path if known, only used if the node has no inode number.
+    pub fn new(
+        node: &VfsNodeRef,
+        attr: &VfsNodeAttr,
+        path: Option<String>,
+        mount: Option<&Arc<u64>>,
+    ) -> Self {
+        let key = if attr.ino() != 0 {
+            LockKey::Inode(mount.map_or(0, |id| **id), attr.ino())
+        } else if let Some(path) = path {
+            LockKey::Path(path)
+        } else {
+            LockKey::Node(Arc::as_ptr(node) as *const () as usize)
+        };
+        Self {
+            id: NEXT_OWNER_ID.fetch_add(1, Ordering::Relaxed),
+            key,
+            used: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets (`kind` is `Some`) or releases a lock on the range
+    /// `[start, start + len)` of the file, or the whole file if `is_flock`.
+    ///
+    /// If it conflicts with the locks of other owners, waits for them to be
+    /// released if `wait` is `true`, otherwise returns
+    /// [`AxError::WouldBlock`](axerrno::AxError::WouldBlock).
+    pub fn set_lock(
+        &self,
+        is_flock: bool,
+        kind: Option<LockKind>,
+        start: u64,
+        len: u64,
+        wait: bool,
+    ) -> AxResult {
+        let key = &self.key;
+        let (start, end) = range(start, len)?;
+        let mut table = LOCK_TABLE.lock();
+        let Some(kind) = kind else {
+            table.release(key, is_flock, self.id, start, end);
+            return Ok(());
+        };
+        let lock = Lock {
+            owner: self.id,
+            kind,
+            start,
+            end,
+        };
+        loop {
+            let blockers: Vec<u64> = table
+                .conflicts(key, is_flock, &lock)
+                .map(|l| l.owner)
+                .collect();
+            if blockers.is_empty() {
+                let file = table.files.entry(key.clone()).or_default();
+                add_lock(file.list(is_flock), lock);
+                self.used.store(true, Ordering::Relaxed);
+                // downgrading may let others in
+                LOCK_RELEASED.notify_all();
+                return Ok(());
+            } else if !wait {
+                return ax_err!(WouldBlock);
+            } else if !cfg!(feature = "multitask") || table.would_deadlock(self.id, blockers) {
+                // no other task can release the locks without `multitask`
+                return ax_err!(Deadlock);
+            }
+
+            let id = table.next_waiter;
+            table.next_waiter += 1;
+            table.waiters.push(Waiter {
+                id,
+                key: key.clone(),
+                is_flock,
+                lock,
+            });
+            table = LOCK_RELEASED.wait(table);
+            table.waiters.retain(|w| w.id != id);
+        }
+    }
+
+    /// Returns the first byte-range lock of other owners that conflicts with
+    /// `lock`, or `None` if it can be set.
+    pub fn get_lock(&self, lock: &FileLock) -> AxResult<Option<FileLock>> {
+        let (start, end) = range(lock.start, lock.len)?;
+        let lock = Lock {
+            owner: self.id,
+            kind: lock.kind,
+            start,
+            end,
+        };
+        let table = LOCK_TABLE.lock();
+        let conflict = table.conflicts(&self.key, false, &lock).next();
+        Ok(conflict.map(|l| FileLock {
+            kind: l.kind,
+            start: l.start,
+            len: if l.end == u64::MAX {
+                0
+            } else {
+                l.end - l.start
+            },
+        }))
+    }
+}
+
+impl Drop for LockOwner {
+    fn drop(&mut self) {
+        if self.used.load(Ordering::Relaxed) {
+            let mut table = LOCK_TABLE.lock();
+            table.release(&self.key, true, self.id, 0, u64::MAX);
+            table.release(&self.key, false, self.id, 0, u64::MAX);
+        }
+    }
+}
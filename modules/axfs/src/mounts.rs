@@ -1,80 +1,477 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsOps;
 
 use crate::fs;
 
+#[cfg(any(
+    feature = "diskfs",
+    feature = "ext4fs",
+    feature = "fatfs",
+    feature = "myfs"
+))]
+use crate::dev;
+
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+use {
+    alloc::{format, string::String},
+    axfs_vfs::VfsResult,
+    fs::pseudofs::{FileNode, PseudoFileSystem},
+};
+
+#[cfg(feature = "procfs")]
+use axfs_vfs::VfsError;
+
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+use {
+    alloc::vec::Vec,
+    axfs_vfs::{VfsNodeRef, VfsNodeType},
+    fs::pseudofs::{DirGenerator, DirNode},
+};
+
+/// Creates a new filesystem of `fs_type` from `source` to be mounted at
+/// runtime.
+#[cfg_attr(
+    not(any(
+        feature = "diskfs",
+        feature = "ext4fs",
+        all(feature = "fatfs", not(feature = "myfs"))
+    )),
+    allow(unused_variables)
+)]
+pub(crate) fn new_fs(source: &str, fs_type: &str) -> AxResult<Arc<dyn VfsOps>> {
+    match fs_type {
+        #[cfg(feature = "devfs")]
+        "devfs" => Ok(devfs()),
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => Ok(ramfs()),
+        #[cfg(feature = "procfs")]
+        "procfs" | "proc" => Ok(procfs()),
+        #[cfg(feature = "sysfs")]
+        "sysfs" => Ok(sysfs()),
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" => fatfs(source, false),
+        #[cfg(feature = "diskfs")]
+        "diskfs" => diskfs(source, false),
+        #[cfg(feature = "ext4fs")]
+        "ext4" | "ext3" | "ext2" => ext4fs(source),
+        #[cfg(feature = "initrd")]
+        "initrd" => initrd(),
+        _ => ax_err!(Unsupported, "unknown filesystem type"),
+    }
+}
+
+/// Creates the filesystem of `fs_type` from the block device `source` to be
+/// mounted on `/`.
+///
+/// Unlike [`new_fs`], an unformatted diskfs device is formatted, and so is
+/// the FAT one if the `use-ramdisk` feature is enabled.
+pub(crate) fn root_fs(source: &str, fs_type: &str) -> AxResult<Arc<dyn VfsOps>> {
+    match fs_type {
+        #[cfg(feature = "myfs")]
+        "myfs" => {
+            let disk = dev::Disk::new(dev::open_block_dev(source)?);
+            Ok(fs::myfs::new_myfs(disk))
+        }
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" => fatfs(source, cfg!(feature = "use-ramdisk")),
+        #[cfg(feature = "diskfs")]
+        "diskfs" => diskfs(source, true),
+        _ => new_fs(source, fs_type),
+    }
+}
+
+/// Opens the FAT filesystem on the block device named `source`, e.g., `vdb`
+/// or `/dev/vdb1`, formatting it first if `format` is true.
+///
+/// The filesystem is never freed after unmounted, as its nodes borrow it for
+/// `'static`, so the device can only be mounted once.
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+fn fatfs(source: &str, format: bool) -> AxResult<Arc<dyn VfsOps>> {
+    let disk = dev::Disk::new(dev::open_block_dev(source)?);
+    let fs = if format {
+        fs::fatfs::FatFileSystem::new(disk)
+    } else {
+        fs::fatfs::FatFileSystem::open(disk)?
+    };
+    let fs = Arc::new(fs);
+    let fs_ref: &'static fs::fatfs::FatFileSystem = unsafe { &*Arc::into_raw(fs.clone()) };
+    fs_ref.init();
+    Ok(fs)
+}
+
+/// Opens the diskfs on the block device named `source`, formatting it first
+/// if `format` is true and it does not hold a diskfs yet.
+///
+/// There is a single global diskfs instance, so only one device can be
+/// mounted as diskfs.
+#[cfg(feature = "diskfs")]
+fn diskfs(source: &str, format: bool) -> AxResult<Arc<dyn VfsOps>> {
+    use axdiskfs::{disk, mkfs, sector::SectorManager, FS};
+    if FS.is_init() {
+        return ax_err!(ResourceBusy, "diskfs is already mounted");
+    }
+    let disk = disk::Disk::new(dev::open_block_dev(source)?);
+    let sector = if format {
+        crate::init_sector_manager(disk).map_err(|_| axerrno::AxError::Io)?
+    } else {
+        let sector = SectorManager::new(disk);
+        if !mkfs::is_formatted(&sector) {
+            return ax_err!(InvalidData, "not a diskfs device");
+        }
+        sector
+    };
+    axdiskfs::initialize_fs(sector);
+    Ok(FS.clone())
+}
+
+/// Opens the ext2/3/4 filesystem on the block device named `source`, which is
+/// read-only if it has the features of ext4.
+#[cfg(feature = "ext4fs")]
+fn ext4fs(source: &str) -> AxResult<Arc<dyn VfsOps>> {
+    let dev = dev::open_block_dev(source)?;
+    Ok(fs::ext4fs::Ext4FileSystem::open(dev)?)
+}
+
+/// Overlays the root filesystem `lower` with a ramfs, which holds all the
+/// changes so that `lower` is never modified.
+#[cfg(feature = "overlayfs")]
+pub(crate) fn overlayfs(lower: Arc<dyn VfsOps>) -> Arc<dyn VfsOps> {
+    Arc::new(fs::overlayfs::OverlayFileSystem::new(lower, ramfs()))
+}
+
+/// Creates a ramfs holding the contents of the initial RAM disk.
+#[cfg(feature = "initrd")]
+fn initrd() -> AxResult<Arc<dyn VfsOps>> {
+    let fs = ramfs();
+    crate::initrd::unpack(crate::initrd::archive(), &fs.root_dir())?;
+    Ok(fs)
+}
+
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
-    let null = fs::devfs::NullDev;
-    let zero = fs::devfs::ZeroDev;
-    let bar = fs::devfs::ZeroDev;
+    use fs::devfs::{BlockDev, FullDev, NullDev, RandomDev, TtyDev, ZeroDev};
+
+    let seed = axhal::time::current_time_nanos();
     let devfs = fs::devfs::DeviceFileSystem::new();
-    let foo_dir = devfs.mkdir("foo");
-    devfs.add("null", Arc::new(null));
-    devfs.add("zero", Arc::new(zero));
-    foo_dir.add("bar", Arc::new(bar));
+    devfs.add("null", Arc::new(NullDev::new()));
+    devfs.add("zero", Arc::new(ZeroDev::new()));
+    devfs.add("full", Arc::new(FullDev::new()));
+    devfs.add("random", Arc::new(RandomDev::new(seed)));
+    devfs.add("urandom", Arc::new(RandomDev::new(!seed)));
+    for name in ["tty", "ttyS0"] {
+        let tty = TtyDev::new(console_read, axhal::console::write_bytes);
+        devfs.add(name, Arc::new(tty));
+    }
+    for (name, dev) in crate::dev::raw_block_devs() {
+        devfs.add(name, Arc::new(BlockDev::new(dev)));
+    }
     Arc::new(devfs)
 }
 
+/// Reads the console input into `buf`, waiting until at least one byte is
+/// available.
+#[cfg(feature = "devfs")]
+fn console_read(buf: &mut [u8]) -> usize {
+    loop {
+        let mut len = 0;
+        while len < buf.len() {
+            match axhal::console::getchar() {
+                Some(c) => buf[len] = c,
+                None => break,
+            }
+            len += 1;
+        }
+        if len > 0 || buf.is_empty() {
+            return len;
+        }
+        #[cfg(feature = "multitask")]
+        axtask::yield_now();
+        #[cfg(not(feature = "multitask"))]
+        core::hint::spin_loop();
+    }
+}
+
 #[cfg(feature = "ramfs")]
 pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
-    Arc::new(fs::ramfs::RamFileSystem::new())
+    Arc::new(fs::ramfs::RamFileSystem::new().with_mem_stats(ramfs_mem_stats))
+}
+
+/// Returns the total and free bytes of the memory backing ramfs, i.e., the
+/// pages of the global allocator.
+#[cfg(feature = "ramfs")]
+fn ramfs_mem_stats() -> (usize, usize) {
+    const PAGE_SIZE: usize = 0x1000;
+    let alloc = axalloc::global_allocator();
+    let free_pages = alloc.available_pages();
+    (
+        (alloc.used_pages() + free_pages) * PAGE_SIZE,
+        free_pages * PAGE_SIZE,
+    )
 }
 
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
-
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
+pub(crate) fn procfs() -> Arc<PseudoFileSystem> {
+    let procfs = PseudoFileSystem::new().with_fs_type(fs::pseudofs::PROC_SUPER_MAGIC);
+
+    procfs.add("meminfo", Arc::new(FileNode::new(meminfo)));
+    procfs.add("uptime", Arc::new(FileNode::new(uptime)));
+    procfs.add("cpuinfo", Arc::new(FileNode::new(cpuinfo)));
+    procfs.add("mounts", Arc::new(FileNode::new(proc_mounts)));
+    #[cfg(feature = "irq")]
+    procfs.add("interrupts", Arc::new(FileNode::new(interrupts)));
+    #[cfg(feature = "net")]
+    procfs
+        .mkdir("net")
+        .add("dev", Arc::new(FileNode::new(net_dev)));
+
+    // Create /proc/sys/net/core/somaxconn, which limits the backlog of new
+    // listening sockets.
+    let sys = procfs.mkdir("sys");
+    #[cfg(feature = "net")]
+    let somaxconn =
+        FileNode::new(|| Ok(format!("{}\n", axnet::listen_backlog()))).with_write(|s| {
+            let backlog = s.trim().parse().map_err(|_| VfsError::InvalidInput)?;
+            axnet::set_listen_backlog(backlog);
+            Ok(())
+        });
+    #[cfg(not(feature = "net"))]
+    let somaxconn = FileNode::new_static("4096\n");
+    sys.mkdir("net")
+        .mkdir("core")
+        .add("somaxconn", Arc::new(somaxconn));
 
     // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
+    sys.mkdir("vm")
+        .add("overcommit_memory", Arc::new(FileNode::new_static("0\n")));
+
+    // Create /proc/sys/kernel/log_level, which sets the max log level.
+    sys.mkdir("kernel").add(
+        "log_level",
+        Arc::new(
+            FileNode::new(|| Ok(format!("{}\n", log::max_level()).to_ascii_lowercase()))
+                .with_write(|s| {
+                    let level = s.trim();
+                    level
+                        .parse::<log::LevelFilter>()
+                        .map_err(|_| VfsError::InvalidInput)?;
+                    axlog::set_max_level(level);
+                    Ok(())
+                }),
+        ),
+    );
 
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+    // Generate /proc/<id>/stat and /proc/self/stat from the task registry
+    #[cfg(feature = "multitask")]
+    procfs.root_dir_node().set_generator(TaskDirGenerator);
+    // Create an empty /proc/self/stat if there are no tasks
+    #[cfg(not(feature = "multitask"))]
+    procfs
+        .mkdir("self")
+        .add("stat", Arc::new(FileNode::new_static("")));
 
-    Ok(Arc::new(procfs))
+    Arc::new(procfs)
+}
+
+/// Generates `/proc/meminfo` from the global allocator.
+#[cfg(feature = "procfs")]
+fn meminfo() -> VfsResult<String> {
+    const PAGE_SIZE: usize = 0x1000;
+    let alloc = axalloc::global_allocator();
+    let used_pages = alloc.used_pages();
+    let free_pages = alloc.available_pages();
+    Ok(format!(
+        "MemTotal:       {:>8} kB\n\
+         MemFree:        {:>8} kB\n\
+         HeapUsed:       {:>8} kB\n\
+         HeapFree:       {:>8} kB\n",
+        (used_pages + free_pages) * PAGE_SIZE / 1024,
+        free_pages * PAGE_SIZE / 1024,
+        alloc.used_bytes() / 1024,
+        alloc.available_bytes() / 1024,
+    ))
+}
+
+/// Generates `/proc/uptime`, i.e., the time since boot and the time spent in
+/// the idle tasks of all CPUs.
+#[cfg(feature = "procfs")]
+fn uptime() -> VfsResult<String> {
+    let uptime = axhal::time::current_time();
+    #[cfg(feature = "multitask")]
+    let idle = axtask::tasks()
+        .map(|t| t.stats())
+        .filter(|s| s.name == "idle")
+        .map(|s| s.cpu_time)
+        .sum::<core::time::Duration>();
+    #[cfg(not(feature = "multitask"))]
+    let idle = core::time::Duration::ZERO;
+    Ok(format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime.as_secs(),
+        uptime.subsec_millis() / 10,
+        idle.as_secs(),
+        idle.subsec_millis() / 10,
+    ))
+}
+
+/// Generates `/proc/cpuinfo`, one paragraph for each CPU.
+#[cfg(feature = "procfs")]
+fn cpuinfo() -> VfsResult<String> {
+    Ok((0..axconfig::SMP)
+        .map(|cpu_id| {
+            format!(
+                "processor\t: {}\narch\t\t: {}\nplatform\t: {}\n\n",
+                cpu_id,
+                axconfig::ARCH,
+                axconfig::PLATFORM,
+            )
+        })
+        .collect())
+}
+
+/// Generates `/proc/mounts`, one line for each mounted filesystem:
+///
+/// ```text
+/// source mount_point fs_type rw 0 0
+/// ```
+#[cfg(feature = "procfs")]
+fn proc_mounts() -> VfsResult<String> {
+    Ok(crate::root::mounts()
+        .into_iter()
+        .map(|m| format!("{} {} {} rw 0 0\n", m.source, m.path, m.fs_type))
+        .collect())
+}
+
+/// Generates `/proc/interrupts`, the number of times each IRQ was handled.
+#[cfg(all(feature = "procfs", feature = "irq"))]
+fn interrupts() -> VfsResult<String> {
+    Ok(axhal::irq::irq_counts()
+        .map(|(irq_num, count)| format!("{:>4}: {:>10}\n", irq_num, count))
+        .collect())
+}
+
+/// Generates `/proc/net/dev`, the traffic statistics of network devices.
+#[cfg(all(feature = "procfs", feature = "net"))]
+fn net_dev() -> VfsResult<String> {
+    let mut content = String::from(
+        "Inter-|   Receive                       |  Transmit\n \
+         face |      bytes    packets   errs |      bytes    packets   errs\n",
+    );
+    for dev in axnet::net_dev_stats() {
+        content += &format!(
+            "{:>6}: {:>10} {:>10} {:>6} {:>10} {:>10} {:>6}\n",
+            dev.name,
+            dev.rx_bytes,
+            dev.rx_packets,
+            dev.rx_errors,
+            dev.tx_bytes,
+            dev.tx_packets,
+            dev.tx_errors,
+        );
+    }
+    Ok(content)
+}
+
+/// Generates the task directories `/proc/<id>` and `/proc/self`.
+///
+/// `/proc/<id>/stat` is a single line of space-separated fields, similar to
+/// that of Linux:
+///
+/// ```text
+/// id (name) state cpu priority cpu_time_ns nr_switches nr_wakeups stack_used stack_size
+/// ```
+///
+/// where `state` is `R` (running or ready), `S` (blocked) or `Z` (exited).
+/// `stack_used` is 0 unless the `stack_stats` feature of axtask is enabled.
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+struct TaskDirGenerator;
+
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+impl TaskDirGenerator {
+    fn task_stats(id: u64) -> Option<axtask::TaskStats> {
+        axtask::tasks()
+            .find(|t| t.id().as_u64() == id)
+            .map(|t| t.stats())
+    }
+
+    fn task_stat(id: u64) -> VfsResult<String> {
+        use axtask::TaskState;
+        let stats = Self::task_stats(id).ok_or(VfsError::NotFound)?;
+        let state = match stats.state {
+            TaskState::Running | TaskState::Ready => 'R',
+            TaskState::Blocked => 'S',
+            TaskState::Exited => 'Z',
+        };
+        Ok(format!(
+            "{} ({}) {} {} {} {} {} {} {} {}\n",
+            stats.id,
+            stats.name,
+            state,
+            stats.cpu_id,
+            stats.priority,
+            stats.cpu_time.as_nanos(),
+            stats.nr_switches,
+            stats.nr_wakeups,
+            stats.stack_high_water,
+            stats.stack_size,
+        ))
+    }
+}
+
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+impl DirGenerator for TaskDirGenerator {
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        core::iter::once(("self".into(), VfsNodeType::Dir))
+            .chain(axtask::tasks().map(|t| (format!("{}", t.id().as_u64()), VfsNodeType::Dir)))
+            .collect()
+    }
+
+    fn lookup(&self, dir: &Arc<DirNode>, name: &str) -> Option<VfsNodeRef> {
+        let id = if name == "self" {
+            axtask::current().id().as_u64()
+        } else {
+            Self::task_stats(name.parse().ok()?)?.id
+        };
+        let node = dir.new_child_dir();
+        node.add("stat", Arc::new(FileNode::new(move || Self::task_stat(id))));
+        Some(node)
+    }
 }
 
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
+pub(crate) fn sysfs() -> Arc<PseudoFileSystem> {
+    let sysfs = PseudoFileSystem::new().with_fs_type(fs::pseudofs::SYSFS_MAGIC);
 
     // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
+    sysfs
+        .mkdir("kernel")
+        .mkdir("mm")
+        .mkdir("transparent_hugepage")
+        .add(
+            "enabled",
+            Arc::new(FileNode::new_static("always [madvise] never\n")),
+        );
 
     // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
-
-    Ok(Arc::new(sysfs))
+    let system = sysfs.mkdir("devices").mkdir("system");
+    system.mkdir("clocksource").mkdir("clocksource0").add(
+        "current_clocksource",
+        Arc::new(FileNode::new_static("tsc\n")),
+    );
+
+    // Create /sys/devices/system/cpu/{possible,online}
+    let cpu = system.mkdir("cpu");
+    cpu.add("possible", Arc::new(FileNode::new(cpu_range)));
+    cpu.add("online", Arc::new(FileNode::new(cpu_range)));
+
+    Arc::new(sysfs)
+}
+
+/// Generates the CPU list in `/sys/devices/system/cpu`, e.g., `0-3`.
+#[cfg(feature = "sysfs")]
+fn cpu_range() -> VfsResult<String> {
+    match axconfig::SMP {
+        1 => Ok(String::from("0\n")),
+        n => Ok(format!("0-{}\n", n - 1)),
+    }
 }
@@ -1,108 +1,282 @@
-//! Root directory of the filesystem
+//! Root directory of the filesystem, and the mount table.
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Filesystems can be mounted on any existing directory, including the ones
+//! in other mounted filesystems, either at boot or at runtime. A path is
+//! dispatched to the filesystem mounted at its longest leading components.
 
 use alloc::{string::String, sync::Arc, vec::Vec};
-#[cfg(feature = "diskfs")]
-use axdiskfs::{disk, initialize_fs, FS};
 use axerrno::{ax_err, AxError, AxResult};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{FileSystemInfo, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axfs_vfs::{VfsResult, VfsSetAttr};
 use axsync::Mutex;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_init::LazyInit;
 
-use crate::{api::FileType, fs, mounts};
+use crate::{api::FileType, mounts};
 
-#[cfg(feature = "diskfs")]
-use crate::init_sector_manager;
+/// The maximum number of symbolic links followed in a path resolution, the
+/// same as `MAXSYMLINKS` of Linux.
+const MAX_SYMLINKS: usize = 40;
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
 static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
 
 struct MountPoint {
-    path: &'static str,
+    /// The canonical absolute path of the mount point.
+    path: String,
+    #[cfg_attr(not(feature = "procfs"), allow(dead_code))]
+    source: String,
+    fs_type: String,
+    fs: Arc<dyn VfsOps>,
+    /// The ID of the mount, unique among all mounts since boot, and `0` for
+    /// the root. Cloned by the files and directories opened in the
+    /// filesystem, the mount point is busy if it is shared.
+    refs: Arc<u64>,
+}
+
+/// A mounted filesystem, as listed in `/proc/mounts`.
+#[cfg(feature = "procfs")]
+pub(crate) struct MountInfo {
+    pub source: String,
+    pub path: String,
+    pub fs_type: String,
+}
+
+/// A directory of a mounted filesystem, mounted again at another path.
+struct BindMount {
+    root: VfsNodeRef,
     fs: Arc<dyn VfsOps>,
 }
 
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    #[cfg_attr(not(feature = "procfs"), allow(dead_code))]
+    main_fs_source: String,
+    main_fs_type: String,
+    mounts: Mutex<Vec<MountPoint>>,
+    next_mount_id: AtomicU64,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(
+        id: u64,
+        path: String,
+        source: String,
+        fs_type: String,
+        fs: Arc<dyn VfsOps>,
+    ) -> Self {
+        Self {
+            path,
+            source,
+            fs_type,
+            fs,
+            refs: Arc::new(id),
+        }
+    }
+
+    /// Returns the number of leading components of `comps` that match the
+    /// mount path, or `None` if not all components of the mount path match.
+    fn matches(&self, comps: &[&str]) -> Option<usize> {
+        let mut n = 0;
+        for comp in self.path.split('/').filter(|c| !c.is_empty()) {
+            if comps.get(n) != Some(&comp) {
+                return None;
+            }
+            n += 1;
+        }
+        Some(n)
     }
 }
 
 impl Drop for MountPoint {
     fn drop(&mut self) {
+        self.fs.sync().ok();
         self.fs.umount().ok();
     }
 }
 
+impl VfsOps for BindMount {
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        self.fs.statfs()
+    }
+
+    fn sync(&self) -> VfsResult {
+        self.fs.sync()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub fn new(main_fs: Arc<dyn VfsOps>, main_fs_source: String, main_fs_type: String) -> Self {
         Self {
             main_fs,
-            mounts: Vec::new(),
+            main_fs_source,
+            main_fs_type,
+            mounts: Mutex::new(Vec::new()),
+            next_mount_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Mounts `fs` at boot, the mount point is created in the main filesystem
+    /// if it does not exist.
+    fn mount_at_boot(&self, path: &str, fs_type: &str, fs: Arc<dyn VfsOps>) -> AxResult {
+        // create the mount point in the main filesystem if it does not exist
+        let root = self.main_fs.root_dir();
+        if root.clone().lookup(path).is_err() {
+            root.create(path, FileType::Dir)?;
         }
+        self.mount(path, fs_type, fs_type, fs)
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    /// Mounts `fs` on the existing directory at the canonical absolute `path`.
+    pub fn mount(&self, path: &str, source: &str, fs_type: &str, fs: Arc<dyn VfsOps>) -> AxResult {
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
+        if self.contains(path) {
+            return ax_err!(ResourceBusy, "mount point already exists");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        let mount_point = self.lookup_mounted_fs(path, |fs, rest| fs.root_dir().lookup(rest))?;
+        if !mount_point.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        // check again and mount under the lock, so that a racing mount on the
+        // same path can't have `fs` set up in its place
+        let mut mounts = self.mounts.lock();
+        if mounts.iter().any(|mp| mp.path == path) {
+            return ax_err!(ResourceBusy, "mount point already exists");
+        }
+        fs.mount(path, mount_point)?;
+        mounts.push(MountPoint::new(
+            self.next_mount_id.fetch_add(1, Ordering::Relaxed),
+            path.into(),
+            source.into(),
+            fs_type.into(),
+            fs,
+        ));
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    /// Unmounts the filesystem mounted at the canonical absolute `path`.
+    ///
+    /// Returns [`AxError::ResourceBusy`] if there are other filesystems mounted
+    /// under it, files opened in it, or the current directory is in it.
+    pub fn umount(&self, path: &str) -> AxResult {
+        if path == "/" {
+            return ax_err!(ResourceBusy, "cannot unmount root filesystem");
+        }
+        let cwd = CURRENT_DIR_PATH.lock().clone();
+        let mut mounts = self.mounts.lock();
+        let idx = match mounts.iter().position(|mp| mp.path == path) {
+            Some(idx) => idx,
+            None => return ax_err!(InvalidInput, "not a mount point"),
+        };
+        let is_under = |p: &str| p.strip_prefix(path).is_some_and(|r| r.starts_with('/'));
+        if mounts.iter().any(|mp| is_under(&mp.path)) {
+            return ax_err!(ResourceBusy, "other filesystems mounted under it");
+        }
+        if is_under(&cwd) || Arc::strong_count(&mounts[idx].refs) > 1 {
+            return ax_err!(ResourceBusy);
+        }
+        let mp = mounts.remove(idx);
+        drop(mounts);
+        drop(mp); // unmount out of the lock
+        Ok(())
+    }
+
+    /// Writes the cached data of the main filesystem and all mounted ones to
+    /// their devices. All are synced even if one fails, the first error is
+    /// returned.
+    pub fn sync(&self) -> AxResult {
+        let mounted: Vec<_> = self.mounts.lock().iter().map(|mp| mp.fs.clone()).collect();
+        core::iter::once(self.main_fs.clone())
+            .chain(mounted)
+            .map(|fs| fs.sync())
+            .fold(Ok(()), AxResult::and)
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.mounts.lock().iter().any(|mp| mp.path == path)
     }
 
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
-        }
+    /// Returns the paths of all mount points, starting with `/`.
+    pub fn mount_points(&self) -> Vec<String> {
+        core::iter::once(String::from("/"))
+            .chain(self.mounts.lock().iter().map(|mp| mp.path.clone()))
+            .collect()
+    }
 
-        let mut idx = 0;
-        let mut max_len = 0;
+    /// Returns all mounted filesystems in mount order, starting with the main
+    /// filesystem.
+    #[cfg(feature = "procfs")]
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        let root = MountInfo {
+            source: self.main_fs_source.clone(),
+            path: "/".into(),
+            fs_type: self.main_fs_type.clone(),
+        };
+        core::iter::once(root)
+            .chain(self.mounts.lock().iter().map(|mp| MountInfo {
+                source: mp.source.clone(),
+                path: mp.path.clone(),
+                fs_type: mp.fs_type.clone(),
+            }))
+            .collect()
+    }
 
-        // Find the filesystem that has the longest mounted path match
+    /// Whether the two absolute paths are in the same filesystem.
+    pub fn same_fs(&self, path1: &str, path2: &str) -> AxResult<bool> {
+        let fs1 = self.lookup_mounted_fs(path1, |fs, _| Ok(fs))?;
+        let fs2 = self.lookup_mounted_fs(path2, |fs, _| Ok(fs))?;
+        Ok(Arc::as_ptr(&fs1) as *const () == Arc::as_ptr(&fs2) as *const ())
+    }
+
+    /// Finds the mount point at the longest leading components of `path`, and
+    /// calls `f` with it (`None` for the main filesystem) and the rest of the
+    /// path, with the mount table locked.
+    fn with_mount<F, T>(&self, path: &str, f: F) -> T
+    where
+        F: FnOnce(Option<&MountPoint>, &str) -> T,
+    {
+        let comps: Vec<&str> = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect();
+        let mounts = self.mounts.lock();
         // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
+        let mut matched = None;
+        let mut max_len = 0;
+        for mp in mounts.iter() {
+            match mp.matches(&comps) {
+                Some(n) if n > max_len => {
+                    matched = Some(mp);
+                    max_len = n;
+                }
+                _ => {}
             }
         }
+        f(matched, &comps[max_len..].join("/"))
+    }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
+    where
+        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
+    {
+        debug!("lookup at root: {}", path);
+        let (fs, rest) = self.with_mount(path, |mp, rest| {
+            let fs = mp.map_or_else(|| self.main_fs.clone(), |mp| mp.fs.clone());
+            (fs, String::from(rest))
+        });
+        // call `f` out of the lock, as it may look up the root again
+        f(fs, &rest)
     }
 }
 
@@ -113,6 +287,10 @@ impl VfsNodeOps for RootDirectory {
         self.main_fs.root_dir().get_attr()
     }
 
+    fn set_attr(&self, changes: &VfsSetAttr) -> VfsResult {
+        self.main_fs.root_dir().set_attr(changes)
+    }
+
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
         self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
     }
@@ -138,88 +316,68 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (dst_fs, dst_rest) =
+            self.lookup_mounted_fs(dst_path, |fs, rest_path| Ok((fs, String::from(rest_path))))?;
         self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
+            if rest_path.is_empty() || dst_rest.is_empty() {
                 ax_err!(PermissionDenied) // cannot rename mount points
+            } else if !Arc::ptr_eq(&fs, &dst_fs) {
+                ax_err!(CrossesDevices)
             } else {
-                fs.root_dir().rename(rest_path, dst_path)
+                fs.root_dir().rename(rest_path, &dst_rest)
             }
         })
     }
-}
-
-#[cfg(feature = "diskfs")]
-pub(crate) fn init_my_rootfs(disk: disk::Disk) {
-    let sector = init_sector_manager(disk).expect("failed to initialize sector manager");
-    initialize_fs(sector);
-    debug!("my diskfs!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
-    let fs_arc = FS.try_get().expect("failed to get fs");
-    let mut root_dir = RootDirectory::new(fs_arc.clone());
-
-    #[cfg(feature = "devfs")]
-    root_dir
-        .mount("/dev", mounts::devfs())
-        .expect("failed to mount devfs at /dev");
-
-    #[cfg(feature = "ramfs")]
-    root_dir
-        .mount("/tmp", mounts::ramfs())
-        .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
-    #[cfg(feature = "procfs")]
-    root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
-        .expect("fail to mount procfs at /proc");
-
-    // Mount another ramfs as sysfs
-    #[cfg(feature = "sysfs")]
-    root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
-        .expect("fail to mount sysfs at /sys");
-
-    ROOT_DIR.init_by(Arc::new(root_dir));
-    CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
-}
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        self.lookup_mounted_fs(path, |fs, rest_path| {
+            if rest_path.is_empty() {
+                ax_err!(AlreadyExists)
+            } else {
+                fs.root_dir().symlink(rest_path, target)
+            }
+        })
+    }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_by(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
-        } else {
-            let main_fs = Arc::new(fs::ramfs::RamFileSystem::new());
-        }
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        self.lookup_mounted_fs(path, |fs, rest_path| {
+            if rest_path.is_empty() {
+                ax_err!(AlreadyExists)
+            } else {
+                fs.root_dir().link(rest_path, node)
+            }
+        })
     }
+}
 
-    let mut root_dir = RootDirectory::new(main_fs);
+/// Mounts the filesystem of `fs_type` from the block device `source` on `/`,
+/// and the pseudo filesystems under it.
+pub(crate) fn init_rootfs(source: &str, fs_type: &str) {
+    let main_fs = mounts::root_fs(source, fs_type)
+        .unwrap_or_else(|e| panic!("failed to open the root filesystem: {:?}", e));
+    #[cfg(feature = "overlayfs")]
+    let (main_fs, fs_type) = (mounts::overlayfs(main_fs), "overlay");
+    let root_dir = RootDirectory::new(main_fs, source.into(), fs_type.into());
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount_at_boot("/dev", "devfs", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount_at_boot("/tmp", "ramfs", mounts::ramfs())
         .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
-    root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
-        .expect("fail to mount procfs at /proc");
+    root_dir
+        .mount_at_boot("/proc", "procfs", mounts::procfs())
+        .expect("failed to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
-    root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
-        .expect("fail to mount sysfs at /sys");
+    root_dir
+        .mount_at_boot("/sys", "sysfs", mounts::sysfs())
+        .expect("failed to mount sysfs at /sys");
 
     ROOT_DIR.init_by(Arc::new(root_dir));
     CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
@@ -243,10 +401,89 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
-    if path.is_empty() {
-        return ax_err!(NotFound);
+/// Reads the target of the symbolic link `node`.
+fn read_link_node(node: &VfsNodeRef) -> AxResult<String> {
+    let mut buf = alloc::vec![0; node.get_attr()?.size() as usize];
+    let len = node.readlink(&mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Expands the symbolic links in `path`, returns an equivalent path without
+/// symbolic links.
+///
+/// The last component is not expanded if `follow_last` is `false`, unless the
+/// path ends with `/`. It's OK that the last component does not exist, so the
+/// result can be used to create it. Returns [`AxError::FilesystemLoop`] if
+/// more than [`MAX_SYMLINKS`] links are encountered.
+///
+/// Paths relative to the current directory are converted to absolute, so that
+/// the filesystems mounted under it can be found. Absolute results are
+/// canonicalized, since no intermediate component is a symbolic link.
+fn resolve_symlinks(dir: Option<&VfsNodeRef>, path: &str, follow_last: bool) -> AxResult<String> {
+    let follow_last = follow_last || path.ends_with('/');
+    let mut path = if dir.is_none() && !path.starts_with('/') {
+        CURRENT_DIR_PATH.lock().clone() + path
+    } else {
+        String::from(path)
+    };
+    let mut nr_links = 0;
+    'restart: loop {
+        let base = parent_node_of(dir, &path);
+        let comps: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let mut resolved = String::from(if path.starts_with('/') { "/" } else { "" });
+        for (i, comp) in comps.iter().enumerate() {
+            let parent_len = resolved.len();
+            resolved += comp;
+            let is_last = i + 1 == comps.len();
+            if !is_last {
+                resolved.push('/');
+            }
+            if *comp == "." || *comp == ".." || (is_last && !follow_last) {
+                continue;
+            }
+
+            let node = match base.clone().lookup(&resolved) {
+                Ok(node) => node,
+                Err(AxError::NotFound) if is_last => break,
+                Err(e) => return Err(e),
+            };
+            if !node.get_attr()?.is_symlink() {
+                continue;
+            }
+            nr_links += 1;
+            if nr_links > MAX_SYMLINKS {
+                return ax_err!(FilesystemLoop);
+            }
+
+            // Replace the link with its target, and resolve the new path again.
+            let target = read_link_node(&node)?;
+            let mut new_path = if target.starts_with('/') {
+                target
+            } else {
+                String::from(&resolved[..parent_len]) + &target
+            };
+            for comp in &comps[i + 1..] {
+                new_path.push('/');
+                new_path += comp;
+            }
+            if path.ends_with('/') {
+                new_path.push('/');
+            }
+            path = new_path;
+            continue 'restart;
+        }
+        if resolved.starts_with('/') {
+            resolved = axfs_vfs::path::canonicalize(&resolved);
+        }
+        if path.ends_with('/') && !resolved.ends_with('/') {
+            resolved.push('/');
+        }
+        return Ok(resolved);
     }
+}
+
+fn lookup_resolved(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
     let node = parent_node_of(dir, path).lookup(path)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
@@ -255,27 +492,184 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
+pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    lookup_resolved(dir, &resolve_symlinks(dir, path, true)?)
+}
+
+/// Like [`lookup`], but does not follow the symbolic link at the last
+/// component.
+pub(crate) fn lookup_no_follow(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    lookup_resolved(dir, &resolve_symlinks(dir, path, false)?)
+}
+
 pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    // create the target if the path is a dangling symbolic link
+    let path = resolve_symlinks(dir, path, true)?;
+    let parent = parent_node_of(dir, &path);
+    parent.create(&path, VfsNodeType::File)?;
+    parent.lookup(&path)
 }
 
 pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+    match lookup_no_follow(dir, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let path = resolve_symlinks(dir, path, false)?;
+            parent_node_of(dir, &path).create(&path, VfsNodeType::Dir)
+        }
         Err(e) => Err(e),
     }
 }
 
+pub(crate) fn symlink(dir: Option<&VfsNodeRef>, target: &str, path: &str) -> AxResult {
+    if path.is_empty() || target.is_empty() {
+        return ax_err!(NotFound);
+    } else if path.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    let path = resolve_symlinks(dir, path, false)?;
+    if lookup_resolved(dir, &path).is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    parent_node_of(dir, &path).symlink(&path, target)
+}
+
+pub(crate) fn read_link(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<String> {
+    let node = lookup_no_follow(dir, path)?;
+    if !node.get_attr()?.is_symlink() {
+        return ax_err!(InvalidInput);
+    }
+    read_link_node(&node)
+}
+
+/// Changes the attributes of the node at `path`, the symbolic link at the last
+/// component is followed if `follow` is `true`.
+pub(crate) fn set_attr(
+    dir: Option<&VfsNodeRef>,
+    path: &str,
+    changes: &VfsSetAttr,
+    follow: bool,
+) -> AxResult {
+    let node = if follow {
+        lookup(dir, path)?
+    } else {
+        lookup_no_follow(dir, path)?
+    };
+    node.set_attr(changes)
+}
+
+/// Returns the attributes of the filesystem containing `path`.
+pub(crate) fn statfs(path: &str) -> AxResult<FileSystemInfo> {
+    let path = resolve_symlinks(None, path, true)?;
+    lookup_resolved(None, &path)?;
+    ROOT_DIR.lookup_mounted_fs(&absolute_path(&path)?, |fs, _| fs.statfs())
+}
+
+pub(crate) fn sync() -> AxResult {
+    ROOT_DIR.sync()
+}
+
+pub(crate) fn mount_points() -> Vec<String> {
+    ROOT_DIR.mount_points()
+}
+
+#[cfg(feature = "procfs")]
+pub(crate) fn mounts() -> Vec<MountInfo> {
+    ROOT_DIR.mounts()
+}
+
+/// Returns the canonical absolute path of the existing directory at `path`.
+fn mount_target(path: &str) -> AxResult<String> {
+    let path = resolve_symlinks(None, path, true)?;
+    if !lookup_resolved(None, &path)?.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    Ok(axfs_vfs::path::canonicalize(&path))
+}
+
+/// Mounts a new filesystem of `fs_type` from `source` on the directory `target`.
+pub(crate) fn mount(source: &str, target: &str, fs_type: &str) -> AxResult {
+    let target = mount_target(target)?;
+    let fs = mounts::new_fs(source, fs_type)?;
+    ROOT_DIR.mount(&target, source, fs_type, fs)
+}
+
+/// Mounts the directory `source` on the directory `target`, so that the same
+/// contents are accessible at both paths.
+pub(crate) fn bind_mount(source: &str, target: &str) -> AxResult {
+    let source = mount_target(source)?;
+    let target = mount_target(target)?;
+    let root = lookup_resolved(None, &source)?;
+    let (fs, fs_type) = ROOT_DIR.with_mount(&source, |mp, _| match mp {
+        Some(mp) => (mp.fs.clone(), mp.fs_type.clone()),
+        None => (ROOT_DIR.main_fs.clone(), ROOT_DIR.main_fs_type.clone()),
+    });
+    let bind = Arc::new(BindMount { root, fs });
+    ROOT_DIR.mount(&target, &source, &fs_type, bind)
+}
+
+/// Unmounts the filesystem mounted on the directory `target`.
+pub(crate) fn umount(target: &str) -> AxResult {
+    ROOT_DIR.umount(&mount_target(target)?)
+}
+
+/// Returns the reference of the mount point containing `path`, which keeps it
+/// from being unmounted while held, and holds the ID of the mount.
+///
+/// Returns `None` if `path` is in the main filesystem, or is relative to `dir`.
+pub(crate) fn mount_ref(dir: Option<&VfsNodeRef>, path: &str) -> Option<Arc<u64>> {
+    if dir.is_some() && !path.starts_with('/') {
+        return None;
+    }
+    let path = resolve_symlinks(None, path, true).ok()?;
+    ROOT_DIR.with_mount(&path, |mp, _| mp.map(|mp| mp.refs.clone()))
+}
+
+/// Returns the canonical absolute path of the file `path` with the symbolic
+/// links expanded, or `None` if it's relative to `dir`.
+pub(crate) fn canonical_path(dir: Option<&VfsNodeRef>, path: &str) -> Option<String> {
+    if dir.is_some() && !path.starts_with('/') {
+        return None;
+    }
+    let path = resolve_symlinks(None, path, true).ok()?;
+    absolute_path(&path).ok()
+}
+
+pub(crate) fn hard_link(old: &str, new: &str) -> AxResult {
+    let old = resolve_symlinks(None, old, false)?;
+    let node = lookup_resolved(None, &old)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied);
+    } else if new.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    let new = resolve_symlinks(None, new, false)?;
+    if lookup_resolved(None, &new).is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    if !ROOT_DIR.same_fs(&absolute_path(&old)?, &absolute_path(&new)?)? {
+        return ax_err!(CrossesDevices);
+    }
+    parent_node_of(None, &new).link(&new, node)
+}
+
 pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let path = &resolve_symlinks(dir, path, false)?;
+    let node = lookup_resolved(dir, path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
@@ -304,7 +698,8 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
         return ax_err!(PermissionDenied);
     }
 
-    let node = lookup(dir, path)?;
+    let path = &resolve_symlinks(dir, path, false)?;
+    let node = lookup_resolved(dir, path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
@@ -344,6 +739,8 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
+    let old = &resolve_symlinks(None, old, false)?;
+    let new = &resolve_symlinks(None, new, false)?;
     if parent_node_of(None, new).lookup(new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new)?;
@@ -198,6 +198,7 @@ fn test_devfs_ramfs() -> Result<()> {
         .collect::<Vec<_>>();
     assert!(dirents.contains(&"null".into()));
     assert!(dirents.contains(&"zero".into()));
+    assert!(dirents.contains(&"vda".into()));
 
     // stat /dev
     let dname = "/dev";
@@ -208,14 +209,36 @@ fn test_devfs_ramfs() -> Result<()> {
     assert!(!md.is_file());
     assert!(md.is_dir());
 
-    // stat /dev/foo/bar
-    let fname = ".//.///././/./dev///.///./foo//././bar";
+    // stat /dev/urandom
+    let fname = ".//.///././/./dev///.///./urandom";
     let file = File::open(fname)?;
     let md = file.metadata()?;
     println!("metadata of {:?}: {:?}", fname, md);
     assert_eq!(md.file_type(), FileType::CharDevice);
     assert!(!md.is_dir());
 
+    // read /dev/urandom, read and write /dev/full
+    let mut buf = [0; 64];
+    File::open("/dev/urandom")?.read_exact(&mut buf)?;
+    assert_ne!(buf, [0; 64]);
+    let mut file = File::options().read(true).write(true).open("/dev/full")?;
+    assert_eq!(file.read(&mut buf)?, 64);
+    assert_eq!(buf, [0; 64]);
+    assert_err!(file.write(b"test"), StorageFull);
+
+    // read /dev/vda at aligned and unaligned offsets
+    let mut file = File::open("/dev/vda")?;
+    let md = file.metadata()?;
+    assert_eq!(md.file_type(), FileType::BlockDevice);
+    let mut blocks = [0; 1024];
+    file.read_exact(&mut blocks)?;
+    let mut buf = [0; 100];
+    file.seek(io::SeekFrom::Start(500))?;
+    file.read_exact(&mut buf)?;
+    assert_eq!(buf, blocks[500..600]);
+    file.seek(io::SeekFrom::Start(md.len() - 10))?;
+    assert_eq!(file.read(&mut buf)?, 10);
+
     // error cases
     assert_err!(fs::metadata("/dev/null/"), NotADirectory);
     assert_err!(fs::create_dir("dev"), AlreadyExists);
@@ -233,7 +256,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::write(".///dev//..//233//.///test.txt", "test"), Ok(()));
     assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
     assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
-    assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
+    assert_eq!(fs::remove_dir("dev//../dev/../.././/233"), Ok(()));
     assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
 
     // tests in /tmp
@@ -248,10 +271,101 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
 
+    // filesystem attributes
+    assert_eq!(fs::statfs("/dev/null")?.fs_type, 0x0102_1994); // devfs
+    assert_eq!(fs::statfs("./tmp/")?.fs_type, 0x8584_58f6); // ramfs
+    assert_err!(fs::statfs("/dev/none"), NotFound);
+    let mount_points = fs::mount_points();
+    assert_eq!(mount_points[0], "/");
+    assert!(mount_points.contains(&"/dev".into()));
+    assert!(mount_points.contains(&"/tmp".into()));
+
     println!("test_devfs_ramfs() OK!");
     Ok(())
 }
 
+fn test_file_locks() -> Result<()> {
+    use axfs::fops::{self, FileLock, LockKind};
+
+    let fname = "/lock.txt";
+    println!("test file locks on {:?}:", fname);
+    fs::write(fname, "0123456789")?;
+
+    // whole-file locks, the same file opened by different paths
+    let a = File::open(fname)?;
+    let b = File::open("//./lock.txt")?;
+    a.try_lock()?;
+    assert_err!(b.try_lock_shared(), WouldBlock);
+    #[cfg(not(feature = "multitask"))]
+    assert_err!(b.lock_shared(), Deadlock); // nobody else can unlock it
+    a.lock_shared()?; // downgrade
+    b.try_lock_shared()?;
+    assert_err!(a.try_lock(), WouldBlock);
+    drop(b); // released on close
+    a.try_lock()?;
+    a.unlock()?;
+    drop(a);
+
+    // byte-range locks are independent of whole-file locks
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let a = fops::File::open(fname, &opts)?;
+    let b = fops::File::open(fname, &opts)?;
+    a.flock(Some(LockKind::Exclusive), false)?;
+    a.set_lock(Some(LockKind::Exclusive), 2, 4, false)?;
+    b.set_lock(Some(LockKind::Shared), 0, 2, false)?;
+    b.set_lock(Some(LockKind::Shared), 6, 0, false)?;
+    assert_err!(b.set_lock(Some(LockKind::Shared), 5, 1, false), WouldBlock);
+    let conflict = b.get_lock(&FileLock {
+        kind: LockKind::Shared,
+        start: 0,
+        len: 0,
+    })?;
+    assert_eq!(
+        conflict,
+        Some(FileLock {
+            kind: LockKind::Exclusive,
+            start: 2,
+            len: 4,
+        })
+    );
+
+    // unlock part of the range
+    a.set_lock(None, 4, 2, false)?;
+    b.set_lock(Some(LockKind::Shared), 4, 2, false)?;
+    assert_err!(
+        a.set_lock(Some(LockKind::Exclusive), 7, 1, false),
+        WouldBlock
+    );
+    assert_err!(
+        b.set_lock(Some(LockKind::Exclusive), 3, 1, false),
+        WouldBlock
+    );
+    drop(a);
+    b.set_lock(Some(LockKind::Exclusive), 0, 0, false)?;
+    drop(b);
+
+    // shared locks need reading, exclusive ones need writing
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    let file = fops::File::open(fname, &opts)?;
+    assert_err!(
+        file.set_lock(Some(LockKind::Exclusive), 0, 0, false),
+        PermissionDenied
+    );
+    file.set_lock(Some(LockKind::Shared), 0, 0, false)?;
+    assert_err!(
+        file.set_lock(Some(LockKind::Shared), u64::MAX - 1, 2, false),
+        InvalidInput
+    );
+    drop(file);
+
+    fs::remove_file(fname)?;
+    println!("test_file_locks() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +373,5 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_file_locks().expect("test_file_locks() failed");
 }
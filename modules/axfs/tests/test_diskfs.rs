@@ -1,8 +1,9 @@
 #![cfg(feature = "diskfs")]
 mod test_common;
 
-use axdiskfs::FS;
+use axdiskfs::{diskfs::CCFS_MAGIC, FS};
 use axdriver::AxDeviceContainer;
+use axfs_vfs::VfsOps;
 use driver_block::ramdisk::RamDisk;
 use std::process::Command;
 
@@ -75,5 +76,14 @@ fn test_diskfs() {
 
     let _ = test_file.write_at_inner(0, "Rust is cool\n".as_bytes());
 
+    let info = fs_arc.statfs().unwrap();
+    assert_eq!(info.fs_type, CCFS_MAGIC);
+    assert_eq!(info.block_size, fs_arc.bytes_per_cluster() as u64);
+    assert!(info.blocks_free > 0 && info.blocks_free < info.blocks);
+    assert_eq!(
+        axfs::api::statfs("/very/long/path/test.txt").unwrap(),
+        info
+    );
+
     test_common::test_all();
 }
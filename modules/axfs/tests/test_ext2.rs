@@ -0,0 +1,178 @@
+#![cfg(feature = "ext4fs")]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File};
+use axio::{Error, Read, Result, Seek, SeekFrom, Write};
+use driver_block::ramdisk::RamDisk;
+
+const IMG_PATH: &str = "resources/ext2.img";
+const EXT4_SUPER_MAGIC: u64 = 0xef53;
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_indexed_dir() -> Result<()> {
+    // look up through the htree
+    for i in [1, 47, 150, 300] {
+        let fname = format!("/many/file-{}.txt", i);
+        assert_eq!(fs::read_to_string(&fname)?, format!("file {}\n", i));
+    }
+    assert_eq!(fs::read_dir("/many")?.count(), 300);
+
+    // adding an entry drops the index
+    fs::write("/many/new.txt", "new")?;
+    fs::remove_file("/many/file-150.txt")?;
+    assert_eq!(fs::read_to_string("/many/new.txt")?, "new");
+    assert_eq!(fs::read_to_string("/many/file-300.txt")?, "file 300\n");
+    assert_eq!(
+        fs::metadata("/many/file-150.txt").err(),
+        Some(Error::NotFound)
+    );
+
+    println!("test_indexed_dir() OK!");
+    Ok(())
+}
+
+fn test_large_file() -> Result<()> {
+    let free = fs::statfs("/")?.blocks_free;
+
+    // beyond the direct and single indirect blocks, with holes
+    let fname = "/large.bin";
+    let mut file = File::create(fname)?;
+    let offset = 300 * 1024 + 100;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&[0x5a; 3000])?;
+    drop(file);
+    let md = fs::metadata(fname)?;
+    assert_eq!(md.len(), offset + 3000);
+    let data = fs::read(fname)?;
+    assert!(data[..offset as usize].iter().all(|&b| b == 0));
+    assert!(data[offset as usize..].iter().all(|&b| b == 0x5a));
+    assert!(fs::statfs("/")?.blocks_free < free);
+
+    // truncate in the middle of a block, then extend
+    let mut file = File::options().read(true).write(true).open(fname)?;
+    file.set_len(offset + 10)?;
+    file.set_len(offset + 100)?;
+    let mut buf = [1; 100];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    assert_eq!(buf[..10], [0x5a; 10]);
+    assert_eq!(buf[10..], [0; 90]);
+    drop(file);
+
+    // the blocks are freed with the file
+    fs::remove_file(fname)?;
+    assert_eq!(fs::statfs("/")?.blocks_free, free);
+
+    println!("test_large_file() OK!");
+    Ok(())
+}
+
+fn test_links_rename() -> Result<()> {
+    // the symbolic link in the image
+    assert_eq!(fs::read_link("/link")?, "very/long/path/test.txt");
+    assert!(fs::symlink_metadata("/link")?.is_symlink());
+
+    // fast and slow symbolic links
+    let long_target = "very/long/path/".repeat(5) + "test.txt";
+    fs::symlink("short.txt", "/fast")?;
+    fs::symlink(&long_target, "/slow")?;
+    assert_eq!(fs::read_link("/fast")?, "short.txt");
+    assert_eq!(fs::read_link("/slow")?, long_target);
+    fs::remove_file("/fast")?;
+    fs::remove_file("/slow")?;
+
+    // hard links
+    fs::hard_link("/short.txt", "/very/hard.txt")?;
+    assert_eq!(fs::metadata("/short.txt")?.nlink(), 2);
+    fs::remove_file("/short.txt")?;
+    assert!(fs::read_to_string("/very/hard.txt")?.starts_with("Rust is cool!\n"));
+    assert_eq!(
+        fs::hard_link("/very", "/very2").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::hard_link("/very/hard.txt", "/tmp/hard.txt").err(),
+        Some(Error::CrossesDevices)
+    );
+
+    // rename files and directories across directories
+    fs::rename("/very/hard.txt", "/short.txt")?;
+    fs::rename("/very/long", "/very-long-dir-name/long")?;
+    assert!(fs::metadata("/very-long-dir-name/long/path/test.txt")?.is_file());
+    assert_eq!(
+        fs::canonicalize("/very-long-dir-name/long/path/../..")?,
+        "/very-long-dir-name"
+    );
+    assert_eq!(fs::metadata("/very")?.nlink(), 2);
+    assert_eq!(fs::metadata("/very-long-dir-name")?.nlink(), 3);
+    assert_eq!(
+        fs::rename("/very-long-dir-name", "/very-long-dir-name/long/x").err(),
+        Some(Error::InvalidInput)
+    );
+    fs::rename("/very-long-dir-name/long", "/very/long")?;
+
+    println!("test_links_rename() OK!");
+    Ok(())
+}
+
+fn test_unlink_open() -> Result<()> {
+    let info = fs::statfs("/")?;
+
+    let fname = "/unlinked.txt";
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(fname)?;
+    file.write_all(b"before unlink\n")?;
+    fs::remove_file(fname)?;
+    assert_eq!(fs::metadata(fname).err(), Some(Error::NotFound));
+    assert_eq!(file.metadata()?.nlink(), 0);
+
+    // the open file is still usable, and its inode is not reused
+    file.write_all(&[0x5a; 5000])?;
+    fs::write("/new.txt", "new")?;
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut data)?;
+    assert_eq!(data.len(), 14 + 5000);
+    assert!(data.starts_with(b"before unlink\n"));
+    assert!(data[14..].iter().all(|&b| b == 0x5a));
+    assert_eq!(fs::read_to_string("/new.txt")?, "new");
+    fs::remove_file("/new.txt")?;
+
+    // freed with the last handle
+    assert!(fs::statfs("/")?.blocks_free < info.blocks_free);
+    drop(file);
+    let new_info = fs::statfs("/")?;
+    assert_eq!(new_info.blocks_free, info.blocks_free);
+    assert_eq!(new_info.files_free, info.files_free);
+
+    println!("test_unlink_open() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext2() {
+    println!("Testing ext2 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    assert_eq!(fs::statfs("/").unwrap().fs_type, EXT4_SUPER_MAGIC);
+    test_common::test_all();
+    test_indexed_dir().expect("test_indexed_dir() failed");
+    test_large_file().expect("test_large_file() failed");
+    test_links_rename().expect("test_links_rename() failed");
+    test_unlink_open().expect("test_unlink_open() failed");
+    fs::sync().unwrap();
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "ext4fs")]
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File};
+use axio::{Error, Read, Result, Seek, SeekFrom};
+use driver_block::ramdisk::RamDisk;
+
+const IMG_PATH: &str = "resources/ext4.img";
+const EXT4_SUPER_MAGIC: u64 = 0xef53;
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_read() -> Result<()> {
+    let contents = fs::read_to_string("/long.txt")?;
+    assert_eq!(contents, "Rust is cool!\n".repeat(1000));
+    assert_eq!(
+        fs::read_to_string("/very/long/path/test.txt")?,
+        "Rust is cool!\n"
+    );
+    assert_eq!(fs::read_link("/link")?, "very/long/path/test.txt");
+    assert_eq!(fs::read_to_string("/link")?, "Rust is cool!\n");
+
+    // the extent tree has an index node, the holes read as zeros
+    let mut file = File::open("/sparse.bin")?;
+    assert_eq!(file.metadata()?.len(), 7 * 65536 + 8);
+    let mut buf = [1; 12];
+    assert_eq!(file.read(&mut buf)?, 12);
+    assert_eq!(buf, *b"chunk 0\n\0\0\0\0");
+    for i in 1..8 {
+        file.seek(SeekFrom::Start(i * 65536 - 4))?;
+        assert_eq!(file.read(&mut buf)?, 12);
+        assert_eq!(buf[..4], [0; 4]);
+        assert_eq!(buf[4..], *format!("chunk {}\n", i).as_bytes());
+    }
+
+    // look up through the htree
+    for i in 1..=300 {
+        let fname = format!("/many/file-{}.txt", i);
+        assert_eq!(fs::read_to_string(&fname)?, format!("file {}\n", i));
+    }
+    assert_eq!(fs::read_dir("/many")?.count(), 300);
+    assert_eq!(
+        fs::metadata("/many/file-301.txt").err(),
+        Some(Error::NotFound)
+    );
+
+    println!("test_read() OK!");
+    Ok(())
+}
+
+fn test_read_only() -> Result<()> {
+    assert_eq!(
+        fs::write("/short.txt", "test").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::create_dir("/new-dir").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::remove_file("/short.txt").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::rename("/short.txt", "/short2.txt").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(fs::read_to_string("/short.txt")?, "Rust is cool!\n");
+
+    // the mounted filesystems are writable
+    fs::write("/tmp/test.txt", "test")?;
+    assert_eq!(fs::read_to_string("/tmp/test.txt")?, "test");
+
+    println!("test_read_only() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext4() {
+    println!("Testing read-only ext4 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    assert_eq!(fs::statfs("/").unwrap().fs_type, EXT4_SUPER_MAGIC);
+    test_read().expect("test_read() failed");
+    test_read_only().expect("test_read_only() failed");
+}
@@ -2,6 +2,7 @@
 mod test_common;
 
 use axdriver::AxDeviceContainer;
+use axfs::api as fs;
 use driver_block::ramdisk::RamDisk;
 
 const IMG_PATH: &str = "resources/fat16.img";
@@ -23,4 +24,15 @@ fn test_fatfs() {
     axfs::init_filesystems(AxDeviceContainer::from_one(disk));
 
     test_common::test_all();
+    test_raw_device();
+}
+
+/// The raw device node sees the data written through the filesystem, before
+/// it's synced to the device.
+fn test_raw_device() {
+    let marker = b"written through the block cache";
+    fs::write("/raw.txt", marker).unwrap();
+    let disk = fs::read("/dev/vda").unwrap();
+    assert_eq!(disk.len(), 2560000);
+    assert!(disk.windows(marker.len()).any(|w| w == marker));
 }
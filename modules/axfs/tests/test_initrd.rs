@@ -0,0 +1,101 @@
+#![cfg(feature = "initrd")]
+mod test_common;
+
+use std::time::Duration;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsNodeType, VfsOps};
+use axio::{Error, Result};
+use driver_block::ramdisk::RamDisk;
+
+const CPIO_PATH: &str = "resources/initrd.cpio";
+const TAR_PATH: &str = "resources/initrd.tar";
+const MTIME: Duration = Duration::from_secs(1_700_000_000);
+const LONG_DIR: &str = "dddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd/\
+                        eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+
+fn load_archive(path: &str) -> std::io::Result<&'static [u8]> {
+    let path = std::env::current_dir()?.join(path);
+    println!("Loading initrd from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(data.leak())
+}
+
+fn test_unpacked() -> Result<()> {
+    // modes, owners and times
+    let md = fs::metadata("/bin/hello")?;
+    assert_eq!(md.permissions().mode(), 0o755);
+    assert_eq!((md.uid(), md.gid()), (1000, 100));
+    assert_eq!(md.modified(), MTIME);
+    assert_eq!(fs::metadata("/secret.txt")?.permissions().mode(), 0o600);
+    assert_eq!(fs::metadata("/empty")?.permissions().mode(), 0o700);
+    assert_eq!(fs::read_dir("/empty")?.count(), 0);
+
+    // links
+    assert_eq!(fs::read_link("/link")?, "very/long/path/test.txt");
+    assert_eq!(fs::read_to_string("/link")?, "Rust is cool!\n");
+    assert_eq!(fs::read_to_string("/bin/hello")?, "#!/bin/sh\necho hello\n");
+    let md = fs::metadata("/bin/hello2")?;
+    assert_eq!(md.nlink(), 2);
+    assert_eq!(md.ino(), fs::metadata("/bin/hello")?.ino());
+
+    // long names and the mount points created at boot
+    let fname = format!("/{}/file.txt", LONG_DIR);
+    assert_eq!(fs::read_to_string(&fname)?, "long name\n");
+    assert!(fs::metadata("/dev/null").is_ok());
+    assert_eq!(fs::metadata("/missing").err(), Some(Error::NotFound));
+
+    println!("test_unpacked() OK!");
+    Ok(())
+}
+
+fn test_unpack_tar() -> std::io::Result<()> {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    axfs::initrd::unpack(load_archive(TAR_PATH)?, &root).unwrap();
+
+    let hello = root.clone().lookup("bin/hello").unwrap();
+    let attr = hello.get_attr().unwrap();
+    assert_eq!(attr.perm().mode(), 0o755);
+    assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    assert_eq!(attr.mtime(), MTIME);
+    let mut buf = [0; 64];
+    let n = hello.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf[..n], b"#!/bin/sh\necho hello\n");
+    let hello2 = root.clone().lookup("bin/hello2").unwrap();
+    assert_eq!(hello2.get_attr().unwrap().nlink(), 2);
+
+    let link = root.clone().lookup("link").unwrap();
+    assert_eq!(link.get_attr().unwrap().file_type(), VfsNodeType::SymLink);
+    let empty = root.clone().lookup("empty").unwrap();
+    assert_eq!(empty.get_attr().unwrap().perm().mode(), 0o700);
+    let file = root
+        .clone()
+        .lookup(&format!("{}/file.txt", LONG_DIR))
+        .unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 10);
+    let long = root.clone().lookup("long.txt").unwrap();
+    assert_eq!(long.get_attr().unwrap().size(), 14000);
+
+    // not an archive
+    assert!(axfs::initrd::unpack(b"not an archive", &root).is_err());
+
+    println!("test_unpack_tar() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initrd() {
+    println!("Testing initrd ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::initrd::set_initrd(load_archive(CPIO_PATH).expect("failed to load initrd"));
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::new(0x1000))); // dummy disk, only listed in /dev.
+
+    test_unpacked().expect("test_unpacked() failed");
+    test_common::test_all();
+    test_unpack_tar().expect("test_unpack_tar() failed");
+}
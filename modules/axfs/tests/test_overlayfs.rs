@@ -0,0 +1,85 @@
+#![cfg(all(feature = "overlayfs", feature = "initrd"))]
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File};
+use axio::{Error, Result, Write};
+use driver_block::ramdisk::RamDisk;
+
+const CPIO_PATH: &str = "resources/initrd.cpio";
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+
+fn load_archive(path: &str) -> std::io::Result<&'static [u8]> {
+    let path = std::env::current_dir()?.join(path);
+    println!("Loading initrd from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(data.leak())
+}
+
+fn test_overlay() -> Result<()> {
+    assert_eq!(fs::statfs("/")?.fs_type, OVERLAYFS_SUPER_MAGIC);
+
+    // copy up on write, keeping the attributes
+    let mut file = File::options().append(true).open("/bin/hello")?;
+    file.write_all(b"echo world\n")?;
+    drop(file);
+    assert_eq!(
+        fs::read_to_string("/bin/hello")?,
+        "#!/bin/sh\necho hello\necho world\n"
+    );
+    assert_eq!(fs::metadata("/bin/hello")?.permissions().mode(), 0o755);
+    assert_eq!(fs::metadata("/bin/hello")?.uid(), 1000);
+
+    // whiteouts
+    fs::remove_file("/secret.txt")?;
+    assert_eq!(fs::metadata("/secret.txt").err(), Some(Error::NotFound));
+    let names = fs::read_dir("/")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(!names.contains(&"secret.txt".into()));
+    assert!(!names.iter().any(|name| name.starts_with(".wh.")));
+    assert!(names.contains(&"long.txt".into()));
+    fs::write("/secret.txt", "new secret")?;
+    assert_eq!(fs::read_to_string("/secret.txt")?, "new secret");
+
+    // merged directories
+    fs::write("/bin/new", "new")?;
+    let mut names = fs::read_dir("/bin")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["hello", "hello2", "new"]);
+    assert_eq!(fs::remove_dir("/bin").err(), Some(Error::DirectoryNotEmpty));
+    for name in names {
+        fs::remove_file(&format!("/bin/{}", name))?;
+    }
+    fs::remove_dir("/bin")?;
+    fs::create_dir("/bin")?;
+    assert_eq!(fs::read_dir("/bin")?.count(), 0);
+
+    // renames
+    fs::rename("/short.txt", "/short2.txt")?;
+    assert_eq!(fs::read_to_string("/short2.txt")?, "Rust is cool!\n");
+    assert_eq!(fs::metadata("/short.txt").err(), Some(Error::NotFound));
+    assert_eq!(
+        fs::rename("/very", "/very2").err(),
+        Some(Error::CrossesDevices)
+    );
+    fs::rename("/short2.txt", "/short.txt")?;
+
+    println!("test_overlay() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_overlayfs() {
+    println!("Testing overlayfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::initrd::set_initrd(load_archive(CPIO_PATH).expect("failed to load initrd"));
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::new(0x1000))); // dummy disk, only listed in /dev.
+
+    test_overlay().expect("test_overlay() failed");
+    test_common::test_all();
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "diskfs")]
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, FileType};
+use driver_block::ramdisk::RamDisk;
+
+const BLOCK_SIZE: usize = 512;
+const DISK_BLOCKS: usize = 8192;
+
+/// Writes an MBR entry of `len` blocks from `start` in `slot`.
+fn mbr_entry(data: &mut [u8], slot: usize, status: u8, ty: u8, start: u32, len: u32) {
+    let entry = &mut data[446 + slot * 16..][..16];
+    entry[0] = status;
+    entry[4] = ty;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&len.to_le_bytes());
+}
+
+/// A disk with a bootable FAT partition of 1 MiB, and a data partition of
+/// 2 MiB after it.
+fn make_disk() -> RamDisk {
+    let mut data = vec![0u8; DISK_BLOCKS * BLOCK_SIZE];
+    mbr_entry(&mut data, 0, 0x80, 0x0c, 2048, 2048);
+    mbr_entry(&mut data, 1, 0, 0x83, 4096, 4096);
+    data[510..512].copy_from_slice(&[0x55, 0xaa]);
+    RamDisk::from(&data)
+}
+
+#[test]
+fn test_partition() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(make_disk()));
+
+    // the data partition is the root, formatted as diskfs
+    let info = fs::statfs("/").unwrap();
+    assert_eq!(info.fs_type, axdiskfs::diskfs::CCFS_MAGIC);
+    assert!(info.blocks * info.block_size <= 4096 * BLOCK_SIZE as u64);
+    fs::write("/hello.txt", "Rust is cool!\n").unwrap();
+    assert_eq!(fs::read_to_string("/hello.txt").unwrap(), "Rust is cool!\n");
+    fs::sync().unwrap();
+
+    let mut devs: Vec<_> = fs::read_dir("/dev")
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|name| name.starts_with("vd"))
+        .collect();
+    devs.sort();
+    assert_eq!(devs, ["vda", "vda1", "vda2"]);
+    for (name, blocks) in [("vda", DISK_BLOCKS), ("vda1", 2048), ("vda2", 4096)] {
+        let md = fs::metadata(&format!("/dev/{name}")).unwrap();
+        assert_eq!(md.file_type(), FileType::BlockDevice);
+        assert_eq!(md.len(), (blocks * BLOCK_SIZE) as u64);
+    }
+}
@@ -8,7 +8,7 @@ use axfs::api::{self as fs, File};
 use axfs::fops::{Disk, MyFileSystemIf};
 use axfs_ramfs::RamFileSystem;
 use axfs_vfs::VfsOps;
-use axio::{Result, Write};
+use axio::{Error, Result, Write};
 use driver_block::ramdisk::RamDisk;
 
 struct MyFileSystemIfImpl;
@@ -40,16 +40,188 @@ fn create_init_files() -> Result<()> {
     Ok(())
 }
 
+fn test_symlinks() -> Result<()> {
+    fs::create_dir("/links")?;
+    fs::write("/links/file.txt", "Rust is cool!\n")?;
+    fs::symlink("file.txt", "/links/rel")?;
+    fs::symlink("/links", "/links/dir")?;
+    fs::symlink("/tmp", "links/tmp")?;
+    fs::symlink("missing.txt", "/links/dangling")?;
+    fs::symlink("loop1", "/links/loop2")?;
+    fs::symlink("loop2", "/links/loop1")?;
+
+    // follow links in all components
+    assert_eq!(fs::read_to_string("/links/rel")?, "Rust is cool!\n");
+    assert_eq!(
+        fs::read_to_string("/links/dir/dir/./rel")?,
+        "Rust is cool!\n"
+    );
+    assert_eq!(fs::read_link("/links/dir/rel")?, "file.txt");
+    assert!(fs::metadata("/links/rel")?.is_file());
+    assert!(fs::metadata("/links/dir/")?.is_dir());
+    assert!(fs::symlink_metadata("/links/rel")?.is_symlink());
+    assert_eq!(fs::symlink_metadata("/links/rel")?.len(), 8);
+
+    // across mount points
+    fs::write("/links/tmp/file.txt", "in tmp")?;
+    assert_eq!(fs::read_to_string("/tmp/file.txt")?, "in tmp");
+    assert_eq!(
+        fs::read_to_string("/links/tmp/../links/rel")?,
+        "Rust is cool!\n"
+    );
+
+    // create the target of a dangling link
+    assert_eq!(fs::metadata("/links/dangling").err(), Some(Error::NotFound));
+    fs::write("/links/dangling", "created")?;
+    assert_eq!(fs::read_to_string("/links/missing.txt")?, "created");
+
+    // hard links
+    fs::hard_link("/links/rel", "/links/hard")?;
+    assert!(fs::symlink_metadata("/links/hard")?.is_symlink());
+    fs::hard_link("/links/file.txt", "/links/dir/hard.txt")?;
+    fs::write("/links/hard.txt", "changed")?;
+    assert_eq!(fs::read_to_string("/links/file.txt")?, "changed");
+    fs::remove_file("/links/file.txt")?;
+    assert_eq!(fs::read_to_string("/links/hard.txt")?, "changed");
+    assert_eq!(
+        fs::hard_link("/links/hard.txt", "/tmp/hard.txt").err(),
+        Some(Error::CrossesDevices)
+    );
+    assert_eq!(
+        fs::hard_link("/links", "/links2").err(),
+        Some(Error::PermissionDenied)
+    );
+
+    // error cases
+    assert_eq!(
+        fs::symlink("file.txt", "/links/rel").err(),
+        Some(Error::AlreadyExists)
+    );
+    assert_eq!(
+        fs::read_link("/links/hard.txt").err(),
+        Some(Error::InvalidInput)
+    );
+    assert_eq!(
+        fs::read_to_string("/links/loop1").err(),
+        Some(Error::FilesystemLoop)
+    );
+    assert_eq!(
+        fs::read_to_string("/links/loop1/x").err(),
+        Some(Error::FilesystemLoop)
+    );
+    assert!(fs::symlink_metadata("/links/loop1")?.is_symlink());
+
+    // remove links, not their targets
+    fs::remove_file("/links/tmp")?;
+    assert!(fs::metadata("/tmp/file.txt")?.is_file());
+    assert_eq!(
+        fs::remove_dir("/links/dir").err(),
+        Some(Error::NotADirectory)
+    );
+    fs::remove_file("/links/dir")?;
+    assert!(fs::metadata("/links")?.is_dir());
+
+    println!("test_symlinks() OK!");
+    Ok(())
+}
+
+fn test_mounts() -> Result<()> {
+    const RAMFS_MAGIC: u64 = 0x8584_58f6;
+    fs::create_dir("/mnt")?;
+    fs::write("/mnt/hidden.txt", "in main fs")?;
+
+    // mount at runtime, hiding the original contents
+    fs::mount("tmpfs", "/mnt", "tmpfs")?;
+    assert_eq!(fs::statfs("/mnt")?.fs_type, RAMFS_MAGIC);
+    assert_eq!(fs::metadata("/mnt/hidden.txt").err(), Some(Error::NotFound));
+    fs::write("/mnt/a.txt", "in mnt")?;
+
+    // nested mount points
+    fs::create_dir("/mnt/sub")?;
+    fs::mount("tmpfs", "/mnt/sub/", "ramfs")?;
+    fs::write("/mnt/sub/b.txt", "in sub")?;
+    assert_eq!(fs::read_to_string("/mnt/sub/../a.txt")?, "in mnt");
+    assert_eq!(fs::read_to_string("/mnt/./sub//b.txt")?, "in sub");
+    assert!(fs::mount_points().contains(&"/mnt/sub".into()));
+    assert_eq!(
+        fs::hard_link("/mnt/a.txt", "/mnt/sub/a.txt").err(),
+        Some(Error::CrossesDevices)
+    );
+
+    // busy mount points
+    assert_eq!(fs::umount("/mnt").err(), Some(Error::ResourceBusy));
+    fs::set_current_dir("/mnt/sub")?;
+    assert_eq!(fs::read_to_string("b.txt")?, "in sub");
+    assert_eq!(fs::read_to_string("../a.txt")?, "in mnt");
+    assert_eq!(fs::umount("/mnt/sub").err(), Some(Error::ResourceBusy));
+    fs::set_current_dir("/mnt")?;
+    assert_eq!(fs::read_to_string("sub/b.txt")?, "in sub");
+    let file = File::open("sub/b.txt")?;
+    assert_eq!(fs::umount("sub").err(), Some(Error::ResourceBusy));
+    drop(file);
+    fs::umount("sub")?;
+    assert_eq!(fs::metadata("sub/b.txt").err(), Some(Error::NotFound));
+    fs::set_current_dir("/")?;
+    fs::umount("/mnt")?;
+    assert_eq!(fs::read_to_string("/mnt/hidden.txt")?, "in main fs");
+    assert!(!fs::mount_points().contains(&"/mnt".into()));
+
+    // bind mounts
+    fs::create_dir("/tmp/bind")?;
+    fs::bind_mount("/very/long", "/tmp/bind")?;
+    assert_eq!(
+        fs::read("/tmp/bind/path/test.txt")?,
+        fs::read("/very/long/path/test.txt")?
+    );
+    fs::write("/tmp/bind/new.txt", "via bind")?;
+    assert_eq!(fs::read_to_string("/very/long/new.txt")?, "via bind");
+    fs::umount("/tmp/bind")?;
+    assert_eq!(
+        fs::metadata("/tmp/bind/new.txt").err(),
+        Some(Error::NotFound)
+    );
+    assert_eq!(fs::read_to_string("/very/long/new.txt")?, "via bind");
+
+    // error cases
+    assert_eq!(
+        fs::mount("tmpfs", "/none", "tmpfs").err(),
+        Some(Error::NotFound)
+    );
+    assert_eq!(
+        fs::mount("tmpfs", "/long.txt", "tmpfs").err(),
+        Some(Error::NotADirectory)
+    );
+    assert_eq!(
+        fs::mount("none", "/mnt", "nofs").err(),
+        Some(Error::Unsupported)
+    );
+    assert_eq!(
+        fs::mount("tmpfs", "/", "tmpfs").err(),
+        Some(Error::InvalidInput)
+    );
+    assert_eq!(
+        fs::mount("tmpfs", "/tmp", "tmpfs").err(),
+        Some(Error::ResourceBusy)
+    );
+    assert_eq!(fs::umount("/mnt").err(), Some(Error::InvalidInput));
+    assert_eq!(fs::umount("/").err(), Some(Error::ResourceBusy));
+
+    println!("test_mounts() OK!");
+    Ok(())
+}
+
 #[test]
 fn test_ramfs() {
     println!("Testing ramfs ...");
 
     axtask::init_scheduler(); // call this to use `axsync::Mutex`.
-    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // dummy disk, actually not used.
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::new(0x1000))); // dummy disk, only listed in /dev.
 
     if let Err(e) = create_init_files() {
         log::warn!("failed to create init files: {:?}", e);
     }
 
     test_common::test_all();
+    test_symlinks().expect("test_symlinks() failed");
+    test_mounts().expect("test_mounts() failed");
 }
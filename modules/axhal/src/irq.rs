@@ -1,5 +1,7 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use handler_table::HandlerTable;
 
 use crate::platform::irq::MAX_IRQ_COUNT;
@@ -11,10 +13,35 @@ pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+static IRQ_COUNTS: [AtomicUsize; MAX_IRQ_COUNT] = [ZERO; MAX_IRQ_COUNT];
+
+/// Returns the number of times the IRQ `irq_num` has been dispatched to the
+/// handler table since boot, on all CPUs.
+pub fn irq_count(irq_num: usize) -> usize {
+    IRQ_COUNTS
+        .get(irq_num)
+        .map_or(0, |cnt| cnt.load(Ordering::Relaxed))
+}
+
+/// Returns an iterator over the IRQ numbers and counts of all IRQs that have
+/// occurred at least once.
+pub fn irq_counts() -> impl Iterator<Item = (usize, usize)> {
+    IRQ_COUNTS
+        .iter()
+        .map(|cnt| cnt.load(Ordering::Relaxed))
+        .enumerate()
+        .filter(|&(_, cnt)| cnt > 0)
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    if let Some(cnt) = IRQ_COUNTS.get(irq_num) {
+        cnt.fetch_add(1, Ordering::Relaxed);
+    }
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
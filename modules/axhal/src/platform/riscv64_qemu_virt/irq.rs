@@ -1,4 +1,4 @@
-//! TODO: PLIC
+//! Interrupt handling through the local interrupt controller and the PLIC.
 
 use crate::irq::IrqHandler;
 use lazy_init::LazyInit;
@@ -35,28 +35,110 @@ macro_rules! with_cause {
     };
 }
 
+/// The platform-level interrupt controller (PLIC), which routes the external
+/// interrupts of devices to the supervisor mode of harts.
+mod plic {
+    use crate::mem::phys_to_virt;
+    use memory_addr::PhysAddr;
+    use spinlock::SpinNoIrq;
+
+    const PLIC_BASE: PhysAddr = PhysAddr::from(axconfig::PLIC_PADDR);
+
+    const PRIORITY_BASE: usize = 0;
+    const ENABLE_BASE: usize = 0x2000;
+    const ENABLE_STRIDE: usize = 0x80;
+    const CONTEXT_BASE: usize = 0x20_0000;
+    const CONTEXT_STRIDE: usize = 0x1000;
+    const CONTEXT_THRESHOLD: usize = 0;
+    const CONTEXT_CLAIM: usize = 4;
+
+    /// Serializes the read-modify-write of enable bits.
+    static ENABLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+    fn reg(offset: usize) -> *mut u32 {
+        phys_to_virt(PLIC_BASE + offset).as_mut_ptr() as *mut u32
+    }
+
+    /// The supervisor-mode context of the current hart.
+    fn this_context() -> usize {
+        2 * crate::cpu::this_cpu_id() + 1
+    }
+
+    fn context_reg(offset: usize) -> *mut u32 {
+        reg(CONTEXT_BASE + CONTEXT_STRIDE * this_context() + offset)
+    }
+
+    /// Enables the source `irq_num` for the current hart, or masks it for all
+    /// harts, by its priority as the enable bits are per hart.
+    ///
+    /// The enable bits are kept when masked, so that it can be unmasked by
+    /// any hart, e.g., in a task migrated to another hart.
+    pub fn set_enable(irq_num: usize, enabled: bool) {
+        let priority = reg(PRIORITY_BASE + irq_num * 4);
+        if !enabled {
+            // SAFETY: the register is in the PLIC MMIO region.
+            // priority 0 means never interrupt
+            unsafe { priority.write_volatile(0) };
+            return;
+        }
+        let enable = reg(ENABLE_BASE + ENABLE_STRIDE * this_context() + irq_num / 32 * 4);
+        let bit = 1 << (irq_num % 32);
+        let _guard = ENABLE_LOCK.lock();
+        // SAFETY: the registers are in the PLIC MMIO region.
+        unsafe {
+            enable.write_volatile(enable.read_volatile() | bit);
+            priority.write_volatile(1);
+        }
+    }
+
+    /// Claims the pending interrupt with the highest priority, returns `0` if
+    /// there is none.
+    pub fn claim() -> usize {
+        // SAFETY: the register is in the PLIC MMIO region.
+        unsafe { context_reg(CONTEXT_CLAIM).read_volatile() as usize }
+    }
+
+    /// Signals the completion of a claimed interrupt.
+    pub fn complete(irq_num: usize) {
+        // SAFETY: the register is in the PLIC MMIO region.
+        unsafe { context_reg(CONTEXT_CLAIM).write_volatile(irq_num as u32) }
+    }
+
+    pub fn init_percpu() {
+        // accept interrupts of all priorities
+        // SAFETY: the register is in the PLIC MMIO region.
+        unsafe { context_reg(CONTEXT_THRESHOLD).write_volatile(0) }
+    }
+}
+
 /// Enables or disables the given IRQ.
-pub fn set_enable(scause: usize, _enabled: bool) {
-    if scause == S_EXT {
-        // TODO: set enable in PLIC
+///
+/// IRQs of devices are numbered by their PLIC interrupt sources, while the
+/// timer IRQ is always enabled.
+pub fn set_enable(irq_num: usize, enabled: bool) {
+    if irq_num & INTC_IRQ_BASE == 0 {
+        plic::set_enable(irq_num, enabled);
     }
 }
 
-/// Registers an IRQ handler for the given IRQ.
+/// Registers an IRQ handler for the given IRQ, which is either the timer IRQ
+/// or a PLIC interrupt source.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
 /// the registration failed.
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    with_cause!(
-        scause,
-        @TIMER => if !TIMER_HANDLER.is_init() {
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    if irq_num == S_TIMER {
+        if !TIMER_HANDLER.is_init() {
             TIMER_HANDLER.init_by(handler);
-            true
-        } else {
-            false
-        },
-        @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
-    )
+            return true;
+        }
+        false
+    } else if irq_num & INTC_IRQ_BASE == 0 {
+        crate::irq::register_handler_common(irq_num, handler)
+    } else {
+        warn!("register handler for IRQ {:#x} failed", irq_num);
+        false
+    }
 }
 
 /// Dispatches the IRQ.
@@ -71,7 +153,13 @@ pub fn dispatch_irq(scause: usize) {
             trace!("IRQ: timer");
             TIMER_HANDLER();
         },
-        @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
+        @EXT => {
+            let irq_num = plic::claim();
+            if irq_num != 0 {
+                crate::irq::dispatch_irq_common(irq_num);
+                plic::complete(irq_num);
+            }
+        },
     );
 }
 
@@ -82,4 +170,5 @@ pub(super) fn init_percpu() {
         sie::set_stimer();
         sie::set_sext();
     }
+    plic::init_percpu();
 }
@@ -9,11 +9,28 @@
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`interfaces`], [`set_interface_addrs`]: Functions to list and configure
+//!   the network interfaces, the loopback interface `lo` and one for each NIC.
+//! - [`routes`], [`add_route`], [`del_route`]: Functions to manage the routing
+//!   table, which chooses the interface for each destination.
+//!
+//! Both IPv4 and IPv6 are supported. Besides the static addresses, each NIC
+//! configures its IPv6 addresses by SLAAC, from a link-local address and the
+//! prefixes announced by the routers.
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `multitask` and `irq`: When both are enabled, the NIC is polled by a
+//!   background task driven by its interrupts and the timer, and blocking
+//!   socket operations sleep until the socket is ready, instead of polling and
+//!   yielding in a loop. The NICs that can't raise interrupts (e.g., PCI
+//!   NICs on x86) are polled by the timer.
+//! - `dhcp`: Configure the address, the default gateway and the DNS servers
+//!   of each NIC by DHCP. The static configuration of `eth0`, given by the
+//!   `AX_IP` and `AX_GW` environment variables at compile time, is used until
+//!   a lease is acquired, and after it is lost.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -34,16 +51,29 @@ cfg_if::cfg_if! {
 
 pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+pub use self::net_impl::{add_route, del_route, routes, RouteEntry};
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{interfaces, set_interface_addrs, NetIfaceInfo};
+pub use self::net_impl::{listen_backlog, set_listen_backlog};
+pub use self::net_impl::{net_dev_stats, NetDevStats};
 
+use alloc::vec::Vec;
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes the network subsystem by NIC devices.
+///
+/// The loopback interface is always available, even without any NIC.
 pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>) {
     info!("Initialize network subsystem...");
 
-    let dev = net_devs.take_one().expect("No NIC device found!");
-    info!("  use NIC 0: {:?}", dev.device_name());
-    net_impl::init(dev);
+    let mut devs = Vec::new();
+    while let Some(dev) = net_devs.take_one() {
+        info!("  use NIC {}: {:?}", devs.len(), dev.device_name());
+        devs.push(dev);
+    }
+    if devs.is_empty() {
+        warn!("No NIC device found!");
+    }
+    net_impl::init(devs);
 }
@@ -0,0 +1,238 @@
+//! The DHCPv4 client.
+//!
+//! Each NIC has a DHCP socket, which acquires a lease from a DHCP server and
+//! renews it. The leased address replaces the IPv4 addresses of the interface,
+//! the router becomes its default gateway, and the DNS servers are used by
+//! [`dns_query`](super::dns_query).
+//!
+//! The static configuration of the interface, if any, is used until the first
+//! lease is acquired, and again when the lease is lost.
+
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+use axhal::time::current_time;
+use axsync::Mutex;
+use lazy_init::LazyInit;
+use smoltcp::socket::dhcpv4::{self, Event};
+use smoltcp::wire::{IpAddress, IpCidr, IpVersion};
+
+use super::addr::UNSPECIFIED_IP;
+use super::{route, SocketHandle, IFACES, SOCKET_SET};
+
+/// How long to wait at boot for the first leases of the interfaces without
+/// static addresses.
+const INIT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the interfaces are polled while waiting at boot.
+const INIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static CLIENTS: LazyInit<Vec<DhcpClient>> = LazyInit::new();
+
+/// The DHCP client of an interface.
+struct DhcpClient {
+    handle: SocketHandle,
+    state: Mutex<DhcpState>,
+}
+
+/// The IPv4 addresses and the default gateway of an interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ipv4Config {
+    addrs: Vec<IpCidr>,
+    gateway: Option<IpAddress>,
+}
+
+/// The configuration leased from a DHCP server.
+struct Lease {
+    addr: IpCidr,
+    router: Option<IpAddress>,
+    dns_servers: Vec<IpAddress>,
+}
+
+/// The static configuration of an interface, and its current lease.
+struct DhcpState {
+    static_config: Ipv4Config,
+    lease: Option<Lease>,
+}
+
+impl DhcpState {
+    fn new(static_config: Ipv4Config) -> Self {
+        Self {
+            static_config,
+            lease: None,
+        }
+    }
+
+    /// Replaces the lease, `None` if it is lost, and returns the configuration
+    /// to apply to the interface.
+    fn update(&mut self, lease: Option<Lease>) -> Ipv4Config {
+        self.lease = lease;
+        match &self.lease {
+            Some(lease) => Ipv4Config {
+                addrs: vec![lease.addr],
+                gateway: lease.router,
+            },
+            None => self.static_config.clone(),
+        }
+    }
+
+    /// Whether the interface has no address until a lease is acquired.
+    fn is_waiting(&self) -> bool {
+        self.static_config.addrs.is_empty() && self.lease.is_none()
+    }
+
+    fn dns_servers(&self) -> &[IpAddress] {
+        self.lease.as_ref().map_or(&[], |lease| &lease.dns_servers)
+    }
+}
+
+impl DhcpClient {
+    fn new(iface: usize) -> Self {
+        let static_config = Ipv4Config {
+            addrs: IFACES[iface]
+                .ip_addrs()
+                .into_iter()
+                .filter(|cidr| cidr.address().version() == IpVersion::Ipv4)
+                .collect(),
+            gateway: route::gateway(IpCidr::new(UNSPECIFIED_IP, 0), iface),
+        };
+        Self {
+            handle: SOCKET_SET.add(iface, dhcpv4::Socket::new()),
+            state: Mutex::new(DhcpState::new(static_config)),
+        }
+    }
+
+    /// Takes the event of the DHCP socket, `Some(None)` if the lease is lost.
+    fn poll(&self) -> Option<Option<Lease>> {
+        SOCKET_SET.with_socket_mut::<dhcpv4::Socket, _, _>(self.handle, |socket| {
+            socket.poll().map(|event| match event {
+                Event::Configured(config) => Some(Lease {
+                    addr: IpCidr::Ipv4(config.address),
+                    router: config.router.map(IpAddress::Ipv4),
+                    dns_servers: config.dns_servers.iter().map(|&addr| addr.into()).collect(),
+                }),
+                Event::Deconfigured => None,
+            })
+        })
+    }
+
+    fn update(&self, lease: Option<Lease>) {
+        let iface = &IFACES[self.handle.iface];
+        match &lease {
+            Some(lease) => info!(
+                "{}: DHCP lease acquired: {}, gateway {:?}, DNS servers {:?}",
+                iface.name, lease.addr, lease.router, lease.dns_servers
+            ),
+            None => info!(
+                "{}: DHCP lease lost, using the static configuration",
+                iface.name
+            ),
+        }
+        let config = self.state.lock().update(lease);
+        if let Err(e) = iface.set_ip_addrs_of(IpVersion::Ipv4, &config.addrs) {
+            warn!("{}: failed to set IP addresses: {:?}", iface.name, e);
+        }
+        let default = IpCidr::new(UNSPECIFIED_IP, 0);
+        if let Err(e) = route::replace(default, config.gateway, iface.index) {
+            warn!("{}: failed to set default gateway: {:?}", iface.name, e);
+        }
+    }
+}
+
+/// Processes the events of the DHCP sockets, after the interfaces are polled.
+pub(crate) fn poll() {
+    let Some(clients) = CLIENTS.try_get() else {
+        return;
+    };
+    for client in clients {
+        if let Some(lease) = client.poll() {
+            client.update(lease);
+        }
+    }
+}
+
+/// Returns the DNS servers leased on all interfaces.
+pub(crate) fn dns_servers() -> Vec<IpAddress> {
+    let Some(clients) = CLIENTS.try_get() else {
+        return Vec::new();
+    };
+    clients
+        .iter()
+        .flat_map(|client| client.state.lock().dns_servers().to_vec())
+        .collect()
+}
+
+pub(crate) fn init() {
+    let nics = IFACES.iter().filter(|iface| !iface.is_loopback());
+    CLIENTS.init_by(nics.map(|iface| DhcpClient::new(iface.index)).collect());
+
+    let waiting = || {
+        CLIENTS
+            .iter()
+            .any(|client| client.state.lock().is_waiting())
+    };
+    let deadline = current_time() + INIT_TIMEOUT;
+    while waiting() {
+        if current_time() >= deadline {
+            warn!("DHCP timed out, will keep trying in the background");
+            break;
+        }
+        SOCKET_SET.poll_interfaces();
+        axtask::sleep(INIT_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn static_config() -> Ipv4Config {
+        Ipv4Config {
+            addrs: vec![IpCidr::new(IpAddress::v4(10, 0, 2, 15), 24)],
+            gateway: Some(IpAddress::v4(10, 0, 2, 2)),
+        }
+    }
+
+    fn lease(router: Option<IpAddress>) -> Lease {
+        Lease {
+            addr: IpCidr::new(IpAddress::v4(192, 168, 1, 100), 24),
+            router,
+            dns_servers: vec![IpAddress::v4(192, 168, 1, 53)],
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let mut state = DhcpState::new(static_config());
+        assert!(!state.is_waiting());
+        assert!(state.dns_servers().is_empty());
+
+        let router = IpAddress::v4(192, 168, 1, 1);
+        let config = state.update(Some(lease(Some(router))));
+        assert_eq!(
+            config.addrs,
+            [IpCidr::new(IpAddress::v4(192, 168, 1, 100), 24)]
+        );
+        assert_eq!(config.gateway, Some(router));
+        assert_eq!(state.dns_servers(), [IpAddress::v4(192, 168, 1, 53)]);
+
+        // the static gateway is not used with a lease
+        assert_eq!(state.update(Some(lease(None))).gateway, None);
+
+        assert_eq!(state.update(None), static_config());
+        assert!(state.dns_servers().is_empty());
+    }
+
+    #[test]
+    fn test_no_static_config() {
+        let no_config = Ipv4Config {
+            addrs: Vec::new(),
+            gateway: None,
+        };
+        let mut state = DhcpState::new(no_config.clone());
+        assert!(state.is_waiting());
+        state.update(Some(lease(Some(IpAddress::v4(192, 168, 1, 1)))));
+        assert!(!state.is_waiting());
+        assert_eq!(state.update(None), no_config);
+        assert!(state.is_waiting());
+    }
+}
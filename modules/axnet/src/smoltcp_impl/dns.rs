@@ -1,13 +1,19 @@
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 use axerrno::{ax_err_type, AxError, AxResult};
 use core::net::IpAddr;
+use core::task::Waker;
 
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
-use smoltcp::wire::DnsQueryType;
+use smoltcp::wire::{DnsQueryType, IpAddress};
 
 use super::addr::into_core_ipaddr;
-use super::{SocketSetWrapper, ETH0, SOCKET_SET};
+use super::poll::SocketWaiter;
+use super::route;
+use super::{SocketHandle, SocketSetWrapper, DNS_SEVER, IFACES, SOCKET_SET};
+
+/// The maximum number of DNS servers of a socket, set by the smoltcp feature
+/// `dns-max-server-count-4`.
+const MAX_SERVERS: usize = 4;
 
 /// A DNS socket.
 struct DnsSocket {
@@ -15,17 +21,23 @@ struct DnsSocket {
 }
 
 impl DnsSocket {
-    #[allow(clippy::new_without_default)]
-    /// Creates a new DNS socket.
-    pub fn new() -> Self {
+    /// Creates a new DNS socket, on the interface to send packets to the first
+    /// DNS server.
+    pub fn new() -> AxResult<Self> {
+        let mut servers = dns_servers();
+        servers.truncate(MAX_SERVERS);
+        let iface = route::lookup(servers[0])
+            .ok_or_else(|| ax_err_type!(NetworkUnreachable, "no route to the DNS server"))?;
         let socket = SocketSetWrapper::new_dns_socket();
-        let handle = Some(SOCKET_SET.add(socket));
-        Self { handle }
+        let dns = Self {
+            handle: Some(SOCKET_SET.add(iface, socket)),
+        };
+        dns.update_servers(&servers);
+        Ok(dns)
     }
 
-    #[allow(dead_code)]
     /// Update the list of DNS servers, will replace all existing servers.
-    pub fn update_servers(self, servers: &[smoltcp::wire::IpAddress]) {
+    pub fn update_servers(&self, servers: &[IpAddress]) {
         SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(self.handle.unwrap(), |socket| {
             socket.update_servers(servers)
         });
@@ -35,10 +47,10 @@ impl DnsSocket {
     pub fn query(&self, name: &str, query_type: DnsQueryType) -> AxResult<Vec<IpAddr>> {
         // let local_addr = self.local_addr.unwrap_or_else(f);
         let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
-        let iface = &ETH0.iface;
+        let mut iface = IFACES[handle.iface].iface.lock();
         let query_handle = SOCKET_SET
             .with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
-                socket.start_query(iface.lock().context(), name, query_type)
+                socket.start_query(iface.context(), name, query_type)
             })
             .map_err(|e| match e {
                 StartQueryError::NoFreeSlot => {
@@ -51,27 +63,23 @@ impl DnsSocket {
                     ax_err_type!(InvalidInput, "socket query() failed: too long name")
                 }
             })?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
+        drop(iface);
+        let register = |waker: &Waker| {
+            SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
+                socket.register_query_waker(query_handle, waker)
+            })
+        };
+        let addrs = SocketWaiter::new().block_on(register, || {
+            SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
                 socket.get_query_result(query_handle).map_err(|e| match e {
                     GetQueryResultError::Pending => AxError::WouldBlock,
                     GetQueryResultError::Failed => {
                         ax_err_type!(ConnectionRefused, "socket query() failed")
                     }
                 })
-            }) {
-                Ok(n) => {
-                    let mut res = Vec::with_capacity(n.capacity());
-                    for ip in n {
-                        res.push(into_core_ipaddr(ip))
-                    }
-                    return Ok(res);
-                }
-                Err(AxError::WouldBlock) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+            })
+        })?;
+        Ok(addrs.into_iter().map(into_core_ipaddr).collect())
     }
 }
 
@@ -83,8 +91,33 @@ impl Drop for DnsSocket {
     }
 }
 
+/// Returns the DNS servers leased by DHCP, or the default one if there is
+/// none.
+fn dns_servers() -> Vec<IpAddress> {
+    #[cfg(feature = "dhcp")]
+    {
+        let servers = super::dhcp::dns_servers();
+        if !servers.is_empty() {
+            return servers;
+        }
+    }
+    vec![DNS_SEVER.parse().expect("invalid DNS server address")]
+}
+
 /// Public function for DNS query.
+///
+/// It returns the IPv4 addresses (A records) followed by the IPv6 addresses
+/// (AAAA records) of `name`.
 pub fn dns_query(name: &str) -> AxResult<alloc::vec::Vec<IpAddr>> {
-    let socket = DnsSocket::new();
-    socket.query(name, DnsQueryType::A)
+    let socket = DnsSocket::new()?;
+    let ipv4_addrs = socket.query(name, DnsQueryType::A);
+    let ipv6_addrs = socket.query(name, DnsQueryType::Aaaa);
+    match (ipv4_addrs, ipv6_addrs) {
+        (Ok(mut addrs), Ok(ipv6_addrs)) => {
+            addrs.extend(ipv6_addrs);
+            Ok(addrs)
+        }
+        (Ok(addrs), Err(_)) | (Err(_), Ok(addrs)) => Ok(addrs),
+        (Err(e), Err(_)) => Err(e),
+    }
 }
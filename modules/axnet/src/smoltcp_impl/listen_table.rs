@@ -1,26 +1,34 @@
 use alloc::{boxed::Box, collections::VecDeque};
 use core::ops::{Deref, DerefMut};
+use core::sync::atomic::Ordering;
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
-use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::iface::SocketSet;
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::{SocketHandle, SocketSetWrapper, LISTEN_BACKLOG, LISTEN_QUEUE_SIZE, SOCKET_SET};
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
     syn_queue: VecDeque<SocketHandle>,
+    backlog: usize,
+    /// Woken up when an incoming connection makes progress.
+    waker: Waker,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(listen_endpoint: IpListenEndpoint, waker: Waker) -> Self {
+        let backlog = LISTEN_BACKLOG.load(Ordering::Relaxed);
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            syn_queue: VecDeque::with_capacity(backlog.min(LISTEN_QUEUE_SIZE)),
+            backlog,
+            waker,
         }
     }
 
@@ -41,6 +49,13 @@ impl Drop for ListenTableEntry {
     }
 }
 
+/// The TCP listening sockets indexed by port, which accept the connections
+/// from all interfaces.
+///
+/// The socket sets must be locked before an entry, as [`incoming_tcp_packet`]
+/// is called by the poll with the socket sets locked.
+///
+/// [`incoming_tcp_packet`]: ListenTable::incoming_tcp_packet
 pub struct ListenTable {
     tcp: Box<[Mutex<Option<Box<ListenTableEntry>>>]>,
 }
@@ -61,12 +76,14 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    /// Starts listening on `listen_endpoint`, `waker` is woken up when the
+    /// incoming connections make progress.
+    pub fn listen(&self, listen_endpoint: IpListenEndpoint, waker: Waker) -> AxResult {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
+            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint, waker)));
             Ok(())
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
@@ -75,25 +92,43 @@ impl ListenTable {
 
     pub fn unlisten(&self, port: u16) {
         debug!("TCP socket unlisten on {}", port);
-        *self.tcp[port as usize].lock() = None;
+        let entry = self.tcp[port as usize].lock().take();
+        drop(entry); // removes the pending sockets
     }
 
     pub fn can_accept(&self, port: u16) -> AxResult<bool> {
+        let sockets = SOCKET_SET.0.lock();
         if let Some(entry) = self.tcp[port as usize].lock().deref() {
-            Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
+            Ok(entry
+                .syn_queue
+                .iter()
+                .any(|&handle| is_connected(&sockets, handle)))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
     }
 
+    /// Registers `waker` to the pending connections on `port`, to be woken up
+    /// when one of them is established.
+    pub fn register_waker(&self, port: u16, waker: &Waker) {
+        let mut sockets = SOCKET_SET.0.lock();
+        if let Some(entry) = self.tcp[port as usize].lock().deref() {
+            for &handle in &entry.syn_queue {
+                let socket = sockets[handle.iface].get_mut::<tcp::Socket>(handle.inner);
+                socket.register_recv_waker(waker);
+            }
+        }
+    }
+
     pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
+        let sockets = SOCKET_SET.0.lock();
         if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
             let syn_queue = &mut entry.syn_queue;
             let (idx, addr_tuple) = syn_queue
                 .iter()
                 .enumerate()
                 .find_map(|(idx, &handle)| {
-                    is_connected(handle).then(|| (idx, get_addr_tuple(handle)))
+                    is_connected(&sockets, handle).then(|| (idx, get_addr_tuple(&sockets, handle)))
                 })
                 .ok_or(AxError::WouldBlock)?; // wait for connection
             if idx > 0 {
@@ -110,10 +145,13 @@ impl ListenTable {
         }
     }
 
+    /// Creates a socket in `sockets` of the interface `iface` for the first
+    /// incoming packet of a connection, if its port is being listened on.
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
         dst: IpEndpoint,
+        iface: usize,
         sockets: &mut SocketSet<'_>,
     ) {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
@@ -121,14 +159,18 @@ impl ListenTable {
                 // not listening on this address
                 return;
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
+            if entry.syn_queue.len() >= entry.backlog {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
             }
             let mut socket = SocketSetWrapper::new_tcp_socket();
             if socket.listen(entry.listen_endpoint).is_ok() {
-                let handle = sockets.add(socket);
+                socket.register_recv_waker(&entry.waker);
+                let handle = SocketHandle {
+                    iface,
+                    inner: sockets.add(socket),
+                };
                 debug!(
                     "TCP socket {}: prepare for connection {} -> {}",
                     handle, src, entry.listen_endpoint
@@ -139,17 +181,15 @@ impl ListenTable {
     }
 }
 
-fn is_connected(handle: SocketHandle) -> bool {
-    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-        !matches!(socket.state(), State::Listen | State::SynReceived)
-    })
+fn is_connected(sockets: &[SocketSet<'_>], handle: SocketHandle) -> bool {
+    let socket = sockets[handle.iface].get::<tcp::Socket>(handle.inner);
+    !matches!(socket.state(), State::Listen | State::SynReceived)
 }
 
-fn get_addr_tuple(handle: SocketHandle) -> (IpEndpoint, IpEndpoint) {
-    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-        (
-            socket.local_endpoint().unwrap(),
-            socket.remote_endpoint().unwrap(),
-        )
-    })
+fn get_addr_tuple(sockets: &[SocketSet<'_>], handle: SocketHandle) -> (IpEndpoint, IpEndpoint) {
+    let socket = sockets[handle.iface].get::<tcp::Socket>(handle.inner);
+    (
+        socket.local_endpoint().unwrap(),
+        socket.remote_endpoint().unwrap(),
+    )
 }
@@ -0,0 +1,109 @@
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::sync::atomic::Ordering;
+
+use smoltcp::iface::SocketSet;
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::time::Instant;
+
+use super::{snoop_packet, DeviceStats};
+
+/// The largest IP packet.
+const LOOPBACK_MTU: usize = 65535;
+
+/// A device that receives the IP packets it transmits.
+///
+/// Unlike [`smoltcp::phy::Loopback`], it creates the sockets for the incoming
+/// TCP connections of the listening sockets, as the NICs do.
+pub(super) struct LoopbackDev {
+    queue: VecDeque<Vec<u8>>,
+    iface: usize,
+    pub(super) stats: DeviceStats,
+}
+
+impl LoopbackDev {
+    pub fn new(iface: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            iface,
+            stats: DeviceStats::default(),
+        }
+    }
+}
+
+impl Device for LoopbackDev {
+    type RxToken<'a> = LoopbackRxToken<'a> where Self: 'a;
+    type TxToken<'a> = LoopbackTxToken<'a> where Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buf = self.queue.pop_front()?;
+        let rx_token = LoopbackRxToken {
+            buf,
+            iface: self.iface,
+            stats: &self.stats,
+        };
+        let tx_token = LoopbackTxToken {
+            queue: &mut self.queue,
+            stats: &self.stats,
+        };
+        Some((rx_token, tx_token))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken {
+            queue: &mut self.queue,
+            stats: &self.stats,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = LOOPBACK_MTU;
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ip;
+        caps
+    }
+}
+
+pub(super) struct LoopbackRxToken<'a> {
+    buf: Vec<u8>,
+    iface: usize,
+    stats: &'a DeviceStats,
+}
+
+pub(super) struct LoopbackTxToken<'a> {
+    queue: &'a mut VecDeque<Vec<u8>>,
+    stats: &'a DeviceStats,
+}
+
+impl<'a> RxToken for LoopbackRxToken<'a> {
+    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
+        snoop_packet(&self.buf, self.iface, sockets).ok();
+    }
+
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        trace!("RECV {} bytes on loopback", self.buf.len());
+        self.stats.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .rx_bytes
+            .fetch_add(self.buf.len() as u64, Ordering::Relaxed);
+        f(&mut self.buf)
+    }
+}
+
+impl<'a> TxToken for LoopbackTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0; len];
+        let ret = f(&mut buf);
+        trace!("SEND {} bytes on loopback", len);
+        self.stats.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.stats.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        self.queue.push_back(buf);
+        ret
+    }
+}
@@ -1,28 +1,42 @@
 mod addr;
 mod bench;
+#[cfg(feature = "dhcp")]
+mod dhcp;
 mod dns;
 mod listen_table;
+mod loopback;
+mod poll;
+mod route;
+mod slaac;
 mod tcp;
 mod udp;
 
-use alloc::vec;
+use alloc::{boxed::Box, format, vec, vec::Vec};
 use core::cell::RefCell;
+use core::fmt;
+use core::net::IpAddr;
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
 
 use axdriver::prelude::*;
+use axerrno::{ax_err, ax_err_type, AxResult};
 use axhal::time::{current_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
 use driver_net::{DevError, NetBufPtr};
 use lazy_init::LazyInit;
-use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::iface::{Config, Interface, Route, SocketSet};
 use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpVersion, Ipv6Address};
 
+use self::addr::{into_core_ipaddr, UNSPECIFIED_IP};
 use self::listen_table::ListenTable;
+use self::loopback::LoopbackDev;
 
 pub use self::dns::dns_query;
+pub use self::route::{add_route, del_route, routes, RouteEntry};
 pub use self::tcp::TcpSocket;
 pub use self::udp::UdpSocket;
 
@@ -40,6 +54,11 @@ const GATEWAY: &str = env_or_default!("AX_GW");
 const DNS_SEVER: &str = "8.8.8.8";
 const IP_PREFIX: u8 = 24;
 
+const LOOPBACK_IP: IpAddress = IpAddress::v4(127, 0, 0, 1);
+const LOOPBACK_PREFIX: u8 = 8;
+const LOOPBACK_IPV6: IpAddress = IpAddress::Ipv6(Ipv6Address::LOOPBACK);
+const LOOPBACK_IPV6_PREFIX: u8 = 128;
+
 const STANDARD_MTU: usize = 1500;
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
@@ -50,26 +69,101 @@ const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
 const LISTEN_QUEUE_SIZE: usize = 512;
 
+/// The maximum length of the queue of pending connections of a listening
+/// socket, like `net.core.somaxconn` in Linux.
+static LISTEN_BACKLOG: AtomicUsize = AtomicUsize::new(LISTEN_QUEUE_SIZE);
+
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
+/// The loopback interface `lo` goes first, followed by a `eth*` interface
+/// for each NIC.
+static IFACES: LazyInit<Vec<InterfaceWrapper>> = LazyInit::new();
 
-struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
+/// The sockets of all interfaces, indexed by the interface index.
+///
+/// Each interface has its own socket set, and only sends and receives packets
+/// for the sockets in it.
+struct SocketSetWrapper<'a>(Mutex<Vec<SocketSet<'a>>>);
+
+/// A handle to a socket in the socket set of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SocketHandle {
+    iface: usize,
+    inner: smoltcp::iface::SocketHandle,
+}
 
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
+    iface: usize,
+    stats: DeviceStats,
+}
+
+/// The device of an interface.
+enum InterfaceDevice {
+    Nic(DeviceWrapper),
+    Loopback(LoopbackDev),
+}
+
+#[derive(Default)]
+struct DeviceStats {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    rx_errors: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_errors: AtomicU64,
+}
+
+/// Packet counters of a network interface, similar to `/proc/net/dev` in
+/// Linux.
+#[derive(Debug, Clone)]
+pub struct NetDevStats {
+    /// The interface name.
+    pub name: &'static str,
+    /// Number of packets received.
+    pub rx_packets: u64,
+    /// Number of bytes received.
+    pub rx_bytes: u64,
+    /// Number of receive errors.
+    pub rx_errors: u64,
+    /// Number of packets transmitted.
+    pub tx_packets: u64,
+    /// Number of bytes transmitted.
+    pub tx_bytes: u64,
+    /// Number of transmit errors.
+    pub tx_errors: u64,
+}
+
+/// The configuration of a network interface.
+#[derive(Debug, Clone)]
+pub struct NetIfaceInfo {
+    /// The interface name.
+    pub name: &'static str,
+    /// The hardware (MAC) address.
+    pub mac_addr: [u8; 6],
+    /// The IP addresses, with the prefix lengths of their networks.
+    pub addrs: Vec<(IpAddr, u8)>,
 }
 
 struct InterfaceWrapper {
+    index: usize,
     name: &'static str,
     ether_addr: EthernetAddress,
-    dev: Mutex<DeviceWrapper>,
+    irq_num: Option<usize>,
+    dev: Mutex<InterfaceDevice>,
     iface: Mutex<Interface>,
 }
 
+impl fmt::Display for SocketHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.inner, IFACES[self.iface].name)
+    }
+}
+
 impl<'a> SocketSetWrapper<'a> {
-    fn new() -> Self {
-        Self(Mutex::new(SocketSet::new(vec![])))
+    fn new(num_ifaces: usize) -> Self {
+        let sets = (0..num_ifaces).map(|_| SocketSet::new(vec![])).collect();
+        Self(Mutex::new(sets))
     }
 
     pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
@@ -95,8 +189,10 @@ impl<'a> SocketSetWrapper<'a> {
         socket::dns::Socket::new(&[server_addr], vec![])
     }
 
-    pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
-        let handle = self.0.lock().add(socket);
+    /// Adds a socket to the socket set of the interface `iface`.
+    pub fn add<T: AnySocket<'a>>(&self, iface: usize, socket: T) -> SocketHandle {
+        let inner = self.0.lock()[iface].add(socket);
+        let handle = SocketHandle { iface, inner };
         debug!("socket {}: created", handle);
         handle
     }
@@ -105,8 +201,8 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&T) -> R,
     {
-        let set = self.0.lock();
-        let socket = set.get(handle);
+        let sets = self.0.lock();
+        let socket = sets[handle.iface].get(handle.inner);
         f(socket)
     }
 
@@ -114,31 +210,51 @@ impl<'a> SocketSetWrapper<'a> {
     where
         F: FnOnce(&mut T) -> R,
     {
-        let mut set = self.0.lock();
-        let socket = set.get_mut(handle);
+        let mut sets = self.0.lock();
+        let socket = sets[handle.iface].get_mut(handle.inner);
         f(socket)
     }
 
-    pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+    /// Polls the interfaces, and returns how long to wait before polling
+    /// again if nothing happens, or `None` if there is no timeout pending.
+    pub fn poll_interfaces(&self) -> Option<Duration> {
+        let delay = IFACES.iter().filter_map(|iface| iface.poll(&self.0)).min();
+        slaac::poll();
+        #[cfg(feature = "dhcp")]
+        dhcp::poll();
+        delay
     }
 
     pub fn remove(&self, handle: SocketHandle) {
-        self.0.lock().remove(handle);
+        self.0.lock()[handle.iface].remove(handle.inner);
         debug!("socket {}: destroyed", handle);
     }
 }
 
 impl InterfaceWrapper {
-    fn new(name: &'static str, dev: AxNetDevice, ether_addr: EthernetAddress) -> Self {
-        let mut config = Config::new(HardwareAddress::Ethernet(ether_addr));
+    fn new(index: usize, name: &'static str, mut dev: InterfaceDevice) -> Self {
+        let (ether_addr, irq_num, hardware_addr) = match &dev {
+            InterfaceDevice::Nic(dev) => {
+                let inner = dev.inner.borrow();
+                let ether_addr = EthernetAddress(inner.mac_address().0);
+                let hardware_addr = HardwareAddress::Ethernet(ether_addr);
+                (ether_addr, inner.irq_num(), hardware_addr)
+            }
+            InterfaceDevice::Loopback(_) => (EthernetAddress([0; 6]), None, HardwareAddress::Ip),
+        };
+        let mut config = Config::new(hardware_addr);
         config.random_seed = RANDOM_SEED;
 
-        let mut dev = DeviceWrapper::new(dev);
-        let iface = Mutex::new(Interface::new(config, &mut dev, Self::current_time()));
+        let now = Self::current_time();
+        let iface = Mutex::new(match &mut dev {
+            InterfaceDevice::Nic(dev) => Interface::new(config, dev, now),
+            InterfaceDevice::Loopback(dev) => Interface::new(config, dev, now),
+        });
         Self {
+            index,
             name,
             ether_addr,
+            irq_num,
             dev: Mutex::new(dev),
             iface,
         }
@@ -156,33 +272,147 @@ impl InterfaceWrapper {
         self.ether_addr
     }
 
-    pub fn setup_ip_addr(&self, ip: IpAddress, prefix_len: u8) {
-        let mut iface = self.iface.lock();
-        iface.update_ip_addrs(|ip_addrs| {
-            ip_addrs.push(IpCidr::new(ip, prefix_len)).unwrap();
+    pub fn irq_num(&self) -> Option<usize> {
+        self.irq_num
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        matches!(*self.dev.lock(), InterfaceDevice::Loopback(_))
+    }
+
+    pub fn ip_addrs(&self) -> Vec<IpCidr> {
+        self.iface.lock().ip_addrs().to_vec()
+    }
+
+    pub fn has_ip_addr(&self, addr: IpAddress) -> bool {
+        self.iface.lock().has_ip_addr(addr)
+    }
+
+    /// Replaces the IP addresses of the interface.
+    pub fn set_ip_addrs(&self, addrs: &[IpCidr]) -> AxResult {
+        let mut res = Ok(());
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            if addrs.len() > ip_addrs.capacity() {
+                res = ax_err!(NoMemory, "too many IP addresses");
+                return;
+            }
+            ip_addrs.clear();
+            for &addr in addrs {
+                ip_addrs.push(addr).unwrap();
+            }
         });
+        res
     }
 
-    pub fn setup_gateway(&self, gateway: IpAddress) {
-        let mut iface = self.iface.lock();
-        match gateway {
-            IpAddress::Ipv4(v4) => iface.routes_mut().add_default_ipv4_route(v4).unwrap(),
-        };
+    /// Replaces the IP addresses of the family `version` of the interface,
+    /// keeping the others.
+    pub fn set_ip_addrs_of(&self, version: IpVersion, addrs: &[IpCidr]) -> AxResult {
+        let mut res = Ok(());
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            let is_other = |cidr: &IpCidr| cidr.address().version() != version;
+            if ip_addrs.iter().filter(|cidr| is_other(cidr)).count() + addrs.len()
+                > ip_addrs.capacity()
+            {
+                res = ax_err!(NoMemory, "too many IP addresses");
+                return;
+            }
+            ip_addrs.retain(is_other);
+            for &addr in addrs {
+                ip_addrs.push(addr).unwrap();
+            }
+        });
+        res
+    }
+
+    /// Adds an IP address to the interface, if it doesn't have it yet.
+    pub fn add_ip_addr(&self, addr: IpCidr) -> AxResult {
+        let mut res = Ok(());
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            if !ip_addrs.contains(&addr) && ip_addrs.push(addr).is_err() {
+                res = ax_err!(NoMemory, "too many IP addresses");
+            }
+        });
+        res
+    }
+
+    /// Removes an IP address from the interface.
+    pub fn remove_ip_addr(&self, addr: IpCidr) {
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            ip_addrs.retain(|cidr| *cidr != addr);
+        });
+    }
+
+    /// Replaces the routes via gateways of the interface, given as pairs of
+    /// the destination network and the gateway.
+    pub fn set_routes(&self, routes: &[(IpCidr, IpAddress)]) -> AxResult {
+        let mut res = Ok(());
+        self.iface.lock().routes_mut().update(|storage| {
+            if routes.len() > storage.capacity() {
+                res = ax_err!(NoMemory, "too many routes");
+                return;
+            }
+            storage.clear();
+            for &(cidr, via_router) in routes {
+                let route = Route {
+                    cidr,
+                    via_router,
+                    preferred_until: None,
+                    expires_at: None,
+                };
+                storage.push(route).unwrap();
+            }
+        });
+        res
     }
 
-    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
+    pub fn info(&self) -> NetIfaceInfo {
+        let addrs = self.ip_addrs().into_iter();
+        NetIfaceInfo {
+            name: self.name,
+            mac_addr: self.ether_addr.0,
+            addrs: addrs
+                .map(|cidr| (into_core_ipaddr(cidr.address()), cidr.prefix_len()))
+                .collect(),
+        }
+    }
+
+    pub fn poll(&self, sockets: &Mutex<Vec<SocketSet>>) -> Option<Duration> {
         let mut dev = self.dev.lock();
         let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
+        let sockets = &mut sockets[self.index];
         let timestamp = Self::current_time();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        match dev.deref_mut() {
+            InterfaceDevice::Nic(dev) => {
+                // the packets arriving from now on will raise a new interrupt
+                dev.inner.borrow_mut().ack_interrupt();
+                iface.poll(timestamp, dev, sockets);
+            }
+            InterfaceDevice::Loopback(dev) => {
+                iface.poll(timestamp, dev, sockets);
+            }
+        }
+        iface
+            .poll_delay(Self::current_time(), sockets)
+            .map(|delay| Duration::from_micros(delay.total_micros()))
+    }
+}
+
+impl InterfaceDevice {
+    fn stats(&self) -> &DeviceStats {
+        match self {
+            Self::Nic(dev) => &dev.stats,
+            Self::Loopback(dev) => &dev.stats,
+        }
     }
 }
 
 impl DeviceWrapper {
-    fn new(inner: AxNetDevice) -> Self {
+    fn new(inner: AxNetDevice, iface: usize) -> Self {
         Self {
             inner: RefCell::new(inner),
+            iface,
+            stats: DeviceStats::default(),
         }
     }
 }
@@ -206,11 +436,12 @@ impl Device for DeviceWrapper {
             Err(err) => {
                 if !matches!(err, DevError::Again) {
                     warn!("receive failed: {:?}", err);
+                    self.stats.rx_errors.fetch_add(1, Ordering::Relaxed);
                 }
                 return None;
             }
         };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+        Some((AxNetRxToken(self, rx_buf), AxNetTxToken(self)))
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
@@ -220,7 +451,7 @@ impl Device for DeviceWrapper {
             return None;
         }
         if dev.can_transmit() {
-            Some(AxNetTxToken(&self.inner))
+            Some(AxNetTxToken(self))
         } else {
             None
         }
@@ -235,12 +466,12 @@ impl Device for DeviceWrapper {
     }
 }
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
-struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
+struct AxNetRxToken<'a>(&'a DeviceWrapper, NetBufPtr);
+struct AxNetTxToken<'a>(&'a DeviceWrapper);
 
 impl<'a> RxToken for AxNetRxToken<'a> {
     fn preprocess(&self, sockets: &mut SocketSet<'_>) {
-        snoop_tcp_packet(self.1.packet(), sockets).ok();
+        snoop_frame(self.1.packet(), self.0.iface, sockets).ok();
     }
 
     fn consume<R, F>(self, f: F) -> R
@@ -253,8 +484,13 @@ impl<'a> RxToken for AxNetRxToken<'a> {
             rx_buf.packet_len(),
             rx_buf.packet()
         );
+        let stats = &self.0.stats;
+        stats.rx_packets.fetch_add(1, Ordering::Relaxed);
+        stats
+            .rx_bytes
+            .fetch_add(rx_buf.packet_len() as u64, Ordering::Relaxed);
         let result = f(rx_buf.packet_mut());
-        self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
+        self.0.inner.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
         result
     }
 }
@@ -264,30 +500,87 @@ impl<'a> TxToken for AxNetTxToken<'a> {
     where
         F: FnOnce(&mut [u8]) -> R,
     {
-        let mut dev = self.0.borrow_mut();
+        let mut dev = self.0.inner.borrow_mut();
         let mut tx_buf = dev.alloc_tx_buffer(len).unwrap();
         let ret = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
-        dev.transmit(tx_buf).unwrap();
+        let stats = &self.0.stats;
+        match dev.transmit(tx_buf) {
+            Ok(()) => {
+                stats.tx_packets.fetch_add(1, Ordering::Relaxed);
+                stats.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+            }
+            Err(err) => {
+                warn!("transmit failed: {:?}", err);
+                stats.tx_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
         ret
     }
 }
 
-fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smoltcp::wire::Error> {
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
+fn snoop_frame(
+    buf: &[u8],
+    iface: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    use smoltcp::wire::{EthernetFrame, EthernetProtocol};
 
     let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
-
-    if ipv4_packet.next_header() == IpProtocol::Tcp {
-        let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
-        let src_addr = (ipv4_packet.src_addr(), tcp_packet.src_port()).into();
-        let dst_addr = (ipv4_packet.dst_addr(), tcp_packet.dst_port()).into();
-        let is_first = tcp_packet.syn() && !tcp_packet.ack();
-        if is_first {
-            // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
+    match ether_frame.ethertype() {
+        EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => {
+            snoop_packet(ether_frame.payload(), iface, sockets)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn snoop_packet(
+    buf: &[u8],
+    iface: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    use smoltcp::wire::{IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket};
+
+    let (src_addr, dst_addr, hop_limit, protocol, payload): (IpAddress, IpAddress, _, _, _) =
+        match IpVersion::of_packet(buf)? {
+            IpVersion::Ipv4 => {
+                let packet = Ipv4Packet::new_checked(buf)?;
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (
+                    src,
+                    dst,
+                    packet.hop_limit(),
+                    packet.next_header(),
+                    packet.payload(),
+                )
+            }
+            IpVersion::Ipv6 => {
+                let packet = Ipv6Packet::new_checked(buf)?;
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (
+                    src,
+                    dst,
+                    packet.hop_limit(),
+                    packet.next_header(),
+                    packet.payload(),
+                )
+            }
+        };
+
+    match protocol {
+        IpProtocol::Tcp => {
+            let tcp_packet = TcpPacket::new_checked(payload)?;
+            let src_addr = (src_addr, tcp_packet.src_port()).into();
+            let dst_addr = (dst_addr, tcp_packet.dst_port()).into();
+            let is_first = tcp_packet.syn() && !tcp_packet.ack();
+            if is_first {
+                // create a socket for the first incoming TCP packet, as the later accept() returns.
+                LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, iface, sockets);
+            }
         }
+        IpProtocol::Icmpv6 => slaac::snoop_icmpv6(src_addr, dst_addr, hop_limit, payload, iface)?,
+        _ => {}
     }
     Ok(())
 }
@@ -300,31 +593,135 @@ pub fn poll_interfaces() {
     SOCKET_SET.poll_interfaces();
 }
 
+/// Returns the packet counters of all network interfaces.
+pub fn net_dev_stats() -> Vec<NetDevStats> {
+    let Some(ifaces) = IFACES.try_get() else {
+        return Vec::new();
+    };
+    ifaces
+        .iter()
+        .map(|iface| {
+            let dev = iface.dev.lock();
+            let stats = dev.stats();
+            NetDevStats {
+                name: iface.name,
+                rx_packets: stats.rx_packets.load(Ordering::Relaxed),
+                rx_bytes: stats.rx_bytes.load(Ordering::Relaxed),
+                rx_errors: stats.rx_errors.load(Ordering::Relaxed),
+                tx_packets: stats.tx_packets.load(Ordering::Relaxed),
+                tx_bytes: stats.tx_bytes.load(Ordering::Relaxed),
+                tx_errors: stats.tx_errors.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+/// Returns the configuration of all network interfaces.
+pub fn interfaces() -> Vec<NetIfaceInfo> {
+    match IFACES.try_get() {
+        Some(ifaces) => ifaces.iter().map(InterfaceWrapper::info).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Replaces the IP addresses of the network interface `name`, given with the
+/// prefix lengths of their networks.
+pub fn set_interface_addrs(name: &str, addrs: &[(IpAddr, u8)]) -> AxResult {
+    let iface = iface_by_name(name)?;
+    let addrs = addrs
+        .iter()
+        .map(|&(addr, prefix_len)| route::new_cidr(addr, prefix_len))
+        .collect::<AxResult<Vec<_>>>()?;
+    IFACES[iface].set_ip_addrs(&addrs)?;
+    info!("{}: IP addresses set to {:?}", name, addrs);
+    Ok(())
+}
+
+/// Returns the index of the interface named `name`.
+fn iface_by_name(name: &str) -> AxResult<usize> {
+    IFACES
+        .try_get()
+        .and_then(|ifaces| ifaces.iter().position(|iface| iface.name == name))
+        .ok_or_else(|| ax_err_type!(NotFound, "no such network interface"))
+}
+
+/// Returns the maximum length of the pending connection queue of listening
+/// TCP sockets.
+pub fn listen_backlog() -> usize {
+    LISTEN_BACKLOG.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum length of the pending connection queue of listening TCP
+/// sockets. It takes effect on the sockets that start listening afterwards.
+pub fn set_listen_backlog(backlog: usize) {
+    LISTEN_BACKLOG.store(backlog.max(1), Ordering::Relaxed);
+}
+
 /// Benchmark raw socket transmit bandwidth.
 pub fn bench_transmit() {
-    ETH0.dev.lock().bench_transmit_bandwidth();
+    with_first_nic(DeviceWrapper::bench_transmit_bandwidth);
 }
 
 /// Benchmark raw socket receive bandwidth.
 pub fn bench_receive() {
-    ETH0.dev.lock().bench_receive_bandwidth();
+    with_first_nic(DeviceWrapper::bench_receive_bandwidth);
 }
 
-pub(crate) fn init(net_dev: AxNetDevice) {
-    let ether_addr = EthernetAddress(net_dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
-
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
-    eth0.setup_gateway(gateway);
+fn with_first_nic<R>(f: impl FnOnce(&mut DeviceWrapper) -> R) -> R {
+    for iface in IFACES.iter() {
+        if let InterfaceDevice::Nic(dev) = iface.dev.lock().deref_mut() {
+            return f(dev);
+        }
+    }
+    panic!("No NIC device found!");
+}
 
-    ETH0.init_by(eth0);
-    SOCKET_SET.init_by(SocketSetWrapper::new());
+pub(crate) fn init(net_devs: Vec<AxNetDevice>) {
+    let lo = InterfaceDevice::Loopback(LoopbackDev::new(0));
+    let mut ifaces = vec![InterfaceWrapper::new(0, "lo", lo)];
+    for (i, dev) in net_devs.into_iter().enumerate() {
+        let index = ifaces.len();
+        let name = Box::leak(format!("eth{}", i).into_boxed_str());
+        let dev = InterfaceDevice::Nic(DeviceWrapper::new(dev, index));
+        ifaces.push(InterfaceWrapper::new(index, name, dev));
+    }
+    let num_ifaces = ifaces.len();
+    IFACES.init_by(ifaces);
+    SOCKET_SET.init_by(SocketSetWrapper::new(num_ifaces));
     LISTEN_TABLE.init_by(ListenTable::new());
 
-    info!("created net interface {:?}:", ETH0.name());
-    info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
-    info!("  gateway:  {}", gateway);
+    let lo = &IFACES[0];
+    let loopback_cidr = IpCidr::new(LOOPBACK_IP, LOOPBACK_PREFIX);
+    let loopback_ipv6_cidr = IpCidr::new(LOOPBACK_IPV6, LOOPBACK_IPV6_PREFIX);
+    lo.set_ip_addrs(&[loopback_cidr, loopback_ipv6_cidr])
+        .unwrap();
+
+    // the NICs get their link-local IPv6 addresses, and send router solicitations
+    slaac::init();
+
+    // `eth0` is configured statically if `AX_IP` is set, the others at runtime.
+    let eth0 = IFACES.get(1).filter(|_| !IP.is_empty()).map(|eth0| {
+        let ip = IP.parse().expect("invalid IP address");
+        let gateway = GATEWAY.parse().expect("invalid gateway IP address");
+        let cidr = IpCidr::new(ip, IP_PREFIX);
+        eth0.set_ip_addrs_of(IpVersion::Ipv4, &[cidr]).unwrap();
+        route::add(IpCidr::new(UNSPECIFIED_IP, 0), gateway, eth0.index).unwrap();
+        (eth0, gateway)
+    });
+
+    for iface in IFACES.iter() {
+        info!("created net interface {:?}:", iface.name());
+        if !iface.is_loopback() {
+            info!("  ether:    {}", iface.ethernet_address());
+        }
+        for cidr in iface.ip_addrs() {
+            info!("  ip:       {}", cidr);
+        }
+    }
+    if let Some((eth0, gateway)) = eth0 {
+        info!("default gateway: {} on {:?}", gateway, eth0.name());
+    }
+    #[cfg(feature = "dhcp")]
+    dhcp::init();
+    poll::init();
 }
@@ -0,0 +1,198 @@
+//! Waiting for network events.
+//!
+//! With both `multitask` and `irq` enabled, the interfaces are polled by a
+//! background task, which is woken up by the NIC interrupts, by the sockets
+//! that have packets to send, or by the timer when smoltcp has timeouts to
+//! handle (see [`Interface::poll_delay`]). A blocking socket operation sleeps
+//! on the wait queue of the socket, which is woken up by smoltcp when the
+//! socket becomes readable or writable, or changes its state.
+//!
+//! The NICs that can't raise interrupts are polled every 10ms instead. These
+//! are the PCI NICs on x86, where the INTx interrupts are not routed, and
+//! those behind PCI bridges or sharing an INTx line with other devices. MSI
+//! and MSI-X are not supported.
+//!
+//! Otherwise, a blocking operation polls the interfaces by itself, and yields
+//! the CPU between attempts.
+//!
+//! [`Interface::poll_delay`]: smoltcp::iface::Interface::poll_delay
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::task::Waker;
+
+use axerrno::{AxError, AxResult};
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "multitask", feature = "irq"))] {
+        use alloc::vec::Vec;
+        use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use core::time::Duration;
+
+        use axtask::WaitQueue;
+        use lazy_init::LazyInit;
+
+        use super::{IFACES, SOCKET_SET};
+
+        /// The polling interval if a NIC can't raise interrupts.
+        const POLL_INTERVAL_NO_IRQ: Duration = Duration::from_millis(10);
+
+        static POLL_REQUESTED: AtomicBool = AtomicBool::new(false);
+        static POLL_WQ: WaitQueue = WaitQueue::new();
+        /// The IRQ numbers of the NICs that raise interrupts.
+        static NIC_IRQS: LazyInit<Vec<usize>> = LazyInit::new();
+
+        /// The events of a socket that blocking operations wait for.
+        pub(crate) struct SocketWaiter {
+            events: AtomicUsize,
+            wq: WaitQueue,
+        }
+
+        impl SocketWaiter {
+            pub fn new() -> Arc<Self> {
+                Arc::new(Self {
+                    events: AtomicUsize::new(0),
+                    wq: WaitQueue::new(),
+                })
+            }
+
+            /// Calls `f` until it returns something other than
+            /// [`Err(WouldBlock)`](AxError::WouldBlock).
+            ///
+            /// Before each call, `register` registers the waker to the sockets
+            /// that `f` waits for, and the current task sleeps until one of
+            /// them wakes it up.
+            pub fn block_on<R, F, T>(self: &Arc<Self>, mut register: R, mut f: F) -> AxResult<T>
+            where
+                R: FnMut(&Waker),
+                F: FnMut() -> AxResult<T>,
+            {
+                let waker = Waker::from(self.clone());
+                loop {
+                    let events = self.events.load(Ordering::Acquire);
+                    register(&waker);
+                    let res = f();
+                    request_poll();
+                    match res {
+                        Err(AxError::WouldBlock) => self
+                            .wq
+                            .wait_until(|| self.events.load(Ordering::Acquire) != events),
+                        res => return res,
+                    }
+                }
+            }
+        }
+
+        impl Wake for SocketWaiter {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref();
+            }
+
+            fn wake_by_ref(self: &Arc<Self>) {
+                self.events.fetch_add(1, Ordering::Release);
+                self.wq.notify_all(false);
+            }
+        }
+
+        /// Wakes up the poll task, to send the queued packets.
+        pub(crate) fn request_poll() {
+            POLL_REQUESTED.store(true, Ordering::Release);
+            POLL_WQ.notify_one(false);
+        }
+
+        fn irq_handler() {
+            // keep them masked until the poll task has acknowledged the NICs
+            for &irq_num in NIC_IRQS.iter() {
+                axhal::irq::set_enable(irq_num, false);
+            }
+            request_poll();
+        }
+
+        /// The background task polling the interfaces, `all_irq` tells whether
+        /// all the NICs raise interrupts.
+        fn poll_task(all_irq: bool) {
+            let requested = || POLL_REQUESTED.swap(false, Ordering::AcqRel);
+            loop {
+                let delay = SOCKET_SET.poll_interfaces();
+                for &irq_num in NIC_IRQS.iter() {
+                    axhal::irq::set_enable(irq_num, true);
+                }
+                let timeout = if all_irq {
+                    delay
+                } else {
+                    Some(delay.map_or(POLL_INTERVAL_NO_IRQ, |delay| {
+                        delay.min(POLL_INTERVAL_NO_IRQ)
+                    }))
+                };
+                match timeout {
+                    Some(timeout) => {
+                        POLL_WQ.wait_timeout_until(timeout, requested);
+                    }
+                    None => POLL_WQ.wait_until(requested),
+                }
+            }
+        }
+
+        pub(crate) fn init() {
+            // the loopback interface is polled when its sockets send packets
+            let nics = IFACES.iter().filter(|iface| !iface.is_loopback());
+            let mut irqs = Vec::new();
+            let mut all_irq = true;
+            for iface in nics {
+                match iface.irq_num() {
+                    Some(irq_num) if axhal::irq::register_handler(irq_num, irq_handler) => {
+                        info!("{}: irq {}", iface.name(), irq_num);
+                        irqs.push(irq_num);
+                    }
+                    _ => {
+                        warn!(
+                            "{}: NIC interrupts unavailable, polling every {:?}",
+                            iface.name(),
+                            POLL_INTERVAL_NO_IRQ
+                        );
+                        all_irq = false;
+                    }
+                }
+            }
+            NIC_IRQS.init_by(irqs);
+            axtask::spawn(move || poll_task(all_irq));
+        }
+    } else {
+        use super::SOCKET_SET;
+
+        /// The events of a socket that blocking operations wait for.
+        pub(crate) struct SocketWaiter;
+
+        impl SocketWaiter {
+            pub fn new() -> Arc<Self> {
+                Arc::new(Self)
+            }
+
+            /// Calls `f` until it returns something other than
+            /// [`Err(WouldBlock)`](AxError::WouldBlock), polling the interface
+            /// before each call.
+            pub fn block_on<R, F, T>(self: &Arc<Self>, _register: R, mut f: F) -> AxResult<T>
+            where
+                R: FnMut(&Waker),
+                F: FnMut() -> AxResult<T>,
+            {
+                loop {
+                    SOCKET_SET.poll_interfaces();
+                    match f() {
+                        Err(AxError::WouldBlock) => axtask::yield_now(),
+                        res => return res,
+                    }
+                }
+            }
+        }
+
+        impl Wake for SocketWaiter {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        /// The interface is polled by the blocking operations.
+        pub(crate) fn request_poll() {}
+
+        pub(crate) fn init() {}
+    }
+}
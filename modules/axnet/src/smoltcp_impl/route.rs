@@ -0,0 +1,404 @@
+//! The routing table.
+//!
+//! A socket sends and receives packets on a single interface, which is looked
+//! up by the destination address when the socket connects, or sends a
+//! datagram. The longest prefix wins among the networks of the interface
+//! addresses, which are directly connected, and the routes via gateways. A
+//! route with the prefix length 0 is a default route.
+//!
+//! The gateway routes are also copied to the smoltcp routing table of their
+//! interfaces, to resolve the next hops of the packets.
+
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axsync::Mutex;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv6Address};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr};
+use super::{iface_by_name, IFACES};
+
+/// An entry of the routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// The destination network address.
+    pub dest: IpAddr,
+    /// The prefix length of the destination network, 0 for a default route.
+    pub prefix_len: u8,
+    /// The next hop, or `None` if the destination network is directly
+    /// connected to the interface.
+    pub gateway: Option<IpAddr>,
+    /// The name of the interface to send packets through.
+    pub iface: &'static str,
+}
+
+struct GatewayRoute {
+    cidr: IpCidr,
+    gateway: IpAddress,
+    iface: usize,
+}
+
+/// The routes via gateways, locked before the interfaces.
+static ROUTES: Mutex<Vec<GatewayRoute>> = Mutex::new(Vec::new());
+
+/// Returns the index of the interface to send packets to `dst`, or `None` if
+/// there is no route to it.
+pub(crate) fn lookup(dst: IpAddress) -> Option<usize> {
+    let networks = IFACES
+        .iter()
+        .flat_map(|iface| {
+            iface
+                .ip_addrs()
+                .into_iter()
+                .map(move |cidr| (cidr, iface.index))
+        })
+        .collect::<Vec<_>>();
+    best_route(dst, &networks, &ROUTES.lock())
+}
+
+/// Returns the interface of the longest prefix containing `dst`, among the
+/// directly connected `networks` and the `routes`. A network wins over a
+/// route of the same prefix length.
+fn best_route(
+    dst: IpAddress,
+    networks: &[(IpCidr, usize)],
+    routes: &[GatewayRoute],
+) -> Option<usize> {
+    let mut best: Option<(u8, usize)> = None;
+    let routes = routes.iter().map(|route| (route.cidr, route.iface));
+    for (cidr, iface) in networks.iter().copied().chain(routes) {
+        let longer = !matches!(best, Some((len, _)) if len >= cidr.prefix_len());
+        if longer && cidr.contains_addr(&dst) {
+            best = Some((cidr.prefix_len(), iface));
+        }
+    }
+    best.map(|(_, iface)| iface)
+}
+
+/// Returns the index of the interface that has the address `addr`.
+pub(crate) fn iface_of_addr(addr: IpAddress) -> Option<usize> {
+    IFACES
+        .iter()
+        .find(|iface| iface.has_ip_addr(addr))
+        .map(|iface| iface.index)
+}
+
+/// Adds a route to `cidr` via `gateway` on the interface `iface`.
+pub(crate) fn add(cidr: IpCidr, gateway: IpAddress, iface: usize) -> AxResult {
+    let mut routes = ROUTES.lock();
+    insert(&mut routes, cidr, gateway, iface)?;
+    if let Err(e) = update_iface(&routes, iface) {
+        routes.pop();
+        return Err(e);
+    }
+    debug!(
+        "route added: {} via {} on {}",
+        cidr, gateway, IFACES[iface].name
+    );
+    Ok(())
+}
+
+/// Removes the route to `cidr`.
+pub(crate) fn remove(cidr: IpCidr) -> AxResult {
+    let mut routes = ROUTES.lock();
+    let idx = routes
+        .iter()
+        .position(|route| same_network(route.cidr, cidr))
+        .ok_or_else(|| ax_err_type!(NotFound, "no such route"))?;
+    let route = routes.remove(idx);
+    update_iface(&routes, route.iface)?;
+    debug!("route removed: {}", cidr);
+    Ok(())
+}
+
+/// Replaces the route to `cidr` on the interface `iface` with the one via
+/// `gateway`, or removes it if `gateway` is `None`.
+///
+/// It fails if another interface has a route to `cidr`.
+pub(crate) fn replace(cidr: IpCidr, gateway: Option<IpAddress>, iface: usize) -> AxResult {
+    let mut routes = ROUTES.lock();
+    replace_in(&mut routes, cidr, gateway, iface)?;
+    update_iface(&routes, iface)?;
+    debug!(
+        "route replaced: {} via {:?} on {}",
+        cidr, gateway, IFACES[iface].name
+    );
+    Ok(())
+}
+
+/// Appends a route to `routes`, unless there is one to `cidr` already.
+fn insert(
+    routes: &mut Vec<GatewayRoute>,
+    cidr: IpCidr,
+    gateway: IpAddress,
+    iface: usize,
+) -> AxResult {
+    if routes.iter().any(|route| same_network(route.cidr, cidr)) {
+        return ax_err!(AlreadyExists, "route already exists");
+    }
+    routes.push(GatewayRoute {
+        cidr,
+        gateway,
+        iface,
+    });
+    Ok(())
+}
+
+/// Replaces the route to `cidr` on `iface` in `routes`, see [`replace`].
+fn replace_in(
+    routes: &mut Vec<GatewayRoute>,
+    cidr: IpCidr,
+    gateway: Option<IpAddress>,
+    iface: usize,
+) -> AxResult {
+    let on_other_iface =
+        |route: &GatewayRoute| route.iface != iface && same_network(route.cidr, cidr);
+    if gateway.is_some() && routes.iter().any(on_other_iface) {
+        return ax_err!(AlreadyExists, "route exists on another interface");
+    }
+    routes.retain(|route| route.iface != iface || !same_network(route.cidr, cidr));
+    if let Some(gateway) = gateway {
+        routes.push(GatewayRoute {
+            cidr,
+            gateway,
+            iface,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the gateway of the route to `cidr` on the interface `iface`.
+pub(crate) fn gateway(cidr: IpCidr, iface: usize) -> Option<IpAddress> {
+    ROUTES
+        .lock()
+        .iter()
+        .find(|route| route.iface == iface && same_network(route.cidr, cidr))
+        .map(|route| route.gateway)
+}
+
+/// Copies the routes of the interface `iface` to its smoltcp routing table.
+fn update_iface(routes: &[GatewayRoute], iface: usize) -> AxResult {
+    let iface_routes = routes
+        .iter()
+        .filter(|route| route.iface == iface)
+        .map(|route| (route.cidr, route.gateway))
+        .collect::<Vec<_>>();
+    IFACES[iface].set_routes(&iface_routes)
+}
+
+fn same_network(a: IpCidr, b: IpCidr) -> bool {
+    a.prefix_len() == b.prefix_len() && a.contains_addr(&b.address())
+}
+
+/// Returns the address of the network `cidr`, with the host bits cleared.
+fn network_addr(cidr: IpCidr) -> IpAddress {
+    match cidr {
+        IpCidr::Ipv4(cidr) => IpAddress::Ipv4(cidr.network().address()),
+        IpCidr::Ipv6(cidr) => {
+            let mut bytes = cidr.address().0;
+            let prefix_len = cidr.prefix_len() as usize;
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                let prefix_bits = prefix_len.saturating_sub(8 * i).min(8);
+                *byte &= (0xff00u16 >> prefix_bits) as u8;
+            }
+            IpAddress::Ipv6(Ipv6Address(bytes))
+        }
+    }
+}
+
+/// Creates a CIDR from an address and a prefix length, checking the length.
+pub(crate) fn new_cidr(addr: IpAddr, prefix_len: u8) -> AxResult<IpCidr> {
+    let max_len = match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if prefix_len > max_len {
+        return ax_err!(InvalidInput, "invalid prefix length");
+    }
+    Ok(IpCidr::new(from_core_ipaddr(addr), prefix_len))
+}
+
+/// Returns the routing table, including the directly connected networks.
+pub fn routes() -> Vec<RouteEntry> {
+    let Some(ifaces) = IFACES.try_get() else {
+        return Vec::new();
+    };
+    let mut entries = Vec::new();
+    for iface in ifaces {
+        for cidr in iface.ip_addrs() {
+            entries.push(RouteEntry {
+                dest: into_core_ipaddr(network_addr(cidr)),
+                prefix_len: cidr.prefix_len(),
+                gateway: None,
+                iface: iface.name,
+            });
+        }
+    }
+    for route in ROUTES.lock().iter() {
+        entries.push(RouteEntry {
+            dest: into_core_ipaddr(network_addr(route.cidr)),
+            prefix_len: route.cidr.prefix_len(),
+            gateway: Some(into_core_ipaddr(route.gateway)),
+            iface: IFACES[route.iface].name,
+        });
+    }
+    entries
+}
+
+/// Adds a route to the network `dest`/`prefix_len` via `gateway` on the
+/// interface named `iface`. The prefix length 0 makes a default route.
+pub fn add_route(dest: IpAddr, prefix_len: u8, gateway: IpAddr, iface: &str) -> AxResult {
+    let cidr = new_cidr(dest, prefix_len)?;
+    if gateway.is_ipv4() != dest.is_ipv4() {
+        return ax_err!(InvalidInput, "gateway of another address family");
+    }
+    add(cidr, from_core_ipaddr(gateway), iface_by_name(iface)?)
+}
+
+/// Removes the route to the network `dest`/`prefix_len`.
+pub fn del_route(dest: IpAddr, prefix_len: u8) -> AxResult {
+    remove(new_cidr(dest, prefix_len)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use axerrno::AxError;
+
+    use super::*;
+
+    fn v4_cidr(a: u8, b: u8, c: u8, d: u8, prefix_len: u8) -> IpCidr {
+        IpCidr::new(IpAddress::v4(a, b, c, d), prefix_len)
+    }
+
+    fn gateway_route(cidr: IpCidr, gateway: IpAddress, iface: usize) -> GatewayRoute {
+        GatewayRoute {
+            cidr,
+            gateway,
+            iface,
+        }
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let gateway = IpAddress::v4(10, 0, 2, 2);
+        let routes = [
+            gateway_route(v4_cidr(0, 0, 0, 0, 0), gateway, 1),
+            gateway_route(v4_cidr(192, 168, 1, 0, 24), gateway, 2),
+        ];
+        let lookup = |dst| best_route(dst, &[], &routes);
+        assert_eq!(lookup(IpAddress::v4(192, 168, 1, 5)), Some(2));
+        assert_eq!(lookup(IpAddress::v4(192, 168, 2, 5)), Some(1));
+        assert_eq!(lookup(IpAddress::v4(8, 8, 8, 8)), Some(1));
+        assert_eq!(
+            best_route(IpAddress::v4(8, 8, 8, 8), &[], &routes[1..]),
+            None
+        );
+        // the IPv4 default route doesn't match IPv6 destinations
+        let dst = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into();
+        assert_eq!(lookup(dst), None);
+    }
+
+    #[test]
+    fn test_networks_and_routes() {
+        let networks = [
+            (v4_cidr(127, 0, 0, 1, 8), 0),
+            (v4_cidr(10, 0, 2, 15, 24), 1),
+        ];
+        let gateway = IpAddress::v4(10, 0, 2, 2);
+        let routes = [
+            gateway_route(v4_cidr(0, 0, 0, 0, 0), gateway, 1),
+            gateway_route(v4_cidr(10, 0, 2, 0, 24), gateway, 2),
+            gateway_route(v4_cidr(10, 0, 2, 128, 25), gateway, 2),
+        ];
+        let lookup = |dst| best_route(dst, &networks, &routes);
+        assert_eq!(lookup(IpAddress::v4(127, 0, 0, 1)), Some(0));
+        // the connected network wins over the route of the same length
+        assert_eq!(lookup(IpAddress::v4(10, 0, 2, 3)), Some(1));
+        // but not over a longer one
+        assert_eq!(lookup(IpAddress::v4(10, 0, 2, 200)), Some(2));
+        assert_eq!(lookup(IpAddress::v4(10, 0, 3, 1)), Some(1));
+    }
+
+    #[test]
+    fn test_add_existing() {
+        let mut routes = Vec::new();
+        let gateway = IpAddress::v4(10, 0, 2, 2);
+        let default = v4_cidr(0, 0, 0, 0, 0);
+        assert_eq!(insert(&mut routes, default, gateway, 1), Ok(()));
+        let other_gateway = IpAddress::v4(10, 0, 3, 2);
+        assert_eq!(
+            insert(&mut routes, default, other_gateway, 2),
+            Err(AxError::AlreadyExists)
+        );
+        // the same network, given by another address in it
+        let net = v4_cidr(192, 168, 1, 0, 24);
+        assert_eq!(insert(&mut routes, net, gateway, 1), Ok(()));
+        assert_eq!(
+            insert(&mut routes, v4_cidr(192, 168, 1, 7, 24), gateway, 1),
+            Err(AxError::AlreadyExists)
+        );
+        assert_eq!(
+            insert(&mut routes, v4_cidr(192, 168, 1, 0, 25), gateway, 1),
+            Ok(())
+        );
+        // the default routes of the two families are different
+        let default_v6 = IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0);
+        let gateway_v6 = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into();
+        assert_eq!(insert(&mut routes, default_v6, gateway_v6, 1), Ok(()));
+        assert_eq!(routes.len(), 4);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut routes = Vec::new();
+        let default = v4_cidr(0, 0, 0, 0, 0);
+        let gateway = IpAddress::v4(10, 0, 2, 2);
+        let new_gateway = IpAddress::v4(10, 0, 2, 3);
+        assert_eq!(replace_in(&mut routes, default, Some(gateway), 1), Ok(()));
+        assert_eq!(
+            replace_in(&mut routes, default, Some(new_gateway), 1),
+            Ok(())
+        );
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].gateway, new_gateway);
+
+        // the route of another interface is kept
+        assert_eq!(
+            replace_in(&mut routes, default, Some(gateway), 2),
+            Err(AxError::AlreadyExists)
+        );
+        assert_eq!(replace_in(&mut routes, default, None, 2), Ok(()));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].iface, 1);
+
+        assert_eq!(replace_in(&mut routes, default, None, 1), Ok(()));
+        assert!(routes.is_empty());
+        assert_eq!(replace_in(&mut routes, default, Some(gateway), 2), Ok(()));
+        assert_eq!(routes[0].iface, 2);
+    }
+
+    #[test]
+    fn test_network_addr() {
+        assert_eq!(
+            network_addr(v4_cidr(192, 168, 1, 7, 24)),
+            IpAddress::v4(192, 168, 1, 0)
+        );
+        let addr = Ipv6Address::new(0x2001, 0xdb8, 1, 0xff, 0x1234, 0, 0, 0x5678);
+        let network = |prefix_len| network_addr(IpCidr::new(addr.into(), prefix_len));
+        assert_eq!(
+            network(64),
+            IpAddress::Ipv6(Ipv6Address::new(0x2001, 0xdb8, 1, 0xff, 0, 0, 0, 0))
+        );
+        assert_eq!(
+            network(61),
+            IpAddress::Ipv6(Ipv6Address::new(0x2001, 0xdb8, 1, 0xf8, 0, 0, 0, 0))
+        );
+        assert_eq!(
+            network(24),
+            IpAddress::Ipv6(Ipv6Address::new(0x2001, 0x0d00, 0, 0, 0, 0, 0, 0))
+        );
+        assert_eq!(network(128), IpAddress::Ipv6(addr));
+        assert_eq!(network(0), IpAddress::Ipv6(Ipv6Address::UNSPECIFIED));
+    }
+}
@@ -0,0 +1,186 @@
+//! IPv6 stateless address autoconfiguration (SLAAC).
+//!
+//! Each NIC gets a link-local address made of its MAC address, and sends a
+//! router solicitation at boot. For the prefix in each router advertisement,
+//! the NIC gets an address with the same interface identifier. The first
+//! router advertising a non-zero lifetime becomes its IPv6 default gateway,
+//! until it advertises a zero lifetime. Neighbor discovery is done by smoltcp.
+//!
+//! As required by RFC 4861, the advertisements are only accepted from
+//! link-local addresses and with the hop limit 255, i.e., from the link.
+//!
+//! Duplicate address detection and the expiration of the addresses are not
+//! implemented.
+
+use alloc::{vec, vec::Vec};
+use core::mem;
+
+use axsync::Mutex;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::icmp;
+use smoltcp::time::Duration;
+use smoltcp::wire::{EthernetAddress, Icmpv6Message, Icmpv6Packet, Icmpv6Repr, IpAddress, IpCidr};
+use smoltcp::wire::{Ipv6Address, NdiscPrefixInfoFlags, NdiscRepr, RawHardwareAddress};
+
+use super::addr::UNSPECIFIED_IPV6;
+use super::{route, InterfaceWrapper, IFACES, SOCKET_SET};
+
+/// The prefix length of the addresses with 64-bit interface identifiers.
+const SLAAC_PREFIX_LEN: u8 = 64;
+const LINK_LOCAL_PREFIX: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+
+/// The router advertisements received by the interfaces, which are handled
+/// after the interfaces are polled.
+static ADVERTS: Mutex<Vec<RouterAdvert>> = Mutex::new(Vec::new());
+
+struct RouterAdvert {
+    iface: usize,
+    router: Ipv6Address,
+    router_lifetime: Duration,
+    /// The announced prefix for SLAAC, and whether it's still valid.
+    prefix: Option<(Ipv6Address, bool)>,
+}
+
+impl RouterAdvert {
+    fn apply(&self) {
+        let iface = &IFACES[self.iface];
+        if let Some((prefix, valid)) = self.prefix {
+            let addr = IpCidr::new(
+                interface_addr(prefix, iface.ethernet_address()).into(),
+                SLAAC_PREFIX_LEN,
+            );
+            let has_addr = iface.ip_addrs().contains(&addr);
+            if valid && !has_addr {
+                match iface.add_ip_addr(addr) {
+                    Ok(()) => info!("{}: IPv6 address configured: {}", iface.name, addr),
+                    Err(e) => warn!("{}: failed to add IPv6 address: {:?}", iface.name, e),
+                }
+            } else if !valid && has_addr {
+                iface.remove_ip_addr(addr);
+                info!("{}: IPv6 address removed: {}", iface.name, addr);
+            }
+        }
+
+        // only the current default router can remove the default route
+        let default = IpCidr::new(UNSPECIFIED_IPV6, 0);
+        let router = IpAddress::from(self.router);
+        let gateway = match route::gateway(default, self.iface) {
+            None if self.router_lifetime != Duration::ZERO => Some(router),
+            Some(current) if current == router && self.router_lifetime == Duration::ZERO => None,
+            _ => return,
+        };
+        if let Err(e) = route::replace(default, gateway, self.iface) {
+            warn!(
+                "{}: failed to set IPv6 default gateway: {:?}",
+                iface.name, e
+            );
+        }
+    }
+}
+
+/// Returns the address in the network `prefix`/64 with the modified EUI-64
+/// interface identifier made of `mac`.
+fn interface_addr(prefix: Ipv6Address, mac: EthernetAddress) -> Ipv6Address {
+    let mut bytes = prefix.0;
+    bytes[8..11].copy_from_slice(&mac.0[..3]);
+    bytes[8] ^= 0x02; // the universal/local bit
+    bytes[11..13].copy_from_slice(&[0xff, 0xfe]);
+    bytes[13..].copy_from_slice(&mac.0[3..]);
+    Ipv6Address(bytes)
+}
+
+/// Records the router advertisement in the ICMPv6 packet `payload`, if it is.
+/// `hop_limit` is the one of the IP packet.
+///
+/// It's called by the poll with the interface locked.
+pub(crate) fn snoop_icmpv6(
+    src_addr: IpAddress,
+    dst_addr: IpAddress,
+    hop_limit: u8,
+    payload: &[u8],
+    iface: usize,
+) -> Result<(), smoltcp::wire::Error> {
+    let packet = Icmpv6Packet::new_checked(payload)?;
+    if packet.msg_type() != Icmpv6Message::RouterAdvert {
+        return Ok(());
+    }
+    // forwarded or off-link advertisements are dropped
+    let IpAddress::Ipv6(router) = src_addr else {
+        return Ok(());
+    };
+    if hop_limit != 255 || !router.is_link_local() {
+        return Ok(());
+    }
+    let checksum_caps = ChecksumCapabilities::default();
+    let repr = Icmpv6Repr::parse(&src_addr, &dst_addr, &packet, &checksum_caps)?;
+    if let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+        router_lifetime,
+        prefix_info,
+        ..
+    }) = repr
+    {
+        let prefix = prefix_info
+            .filter(|info| {
+                info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+                    && info.prefix_len == SLAAC_PREFIX_LEN
+            })
+            .map(|info| (info.prefix, info.valid_lifetime != Duration::ZERO));
+        ADVERTS.lock().push(RouterAdvert {
+            iface,
+            router,
+            router_lifetime,
+            prefix,
+        });
+    }
+    Ok(())
+}
+
+/// Handles the router advertisements received, after the interfaces are
+/// polled.
+pub(crate) fn poll() {
+    let adverts = mem::take(&mut *ADVERTS.lock());
+    for advert in adverts {
+        advert.apply();
+    }
+}
+
+/// Sends a router solicitation on the interface, to get a router
+/// advertisement without waiting for the periodic one.
+fn solicit_router(iface: &InterfaceWrapper, src_addr: Ipv6Address) {
+    let repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+        lladdr: Some(RawHardwareAddress::from_bytes(&iface.ethernet_address().0)),
+    });
+    let dst_addr = Ipv6Address::LINK_LOCAL_ALL_ROUTERS;
+    let mut buf = vec![0; repr.buffer_len()];
+    repr.emit(
+        &src_addr.into(),
+        &dst_addr.into(),
+        &mut Icmpv6Packet::new_unchecked(&mut buf[..]),
+        &ChecksumCapabilities::default(),
+    );
+
+    let new_buffer = || icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 64]);
+    let mut socket = icmp::Socket::new(new_buffer(), new_buffer());
+    // neighbor discovery messages from other hops are dropped
+    socket.set_hop_limit(Some(255));
+    if let Err(e) = socket.send_slice(&buf, dst_addr.into()) {
+        warn!(
+            "{}: failed to send router solicitation: {:?}",
+            iface.name, e
+        );
+        return;
+    }
+    let handle = SOCKET_SET.add(iface.index, socket);
+    SOCKET_SET.poll_interfaces();
+    SOCKET_SET.remove(handle);
+}
+
+pub(crate) fn init() {
+    for iface in IFACES.iter().filter(|iface| !iface.is_loopback()) {
+        let link_local = interface_addr(LINK_LOCAL_PREFIX, iface.ethernet_address());
+        iface
+            .add_ip_addr(IpCidr::new(link_local.into(), SLAAC_PREFIX_LEN))
+            .unwrap();
+        solicit_router(iface, link_local);
+    }
+}
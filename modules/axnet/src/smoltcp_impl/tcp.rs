@@ -1,17 +1,21 @@
+use alloc::sync::Arc;
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
 use axsync::Mutex;
+use spin::Once;
 
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
+use super::poll::{self, SocketWaiter};
+use super::route;
+use super::{SocketHandle, SocketSetWrapper, IFACES, LISTEN_TABLE, SOCKET_SET};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -41,6 +45,8 @@ pub struct TcpSocket {
     local_addr: UnsafeCell<IpEndpoint>,
     peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
+    /// Created on the first blocking operation.
+    waiter: Once<Arc<SocketWaiter>>,
 }
 
 unsafe impl Sync for TcpSocket {}
@@ -54,6 +60,7 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
+            waiter: Once::new(),
         }
     }
 
@@ -69,6 +76,7 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
+            waiter: Once::new(),
         }
     }
 
@@ -120,18 +128,33 @@ impl TcpSocket {
     /// The local port is generated automatically.
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
-            // SAFETY: no other threads can read or write these fields.
-            let handle = unsafe { self.handle.get().read() }
-                .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
-
-            // TODO: check remote addr unreachable
             let remote_endpoint = from_core_sockaddr(remote_addr);
             let bound_endpoint = self.bound_endpoint()?;
-            let iface = &ETH0.iface;
+            let iface = match bound_endpoint.addr {
+                Some(addr) => route::iface_of_addr(addr),
+                None => route::lookup(remote_endpoint.addr),
+            }
+            .ok_or_else(|| ax_err_type!(NetworkUnreachable, "socket connect() failed"))?;
+
+            // SAFETY: no other threads can read or write these fields.
+            let handle = match unsafe { self.handle.get().read() } {
+                Some(handle) if handle.iface == iface => handle,
+                old_handle => {
+                    // the socket of a failed connection on another interface
+                    if let Some(old_handle) = old_handle {
+                        SOCKET_SET.remove(old_handle);
+                    }
+                    let handle = SOCKET_SET.add(iface, SocketSetWrapper::new_tcp_socket());
+                    unsafe { self.handle.get().write(Some(handle)) };
+                    handle
+                }
+            };
+
+            let mut iface = IFACES[iface].iface.lock();
             let (local_endpoint, remote_endpoint) = SOCKET_SET
                 .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                     socket
-                        .connect(iface.lock().context(), remote_endpoint, bound_endpoint)
+                        .connect(iface.context(), remote_endpoint, bound_endpoint)
                         .or_else(|e| match e {
                             ConnectError::InvalidState => {
                                 ax_err!(BadState, "socket connect() failed")
@@ -150,7 +173,6 @@ impl TcpSocket {
                 // have changed the state to `BUSY`.
                 self.local_addr.get().write(local_endpoint);
                 self.peer_addr.get().write(remote_endpoint);
-                self.handle.get().write(Some(handle));
             }
             Ok(())
         })
@@ -158,9 +180,13 @@ impl TcpSocket {
 
         // Here our state must be `CONNECTING`, and only one thread can run here.
         if self.is_nonblocking() {
+            poll::request_poll(); // send SYN
             Err(AxError::WouldBlock)
         } else {
-            self.block_on(|| {
+            // SAFETY: `self.handle` should be initialized above.
+            let handle = unsafe { self.handle.get().read().unwrap() };
+            let register = |waker: &Waker| register_waker(handle, waker, true, true);
+            self.block_on(register, || {
                 let PollState { writable, .. } = self.poll_connect()?;
                 if !writable {
                     Err(AxError::WouldBlock)
@@ -209,7 +235,7 @@ impl TcpSocket {
             unsafe {
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            LISTEN_TABLE.listen(bound_endpoint, Waker::from(self.waiter().clone()))?;
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
@@ -229,7 +255,8 @@ impl TcpSocket {
 
         // SAFETY: `self.local_addr` should be initialized after `bind()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
+        let register = |waker: &Waker| LISTEN_TABLE.register_waker(local_port, waker);
+        self.block_on(register, || {
             let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
             Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
@@ -279,7 +306,8 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        let register = |waker: &Waker| register_waker(handle, waker, true, false);
+        self.block_on(register, || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() {
                     // not open
@@ -312,7 +340,8 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        let register = |waker: &Waker| register_waker(handle, waker, false, true);
+        self.block_on(register, || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() || !socket.may_send() {
                     // closed by remote
@@ -469,26 +498,27 @@ impl TcpSocket {
         })
     }
 
+    fn waiter(&self) -> &Arc<SocketWaiter> {
+        self.waiter.call_once(SocketWaiter::new)
+    }
+
     /// Block the current thread until the given function completes or fails.
     ///
     /// If the socket is non-blocking, it calls the function once and returns
     /// immediately. Otherwise, it may call the function multiple times if it
-    /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// returns [`Err(WouldBlock)`](AxError::WouldBlock), sleeping in between
+    /// until woken up by a waker that `register` registers.
+    fn block_on<R, F, T>(&self, register: R, mut f: F) -> AxResult<T>
     where
+        R: FnMut(&Waker),
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
-            f()
+            let res = f();
+            poll::request_poll();
+            res
         } else {
-            loop {
-                SOCKET_SET.poll_interfaces();
-                match f() {
-                    Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
-                    Err(e) => return Err(e),
-                }
-            }
+            self.waiter().block_on(register, f)
         }
     }
 }
@@ -503,6 +533,19 @@ impl Drop for TcpSocket {
     }
 }
 
+/// Registers `waker` to be woken up when the socket `handle` changes its
+/// state, or becomes readable (`recv`) or writable (`send`).
+fn register_waker(handle: SocketHandle, waker: &Waker, recv: bool, send: bool) {
+    SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        if recv {
+            socket.register_recv_waker(waker);
+        }
+        if send {
+            socket.register_send_waker(waker);
+        }
+    });
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
@@ -1,37 +1,43 @@
+use alloc::{sync::Arc, vec::Vec};
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
 use axsync::Mutex;
 use spin::RwLock;
 
-use smoltcp::iface::SocketHandle;
 use smoltcp::socket::udp::{self, BindError, SendError};
-use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
+use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, SOCKET_SET};
+use super::poll::{self, SocketWaiter};
+use super::route;
+use super::{SocketHandle, SocketSetWrapper, IFACES, SOCKET_SET};
 
 /// A UDP socket that provides POSIX-like APIs.
+///
+/// It's bound on all interfaces if the bound address is unspecified, with a
+/// smoltcp socket on each of them.
 pub struct UdpSocket {
-    handle: SocketHandle,
+    handles: RwLock<Vec<SocketHandle>>,
     local_addr: RwLock<Option<IpEndpoint>>,
     peer_addr: RwLock<Option<IpEndpoint>>,
     nonblock: AtomicBool,
+    waiter: Arc<SocketWaiter>,
 }
 
 impl UdpSocket {
     /// Creates a new UDP socket.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_udp_socket();
-        let handle = SOCKET_SET.add(socket);
         Self {
-            handle,
+            handles: RwLock::new(Vec::new()),
             local_addr: RwLock::new(None),
             peer_addr: RwLock::new(None),
             nonblock: AtomicBool::new(false),
+            waiter: SocketWaiter::new(),
         }
     }
 
@@ -88,15 +94,34 @@ impl UdpSocket {
             addr: (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr),
             port: local_endpoint.port,
         };
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-            socket.bind(endpoint).or_else(|e| match e {
-                BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
-                BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
-            })
-        })?;
+        let ifaces = match endpoint.addr {
+            Some(addr) => match route::iface_of_addr(addr) {
+                Some(iface) => iface..iface + 1,
+                None => return ax_err!(InvalidInput, "socket bind() failed: not a local address"),
+            },
+            None => 0..IFACES.len(),
+        };
+        let mut handles = Vec::with_capacity(ifaces.len());
+        for iface in ifaces {
+            let handle = SOCKET_SET.add(iface, SocketSetWrapper::new_udp_socket());
+            handles.push(handle);
+            let res = SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                socket.bind(endpoint).or_else(|e| match e {
+                    BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
+                    BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
+                })
+            });
+            if res.is_err() {
+                handles
+                    .into_iter()
+                    .for_each(|handle| SOCKET_SET.remove(handle));
+                return res;
+            }
+            debug!("UDP socket {}: bound on {}", handle, endpoint);
+        }
 
+        *self.handles.write() = handles;
         *self_local_addr = Some(local_endpoint);
-        debug!("UDP socket {}: bound on {}", self.handle, endpoint);
         Ok(())
     }
 
@@ -142,7 +167,7 @@ impl UdpSocket {
         }
 
         *self_peer_addr = Some(from_core_sockaddr(addr));
-        debug!("UDP socket {}: connected to {}", self.handle, addr);
+        debug!("UDP socket connected to {}", addr);
         Ok(())
     }
 
@@ -172,10 +197,12 @@ impl UdpSocket {
 
     /// Close the socket.
     pub fn shutdown(&self) -> AxResult {
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-            debug!("UDP socket {}: shutting down", self.handle);
-            socket.close();
-        });
+        for &handle in self.handles.read().iter() {
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                debug!("UDP socket {}: shutting down", handle);
+                socket.close();
+            });
+        }
         SOCKET_SET.poll_interfaces();
         Ok(())
     }
@@ -188,12 +215,17 @@ impl UdpSocket {
                 writable: false,
             });
         }
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-            Ok(PollState {
-                readable: socket.can_recv(),
-                writable: socket.can_send(),
-            })
-        })
+        let mut state = PollState {
+            readable: false,
+            writable: false,
+        };
+        for &handle in self.handles.read().iter() {
+            SOCKET_SET.with_socket::<udp::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable |= socket.can_send();
+            });
+        }
+        Ok(state)
     }
 }
 
@@ -211,8 +243,14 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+        let handle = self.send_handle(remote_endpoint.addr)?;
+        let register = |waker: &Waker| {
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                socket.register_send_waker(waker)
+            })
+        };
+        self.block_on(register, || {
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
                 if socket.can_send() {
                     socket
                         .send_slice(buf, remote_endpoint)
@@ -239,34 +277,53 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-                if socket.can_recv() {
+        let handles = self.handles.read().clone();
+        let register = |waker: &Waker| {
+            for &handle in &handles {
+                SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker)
+                });
+            }
+        };
+        self.block_on(register, || {
+            for &handle in &handles {
+                let res = SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
                     // data available
-                    op(socket)
-                } else {
-                    // no more data
-                    Err(AxError::WouldBlock)
+                    socket.can_recv().then(|| op(socket))
+                });
+                match res {
+                    Some(Err(AxError::WouldBlock)) | None => continue,
+                    Some(res) => return res,
                 }
-            })
+            }
+            // no more data
+            Err(AxError::WouldBlock)
         })
     }
 
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// Returns the socket on the interface to send packets to `remote_addr`.
+    fn send_handle(&self, remote_addr: IpAddress) -> AxResult<SocketHandle> {
+        let handles = self.handles.read();
+        if let [handle] = handles[..] {
+            return Ok(handle);
+        }
+        route::lookup(remote_addr)
+            .and_then(|iface| handles.iter().find(|handle| handle.iface == iface))
+            .copied()
+            .ok_or_else(|| ax_err_type!(NetworkUnreachable, "socket send() failed"))
+    }
+
+    fn block_on<R, F, T>(&self, register: R, mut f: F) -> AxResult<T>
     where
+        R: FnMut(&Waker),
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
-            f()
+            let res = f();
+            poll::request_poll();
+            res
         } else {
-            loop {
-                SOCKET_SET.poll_interfaces();
-                match f() {
-                    Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
-                    Err(e) => return Err(e),
-                }
-            }
+            self.waiter.block_on(register, f)
         }
     }
 }
@@ -274,7 +331,9 @@ impl UdpSocket {
 impl Drop for UdpSocket {
     fn drop(&mut self) {
         self.shutdown().ok();
-        SOCKET_SET.remove(self.handle);
+        for &handle in self.handles.get_mut().iter() {
+            SOCKET_SET.remove(handle);
+        }
     }
 }
 
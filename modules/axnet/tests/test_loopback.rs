@@ -0,0 +1,94 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use axdriver::AxDeviceContainer;
+use axerrno::AxError;
+use axnet::{TcpSocket, UdpSocket};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const LOCALHOST_V6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+fn test_interfaces() {
+    let ifaces = axnet::interfaces();
+    assert_eq!(ifaces.len(), 1);
+    assert_eq!(ifaces[0].name, "lo");
+    assert_eq!(ifaces[0].addrs, [(LOCALHOST, 8), (LOCALHOST_V6, 128)]);
+
+    let routes = axnet::routes();
+    assert_eq!(routes.len(), 2);
+    assert_eq!(routes[0].dest, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)));
+    assert_eq!(routes[0].prefix_len, 8);
+    assert_eq!(routes[0].gateway, None);
+    assert_eq!(routes[0].iface, "lo");
+    assert_eq!(routes[1].dest, LOCALHOST_V6);
+    assert_eq!(routes[1].prefix_len, 128);
+    assert_eq!(routes[1].iface, "lo");
+
+    // no default route without a NIC
+    let socket = TcpSocket::new();
+    let remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 2, 2)), 80);
+    assert_eq!(
+        socket.connect(remote_addr).err(),
+        Some(AxError::NetworkUnreachable)
+    );
+    assert_eq!(
+        axnet::add_route(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0, LOCALHOST, "eth0").err(),
+        Some(AxError::NotFound)
+    );
+}
+
+fn test_tcp(addr: SocketAddr) {
+    let listener = TcpSocket::new();
+    listener.bind(addr).unwrap();
+    listener.listen().unwrap();
+
+    let client = TcpSocket::new();
+    client.connect(addr).unwrap();
+    let server = listener.accept().unwrap();
+    assert_eq!(server.local_addr().unwrap(), addr);
+    assert_eq!(server.peer_addr().unwrap(), client.local_addr().unwrap());
+
+    let mut buf = [0; 16];
+    assert_eq!(client.send(b"ping").unwrap(), 4);
+    assert_eq!(server.recv(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(server.send(b"pong").unwrap(), 4);
+    assert_eq!(client.recv(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"pong");
+
+    client.shutdown().unwrap();
+    assert_eq!(server.recv(&mut buf).unwrap(), 0);
+}
+
+fn test_udp(addr: SocketAddr) {
+    let server = UdpSocket::new();
+    server.bind(addr).unwrap();
+
+    let client = UdpSocket::new();
+    let unspecified = match addr.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    client.bind(SocketAddr::new(unspecified, 0)).unwrap();
+    assert_eq!(client.send_to(b"ping", addr).unwrap(), 4);
+
+    let mut buf = [0; 16];
+    let (len, client_addr) = server.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"ping");
+    assert_eq!(client_addr.ip(), addr.ip());
+    assert_eq!(server.send_to(b"pong", client_addr).unwrap(), 4);
+    let (len, server_addr) = client.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"pong");
+    assert_eq!(server_addr, addr);
+}
+
+#[test]
+fn test_loopback() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axnet::init_network(AxDeviceContainer::default());
+
+    test_interfaces();
+    test_tcp(SocketAddr::new(LOCALHOST, 5555));
+    test_udp(SocketAddr::new(LOCALHOST, 5556));
+    test_tcp(SocketAddr::new(LOCALHOST_V6, 5557));
+    test_udp(SocketAddr::new(LOCALHOST_V6, 5558));
+}
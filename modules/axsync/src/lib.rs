@@ -3,13 +3,20 @@
 //! Currently supported primitives:
 //!
 //! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`Condvar`]: A condition variable used with [`Mutex`].
+//! - [`RwLock`]: A readers-writer lock with writer preference.
+//! - [`Semaphore`]: A counting semaphore.
+//! - [`Barrier`]: A barrier to synchronize a group of tasks.
 //! - mod [`spin`](spinlock): spin-locks.
 //!
 //! # Cargo Features
 //!
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
-//!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
-//!   feature is enabled by default.
+//!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`], and other
+//!   primitives will spin instead of blocking the current task. This feature is
+//!   enabled by default.
+//! - `irq`: Interrupts are enabled. If this feature is enabled, timed waits
+//!   such as [`Condvar::wait_timeout`] can be used.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
@@ -19,6 +26,15 @@ pub use spinlock as spin;
 #[cfg(feature = "multitask")]
 mod mutex;
 
+mod barrier;
+mod condvar;
+mod rwlock;
+mod semaphore;
+mod wait_queue;
+
+#[cfg(test)]
+mod tests;
+
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
 pub use self::mutex::{Mutex, MutexGuard};
@@ -26,3 +42,8 @@ pub use self::mutex::{Mutex, MutexGuard};
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
 pub use spinlock::{SpinNoIrq as Mutex, SpinNoIrqGuard as MutexGuard};
+
+pub use self::barrier::{Barrier, BarrierWaitResult};
+pub use self::condvar::{Condvar, WaitTimeoutResult};
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::semaphore::{Semaphore, SemaphoreGuard};
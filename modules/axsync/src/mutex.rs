@@ -5,7 +5,8 @@ use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicU64, Ordering};
 
-use axtask::{current, WaitQueue};
+use axtask::{current, AxTaskRef, WaitQueue};
+use spinlock::SpinNoIrq;
 
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
@@ -13,12 +14,23 @@ use axtask::{current, WaitQueue};
 /// When the mutex is locked, the current task will block and be put into the
 /// wait queue. When the mutex is unlocked, all tasks waiting on the queue
 /// will be woken up.
+///
+/// The mutex supports priority inheritance: if a task is blocked on the mutex,
+/// the owner's priority is boosted to the waiter's (if higher), until the
+/// owner releases the mutex.
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_id: AtomicU64,
+    owner: SpinNoIrq<MutexOwner>,
     data: UnsafeCell<T>,
 }
 
+struct MutexOwner {
+    task: Option<AxTaskRef>,
+    /// Whether the owner's priority is boosted by the waiters.
+    boosted: bool,
+}
+
 /// A guard that provides mutable data access.
 ///
 /// When the guard falls out of scope it will release the lock.
@@ -38,6 +50,10 @@ impl<T> Mutex<T> {
         Self {
             wq: WaitQueue::new(),
             owner_id: AtomicU64::new(0),
+            owner: SpinNoIrq::new(MutexOwner {
+                task: None,
+                boosted: false,
+            }),
             data: UnsafeCell::new(data),
         }
     }
@@ -69,24 +85,36 @@ impl<T: ?Sized> Mutex<T> {
     /// The returned value may be dereferenced for data access
     /// and the lock will be dropped when the guard falls out of scope.
     pub fn lock(&self) -> MutexGuard<T> {
-        let current_id = current().id().as_u64();
+        let curr = current();
+        let current_id = curr.id().as_u64();
         loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
+            let mut owner = self.owner.lock();
+            match self.owner_id.compare_exchange(
                 0,
                 current_id,
                 Ordering::Acquire,
                 Ordering::Relaxed,
             ) {
-                Ok(_) => break,
+                Ok(_) => {
+                    owner.task = Some(curr.as_task_ref().clone());
+                    break;
+                }
                 Err(owner_id) => {
                     assert_ne!(
                         owner_id,
                         current_id,
                         "{} tried to acquire mutex it already owns.",
-                        current().id_name()
+                        curr.id_name()
                     );
+                    // Lend our priority to the owner, so that it will not be
+                    // starved by the tasks with lower priority than us.
+                    if let Some(task) = owner.task.as_ref() {
+                        if curr.priority() < task.priority() {
+                            axtask::inherit_priority(task, self.key(), curr.priority());
+                            owner.boosted = true;
+                        }
+                    }
+                    drop(owner);
                     // Wait until the lock looks unlocked before retrying
                     self.wq.wait_until(|| !self.is_locked());
                 }
@@ -101,14 +129,16 @@ impl<T: ?Sized> Mutex<T> {
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
     #[inline(always)]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let current_id = current().id().as_u64();
+        let curr = current();
+        let mut owner = self.owner.lock();
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
         if self
             .owner_id
-            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+            .compare_exchange(0, curr.id().as_u64(), Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            owner.task = Some(curr.as_task_ref().clone());
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -126,6 +156,7 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
+        let mut owner = self.owner.lock();
         let owner_id = self.owner_id.swap(0, Ordering::Release);
         assert_eq!(
             owner_id,
@@ -133,7 +164,23 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
-        self.wq.notify_one(true);
+        owner.task = None;
+        let boosted = core::mem::replace(&mut owner.boosted, false);
+        drop(owner);
+
+        if boosted {
+            axtask::disinherit_priority(self.key());
+            // Wake up all waiters, so that the remaining ones can lend their
+            // priorities to the new owner.
+            self.wq.notify_all(true);
+        } else {
+            self.wq.notify_one(true);
+        }
+    }
+
+    /// The key to identify the priority inherited through this mutex.
+    fn key(&self) -> usize {
+        self as *const Self as *const () as usize
     }
 
     /// Returns a mutable reference to the underlying data.
@@ -167,6 +214,24 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
     }
 }
 
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Temporarily unlocks the mutex to execute the given function, and
+    /// re-locks it before returning.
+    ///
+    /// This is safe because `&mut` guarantees that there exist no other
+    /// references to the data protected by the mutex.
+    pub fn unlocked<F, U>(s: &mut Self, f: F) -> U
+    where
+        F: FnOnce() -> U,
+    {
+        unsafe { s.lock.force_unlock() };
+        let ret = f();
+        // The guard `s` is still valid, do not release the lock twice.
+        core::mem::forget(s.lock.lock());
+        ret
+    }
+}
+
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
     #[inline(always)]
@@ -199,11 +264,9 @@ impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
 
 #[cfg(test)]
 mod tests {
+    use crate::tests::{INIT, SERIAL};
     use crate::Mutex;
     use axtask as thread;
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
 
     fn may_interrupt() {
         // simulate interrupts
@@ -214,6 +277,7 @@ mod tests {
 
     #[test]
     fn lots_and_lots() {
+        let _lock = SERIAL.lock();
         INIT.call_once(thread::init_scheduler);
 
         const NUM_TASKS: u32 = 10;
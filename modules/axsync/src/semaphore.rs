@@ -0,0 +1,94 @@
+//! A counting semaphore.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::wait_queue::WaitQueue;
+
+/// A counting, blocking, semaphore.
+///
+/// Semaphores are a form of atomic counter where access is only granted if the
+/// counter is a positive value. Each acquisition will block the current task
+/// until the counter is positive, and each release will increment the counter
+/// and wake up one blocked task (if any).
+pub struct Semaphore {
+    count: AtomicUsize,
+    wq: WaitQueue,
+}
+
+/// An RAII guard which will release a resource acquired from a semaphore when
+/// dropped.
+pub struct SemaphoreGuard<'a> {
+    sem: &'a Semaphore,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the initial count specified.
+    pub const fn new(count: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(count),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Returns the current count of the semaphore.
+    ///
+    /// This function provides no synchronization guarantees and so its result
+    /// should be considered 'out of date' the instant it is called.
+    pub fn available_permits(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Acquires a resource of this semaphore, blocking the current task until
+    /// it can do so.
+    ///
+    /// This method will block until the internal count of the semaphore is at
+    /// least 1, then decrement it.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            self.wq
+                .wait_until(|| self.count.load(Ordering::Relaxed) > 0);
+        }
+    }
+
+    /// Tries to acquire a resource of this semaphore without blocking.
+    ///
+    /// Returns `true` if the internal count was positive and has been
+    /// decremented.
+    pub fn try_acquire(&self) -> bool {
+        let mut count = self.count.load(Ordering::Relaxed);
+        while count > 0 {
+            match self.count.compare_exchange_weak(
+                count,
+                count - 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(c) => count = c,
+            }
+        }
+        false
+    }
+
+    /// Releases a resource from this semaphore.
+    ///
+    /// This will increment the internal count of the semaphore, and wake up
+    /// one task blocked in [`acquire`](Self::acquire).
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+
+    /// Acquires a resource of this semaphore, returning an RAII guard to
+    /// release the semaphore when dropped.
+    pub fn access(&self) -> SemaphoreGuard {
+        self.acquire();
+        SemaphoreGuard { sem: self }
+    }
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.sem.release();
+    }
+}
@@ -0,0 +1,152 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex as StdMutex, Once};
+
+use crate::{Barrier, Condvar, Mutex, RwLock, Semaphore};
+use axtask as thread;
+
+pub(crate) static INIT: Once = Once::new();
+pub(crate) static SERIAL: StdMutex<()> = StdMutex::new(());
+
+fn may_interrupt() {
+    // simulate interrupts
+    if rand::random::<u32>() % 3 == 0 {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn test_condvar() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static READY: Mutex<usize> = Mutex::new(0);
+    static CVAR: Condvar = Condvar::new();
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            may_interrupt();
+            *READY.lock() += 1;
+            CVAR.notify_one();
+        });
+    }
+
+    let ready = CVAR.wait_while(READY.lock(), |ready| *ready < NUM_TASKS);
+    assert_eq!(*ready, NUM_TASKS);
+    println!("Condvar test OK");
+}
+
+#[test]
+fn test_rwlock() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_READERS: usize = 10;
+    const NUM_WRITERS: usize = 5;
+    const NUM_ITERS: usize = 100;
+    static LOCK: RwLock<(usize, usize)> = RwLock::new((0, 0));
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_WRITERS {
+        thread::spawn(|| {
+            for _ in 0..NUM_ITERS {
+                let mut data = LOCK.write();
+                data.0 += 1;
+                may_interrupt();
+                data.1 += 1;
+                drop(data);
+                may_interrupt();
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+    for _ in 0..NUM_READERS {
+        thread::spawn(|| {
+            for _ in 0..NUM_ITERS {
+                let data = LOCK.read();
+                may_interrupt();
+                // writers never run concurrently with readers.
+                assert_eq!(data.0, data.1);
+                drop(data);
+                may_interrupt();
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    while FINISHED.load(Ordering::Relaxed) < NUM_READERS + NUM_WRITERS {
+        thread::yield_now();
+    }
+    assert_eq!(
+        *LOCK.read(),
+        (NUM_WRITERS * NUM_ITERS, NUM_WRITERS * NUM_ITERS)
+    );
+    assert_eq!(LOCK.reader_count(), 0);
+    assert!(!LOCK.is_write_locked());
+    println!("RwLock test OK");
+}
+
+#[test]
+fn test_semaphore() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_PERMITS: usize = 3;
+    static SEM: Semaphore = Semaphore::new(NUM_PERMITS);
+    static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            for _ in 0..10 {
+                let _guard = SEM.access();
+                let active = ACTIVE.fetch_add(1, Ordering::Relaxed) + 1;
+                assert!(active <= NUM_PERMITS);
+                may_interrupt();
+                ACTIVE.fetch_sub(1, Ordering::Relaxed);
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    while FINISHED.load(Ordering::Relaxed) < NUM_TASKS {
+        thread::yield_now();
+    }
+    assert_eq!(SEM.available_permits(), NUM_PERMITS);
+    println!("Semaphore test OK");
+}
+
+#[test]
+fn test_barrier() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_ROUNDS: usize = 5;
+    static BARRIER: Barrier = Barrier::new(NUM_TASKS);
+    static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+    static LEADERS: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            for round in 0..NUM_ROUNDS {
+                ARRIVED.fetch_add(1, Ordering::Relaxed);
+                may_interrupt();
+                if BARRIER.wait().is_leader() {
+                    LEADERS.fetch_add(1, Ordering::Relaxed);
+                }
+                // all tasks have arrived in this round.
+                assert!(ARRIVED.load(Ordering::Relaxed) >= (round + 1) * NUM_TASKS);
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    while FINISHED.load(Ordering::Relaxed) < NUM_TASKS {
+        thread::yield_now();
+    }
+    assert_eq!(LEADERS.load(Ordering::Relaxed), NUM_ROUNDS);
+    println!("Barrier test OK");
+}
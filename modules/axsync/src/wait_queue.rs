@@ -0,0 +1,47 @@
+//! The wait queue used by the blocking primitives.
+//!
+//! If the `multitask` feature is enabled, it's [`axtask::WaitQueue`], otherwise
+//! it's a dummy queue which spins until the condition becomes true.
+
+#[cfg(feature = "multitask")]
+pub(crate) use axtask::WaitQueue;
+
+#[cfg(not(feature = "multitask"))]
+pub(crate) struct WaitQueue;
+
+#[cfg(not(feature = "multitask"))]
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    pub fn wait_until<F>(&self, condition: F)
+    where
+        F: Fn() -> bool,
+    {
+        while !condition() {
+            core::hint::spin_loop();
+        }
+    }
+
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_until<F>(&self, dur: core::time::Duration, condition: F) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        let deadline = axhal::time::current_time() + dur;
+        while axhal::time::current_time() < deadline {
+            if condition() {
+                return false;
+            }
+            core::hint::spin_loop();
+        }
+        true
+    }
+
+    pub fn notify_one(&self, _resched: bool) -> bool {
+        false
+    }
+
+    pub fn notify_all(&self, _resched: bool) {}
+}
@@ -2,13 +2,20 @@
 
 use alloc::{string::String, sync::Arc};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+pub(crate) use crate::run_queue::{current_run_queue, AxRunQueue};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState, TaskStats};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
+#[doc(cfg(feature = "multitask"))]
+pub use scheduler::RTPolicy;
+
+#[cfg(feature = "sched_edf")]
+#[doc(cfg(feature = "sched_edf"))]
+pub use scheduler::EDFParams;
+
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
 
@@ -17,21 +24,35 @@ cfg_if::cfg_if! {
         const MAX_TIME_SLICE: usize = 5;
         pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
         pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
+        pub(crate) const DEFAULT_PRIO: isize = 0;
     } else if #[cfg(feature = "sched_cfs")] {
         pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
         pub(crate) type Scheduler = scheduler::CFScheduler<TaskInner>;
+        pub(crate) const DEFAULT_PRIO: isize = 0;
+    } else if #[cfg(feature = "sched_rt")] {
+        const MAX_TIME_SLICE: usize = 5;
+        pub(crate) type AxTask = scheduler::RTTask<TaskInner, MAX_TIME_SLICE>;
+        pub(crate) type Scheduler = scheduler::RTScheduler<TaskInner, MAX_TIME_SLICE>;
+        pub(crate) const DEFAULT_PRIO: isize = scheduler::RT_DEFAULT_PRIO as isize;
+    } else if #[cfg(feature = "sched_edf")] {
+        pub(crate) type AxTask = scheduler::EDFTask<TaskInner>;
+        pub(crate) type Scheduler = scheduler::EDFScheduler<TaskInner>;
+        pub(crate) const DEFAULT_PRIO: isize = 0;
     } else {
         // If no scheduler features are set, use FIFO as the default.
         pub(crate) type AxTask = scheduler::FifoTask<TaskInner>;
         pub(crate) type Scheduler = scheduler::FifoScheduler<TaskInner>;
+        pub(crate) const DEFAULT_PRIO: isize = 0;
     }
 }
 
 #[cfg(feature = "preempt")]
 struct KernelGuardIfImpl;
 
+// Unit tests link this crate twice (through the dev-dependency `axsync`), do
+// not export the implementation from both.
 #[cfg(feature = "preempt")]
-#[crate_interface::impl_interface]
+#[cfg_attr(not(test), crate_interface::impl_interface)]
 impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     fn disable_preempt() {
         if let Some(curr) = current_may_uninit() {
@@ -75,6 +96,8 @@ pub fn init_scheduler() {
 /// Initializes the task scheduler for secondary CPUs.
 pub fn init_scheduler_secondary() {
     crate::run_queue::init_secondary();
+    #[cfg(feature = "irq")]
+    crate::timers::init();
 }
 
 /// Handles periodic timer ticks for the task manager.
@@ -84,7 +107,16 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
+}
+
+/// Returns an iterator over all alive tasks, in order of their IDs.
+///
+/// It takes a snapshot of the task registry, tasks spawned or dropped during
+/// the iteration are not reflected. Use [`TaskInner::stats`] to get the
+/// statistics of each task.
+pub fn tasks() -> impl Iterator<Item = AxTaskRef> {
+    crate::task::all_tasks().into_iter()
 }
 
 /// Spawns a new task with the given parameters.
@@ -95,7 +127,7 @@ where
     F: FnOnce() + Send + 'static,
 {
     let task = TaskInner::new(f, name, stack_size);
-    RUN_QUEUE.lock().add_task(task.clone());
+    current_run_queue().add_task(task.clone());
     task
 }
 
@@ -118,17 +150,91 @@ where
 /// example, in the [CFS] scheduler, the priority is the nice value, ranging from
 /// -20 to 19.
 ///
-/// Returns `true` if the priority is set successfully.
+/// Returns `true` if the priority is set successfully. The smaller the value,
+/// the higher the priority. The effective priority may be temporarily higher
+/// than the one set here, if the task is holding a lock that a higher-priority
+/// task is waiting for (see [`inherit_priority`]).
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Sets the scheduling policy of the current task for the [real-time]
+/// scheduler, i.e., whether it's preempted by the tasks of the same priority
+/// when its time slice is used up.
+///
+/// Returns `false` if the underlying scheduler is not the real-time scheduler.
+///
+/// [real-time]: scheduler::RTScheduler
+pub fn set_current_policy(policy: RTPolicy) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "sched_rt")] {
+            current().as_task_ref().set_policy(policy);
+            true
+        } else {
+            let _ = policy;
+            false
+        }
+    }
+}
+
+/// Sets the timing parameters of the current task for the [EDF] scheduler, or
+/// turns it into a background task if `params` is [`None`].
+///
+/// Returns `false` if the parameters are invalid, or the task can not be
+/// admitted as the total CPU bandwidth would exceed 100%. The bandwidth is
+/// reserved on the current CPU, so the task is bound to it on success.
+///
+/// [EDF]: scheduler::EDFScheduler
+#[cfg(feature = "sched_edf")]
+#[doc(cfg(feature = "sched_edf"))]
+pub fn set_current_deadline(params: Option<EDFParams>) -> bool {
+    current_run_queue().set_current_deadline(params)
+}
+
+/// Boosts the priority of the given task to `prio` if it's higher than the
+/// task's, on behalf of the lock identified by `key` (usually its address).
+///
+/// It's used to implement priority inheritance for blocking locks: the waiter
+/// calls it on the lock owner before blocking, and the owner drops the boost
+/// by [`disinherit_priority`] when releasing the lock.
+pub fn inherit_priority(task: &AxTaskRef, key: usize, prio: isize) {
+    if task.inherit_priority(key, prio) {
+        crate::run_queue::update_task_priority(task);
+    }
+}
+
+/// Drops the priority that the current task inherited through the lock
+/// identified by `key`, by [`inherit_priority`].
+pub fn disinherit_priority(key: usize) {
+    let curr = current();
+    if curr.disinherit_priority(key) {
+        crate::run_queue::update_task_priority(curr.as_task_ref());
+    }
+}
+
+/// Set the CPU affinity for current task.
+///
+/// `cpumask` is a bitmap of CPU IDs that the task is allowed to run on. If the
+/// current CPU is not in the mask, the current task is migrated to one of the
+/// allowed CPUs immediately.
+///
+/// Returns `false` if no CPU in the mask is available. A deadline task (see
+/// `set_current_deadline`) can not change its affinity, as its bandwidth is
+/// reserved on the current CPU.
+pub fn set_current_affinity(cpumask: usize) -> bool {
+    let cpumask = cpumask & (usize::MAX >> (usize::BITS as usize - axconfig::SMP));
+    if cpumask == 0 {
+        return false;
+    }
+    current_run_queue().set_current_affinity(cpumask)
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
@@ -143,14 +249,14 @@ pub fn sleep(dur: core::time::Duration) {
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    current_run_queue().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
 /// Exits the current task.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
@@ -1,72 +1,211 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use kernel_guard::NoPreemptIrqSave;
 use lazy_init::LazyInit;
 use scheduler::BaseScheduler;
-use spinlock::SpinNoIrq;
+use spinlock::{SpinNoIrq, SpinRaw};
 
 use crate::task::{CurrentTask, TaskState};
 use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+/// The run queue of each CPU.
+#[percpu::def_percpu]
+static RUN_QUEUE: LazyInit<AxRunQueue> = LazyInit::new();
 
-// TODO: per-CPU
+#[percpu::def_percpu]
 static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
 
+#[percpu::def_percpu]
 static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
 
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
+/// The task that was running before the last context switch on this CPU. Its
+/// `on_cpu` flag is cleared once the switch is completed.
+#[cfg_attr(not(feature = "smp"), allow(dead_code))]
+#[percpu::def_percpu]
+static PREV_TASK: usize = 0;
+
+/// Bitmap of CPUs whose run queues have been initialized.
+static ONLINE_CPUS: AtomicUsize = AtomicUsize::new(0);
+
+/// Balance the load every `BALANCE_INTERVAL` timer ticks.
+#[cfg(all(feature = "smp", feature = "irq"))]
+const BALANCE_INTERVAL: usize = 4;
+
 pub(crate) struct AxRunQueue {
-    scheduler: Scheduler,
+    cpu_id: usize,
+    scheduler: SpinRaw<Scheduler>, // IRQs and preemption are disabled by `AxRunQueueRef`
+    nr_ready: AtomicUsize,
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    ticks: AtomicUsize,
+}
+
+/// A reference to the run queue of the current CPU.
+///
+/// Preemption and local IRQs are disabled while holding it, so that the
+/// current task will not be migrated to other CPUs.
+pub(crate) struct AxRunQueueRef {
+    inner: &'static AxRunQueue,
+    _guard: NoPreemptIrqSave,
+}
+
+impl Deref for AxRunQueueRef {
+    type Target = AxRunQueue;
+    fn deref(&self) -> &Self::Target {
+        self.inner
+    }
+}
+
+/// Returns the run queue of the current CPU, with preemption and local IRQs
+/// disabled.
+pub(crate) fn current_run_queue() -> AxRunQueueRef {
+    let guard = NoPreemptIrqSave::new();
+    // Safety: preemption is disabled, and the run queue of the current CPU
+    // has been initialized in `init()` or `init_secondary()`.
+    let inner = unsafe { RUN_QUEUE.current_ref_raw().get_unchecked() };
+    AxRunQueueRef {
+        inner,
+        _guard: guard,
+    }
+}
+
+/// Returns the run queue of the given CPU, or [`None`] if it's not online.
+fn remote_run_queue(cpu_id: usize) -> Option<&'static AxRunQueue> {
+    if cpu_id < axconfig::SMP && ONLINE_CPUS.load(Ordering::Acquire) & (1 << cpu_id) != 0 {
+        // Safety: the run queue is initialized and never be dropped.
+        Some(unsafe { RUN_QUEUE.remote_ref_raw(cpu_id).get_unchecked() })
+    } else {
+        None
+    }
+}
+
+/// Selects the run queue to put the given ready task on.
+///
+/// It prefers the current CPU if it's allowed by the task's CPU affinity,
+/// otherwise the least loaded one of the allowed CPUs.
+fn select_run_queue(curr_rq: &AxRunQueue, task: &AxTaskRef) -> &'static AxRunQueue {
+    let cpumask = task.cpumask();
+    if cpumask & (1 << curr_rq.cpu_id) != 0 {
+        return remote_run_queue(curr_rq.cpu_id).unwrap();
+    }
+    (0..axconfig::SMP)
+        .filter(|&cpu_id| cpumask & (1 << cpu_id) != 0)
+        .filter_map(remote_run_queue)
+        .min_by_key(|rq| rq.nr_ready())
+        .unwrap_or_else(|| remote_run_queue(curr_rq.cpu_id).unwrap())
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
+    pub fn new(cpu_id: usize) -> Self {
         let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
+        gc_task.set_cpumask(1 << cpu_id);
+        gc_task.set_cpu_id(cpu_id);
         let mut scheduler = Scheduler::new();
         scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+        Self {
+            cpu_id,
+            scheduler: SpinRaw::new(scheduler),
+            nr_ready: AtomicUsize::new(1),
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            ticks: AtomicUsize::new(0),
+        }
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
+    /// Returns the number of ready tasks in this run queue.
+    pub fn nr_ready(&self) -> usize {
+        self.nr_ready.load(Ordering::Relaxed)
+    }
+
+    pub fn add_task(&self, task: AxTaskRef) {
         debug!("task spawn: {}", task.id_name());
         assert!(task.is_ready());
-        self.scheduler.add_task(task);
+        select_run_queue(self, &task).enqueue(task);
     }
 
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        #[cfg(feature = "sched_edf")]
+        if curr.is_idle() {
+            // keep the clock of deadlines running.
+            self.scheduler.lock().idle_tick();
+        }
+        if !curr.is_idle() && self.scheduler.lock().task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        if self.ticks.fetch_add(1, Ordering::Relaxed) % BALANCE_INTERVAL == 0 {
+            self.balance();
+        }
     }
 
-    pub fn yield_current(&mut self) {
+    pub fn yield_current(&self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
-        self.scheduler
-            .set_priority(crate::current().as_task_ref(), prio)
+    pub fn set_current_priority(&self, prio: isize) -> bool {
+        let curr = crate::current();
+        let mut scheduler = self.scheduler.lock();
+        if !scheduler.set_priority(curr.as_task_ref(), prio) {
+            return false;
+        }
+        // The effective priority may be boosted by priority inheritance.
+        curr.set_base_priority(prio);
+        curr.take_priority_changed();
+        scheduler.set_priority(curr.as_task_ref(), curr.priority());
+        true
+    }
+
+    #[cfg(feature = "sched_edf")]
+    pub fn set_current_deadline(&self, params: Option<scheduler::EDFParams>) -> bool {
+        let curr = crate::current();
+        if !self.scheduler.lock().set_params(curr.as_task_ref(), params) {
+            return false;
+        }
+        if params.is_some() {
+            curr.set_cpumask(1 << self.cpu_id);
+        }
+        true
+    }
+
+    /// Sets the CPU affinity of the current task. If the current CPU is not
+    /// allowed any more, the current task is migrated to another CPU.
+    ///
+    /// Returns `false` if the current task is a deadline task, which is bound
+    /// to the CPU where its bandwidth is reserved.
+    pub fn set_current_affinity(&self, cpumask: usize) -> bool {
+        let curr = crate::current();
+        #[cfg(feature = "sched_edf")]
+        if curr.as_task_ref().params().is_some() {
+            return false;
+        }
+        curr.set_cpumask(cpumask);
+        if cpumask & (1 << self.cpu_id) == 0 {
+            debug!("task migrate: {}", curr.id_name());
+            assert!(curr.is_running());
+            curr.set_state(TaskState::Ready);
+            select_run_queue(self, curr.as_task_ref()).enqueue(curr.clone());
+            self.resched(false);
+        }
+        true
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(&self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we get the reference of the run queue, we must have disabled
+        // both IRQs and preemption. So we need to set `current_disable_count`
+        // to 1 in `can_preempt()` to obtain the preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,25 +220,37 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(&self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
         assert!(!curr.is_idle());
         if curr.is_init() {
-            EXITED_TASKS.lock().clear();
+            // Safety: IRQs and preemption are disabled.
+            unsafe { EXITED_TASKS.current_ref_raw() }.lock().clear();
             axhal::misc::terminate();
         } else {
             curr.set_state(TaskState::Exited);
+            // Release the CPU bandwidth reserved for the task.
+            #[cfg(feature = "sched_edf")]
+            self.scheduler.lock().set_params(curr.as_task_ref(), None);
             curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            // Safety: IRQs and preemption are disabled.
+            unsafe {
+                EXITED_TASKS
+                    .current_ref_raw()
+                    .lock()
+                    .push_back(curr.clone());
+                WAIT_FOR_EXIT
+                    .current_ref_raw()
+                    .notify_one_locked(false, self);
+            }
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
+    pub fn block_current<F>(&self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
     {
@@ -108,8 +259,10 @@ impl AxRunQueue {
         assert!(curr.is_running());
         assert!(!curr.is_idle());
 
-        // we must not block current task with preemption disabled.
-        #[cfg(feature = "preempt")]
+        // we must not block current task with preemption disabled. The guards
+        // of `kernel_guard` are no-ops in user mode (e.g., unit tests), where
+        // the preemption count is not maintained.
+        #[cfg(all(feature = "preempt", target_os = "none"))]
         assert!(curr.can_preempt(1));
 
         curr.set_state(TaskState::Blocked);
@@ -117,12 +270,17 @@ impl AxRunQueue {
         self.resched(false);
     }
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
+    pub fn unblock_task(&self, task: AxTaskRef, resched: bool) {
         debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
-            if resched {
+        // The task may be woken up by multiple events (e.g., timer and
+        // `notify()`) on different CPUs at the same time, only one of them
+        // wins the state transition.
+        if task.transition_state(TaskState::Blocked, TaskState::Ready) {
+            task.account_wakeup();
+            let rq = select_run_queue(self, &task);
+            // the priority changed while it was blocked is applied here
+            rq.enqueue(task);
+            if resched && rq.cpu_id == self.cpu_id {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
             }
@@ -130,7 +288,7 @@ impl AxRunQueue {
     }
 
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(&self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -146,24 +304,104 @@ impl AxRunQueue {
 }
 
 impl AxRunQueue {
+    fn enqueue(&self, task: AxTaskRef) {
+        task.set_cpu_id(self.cpu_id);
+        let mut scheduler = self.scheduler.lock();
+        sync_priority(&mut scheduler, &task);
+        scheduler.add_task(task);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        let task = self.scheduler.lock().pick_next_task();
+        if task.is_some() {
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+        }
+        task
+    }
+
+    /// Tries to steal a ready task, which is allowed to run on this CPU, from
+    /// the given run queue.
+    ///
+    /// It never spins on the lock of the remote run queue, to avoid deadlocks
+    /// with the CPU which is stealing from us at the same time.
+    #[cfg(feature = "smp")]
+    fn steal_from(&self, src: &AxRunQueue) -> Option<AxTaskRef> {
+        let task = {
+            let mut scheduler = src.scheduler.try_lock()?;
+            let task = scheduler.pick_next_task()?;
+            if task.cpumask() & (1 << self.cpu_id) == 0 {
+                scheduler.put_prev_task(task, false);
+                return None;
+            }
+            task
+        };
+        src.nr_ready.fetch_sub(1, Ordering::Relaxed);
+        trace!(
+            "task steal: {}, CPU {} -> {}",
+            task.id_name(),
+            src.cpu_id,
+            self.cpu_id
+        );
+        Some(task)
+    }
+
+    /// Steals a ready task from the busiest run queue, if there is one.
+    #[cfg(feature = "smp")]
+    fn steal_task(&self) -> Option<AxTaskRef> {
+        let busiest = (0..axconfig::SMP)
+            .filter(|&cpu_id| cpu_id != self.cpu_id)
+            .filter_map(remote_run_queue)
+            .max_by_key(|rq| rq.nr_ready())?;
+        if busiest.nr_ready() == 0 {
+            return None;
+        }
+        self.steal_from(busiest)
+    }
+
+    /// Moves one task from the busiest run queue to this run queue, if the
+    /// load is imbalanced.
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    fn balance(&self) {
+        let Some(busiest) = (0..axconfig::SMP)
+            .filter(|&cpu_id| cpu_id != self.cpu_id)
+            .filter_map(remote_run_queue)
+            .max_by_key(|rq| rq.nr_ready())
+        else {
+            return;
+        };
+        if busiest.nr_ready() > self.nr_ready() + 1 {
+            if let Some(task) = self.steal_from(busiest) {
+                self.enqueue(task);
+            }
+        }
+    }
+
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
-    fn resched(&mut self, preempt: bool) {
+    fn resched(&self, preempt: bool) {
         let prev = crate::current();
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                let mut scheduler = self.scheduler.lock();
+                sync_priority(&mut scheduler, prev.as_task_ref());
+                scheduler.put_prev_task(prev.clone(), preempt);
+                self.nr_ready.fetch_add(1, Ordering::Relaxed);
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
+        let next = self.pick_next_task();
+        #[cfg(feature = "smp")]
+        let next = next.or_else(|| self.steal_task());
+        let next = next.unwrap_or_else(|| unsafe {
             // Safety: IRQs must be disabled at this time.
             IDLE_TASK.current_ref_raw().get_unchecked().clone()
         });
+        next.set_cpu_id(self.cpu_id);
         self.switch_to(prev, next);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn switch_to(&self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
             prev_task.id_name(),
@@ -176,6 +414,20 @@ impl AxRunQueue {
             return;
         }
 
+        let now = axhal::time::current_time_nanos();
+        prev_task.account_switch_out(now);
+        next_task.account_switch_in(now);
+
+        // The next task may be still switching out on another CPU, wait for
+        // its context to be saved.
+        #[cfg(feature = "smp")]
+        {
+            while next_task.on_cpu() {
+                core::hint::spin_loop();
+            }
+            next_task.set_on_cpu(true);
+        }
+
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
@@ -185,19 +437,101 @@ impl AxRunQueue {
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
+            #[cfg(feature = "smp")]
+            PREV_TASK.write_current_raw(Arc::as_ptr(prev_task.as_task_ref()) as usize);
+
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            // Now we are back and may be running on another CPU.
+            #[cfg(feature = "smp")]
+            clear_prev_task_on_cpu();
         }
     }
 }
 
+/// Applies the changed priority of the task to the scheduler, if any.
+fn sync_priority(scheduler: &mut Scheduler, task: &AxTaskRef) {
+    if task.take_priority_changed() {
+        scheduler.set_priority(task, task.priority());
+    }
+}
+
+/// Applies the changed priority of the given task to the scheduler of the run
+/// queue it belongs to.
+///
+/// A ready task is re-queued with the new priority. The priority of a blocked
+/// task is applied when it's woken up.
+pub(crate) fn update_task_priority(task: &AxTaskRef) {
+    let _guard = NoPreemptIrqSave::new();
+    loop {
+        if matches!(task.state(), TaskState::Blocked | TaskState::Exited) {
+            return;
+        }
+        let Some(rq) = remote_run_queue(task.cpu_id()) else {
+            return;
+        };
+        let mut scheduler = rq.scheduler.lock();
+        if !task.priority_changed() {
+            return; // already applied by others
+        }
+        if task.cpu_id() == rq.cpu_id {
+            match task.state() {
+                TaskState::Running => {
+                    sync_priority(&mut scheduler, task);
+                    return;
+                }
+                TaskState::Ready => {
+                    if let Some(task) = scheduler.remove_task(task) {
+                        sync_priority(&mut scheduler, &task);
+                        // Put it back without losing its place in the queue,
+                        // as it's not the one to blame.
+                        scheduler.put_prev_task(task, true);
+                        return;
+                    }
+                    // The task is moving between run queues, or is being put
+                    // back to the run queue, try again later.
+                }
+                _ => return,
+            }
+        }
+        drop(scheduler);
+        core::hint::spin_loop();
+    }
+}
+
+/// Clears the `on_cpu` flag of the previous task after the context switch is
+/// completed, so that it can be scheduled on other CPUs.
+///
+/// # Safety
+///
+/// IRQs and preemption must be disabled.
+#[cfg(feature = "smp")]
+pub(crate) unsafe fn clear_prev_task_on_cpu() {
+    let prev = PREV_TASK.read_current_raw() as *const crate::AxTask;
+    if !prev.is_null() {
+        // The previous task can not be dropped before its `on_cpu` flag is
+        // cleared, since it's not allowed to run on any CPU until then.
+        (*prev).set_on_cpu(false);
+        PREV_TASK.write_current_raw(0);
+    }
+}
+
 fn gc_entry() {
+    // The gc task is bound to its CPU, so it always accesses the per-CPU data
+    // of the same CPU.
+    let (exited_tasks, wait_for_exit) = unsafe {
+        (
+            EXITED_TASKS.current_ref_raw(),
+            WAIT_FOR_EXIT.current_ref_raw(),
+        )
+    };
     loop {
         // Drop all exited tasks and recycle resources.
-        let n = EXITED_TASKS.lock().len();
+        let n = exited_tasks.lock().len();
         for _ in 0..n {
             // Do not do the slow drops in the critical section.
-            let task = EXITED_TASKS.lock().pop_front();
+            let task = exited_tasks.lock().pop_front();
             if let Some(task) = task {
                 if Arc::strong_count(&task) == 1 {
                     // If I'm the last holder of the task, drop it immediately.
@@ -205,29 +539,43 @@ fn gc_entry() {
                 } else {
                     // Otherwise (e.g, `switch_to` is not compeleted, held by the
                     // joiner, etc), push it back and wait for them to drop first.
-                    EXITED_TASKS.lock().push_back(task);
+                    exited_tasks.lock().push_back(task);
                 }
             }
         }
-        WAIT_FOR_EXIT.wait();
+        wait_for_exit.wait();
     }
 }
 
 pub(crate) fn init() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
+    idle_task.set_cpumask(1 << cpu_id);
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
 
     let main_task = TaskInner::new_init("main".into());
+    main_task.set_cpu_id(cpu_id);
     main_task.set_state(TaskState::Running);
+    main_task.account_switch_in(axhal::time::current_time_nanos());
 
-    RUN_QUEUE.init_by(AxRunQueue::new());
+    RUN_QUEUE.with_current(|rq| rq.init_by(AxRunQueue::new(cpu_id)));
+    ONLINE_CPUS.fetch_or(1 << cpu_id, Ordering::Release);
     unsafe { CurrentTask::init_current(main_task) }
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     let idle_task = TaskInner::new_init("idle".into());
+    idle_task.set_cpumask(1 << cpu_id);
+    idle_task.set_cpu_id(cpu_id);
     idle_task.set_state(TaskState::Running);
+    idle_task.account_switch_in(axhal::time::current_time_nanos());
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
+
+    RUN_QUEUE.with_current(|rq| rq.init_by(AxRunQueue::new(cpu_id)));
+    ONLINE_CPUS.fetch_or(1 << cpu_id, Ordering::Release);
     unsafe { CurrentTask::init_current(idle_task) }
 }
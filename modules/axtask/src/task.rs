@@ -1,13 +1,14 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
-use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
-
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicIsize, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
+use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull, time::Duration};
 
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
+use spinlock::SpinNoIrq;
 
 use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
 
@@ -18,13 +19,57 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// The task is running on a CPU.
     Running = 1,
+    /// The task is in a run queue, waiting to be scheduled.
     Ready = 2,
+    /// The task is blocked, e.g., waiting in a wait queue or sleeping.
     Blocked = 3,
+    /// The task has exited, but is not dropped yet.
     Exited = 4,
 }
 
+/// A snapshot of the statistics of a task, returned by [`TaskInner::stats`].
+#[derive(Debug, Clone)]
+pub struct TaskStats {
+    /// The task ID.
+    pub id: u64,
+    /// The task name.
+    pub name: String,
+    /// The current state.
+    pub state: TaskState,
+    /// The CPU whose run queue the task belongs to.
+    pub cpu_id: usize,
+    /// The effective priority.
+    pub priority: isize,
+    /// The total CPU time consumed, including the current run.
+    pub cpu_time: Duration,
+    /// The number of times the task has been switched out.
+    pub nr_switches: u64,
+    /// The number of times the task has been woken up from the blocked state.
+    pub nr_wakeups: u64,
+    /// The size of the kernel stack in bytes, or 0 if the task runs on the
+    /// boot stack.
+    pub stack_size: usize,
+    /// The maximum number of bytes of the kernel stack that have ever been
+    /// used, an estimate that may be a bit low. It's 0 without the
+    /// `stack_stats` feature.
+    pub stack_high_water: usize,
+}
+
+/// All alive tasks, indexed by the task IDs.
+static TASK_REGISTRY: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Returns references to all alive tasks, in order of their IDs.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    TASK_REGISTRY
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
 /// The inner task structure.
 pub struct TaskInner {
     id: TaskId,
@@ -35,9 +80,30 @@ pub struct TaskInner {
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
 
+    /// The CPU whose run queue the task belongs to.
+    cpu_id: AtomicUsize,
+    /// Bitmap of CPUs that the task is allowed to run on.
+    cpumask: AtomicUsize,
+    /// Whether the task is running on a CPU, or its context is being saved.
+    #[cfg(feature = "smp")]
+    on_cpu: AtomicBool,
+
+    /// The priority set by [`set_priority`](crate::set_priority). The smaller
+    /// the value, the higher the priority.
+    base_prio: AtomicIsize,
+    /// The effective priority, which may be boosted by priority inheritance.
+    prio: AtomicIsize,
+    /// Whether `prio` has been changed but not applied to the scheduler yet.
+    prio_changed: AtomicBool,
+    /// Priorities inherited from the waiters of the locks held by the task,
+    /// indexed by the addresses of the locks.
+    inherited_prios: SpinNoIrq<BTreeMap<usize, isize>>,
+
     in_wait_queue: AtomicBool,
+    /// The CPU whose timer list holds the wakeup event of the task,
+    /// `usize::MAX` if there is none.
     #[cfg(feature = "irq")]
-    in_timer_list: AtomicBool,
+    timer_cpu: AtomicUsize,
 
     #[cfg(feature = "preempt")]
     need_resched: AtomicBool,
@@ -47,6 +113,13 @@ pub struct TaskInner {
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
 
+    /// The CPU time consumed before the current run, in nanoseconds.
+    cpu_time: AtomicU64,
+    /// The time when the task was last switched in, in nanoseconds.
+    last_run: AtomicU64,
+    nr_switches: AtomicU64,
+    nr_wakeups: AtomicU64,
+
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
 }
@@ -95,6 +168,50 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Gets the ID of the CPU whose run queue the task belongs to.
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    /// Gets the CPU affinity of the task, as a bitmap of CPU IDs.
+    pub fn cpumask(&self) -> usize {
+        self.cpumask.load(Ordering::Acquire)
+    }
+
+    /// Gets the effective priority of the task.
+    ///
+    /// It's the priority set by [`set_priority`](crate::set_priority), or the
+    /// priority inherited from a higher-priority task that is waiting for a
+    /// lock held by this task.
+    pub fn priority(&self) -> isize {
+        self.prio.load(Ordering::Acquire)
+    }
+
+    /// Takes a snapshot of the statistics of the task.
+    pub fn stats(&self) -> TaskStats {
+        let state = self.state();
+        let mut cpu_time = self.cpu_time.load(Ordering::Acquire);
+        if state == TaskState::Running {
+            let now = axhal::time::current_time_nanos();
+            cpu_time += now.saturating_sub(self.last_run.load(Ordering::Acquire));
+        }
+        TaskStats {
+            id: self.id.as_u64(),
+            name: self.name.clone(),
+            state,
+            cpu_id: self.cpu_id(),
+            priority: self.priority(),
+            cpu_time: Duration::from_nanos(cpu_time),
+            nr_switches: self.nr_switches.load(Ordering::Relaxed),
+            nr_wakeups: self.nr_wakeups.load(Ordering::Relaxed),
+            stack_size: self.kstack.as_ref().map_or(0, |s| s.size()),
+            #[cfg(feature = "stack_stats")]
+            stack_high_water: self.kstack.as_ref().map_or(0, |s| s.high_water_mark()),
+            #[cfg(not(feature = "stack_stats"))]
+            stack_high_water: 0,
+        }
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -115,15 +232,27 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            cpu_id: AtomicUsize::new(0),
+            cpumask: AtomicUsize::new(usize::MAX),
+            #[cfg(feature = "smp")]
+            on_cpu: AtomicBool::new(false),
+            base_prio: AtomicIsize::new(crate::api::DEFAULT_PRIO),
+            prio: AtomicIsize::new(crate::api::DEFAULT_PRIO),
+            prio_changed: AtomicBool::new(false),
+            inherited_prios: SpinNoIrq::new(BTreeMap::new()),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
-            in_timer_list: AtomicBool::new(false),
+            timer_cpu: AtomicUsize::new(usize::MAX),
             #[cfg(feature = "preempt")]
             need_resched: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
+            cpu_time: AtomicU64::new(0),
+            last_run: AtomicU64::new(0),
+            nr_switches: AtomicU64::new(0),
+            nr_wakeups: AtomicU64::new(0),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
         }
@@ -142,17 +271,27 @@ impl TaskInner {
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        t.into_ref()
     }
 
     pub(crate) fn new_init(name: String) -> AxTaskRef {
         // init_task does not change PC and SP, so `entry` and `kstack` fields are not used.
         let mut t = Self::new_common(TaskId::new(), name);
         t.is_init = true;
+        #[cfg(feature = "smp")]
+        t.on_cpu.store(true, Ordering::Relaxed);
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        t.into_ref()
+    }
+
+    /// Wraps the task into an [`AxTaskRef`], and adds it to the registry.
+    fn into_ref(self) -> AxTaskRef {
+        let id = self.id.as_u64();
+        let task = Arc::new(AxTask::new(self));
+        TASK_REGISTRY.lock().insert(id, Arc::downgrade(&task));
+        task
     }
 
     #[inline]
@@ -165,6 +304,94 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Transitions the state from `current` to `new` atomically. Returns
+    /// `false` if the task is not in the `current` state.
+    #[inline]
+    pub(crate) fn transition_state(&self, current: TaskState, new: TaskState) -> bool {
+        self.state
+            .compare_exchange(
+                current as u8,
+                new as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release)
+    }
+
+    #[inline]
+    pub(crate) fn set_cpumask(&self, cpumask: usize) {
+        self.cpumask.store(cpumask, Ordering::Release)
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release)
+    }
+
+    /// Sets the base priority of the task. Returns `true` if the effective
+    /// priority is changed.
+    pub(crate) fn set_base_priority(&self, prio: isize) -> bool {
+        let inherited_prios = self.inherited_prios.lock();
+        self.base_prio.store(prio, Ordering::Release);
+        self.update_priority(&inherited_prios)
+    }
+
+    /// Inherits the priority `prio` from a waiter of the lock identified by
+    /// `key`. Returns `true` if the effective priority is changed.
+    pub(crate) fn inherit_priority(&self, key: usize, prio: isize) -> bool {
+        let mut inherited_prios = self.inherited_prios.lock();
+        let entry = inherited_prios.entry(key).or_insert(prio);
+        *entry = (*entry).min(prio);
+        self.update_priority(&inherited_prios)
+    }
+
+    /// Drops the priority inherited through the lock identified by `key`.
+    /// Returns `true` if the effective priority is changed.
+    pub(crate) fn disinherit_priority(&self, key: usize) -> bool {
+        let mut inherited_prios = self.inherited_prios.lock();
+        if inherited_prios.remove(&key).is_none() {
+            return false;
+        }
+        self.update_priority(&inherited_prios)
+    }
+
+    fn update_priority(&self, inherited_prios: &BTreeMap<usize, isize>) -> bool {
+        let base_prio = self.base_prio.load(Ordering::Acquire);
+        let prio = inherited_prios
+            .values()
+            .copied()
+            .fold(base_prio, isize::min);
+        if self.prio.swap(prio, Ordering::AcqRel) != prio {
+            self.prio_changed.store(true, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub(crate) fn priority_changed(&self) -> bool {
+        self.prio_changed.load(Ordering::Acquire)
+    }
+
+    /// Clears the `prio_changed` flag, returns whether it was set.
+    #[inline]
+    pub(crate) fn take_priority_changed(&self) -> bool {
+        self.prio_changed.swap(false, Ordering::AcqRel)
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -175,11 +402,6 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
-    #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
-    }
-
     #[inline]
     pub(crate) const fn is_init(&self) -> bool {
         self.is_init
@@ -203,13 +425,22 @@ impl TaskInner {
     #[inline]
     #[cfg(feature = "irq")]
     pub(crate) fn in_timer_list(&self) -> bool {
-        self.in_timer_list.load(Ordering::Acquire)
+        self.timer_cpu().is_some()
+    }
+
+    /// Returns the CPU whose timer list holds the wakeup event of the task.
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn timer_cpu(&self) -> Option<usize> {
+        let cpu_id = self.timer_cpu.load(Ordering::Acquire);
+        (cpu_id != usize::MAX).then_some(cpu_id)
     }
 
     #[inline]
     #[cfg(feature = "irq")]
-    pub(crate) fn set_in_timer_list(&self, in_timer_list: bool) {
-        self.in_timer_list.store(in_timer_list, Ordering::Release);
+    pub(crate) fn set_timer_cpu(&self, cpu_id: Option<usize>) {
+        self.timer_cpu
+            .store(cpu_id.unwrap_or(usize::MAX), Ordering::Release);
     }
 
     #[inline]
@@ -243,14 +474,33 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let rq = crate::current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    /// Accounts the CPU time of the run ending at `now`.
+    #[inline]
+    pub(crate) fn account_switch_out(&self, now: u64) {
+        let delta = now.saturating_sub(self.last_run.load(Ordering::Acquire));
+        self.cpu_time.fetch_add(delta, Ordering::AcqRel);
+        self.nr_switches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the start time of the run beginning at `now`.
+    #[inline]
+    pub(crate) fn account_switch_in(&self, now: u64) {
+        self.last_run.store(now, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn account_wakeup(&self) {
+        self.nr_wakeups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &AxRunQueue) {
         self.exit_code.store(exit_code, Ordering::Release);
         self.wait_for_exit.notify_all_locked(false, rq);
     }
@@ -274,6 +524,7 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_REGISTRY.lock().remove(&self.id.as_u64());
     }
 }
 
@@ -285,8 +536,13 @@ struct TaskStack {
 impl TaskStack {
     pub fn alloc(size: usize) -> Self {
         let layout = Layout::from_size_align(size, 16).unwrap();
+        // Zero-filled for `high_water_mark()`.
+        #[cfg(feature = "stack_stats")]
+        let ptr = unsafe { alloc::alloc::alloc_zeroed(layout) };
+        #[cfg(not(feature = "stack_stats"))]
+        let ptr = unsafe { alloc::alloc::alloc(layout) };
         Self {
-            ptr: NonNull::new(unsafe { alloc::alloc::alloc(layout) }).unwrap(),
+            ptr: NonNull::new(ptr).unwrap(),
             layout,
         }
     }
@@ -294,6 +550,28 @@ impl TaskStack {
     pub const fn top(&self) -> VirtAddr {
         unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
     }
+
+    pub const fn size(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns the maximum stack usage so far.
+    ///
+    /// The stack grows downwards from a zero-filled memory, so the bytes below
+    /// the lowest non-zero word have never been touched. It's approximate:
+    /// the deepest words may have been written with zeros, and the stack of a
+    /// running task may grow while it's scanned.
+    #[cfg(feature = "stack_stats")]
+    pub fn high_water_mark(&self) -> usize {
+        let words = self.ptr.as_ptr() as *const usize;
+        let nr_words = self.size() / core::mem::size_of::<usize>();
+        // The stack may be in use by another CPU, do not let the compiler
+        // assume its contents are stable.
+        let unused_words = (0..nr_words)
+            .take_while(|&i| unsafe { words.add(i).read_volatile() } == 0)
+            .count();
+        self.size() - unused_words * core::mem::size_of::<usize>()
+    }
 }
 
 impl Drop for TaskStack {
@@ -355,8 +633,11 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // the previous task has been switched out completely
+    #[cfg(feature = "smp")]
+    unsafe {
+        crate::run_queue::clear_prev_task_on_cpu()
+    };
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
@@ -23,7 +23,7 @@ fn test_sched_fifo() {
                 assert_eq!(order, i); // FIFO scheduler
             },
             format!("T{}", i),
-            0x1000,
+            0x4000,
         );
     }
 
@@ -39,9 +39,9 @@ fn test_fp_state_switch() {
 
     const NUM_TASKS: usize = 5;
     const FLOATS: [f64; NUM_TASKS] = [
-        3.141592653589793,
-        2.718281828459045,
-        -1.4142135623730951,
+        core::f64::consts::PI,
+        core::f64::consts::E,
+        -core::f64::consts::SQRT_2,
         0.0,
         0.618033988749895,
     ];
@@ -120,11 +120,97 @@ fn test_task_join() {
                 axtask::exit(i as _);
             },
             format!("T{}", i),
-            0x1000,
+            0x4000,
         ));
     }
 
-    for i in 0..NUM_TASKS {
-        assert_eq!(tasks[i].join(), Some(i as _));
+    for (i, task) in tasks.iter().enumerate() {
+        assert_eq!(task.join(), Some(i as _));
+    }
+}
+
+#[test]
+fn test_cpu_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let cpu_id = current().cpu_id();
+    assert!(!axtask::set_current_affinity(0));
+    assert!(axtask::set_current_affinity(1 << cpu_id));
+    assert_eq!(current().cpumask(), 1 << cpu_id);
+    assert_eq!(current().cpu_id(), cpu_id);
+
+    let task = axtask::spawn(move || {
+        assert_eq!(current().cpu_id(), cpu_id);
+    });
+    assert_eq!(task.join(), Some(0));
+    assert!(axtask::set_current_affinity(usize::MAX));
+}
+
+#[cfg(feature = "sched_rt")]
+#[test]
+fn test_sched_policy() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use axtask::RTPolicy;
+    assert_eq!(current().as_task_ref().policy(), RTPolicy::RoundRobin);
+    assert!(axtask::set_current_policy(RTPolicy::Fifo));
+    assert_eq!(current().as_task_ref().policy(), RTPolicy::Fifo);
+    assert!(axtask::set_current_policy(RTPolicy::RoundRobin));
+    assert_eq!(current().as_task_ref().policy(), RTPolicy::RoundRobin);
+}
+
+#[cfg(feature = "sched_edf")]
+#[test]
+fn test_deadline_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let cpu_id = current().cpu_id();
+    let params = axtask::EDFParams {
+        runtime: 1,
+        deadline: 10,
+        period: 10,
+    };
+    assert!(axtask::set_current_deadline(Some(params)));
+    assert_eq!(current().cpumask(), 1 << cpu_id);
+    // a deadline task is bound to the CPU where its bandwidth is reserved.
+    assert!(!axtask::set_current_affinity(usize::MAX));
+    assert!(!axtask::set_current_affinity(1 << cpu_id));
+    assert_eq!(current().cpumask(), 1 << cpu_id);
+
+    assert!(axtask::set_current_deadline(None));
+    assert!(axtask::set_current_affinity(usize::MAX));
+}
+
+#[test]
+fn test_task_stats() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let task = axtask::spawn_raw(|| WQ.wait(), "stats".into(), 0x4000);
+    let id = task.id().as_u64();
+    assert!(axtask::tasks().any(|t| t.id() == task.id()));
+
+    // Wait for the task to be blocked, then wake it up.
+    while !WQ.notify_one(true) {
+        axtask::yield_now();
     }
+    assert_eq!(task.join(), Some(0));
+
+    let stats = task.stats();
+    assert_eq!(stats.id, id);
+    assert_eq!(stats.name, "stats");
+    assert_eq!(stats.state, axtask::TaskState::Exited);
+    assert_eq!(stats.nr_wakeups, 1);
+    assert!(stats.nr_switches >= 2);
+    assert_eq!(stats.stack_size, 0x4000);
+    assert!(stats.stack_high_water > 0 && stats.stack_high_water <= 0x4000);
+
+    let curr_stats = current().stats();
+    assert_eq!(curr_stats.state, axtask::TaskState::Running);
+    assert_eq!(curr_stats.stack_size, 0); // the main task runs on the boot stack
 }
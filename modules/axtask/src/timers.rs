@@ -1,40 +1,62 @@
 use alloc::sync::Arc;
 use axhal::time::current_time;
+use kernel_guard::NoPreemptIrqSave;
 use lazy_init::LazyInit;
 use spinlock::SpinNoIrq;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
-use crate::{AxTaskRef, RUN_QUEUE};
+use crate::{current_run_queue, AxTaskRef};
 
-// TODO: per-CPU
+/// The timer list of each CPU, checked by its own timer ticks.
+///
+/// A woken task is put on a run queue allowed by its CPU affinity, which may
+/// not be the one of the CPU whose timer expired.
+#[percpu::def_percpu]
 static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
 
 struct TaskWakeupEvent(AxTaskRef);
 
 impl TimerEvent for TaskWakeupEvent {
     fn callback(self, _now: TimeValue) {
-        let mut rq = RUN_QUEUE.lock();
-        self.0.set_in_timer_list(false);
+        let rq = current_run_queue();
+        self.0.set_timer_cpu(None);
         rq.unblock_task(self.0, true);
     }
 }
 
+/// Returns the timer list of the given CPU, which must have been initialized.
+fn timer_list(cpu_id: usize) -> &'static SpinNoIrq<TimerList<TaskWakeupEvent>> {
+    // Safety: the timer list of a CPU is never dropped, and its events are
+    // only set by the tasks running on it, after it is initialized.
+    unsafe { TIMER_LIST.remote_ref_raw(cpu_id) }
+}
+
+/// Sets the wakeup event of the task in the timer list of the current CPU.
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(true);
+    let _guard = NoPreemptIrqSave::new();
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let mut timers = timer_list(cpu_id).lock();
+    task.set_timer_cpu(Some(cpu_id));
     timers.set(deadline, TaskWakeupEvent(task));
 }
 
+/// Cancels the wakeup event of the task, in the timer list it was set in.
 pub fn cancel_alarm(task: &AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(false);
+    let Some(cpu_id) = task.timer_cpu() else {
+        return;
+    };
+    let mut timers = timer_list(cpu_id).lock();
+    task.set_timer_cpu(None);
     timers.cancel(|t| Arc::ptr_eq(&t.0, task));
 }
 
+/// Wakes up the tasks whose deadlines have passed in the timer list of the
+/// current CPU. It's called by the timer IRQ handler.
 pub fn check_events() {
+    let timers = timer_list(axhal::cpu::this_cpu_id());
     loop {
         let now = current_time();
-        let event = TIMER_LIST.lock().expire_one(now);
+        let event = timers.lock().expire_one(now);
         if let Some((_deadline, event)) = event {
             event.callback(now);
         } else {
@@ -43,6 +65,7 @@ pub fn check_events() {
     }
 }
 
+/// Initializes the timer list of the current CPU.
 pub fn init() {
-    TIMER_LIST.init_by(SpinNoIrq::new(TimerList::new()));
+    TIMER_LIST.with_current(|timers| timers.init_by(SpinNoIrq::new(TimerList::new())));
 }
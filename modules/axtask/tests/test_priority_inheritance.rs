@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
+
+use axsync::Mutex;
+use axtask::{current, WaitQueue};
+
+// The smaller the value, the higher the priority, both for the real-time
+// priorities and the nice values of CFS.
+const HIGH: isize = 1;
+const MEDIUM: isize = 5;
+const LOW: isize = 10;
+
+const NUM_MEDIUM_TASKS: usize = 5;
+const MEDIUM_SPINS: usize = 100;
+
+static MUTEX: Mutex<()> = Mutex::new(());
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static CONTENDED: AtomicBool = AtomicBool::new(false);
+static BOOSTED_PRIO: AtomicIsize = AtomicIsize::new(0);
+static LOW_WQ: WaitQueue = WaitQueue::new();
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+static MEDIUM_READY: AtomicUsize = AtomicUsize::new(0);
+static MEDIUM_DONE: AtomicUsize = AtomicUsize::new(0);
+static START_WQ: WaitQueue = WaitQueue::new();
+
+#[test]
+fn test_priority_inheritance() {
+    axtask::init_scheduler();
+    let main_prio = current().priority();
+
+    let low = axtask::spawn(|| {
+        axtask::set_priority(LOW);
+        let prio = current().priority();
+        let guard = MUTEX.lock();
+        LOCKED.store(true, Ordering::Release);
+        // Hold the lock until the high-priority task contends for it.
+        LOW_WQ.wait_until(|| CONTENDED.load(Ordering::Acquire));
+        BOOSTED_PRIO.store(current().priority(), Ordering::Release);
+        drop(guard);
+        assert_eq!(current().priority(), prio);
+    });
+    while !LOCKED.load(Ordering::Acquire) {
+        axtask::yield_now();
+    }
+
+    let mediums = (0..NUM_MEDIUM_TASKS)
+        .map(|_| {
+            axtask::spawn(|| {
+                axtask::set_priority(MEDIUM);
+                MEDIUM_READY.fetch_add(1, Ordering::Release);
+                START_WQ.wait_until(|| STARTED.load(Ordering::Acquire));
+                for _ in 0..MEDIUM_SPINS {
+                    axtask::yield_now();
+                }
+                MEDIUM_DONE.fetch_add(1, Ordering::Release);
+            })
+        })
+        .collect::<Vec<_>>();
+    while MEDIUM_READY.load(Ordering::Acquire) < NUM_MEDIUM_TASKS {
+        axtask::yield_now();
+    }
+
+    // The medium-priority tasks start spinning, and the low-priority task is
+    // woken up to release the lock, which it can't do before the spinners
+    // finish unless it inherits our priority. Only the real-time scheduler
+    // orders the tasks strictly by priority without timer ticks.
+    let prio_supported = axtask::set_priority(HIGH);
+    STARTED.store(true, Ordering::Release);
+    START_WQ.notify_all(false);
+    CONTENDED.store(true, Ordering::Release);
+    LOW_WQ.notify_one(false);
+
+    let guard = MUTEX.lock();
+    assert_eq!(MEDIUM_DONE.load(Ordering::Acquire), 0);
+    if prio_supported {
+        assert_eq!(BOOSTED_PRIO.load(Ordering::Acquire), HIGH);
+    }
+    drop(guard);
+
+    axtask::set_priority(main_prio);
+    assert_eq!(low.join(), Some(0));
+    for task in mediums {
+        assert_eq!(task.join(), Some(0));
+    }
+    assert_eq!(MEDIUM_DONE.load(Ordering::Acquire), NUM_MEDIUM_TASKS);
+}
@@ -5,6 +5,14 @@ define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" --test test_ramfs -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "fatfs" --test test_fatfs -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "diskfs" --test test_diskfs -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "ext4fs" --test test_ext2 --test test_ext4 -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "initrd" --test test_initrd -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "initrd overlayfs" --test test_overlayfs -- --nocapture)
+  $(call run_cmd,cargo test,-p axsync $(1) --features "axtask/sched_cfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_cfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_rt" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_edf" -- --nocapture)
+  $(call run_cmd,cargo test,-p axnet $(1) --features "dhcp" -- --nocapture)
   $(call run_cmd,cargo test,--workspace --exclude "arceos-*" $(1) -- --nocapture)
 endef
 
@@ -20,10 +20,17 @@ endef
 
 define make_disk_image_diskfs
 	@printf "    $(GREEN_C)Creating$(END_C) diskfs disk image \"$(1)\" ...\n"
-	@dd if=/dev/zero of=$(1) bs=1M count=2
+	@cargo run -q --release --manifest-path tools/diskfs/Cargo.toml -- mkfs -s 2M $(1)
+endef
+
+define make_disk_image_ext4fs
+  @printf "    $(GREEN_C)Creating$(END_C) ext2 disk image \"$(1)\" ...\n"
+  @dd if=/dev/zero of=$(1) bs=1M count=64
+  @mkfs.ext2 -q -E root_owner=0:0 $(1)
 endef
 
 define make_disk_image
 	$(if $(filter $(1),fatfs), $(call make_disk_image_fatfs,$(2)))
 	$(if $(filter $(1),diskfs), $(call make_disk_image_diskfs,$(2)))
+	$(if $(filter $(1),ext4fs), $(call make_disk_image_ext4fs,$(2)))
 endef
@@ -0,0 +1,209 @@
+//! Host-side tools for axdiskfs images: `mkfs` creates and populates an
+//! image, `fsck` checks and repairs one.
+
+#![deny(missing_docs)]
+
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use axdiskfs::fsck;
+use axdiskfs::image::Image;
+use axdiskfs::layout::Version;
+use axdiskfs::mkfs::{self, FormatOptions};
+use axdiskfs::{disk::Disk, sector::SectorManager};
+use driver_block::ramdisk::RamDisk;
+
+const USAGE: &str = "\
+usage: diskfs mkfs [-s SIZE] [-c SECTORS] [-V VERSION] [-d DIR] IMAGE
+       diskfs fsck [-r] IMAGE
+
+mkfs options:
+  -s SIZE     image size, with an optional K, M or G suffix (default: the
+              size of an existing IMAGE, or 2M)
+  -c SECTORS  sectors per cluster, a power of two (default: 1)
+  -V VERSION  format version, 1 for the original format with short names
+              and 32-bit sizes (default: 2)
+  -d DIR      copy the files under DIR into the image
+
+fsck options:
+  -r          repair the problems found";
+
+// exit codes of fsck, the same as e2fsck
+const FSCK_CORRECTED: u8 = 1;
+const FSCK_UNCORRECTED: u8 = 4;
+const FSCK_ERROR: u8 = 8;
+
+/// Load an image file into a RAM disk, resized to `size` if given.
+fn load(path: &str, size: Option<usize>) -> Result<SectorManager, String> {
+    let mut data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound && size.is_some() => Vec::new(),
+        Err(e) => return Err(format!("{path}: {e}")),
+    };
+    if let Some(size) = size {
+        data.resize(size, 0);
+    }
+    if data.is_empty() {
+        return Err(format!("{path}: empty image"));
+    }
+    Ok(SectorManager::new(Disk::new(RamDisk::from(&data))))
+}
+
+/// Write the RAM disk back to the image file.
+fn save(path: &str, sector: &SectorManager) -> Result<(), String> {
+    let mut data = vec![0u8; sector.sector_count() as usize];
+    sector
+        .read_sector_at(0, &mut data)
+        .map_err(|e| format!("{path}: {e:?}"))?;
+    fs::write(path, data).map_err(|e| format!("{path}: {e}"))
+}
+
+/// Parse a size like `64M`.
+fn parse_size(s: &str) -> Option<usize> {
+    let (num, unit) = match s.char_indices().last()? {
+        (i, 'K' | 'k') => (&s[..i], 1 << 10),
+        (i, 'M' | 'm') => (&s[..i], 1 << 20),
+        (i, 'G' | 'g') => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    num.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Copy the files and directories under `dir` into the image directory
+/// starting at `cluster`, in name order so that images are reproducible.
+fn populate(image: &mut Image, cluster: u32, dir: &Path) -> Result<(), String> {
+    let mut entries = fs::read_dir(dir)
+        .and_then(|it| it.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {e}", dir.display()))?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let err = |e: String| format!("{}: {e}", path.display());
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|_| err("name is not UTF-8".into()))?;
+        let ty = entry.file_type().map_err(|e| err(e.to_string()))?;
+        if ty.is_dir() {
+            let child = image
+                .create_dir(cluster, &name)
+                .map_err(|e| err(format!("{e:?}")))?;
+            populate(image, child, &path)?;
+        } else if ty.is_file() {
+            let data = fs::read(&path).map_err(|e| err(e.to_string()))?;
+            image
+                .create_file(cluster, &name, &data)
+                .map_err(|e| err(format!("{e:?}")))?;
+        } else {
+            eprintln!("{}: skipped, not a regular file", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn mkfs(args: &[String]) -> Result<(), String> {
+    let mut size = None;
+    let mut cluster_size = 1;
+    let mut version = Version::CURRENT;
+    let mut dir = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg}: missing value"));
+        match arg.as_str() {
+            "-s" => {
+                let v = value()?;
+                size = Some(parse_size(v).ok_or(format!("invalid size: {v}"))?);
+            }
+            "-c" => {
+                let v = value()?;
+                cluster_size = v
+                    .parse()
+                    .map_err(|_| format!("invalid cluster size: {v}"))?;
+            }
+            "-V" => {
+                let v = value()?;
+                version = match v.as_str() {
+                    "1" => Version::V1,
+                    "2" => Version::V2,
+                    _ => return Err(format!("invalid version: {v}")),
+                };
+            }
+            "-d" => dir = Some(value()?.clone()),
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg.clone()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+    let size = size.or_else(|| match fs::metadata(&path) {
+        Ok(meta) if meta.len() > 0 => None,
+        _ => Some(2 << 20),
+    });
+    let sector = load(&path, size)?;
+
+    let options = FormatOptions::for_disk(&sector)
+        .version(version)
+        .cluster_size(cluster_size);
+    let boot = mkfs::format(&sector, &options).map_err(|e| format!("failed to format: {e:?}"))?;
+    if let Some(dir) = dir {
+        let mut image = Image::open(&sector).map_err(|e| format!("{e:?}"))?;
+        let root = image.root_cluster();
+        populate(&mut image, root, Path::new(&dir))?;
+        image.flush().map_err(|e| format!("{e:?}"))?;
+    }
+    save(&path, &sector)?;
+    println!(
+        "{path}: {} clusters of {} bytes",
+        boot.clusters_count(),
+        boot.bytes_per_cluster()
+    );
+    Ok(())
+}
+
+fn fsck(args: &[String]) -> Result<(), String> {
+    let (repair, path) = match args {
+        [path] if !path.starts_with('-') => (false, path),
+        [flag, path] if flag == "-r" => (true, path),
+        _ => return Err(USAGE.into()),
+    };
+    let sector = load(path, None)?;
+    let report = fsck::check(&sector, repair).map_err(|e| match e {
+        driver_block::DevError::InvalidParam => format!("{path}: not a diskfs image"),
+        e => format!("{path}: {e:?}"),
+    })?;
+    for problem in &report.problems {
+        println!("{problem}");
+    }
+    if report.repaired {
+        save(path, &sector)?;
+    }
+    println!(
+        "{path}: {} files, {} directories, {}/{} clusters",
+        report.files, report.dirs, report.used_clusters, report.total_clusters
+    );
+    if report.is_clean() {
+        Ok(())
+    } else if report.repaired {
+        std::process::exit(FSCK_CORRECTED.into())
+    } else {
+        std::process::exit(FSCK_UNCORRECTED.into())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("mkfs") => mkfs(&args[1..]).map_err(|e| (e, 1)),
+        Some("fsck") => fsck(&args[1..]).map_err(|e| (e, FSCK_ERROR)),
+        _ => Err((USAGE.into(), 1)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err((e, code)) => {
+            eprintln!("{e}");
+            ExitCode::from(code)
+        }
+    }
+}
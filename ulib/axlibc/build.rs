@@ -55,6 +55,7 @@ typedef struct {{
 
         let allow_types = [
             "stat",
+            "statfs",
             "size_t",
             "ssize_t",
             "off_t",
@@ -67,6 +68,7 @@ typedef struct {{
             "timeval",
             "pthread_.*",
             "epoll_event",
+            "flock",
             "iovec",
             "tm",
         ];
@@ -82,6 +84,10 @@ typedef struct {{
             "SOL_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
+            "AT_.*",
+            "UTIME_.*",
+            "MS_.*",
+            "LOCK_.*",
         ];
 
         #[derive(Debug)]
@@ -1,9 +1,11 @@
-#include <stdio.h>
+#ifdef AX_CONFIG_FS
+
+#include <axlibc.h>
 #include <sys/file.h>
 
-// TODO
-int flock(int __fd, int __operation)
+int flock(int fd, int operation)
 {
-    unimplemented();
-    return 0;
+    return ax_flock(fd, operation);
 }
+
+#endif // AX_CONFIG_FS
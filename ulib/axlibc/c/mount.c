@@ -0,0 +1,23 @@
+#ifdef AX_CONFIG_FS
+
+#include <axlibc.h>
+#include <sys/mount.h>
+
+int mount(const char *source, const char *target, const char *filesystemtype,
+          unsigned long mountflags, const void *data)
+{
+    return ax_mount(source, target, filesystemtype, mountflags);
+}
+
+int umount(const char *target)
+{
+    return ax_umount(target);
+}
+
+// TODO: support MNT_DETACH
+int umount2(const char *target, int flags)
+{
+    return ax_umount(target);
+}
+
+#endif // AX_CONFIG_FS
@@ -14,28 +14,43 @@
 
 int h_errno;
 
-/*Only IPv4. Ports are always 0. Ignore service and hint. Results' ai_flags, ai_socktype,
- * ai_protocol and ai_canonname are 0 or NULL.  */
+/* IPv4 and IPv6. Only ai_family of the hint is used. Results' ai_socktype and ai_protocol
+ * are hard-coded to SOCK_STREAM and IPPROTO_TCP, ai_flags and ai_canonname are 0 or NULL. */
 int getaddrinfo(const char *__restrict node, const char *__restrict service,
                 const struct addrinfo *__restrict hints, struct addrinfo **__restrict res)
 {
-    struct sockaddr *addrs = (struct sockaddr *)malloc(MAXADDRS * sizeof(struct sockaddr));
+    int family = hints ? hints->ai_family : AF_UNSPEC;
+    if (family != AF_UNSPEC && family != AF_INET && family != AF_INET6)
+        return EAI_FAMILY;
+    struct sockaddr_storage *addrs =
+        (struct sockaddr_storage *)malloc(MAXADDRS * sizeof(struct sockaddr_storage));
     int res_len = ax_getaddrinfo(node, service, addrs, MAXADDRS);
-    if (res_len < 0)
+    if (res_len < 0) {
+        free(addrs);
         return EAI_FAIL;
-    if (res_len == 0)
-        return EAI_NONAME;
-    struct addrinfo *_res = (struct addrinfo *)calloc(res_len, sizeof(struct addrinfo));
+    }
+    // keep the addresses of the requested family at the front
+    int len = 0;
     for (int i = 0; i < res_len; i++) {
-        (_res + i)->ai_family = AF_INET;
-        (_res + i)->ai_addrlen = sizeof(struct sockaddr);
-        (_res + i)->ai_addr = (addrs + i);
+        if (family == AF_UNSPEC || (addrs + i)->ss_family == family)
+            *(addrs + len++) = *(addrs + i);
+    }
+    if (len == 0) {
+        free(addrs);
+        return EAI_NONAME;
+    }
+    struct addrinfo *_res = (struct addrinfo *)calloc(len, sizeof(struct addrinfo));
+    for (int i = 0; i < len; i++) {
+        (_res + i)->ai_family = (addrs + i)->ss_family;
+        (_res + i)->ai_addrlen = (addrs + i)->ss_family == AF_INET6 ? sizeof(struct sockaddr_in6)
+                                                                    : sizeof(struct sockaddr_in);
+        (_res + i)->ai_addr = (struct sockaddr *)(addrs + i);
         (_res + i)->ai_next = (_res + i + 1);
         // TODO: This is a hard-code part, only return TCP parameters
         (_res + i)->ai_socktype = SOCK_STREAM;
         (_res + i)->ai_protocol = IPPROTO_TCP;
     }
-    (_res + res_len - 1)->ai_next = NULL;
+    (_res + len - 1)->ai_next = NULL;
     *res = _res;
     return 0;
 }
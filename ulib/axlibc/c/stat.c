@@ -1,23 +1,43 @@
+#include <axlibc.h>
+#include <errno.h>
+#include <fcntl.h>
 #include <stdio.h>
 #include <sys/stat.h>
 #include <sys/types.h>
 
-// TODO:
+#ifdef AX_CONFIG_FS
+
 int fchmod(int fd, mode_t mode)
 {
-    unimplemented();
-    return 0;
+    return ax_fchmod(fd, mode);
 }
 
-// TODO:
-int mkdir(const char *path, mode_t mode)
+int chmod(const char *path, mode_t mode)
 {
-    unimplemented();
-    return 0;
+    return ax_chmod(path, mode);
 }
 
-// TODO
-int chmod(const char *path, mode_t mode)
+int futimens(int fd, const struct timespec times[2])
+{
+    return ax_futimens(fd, times);
+}
+
+int utimensat(int dirfd, const char *path, const struct timespec times[2], int flags)
+{
+    if (!path)
+        return ax_futimens(dirfd, times);
+    if (dirfd != AT_FDCWD && path[0] != '/') {
+        // TODO: paths relative to a directory fd
+        errno = ENOSYS;
+        return -1;
+    }
+    return ax_utimensat(path, times, flags);
+}
+
+#endif // AX_CONFIG_FS
+
+// TODO:
+int mkdir(const char *path, mode_t mode)
 {
     unimplemented();
     return 0;
@@ -0,0 +1,34 @@
+#ifdef AX_CONFIG_FS
+
+#include <axlibc.h>
+#include <string.h>
+#include <sys/statfs.h>
+#include <sys/statvfs.h>
+
+int statfs(const char *path, struct statfs *buf)
+{
+    return ax_statfs(path, buf);
+}
+
+int statvfs(const char *path, struct statvfs *buf)
+{
+    struct statfs st;
+    int ret = ax_statfs(path, &st);
+    if (ret < 0)
+        return ret;
+    memset(buf, 0, sizeof(*buf));
+    buf->f_bsize = st.f_bsize;
+    buf->f_frsize = st.f_frsize ? st.f_frsize : st.f_bsize;
+    buf->f_blocks = st.f_blocks;
+    buf->f_bfree = st.f_bfree;
+    buf->f_bavail = st.f_bavail;
+    buf->f_files = st.f_files;
+    buf->f_ffree = st.f_ffree;
+    buf->f_favail = st.f_ffree;
+    buf->f_fsid = st.f_fsid.__val[0];
+    buf->f_flag = st.f_flags;
+    buf->f_namemax = st.f_namelen;
+    return 0;
+}
+
+#endif // AX_CONFIG_FS
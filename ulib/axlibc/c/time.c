@@ -27,7 +27,7 @@ size_t strftime(char *restrict buf, size_t size, const char *restrict format,
 {
     return ax_strftime(buf, size, format, timeptr);
 }
-#endif
+#endif // AX_CONFIG_FS
 
 int __secs_to_tm(long long t, struct tm *tm)
 {
@@ -170,12 +170,23 @@ int gettimeofday(struct timeval *tv, struct timezone *tz)
     return 0;
 }
 
-// TODO:
+#ifdef AX_CONFIG_FS
 int utimes(const char *filename, const struct timeval times[2])
 {
-    unimplemented();
-    return 0;
+    struct timespec ts[2];
+    if (!times)
+        return ax_utimensat(filename, NULL, 0);
+    for (int i = 0; i < 2; i++) {
+        if (times[i].tv_usec < 0 || times[i].tv_usec >= 1000000) {
+            errno = EINVAL;
+            return -1;
+        }
+        ts[i].tv_sec = times[i].tv_sec;
+        ts[i].tv_nsec = times[i].tv_usec * 1000;
+    }
+    return ax_utimensat(filename, ts, 0);
 }
+#endif // AX_CONFIG_FS
 
 // TODO: Should match _clk,
 int clock_gettime(clockid_t _clk, struct timespec *ts)
@@ -221,7 +232,7 @@ double difftime(time_t t1, time_t t0)
 {
     return t1 - t0;
 }
-#endif
+#endif // AX_CONFIG_FS
 
 time_t mktime(struct tm *tm)
 {
@@ -139,11 +139,19 @@ int stat(const char *path, struct stat *buf)
     return ax_stat(path, buf);
 }
 
-// TODO:
 ssize_t readlink(const char *path, char *buf, size_t bufsiz)
 {
-    unimplemented();
-    return 0;
+    return ax_readlink(path, buf, bufsiz);
+}
+
+int symlink(const char *target, const char *linkpath)
+{
+    return ax_symlink(target, linkpath);
+}
+
+int link(const char *oldpath, const char *newpath)
+{
+    return ax_link(oldpath, newpath);
 }
 
 // TODO:
@@ -165,25 +173,29 @@ off_t lseek(int fd, off_t offset, int whence)
     return ax_lseek(fd, offset, whence);
 }
 
-// TODO:
 int fsync(int fd)
 {
-    unimplemented();
-    return 0;
+    return ax_fsync(fd);
 }
 
-// TODO
-int fdatasync(int __fildes)
+int fdatasync(int fd)
 {
-    unimplemented();
-    return 0;
+    return ax_fsync(fd);
+}
+
+int chown(const char *path, uid_t owner, gid_t group)
+{
+    return ax_chown(path, owner, group, 0);
+}
+
+int lchown(const char *path, uid_t owner, gid_t group)
+{
+    return ax_chown(path, owner, group, AT_SYMLINK_NOFOLLOW);
 }
 
-// TODO:
 int fchown(int fd, uid_t owner, gid_t group)
 {
-    unimplemented("owner: %x group: %x", owner, group);
-    return 0;
+    return ax_fchown(fd, owner, group);
 }
 
 // TODO:
@@ -0,0 +1,26 @@
+#ifndef __SYS_STATVFS_H__
+#define __SYS_STATVFS_H__
+
+#include <sys/types.h>
+
+struct statvfs {
+    unsigned long f_bsize;   /* filesystem block size */
+    unsigned long f_frsize;  /* fragment size */
+    fsblkcnt_t f_blocks;     /* size of fs in f_frsize units */
+    fsblkcnt_t f_bfree;      /* number of free blocks */
+    fsblkcnt_t f_bavail;     /* number of free blocks for unprivileged users */
+    fsfilcnt_t f_files;      /* number of inodes */
+    fsfilcnt_t f_ffree;      /* number of free inodes */
+    fsfilcnt_t f_favail;     /* number of free inodes for unprivileged users */
+    unsigned long f_fsid;    /* filesystem ID */
+    unsigned long f_flag;    /* mount flags */
+    unsigned long f_namemax; /* maximum filename length */
+    int __reserved[6];
+};
+
+#define ST_RDONLY 1
+#define ST_NOSUID 2
+
+int statvfs(const char *path, struct statvfs *buf);
+
+#endif // __SYS_STATVFS_H__
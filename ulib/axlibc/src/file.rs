@@ -1,9 +1,10 @@
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_long};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::{prelude::*, PollState, SeekFrom};
-use axstd::fs::OpenOptions;
+use axstd::fs::{FileLock, LockKind, Metadata, OpenOptions, Permissions};
 use axstd::sync::Mutex;
 
 use crate::{ctypes, fd_ops::FileLike, utils::char_ptr_to_str};
@@ -40,20 +41,7 @@ impl FileLike for File {
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
         let metadata = self.0.lock().metadata()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.permissions().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(metadata_to_stat(&metadata))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -72,6 +60,147 @@ impl FileLike for File {
     }
 }
 
+/// Convert [`Metadata`] to `struct stat`.
+fn metadata_to_stat(metadata: &Metadata) -> ctypes::stat {
+    let ty = metadata.file_type() as u8;
+    let perm = metadata.permissions().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: metadata.ino() as _,
+        st_nlink: metadata.nlink() as _,
+        st_mode,
+        st_uid: metadata.uid() as _,
+        st_gid: metadata.gid() as _,
+        st_size: metadata.size() as _,
+        st_blocks: metadata.blocks() as _,
+        st_blksize: 512,
+        st_atim: metadata.accessed().into(),
+        st_mtim: metadata.modified().into(),
+        st_ctim: metadata.changed().into(),
+        ..Default::default()
+    }
+}
+
+/// Convert a `uid_t`/`gid_t` argument to an optional ID, `(uid_t)-1` means
+/// unchanged.
+fn id_to_option(id: u32) -> Option<u32> {
+    if id == u32::MAX {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+/// Convert the `times` argument of `utimensat` to (atime, mtime). A null
+/// pointer sets both to the current time.
+unsafe fn timespecs_to_times(
+    times: *const ctypes::timespec,
+) -> LinuxResult<(Option<Duration>, Option<Duration>)> {
+    let now = axhal::time::current_time();
+    if times.is_null() {
+        return Ok((Some(now), Some(now)));
+    }
+    let convert = |ts: ctypes::timespec| match ts.tv_nsec {
+        n if n == ctypes::UTIME_NOW as c_long => Ok(Some(now)),
+        n if n == ctypes::UTIME_OMIT as c_long => Ok(None),
+        n if !(0..1_000_000_000).contains(&n) || ts.tv_sec < 0 => Err(LinuxError::EINVAL),
+        _ => Ok(Some(ts.into())),
+    };
+    let times = unsafe { core::slice::from_raw_parts(times, 2) };
+    Ok((convert(times[0])?, convert(times[1])?))
+}
+
+/// Convert the range of `struct flock` to the offset from the beginning of
+/// `file` and the length (`0` for up to the end of the file).
+fn flock_to_range(file: &File, fl: &ctypes::flock) -> LinuxResult<(u64, u64)> {
+    let base = match fl.l_whence {
+        0 => 0,
+        1 => file.0.lock().stream_position()? as i64,
+        2 => file.0.lock().metadata()?.len() as i64,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let mut start = base.checked_add(fl.l_start).ok_or(LinuxError::EOVERFLOW)?;
+    let mut len = fl.l_len;
+    if len < 0 {
+        // the range before `start`
+        start = start.checked_add(len).ok_or(LinuxError::EINVAL)?;
+        len = len.checked_neg().ok_or(LinuxError::EINVAL)?;
+    }
+    if start < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok((start as u64, len as u64))
+}
+
+/// Get, set or release a byte-range lock of the file indicated by `fd` for
+/// the `F_GETLK`, `F_SETLK` and `F_SETLKW` commands of `fcntl`, with the
+/// `struct flock` at `arg`.
+///
+/// The locks are owned by the opened file, and released when it's closed,
+/// so `l_pid` of the lock returned by `F_GETLK` is always `-1`.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, arg: usize) -> LinuxResult<c_int> {
+    let fl = arg as *mut ctypes::flock;
+    if fl.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let file = File::from_fd(fd)?;
+    let fl = unsafe { &mut *fl };
+    let (start, len) = flock_to_range(&file, fl)?;
+    let kind = match fl.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockKind::Shared),
+        ctypes::F_WRLCK => Some(LockKind::Exclusive),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    // don't hold the file while waiting for the lock
+    let locker = file.0.lock().locker();
+    if cmd == ctypes::F_GETLK {
+        let kind = kind.ok_or(LinuxError::EINVAL)?;
+        let lock = FileLock { kind, start, len };
+        match locker.get_lock(&lock)? {
+            Some(lock) => {
+                fl.l_type = match lock.kind {
+                    LockKind::Shared => ctypes::F_RDLCK,
+                    LockKind::Exclusive => ctypes::F_WRLCK,
+                } as _;
+                fl.l_whence = 0;
+                fl.l_start = lock.start as _;
+                fl.l_len = lock.len as _;
+                fl.l_pid = -1;
+            }
+            None => fl.l_type = ctypes::F_UNLCK as _,
+        }
+    } else {
+        let wait = cmd == ctypes::F_SETLKW;
+        locker.set_lock(kind, start, len, wait)?;
+    }
+    Ok(0)
+}
+
+/// Apply or remove an advisory lock on the file indicated by `fd`.
+///
+/// `operation` is one of `LOCK_SH`, `LOCK_EX` and `LOCK_UN`, with `LOCK_NB`
+/// to return `EWOULDBLOCK` instead of blocking.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("ax_flock <= {} {:#x}", fd, operation);
+    ax_call_body!(ax_flock, {
+        let operation = operation as u32;
+        let kind = match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => Some(LockKind::Shared),
+            ctypes::LOCK_EX => Some(LockKind::Exclusive),
+            ctypes::LOCK_UN => None,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let wait = operation & ctypes::LOCK_NB == 0;
+        let locker = File::from_fd(fd)?.0.lock().locker();
+        locker.flock(kind, wait)?;
+        Ok(0)
+    })
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -137,6 +266,18 @@ pub unsafe extern "C" fn ax_lseek(
     })
 }
 
+/// Write the cached data of the file indicated by `fd` to its device.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_fsync(fd: c_int) -> c_int {
+    debug!("ax_fsync <= {}", fd);
+    ax_call_body!(ax_fsync, {
+        File::from_fd(fd)?.0.lock().flush()?;
+        Ok(0)
+    })
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -166,7 +307,235 @@ pub unsafe extern "C" fn ax_lstat(path: *const c_char, buf: *mut ctypes::stat) -
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let metadata = axstd::fs::symlink_metadata(path?)?;
+        unsafe { *buf = metadata_to_stat(&metadata) };
+        Ok(0)
+    })
+}
+
+/// Get the attributes of the filesystem containing `path` and write into
+/// `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("ax_statfs <= {:?} {:#x}", path, buf as usize);
+    ax_call_body!(ax_statfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let info = axstd::fs::statfs(path?)?;
+        unsafe {
+            *buf = ctypes::statfs {
+                f_type: info.fs_type as _,
+                f_bsize: info.block_size as _,
+                f_blocks: info.blocks,
+                f_bfree: info.blocks_free,
+                f_bavail: info.blocks_avail,
+                f_files: info.files,
+                f_ffree: info.files_free,
+                f_namelen: info.name_len as _,
+                f_frsize: info.block_size as _,
+                ..Default::default()
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Mount the filesystem of `fstype` from `source` on the directory `target`,
+/// or mount the directory `source` on `target` as well if `flags` contains
+/// `MS_BIND`. Other flags are ignored.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: core::ffi::c_ulong,
+) -> c_int {
+    ax_call_body!(ax_mount, {
+        let source = char_ptr_to_str(source)?;
+        let target = char_ptr_to_str(target)?;
+        debug!("ax_mount <= {:?} {:?} {:#x}", source, target, flags);
+        if flags & ctypes::MS_BIND as core::ffi::c_ulong != 0 {
+            axstd::fs::bind_mount(source, target)?;
+        } else {
+            axstd::fs::mount(source, target, char_ptr_to_str(fstype)?)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted on the directory `target`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_umount(target: *const c_char) -> c_int {
+    ax_call_body!(ax_umount, {
+        let target = char_ptr_to_str(target)?;
+        debug!("ax_umount <= {:?}", target);
+        axstd::fs::umount(target)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`, without the
+/// terminating null byte. The target is truncated if `buf` is too small.
+///
+/// Return the number of bytes placed in `buf`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!("ax_readlink <= {:?} {:#x} {}", path, buf as usize, bufsize);
+    ax_call_body!(ax_readlink, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let target = axstd::fs::read_link(path?)?;
+        let len = target.len().min(bufsize);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Create a symbolic link `linkpath` which contains the string `target`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    ax_call_body!(ax_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!("ax_symlink <= {:?} {:?}", target, linkpath);
+        axstd::fs::symlink(target, linkpath)?;
+        Ok(0)
+    })
+}
+
+/// Create a new hard link `newpath` to the existing file `oldpath`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    ax_call_body!(ax_link, {
+        let oldpath = char_ptr_to_str(oldpath)?;
+        let newpath = char_ptr_to_str(newpath)?;
+        debug!("ax_link <= {:?} {:?}", oldpath, newpath);
+        axstd::fs::hard_link(oldpath, newpath)?;
+        Ok(0)
+    })
+}
+
+/// Change the permission bits of the file `path`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    ax_call_body!(ax_chmod, {
+        let path = char_ptr_to_str(path)?;
+        debug!("ax_chmod <= {:?} {:#o}", path, mode);
+        axstd::fs::set_permissions(path, Permissions::from_bits_truncate(mode as _))?;
+        Ok(0)
+    })
+}
+
+/// Change the permission bits of the file indicated by `fd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("ax_fchmod <= {} {:#o}", fd, mode);
+    ax_call_body!(ax_fchmod, {
+        let perm = Permissions::from_bits_truncate(mode as _);
+        File::from_fd(fd)?.0.lock().set_permissions(perm)?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and group of the file `path`. An ID of `-1` is left
+/// unchanged. If `flags` contains `AT_SYMLINK_NOFOLLOW`, a symbolic link
+/// itself is changed instead of its target.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_chown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    flags: c_int,
+) -> c_int {
+    ax_call_body!(ax_chown, {
+        let path = char_ptr_to_str(path)?;
+        debug!("ax_chown <= {:?} {} {} {:#x}", path, owner, group, flags);
+        let (uid, gid) = (id_to_option(owner), id_to_option(group));
+        if flags as u32 & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axstd::fs::lchown(path, uid, gid)?;
+        } else {
+            axstd::fs::chown(path, uid, gid)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Change the owner and group of the file indicated by `fd`. An ID of `-1`
+/// is left unchanged.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    debug!("ax_fchown <= {} {} {}", fd, owner, group);
+    ax_call_body!(ax_fchown, {
+        let (uid, gid) = (id_to_option(owner), id_to_option(group));
+        File::from_fd(fd)?.0.lock().set_owner(uid, gid)?;
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of the file `path`, with
+/// nanosecond precision. `UTIME_NOW` and `UTIME_OMIT` are supported in
+/// `tv_nsec`, and a null `times` sets both to the current time. If `flags`
+/// contains `AT_SYMLINK_NOFOLLOW`, a symbolic link itself is changed.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_utimensat(
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    ax_call_body!(ax_utimensat, {
+        let path = char_ptr_to_str(path)?;
+        debug!(
+            "ax_utimensat <= {:?} {:#x} {:#x}",
+            path, times as usize, flags
+        );
+        let (atime, mtime) = unsafe { timespecs_to_times(times)? };
+        if flags as u32 & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axstd::fs::set_symlink_times(path, atime, mtime)?;
+        } else {
+            axstd::fs::set_times(path, atime, mtime)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Like [`ax_utimensat`], but changes the file indicated by `fd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ax_futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    debug!("ax_futimens <= {} {:#x}", fd, times as usize);
+    ax_call_body!(ax_futimens, {
+        let (atime, mtime) = unsafe { timespecs_to_times(times)? };
+        File::from_fd(fd)?.0.lock().set_times(atime, mtime)?;
         Ok(0)
     })
 }
@@ -89,7 +89,11 @@ pub use self::fd_ops::{ax_close, ax_dup, ax_dup3, ax_fcntl, ax_fstat, ax_read, a
 pub use self::uio::ax_writev;
 
 #[cfg(feature = "fs")]
-pub use self::file::{ax_getcwd, ax_lseek, ax_lstat, ax_open, ax_stat};
+pub use self::file::{
+    ax_chmod, ax_chown, ax_fchmod, ax_fchown, ax_flock, ax_fsync, ax_futimens, ax_getcwd, ax_link,
+    ax_lseek, ax_lstat, ax_mount, ax_open, ax_readlink, ax_stat, ax_statfs, ax_symlink, ax_umount,
+    ax_utimensat,
+};
 
 #[cfg(feature = "net")]
 pub use self::socket::{
@@ -1,7 +1,7 @@
 use alloc::{sync::Arc, vec};
 use core::ffi::{c_char, c_int, c_void};
 use core::mem::size_of;
-use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
@@ -10,12 +10,22 @@ use axstd::sync::Mutex;
 
 use crate::{ctypes, fd_ops::FileLike, utils::char_ptr_to_str};
 
-pub enum Socket {
+pub struct Socket {
+    /// The address family given at creation, `AF_INET` or `AF_INET6`.
+    domain: u32,
+    inner: SocketInner,
+}
+
+enum SocketInner {
     Udp(Mutex<UdpSocket>),
     Tcp(Mutex<TcpSocket>),
 }
 
 impl Socket {
+    fn new(domain: u32, inner: SocketInner) -> Self {
+        Self { domain, inner }
+    }
+
     fn add_to_fd_table(self) -> LinuxResult<c_int> {
         super::fd_ops::add_file_like(Arc::new(self))
     }
@@ -28,97 +38,118 @@ impl Socket {
     }
 
     fn send(&self, buf: &[u8]) -> LinuxResult<usize> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
         }
     }
 
     fn recv(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
         }
     }
 
     pub fn poll(&self) -> LinuxResult<PollState> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
         }
     }
 
     fn local_addr(&self) -> LinuxResult<SocketAddr> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
         }
     }
 
     fn peer_addr(&self) -> LinuxResult<SocketAddr> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
         }
     }
 
+    /// Checks that `addr` is in the address family of the socket.
+    fn check_family(&self, addr: &SocketAddr) -> LinuxResult {
+        let family = match addr {
+            SocketAddr::V4(_) => ctypes::AF_INET,
+            SocketAddr::V6(_) => ctypes::AF_INET6,
+        };
+        if family != self.domain {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        Ok(())
+    }
+
     fn bind(&self, addr: SocketAddr) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+        self.check_family(&addr)?;
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
         }
     }
 
     fn connect(&self, addr: SocketAddr) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().connect(addr)?),
+        self.check_family(&addr)?;
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().connect(addr)?),
         }
     }
 
     fn sendto(&self, buf: &[u8], addr: SocketAddr) -> LinuxResult<usize> {
-        match self {
+        self.check_family(&addr)?;
+        match &self.inner {
             // diff: must bind before sendto
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
-            Socket::Tcp(_) => Err(LinuxError::EISCONN),
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
+            SocketInner::Tcp(_) => Err(LinuxError::EISCONN),
         }
     }
 
     fn recvfrom(&self, buf: &mut [u8]) -> LinuxResult<(usize, Option<SocketAddr>)> {
-        match self {
+        match &self.inner {
             // diff: must bind before recvfrom
-            Socket::Udp(udpsocket) => Ok(udpsocket
+            SocketInner::Udp(udpsocket) => Ok(udpsocket
                 .lock()
                 .recv_from(buf)
                 .map(|res| (res.0, Some(res.1)))?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
         }
     }
 
     fn listen(&self) -> LinuxResult {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+        match &self.inner {
+            SocketInner::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
         }
     }
 
-    fn accept(&self) -> LinuxResult<TcpSocket> {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+    fn accept(&self) -> LinuxResult<Socket> {
+        match &self.inner {
+            SocketInner::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            SocketInner::Tcp(tcpsocket) => {
+                let new_socket = tcpsocket.lock().accept()?;
+                Ok(Socket::new(
+                    self.domain,
+                    SocketInner::Tcp(Mutex::new(new_socket)),
+                ))
+            }
         }
     }
 
     fn shutdown(&self) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => {
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => {
                 let udpsocket = udpsocket.lock();
                 udpsocket.peer_addr()?;
                 udpsocket.shutdown()?;
                 Ok(())
             }
 
-            Socket::Tcp(tcpsocket) => {
+            SocketInner::Tcp(tcpsocket) => {
                 let tcpsocket = tcpsocket.lock();
                 tcpsocket.peer_addr()?;
                 tcpsocket.shutdown()?;
@@ -160,9 +191,9 @@ impl FileLike for Socket {
     }
 
     fn set_nonblocking(&self, nonblock: bool) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
-            Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
+            SocketInner::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
         }
         Ok(())
     }
@@ -192,14 +223,65 @@ impl From<ctypes::sockaddr_in> for SocketAddrV4 {
     }
 }
 
-fn into_sockaddr(addr: SocketAddr) -> (ctypes::sockaddr, ctypes::socklen_t) {
+impl From<SocketAddrV6> for ctypes::sockaddr_in6 {
+    fn from(addr: SocketAddrV6) -> ctypes::sockaddr_in6 {
+        ctypes::sockaddr_in6 {
+            sin6_family: ctypes::AF_INET6 as u16,
+            sin6_port: addr.port().to_be(),
+            sin6_flowinfo: addr.flowinfo(),
+            sin6_addr: ctypes::in6_addr {
+                __in6_union: ctypes::in6_addr__bindgen_ty_1 {
+                    __s6_addr: addr.ip().octets(),
+                },
+            },
+            sin6_scope_id: addr.scope_id(),
+        }
+    }
+}
+
+impl From<ctypes::sockaddr_in6> for SocketAddrV6 {
+    fn from(addr: ctypes::sockaddr_in6) -> SocketAddrV6 {
+        SocketAddrV6::new(
+            Ipv6Addr::from(unsafe { addr.sin6_addr.__in6_union.__s6_addr }),
+            u16::from_be(addr.sin6_port),
+            addr.sin6_flowinfo,
+            addr.sin6_scope_id,
+        )
+    }
+}
+
+/// Converts `addr` to a `sockaddr_in` or a `sockaddr_in6` in a
+/// `sockaddr_storage`, and returns it with its length.
+fn into_sockaddr(addr: SocketAddr) -> (ctypes::sockaddr_storage, ctypes::socklen_t) {
     debug!("    Sockaddr: {}", addr);
-    match addr {
-        SocketAddr::V4(addr) => (
-            unsafe { *(&ctypes::sockaddr_in::from(addr) as *const _ as *const ctypes::sockaddr) },
-            size_of::<ctypes::sockaddr>() as _,
-        ),
-        SocketAddr::V6(_) => panic!("IPv6 is not supported"),
+    let mut storage = ctypes::sockaddr_storage::default();
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let addr = ctypes::sockaddr_in::from(addr);
+            unsafe { (&mut storage as *mut _ as *mut ctypes::sockaddr_in).write(addr) };
+            size_of::<ctypes::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let addr = ctypes::sockaddr_in6::from(addr);
+            unsafe { (&mut storage as *mut _ as *mut ctypes::sockaddr_in6).write(addr) };
+            size_of::<ctypes::sockaddr_in6>()
+        }
+    };
+    (storage, len as _)
+}
+
+/// Writes `addr` to the buffer `dst` of `*addrlen` bytes, and sets `*addrlen`
+/// to the length of `addr`. It's truncated if the buffer is too small.
+unsafe fn write_sockaddr(
+    addr: SocketAddr,
+    dst: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) {
+    let (storage, len) = into_sockaddr(addr);
+    let copy_len = len.min(unsafe { *addrlen }) as usize;
+    unsafe {
+        core::ptr::copy_nonoverlapping(&storage as *const _ as *const u8, dst as *mut u8, copy_len);
+        *addrlen = len;
     }
 }
 
@@ -210,16 +292,21 @@ fn from_sockaddr(
     if addr.is_null() {
         return Err(LinuxError::EFAULT);
     }
-    if addrlen != size_of::<ctypes::sockaddr>() as _ {
+    if addrlen < size_of::<ctypes::sockaddr>() as _ {
         return Err(LinuxError::EINVAL);
     }
 
-    let mid = unsafe { *(addr as *const ctypes::sockaddr_in) };
-    if mid.sin_family != ctypes::AF_INET as u16 {
-        return Err(LinuxError::EINVAL);
-    }
-
-    let res = SocketAddr::V4(mid.into());
+    let res = match unsafe { (*addr).sa_family } as u32 {
+        ctypes::AF_INET => {
+            let addr = unsafe { *(addr as *const ctypes::sockaddr_in) };
+            SocketAddr::V4(addr.into())
+        }
+        ctypes::AF_INET6 if addrlen >= size_of::<ctypes::sockaddr_in6>() as _ => {
+            let addr = unsafe { *(addr as *const ctypes::sockaddr_in6) };
+            SocketAddr::V6(addr.into())
+        }
+        _ => return Err(LinuxError::EINVAL),
+    };
     debug!("    load sockaddr:{:#x} => {:?}", addr as usize, res);
     Ok(res)
 }
@@ -232,14 +319,17 @@ pub unsafe extern "C" fn ax_socket(domain: c_int, socktype: c_int, protocol: c_i
     debug!("ax_socket <= {} {} {}", domain, socktype, protocol);
     let (domain, socktype, protocol) = (domain as u32, socktype as u32, protocol as u32);
     ax_call_body!(ax_socket, {
-        match (domain, socktype, protocol) {
-            (ctypes::AF_INET, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
-            | (ctypes::AF_INET, ctypes::SOCK_STREAM, 0) => {
-                Socket::Tcp(Mutex::new(TcpSocket::new())).add_to_fd_table()
+        if domain != ctypes::AF_INET && domain != ctypes::AF_INET6 {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        match (socktype, protocol) {
+            (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP) | (ctypes::SOCK_STREAM, 0) => {
+                Socket::new(domain, SocketInner::Tcp(Mutex::new(TcpSocket::new())))
+                    .add_to_fd_table()
             }
-            (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
-            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => {
-                Socket::Udp(Mutex::new(UdpSocket::new())).add_to_fd_table()
+            (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP) | (ctypes::SOCK_DGRAM, 0) => {
+                Socket::new(domain, SocketInner::Udp(Mutex::new(UdpSocket::new())))
+                    .add_to_fd_table()
             }
             _ => Err(LinuxError::EINVAL),
         }
@@ -360,9 +450,7 @@ pub unsafe extern "C" fn ax_recvfrom(
 
         let res = socket.recvfrom(buf)?;
         if let Some(addr) = res.1 {
-            unsafe {
-                (*socket_addr, *addrlen) = into_sockaddr(addr);
-            }
+            unsafe { write_sockaddr(addr, socket_addr, addrlen) };
         }
         Ok(res.0)
     })
@@ -426,10 +514,8 @@ pub unsafe extern "C" fn ax_accept(
         let socket = Socket::from_fd(socket_fd)?;
         let new_socket = socket.accept()?;
         let addr = new_socket.peer_addr()?;
-        let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
-        unsafe {
-            (*socket_addr, *socket_len) = into_sockaddr(addr);
-        }
+        let new_fd = new_socket.add_to_fd_table()?;
+        unsafe { write_sockaddr(addr, socket_addr, socket_len) };
         Ok(new_fd)
     })
 }
@@ -456,7 +542,7 @@ pub unsafe extern "C" fn ax_shutdown(
 pub unsafe extern "C" fn ax_getaddrinfo(
     node: *const c_char,
     service: *const c_char,
-    addrs: *mut ctypes::sockaddr,
+    addrs: *mut ctypes::sockaddr_storage,
     len: ctypes::size_t,
 ) -> c_int {
     let name = char_ptr_to_str(node);
@@ -509,9 +595,7 @@ pub unsafe extern "C" fn ax_getsockname(
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?);
-        }
+        unsafe { write_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?, addr, addrlen) };
         Ok(0)
     })
 }
@@ -534,9 +618,7 @@ pub unsafe extern "C" fn ax_getpeername(
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?);
-        }
+        unsafe { write_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?, addr, addrlen) };
         Ok(0)
     })
 }
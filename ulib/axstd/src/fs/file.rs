@@ -1,5 +1,5 @@
 use crate::io::{prelude::*, Result, SeekFrom};
-use core::fmt;
+use core::{fmt, time::Duration};
 
 use arceos_api::fs as api;
 
@@ -10,13 +10,29 @@ pub type FileType = api::AxFileType;
 /// Representation of the various permissions on a file.
 pub type Permissions = api::AxFilePerm;
 
+/// A byte-range advisory lock of a file, see [`File::set_lock`].
+pub type FileLock = api::AxFileLock;
+
+/// The type of an advisory lock, shared or exclusive.
+pub type LockKind = api::AxLockKind;
+
 /// An object providing access to an open file on the filesystem.
 pub struct File {
     inner: api::AxFileHandle,
 }
 
+/// A handle to set the advisory locks of an open file, returned by
+/// [`File::locker`].
+///
+/// It doesn't borrow the file, so the file can be used by others while
+/// waiting for a lock.
+#[derive(Clone)]
+pub struct FileLocker {
+    inner: api::AxFileLocker,
+}
+
 /// Metadata information about a file.
-pub struct Metadata(api::AxFileAttr);
+pub struct Metadata(pub(super) api::AxFileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -90,6 +106,11 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link.
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +131,41 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the inode number of the file.
+    pub const fn ino(&self) -> u64 {
+        self.0.ino()
+    }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.0.nlink()
+    }
+
+    /// Returns the user ID of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group ID of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
+
+    /// Returns the last access time of the file.
+    pub const fn accessed(&self) -> Duration {
+        self.0.atime()
+    }
+
+    /// Returns the last modification time of the file.
+    pub const fn modified(&self) -> Duration {
+        self.0.mtime()
+    }
+
+    /// Returns the last status change time of the file.
+    pub const fn changed(&self) -> Duration {
+        self.0.ctime()
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +175,7 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
             .finish_non_exhaustive()
     }
 }
@@ -162,6 +219,111 @@ impl File {
     pub fn metadata(&self) -> Result<Metadata> {
         api::ax_file_attr(&self.inner).map(Metadata)
     }
+
+    /// Changes the permissions of the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        let changes = api::AxSetAttr {
+            mode: Some(perm),
+            ..Default::default()
+        };
+        api::ax_set_file_attr(&self.inner, &changes)
+    }
+
+    /// Changes the owner and group of the underlying file, `None` leaves the
+    /// ID unchanged.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let changes = api::AxSetAttr {
+            uid,
+            gid,
+            ..Default::default()
+        };
+        api::ax_set_file_attr(&self.inner, &changes)
+    }
+
+    /// Changes the last access and modification times of the underlying
+    /// file, `None` leaves the time unchanged.
+    pub fn set_times(&self, accessed: Option<Duration>, modified: Option<Duration>) -> Result<()> {
+        let changes = api::AxSetAttr {
+            atime: accessed,
+            mtime: modified,
+            ..Default::default()
+        };
+        api::ax_set_file_attr(&self.inner, &changes)
+    }
+
+    /// Returns a [`FileLocker`] to set the advisory locks of the file.
+    pub fn locker(&self) -> FileLocker {
+        FileLocker {
+            inner: api::ax_file_locker(&self.inner),
+        }
+    }
+
+    /// Acquires an exclusive advisory lock on the file, blocking until it
+    /// can be acquired.
+    pub fn lock(&self) -> Result<()> {
+        self.locker().flock(Some(LockKind::Exclusive), true)
+    }
+
+    /// Acquires a shared advisory lock on the file, blocking until it can be
+    /// acquired.
+    pub fn lock_shared(&self) -> Result<()> {
+        self.locker().flock(Some(LockKind::Shared), true)
+    }
+
+    /// Tries to acquire an exclusive advisory lock on the file, returns
+    /// [`WouldBlock`](crate::io::Error::WouldBlock) if it's locked by others.
+    pub fn try_lock(&self) -> Result<()> {
+        self.locker().flock(Some(LockKind::Exclusive), false)
+    }
+
+    /// Tries to acquire a shared advisory lock on the file, returns
+    /// [`WouldBlock`](crate::io::Error::WouldBlock) if it's exclusively
+    /// locked by others.
+    pub fn try_lock_shared(&self) -> Result<()> {
+        self.locker().flock(Some(LockKind::Shared), false)
+    }
+
+    /// Releases the advisory lock on the file.
+    pub fn unlock(&self) -> Result<()> {
+        self.locker().flock(None, false)
+    }
+
+    /// Sets a byte-range advisory lock, see [`FileLocker::set_lock`].
+    pub fn set_lock(&self, kind: Option<LockKind>, start: u64, len: u64, wait: bool) -> Result<()> {
+        self.locker().set_lock(kind, start, len, wait)
+    }
+
+    /// Returns the first byte-range lock set through other files that
+    /// prevents setting `lock`, or `None` if it can be set.
+    pub fn get_lock(&self, lock: &FileLock) -> Result<Option<FileLock>> {
+        self.locker().get_lock(lock)
+    }
+}
+
+impl FileLocker {
+    /// Sets a whole-file advisory lock of `kind`, or releases it if `kind`
+    /// is `None`. If it's locked by others incompatibly, waits for them to
+    /// unlock if `wait` is `true`, otherwise returns
+    /// [`WouldBlock`](crate::io::Error::WouldBlock).
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> Result<()> {
+        api::ax_flock_file(&self.inner, kind, wait)
+    }
+
+    /// Sets a byte-range advisory lock on `len` bytes from `start` (`0` for
+    /// up to the end of the file), or releases the range if `kind` is
+    /// `None`. It waits or fails like [`FileLocker::flock`].
+    ///
+    /// These locks are independent of the whole-file ones set by
+    /// [`FileLocker::flock`].
+    pub fn set_lock(&self, kind: Option<LockKind>, start: u64, len: u64, wait: bool) -> Result<()> {
+        api::ax_set_file_lock(&self.inner, kind, start, len, wait)
+    }
+
+    /// Returns the first byte-range lock set through other files that
+    /// prevents setting `lock`, or `None` if it can be set.
+    pub fn get_lock(&self, lock: &FileLock) -> Result<Option<FileLock>> {
+        api::ax_get_file_lock(&self.inner, lock)
+    }
 }
 
 impl Read for File {
@@ -4,12 +4,18 @@ mod dir;
 mod file;
 
 use crate::io::{self, prelude::*};
+use arceos_api::fs::AxSetAttr;
+use core::time::Duration;
 
 #[cfg(feature = "alloc")]
 use alloc::{string::String, vec::Vec};
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
-pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use self::file::{File, FileLock, FileLocker, FileType, LockKind};
+pub use self::file::{Metadata, OpenOptions, Permissions};
+
+/// Attributes of a mounted filesystem, returned by [`statfs`].
+pub type FileSystemInfo = arceos_api::fs::AxFileSystemInfo;
 
 /// Read the entire contents of a file into a bytes vector.
 #[cfg(feature = "alloc")]
@@ -42,6 +48,93 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Query the metadata about a file without following symlinks.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    arceos_api::fs::ax_symlink_attr(path).map(Metadata)
+}
+
+/// Reads a symbolic link, returning the file that the link points to.
+#[cfg(feature = "alloc")]
+pub fn read_link(path: &str) -> io::Result<String> {
+    arceos_api::fs::ax_read_link(path)
+}
+
+/// Creates a new symbolic link on the filesystem.
+///
+/// The `link` path will be a symbolic link pointing to the `original` path.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_symlink(original, link)
+}
+
+/// Creates a new hard link on the filesystem.
+///
+/// The `link` path will be a link pointing to the `original` path. Note that
+/// both paths must be in the same mounted fs.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_hard_link(original, link)
+}
+
+/// Changes the permissions found on a file or a directory.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    let changes = AxSetAttr {
+        mode: Some(perm),
+        ..Default::default()
+    };
+    arceos_api::fs::ax_set_attr(path, &changes)
+}
+
+/// Changes the owner and group of the specified path, `None` leaves the ID
+/// unchanged.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    let changes = AxSetAttr {
+        uid,
+        gid,
+        ..Default::default()
+    };
+    arceos_api::fs::ax_set_attr(path, &changes)
+}
+
+/// Like [`chown`], but changes the symbolic link itself instead of its
+/// target.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    let changes = AxSetAttr {
+        uid,
+        gid,
+        ..Default::default()
+    };
+    arceos_api::fs::ax_set_symlink_attr(path, &changes)
+}
+
+/// Changes the last access and modification times of the specified path,
+/// `None` leaves the time unchanged.
+pub fn set_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> io::Result<()> {
+    let changes = AxSetAttr {
+        atime: accessed,
+        mtime: modified,
+        ..Default::default()
+    };
+    arceos_api::fs::ax_set_attr(path, &changes)
+}
+
+/// Like [`set_times`], but changes the symbolic link itself instead of its
+/// target.
+pub fn set_symlink_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> io::Result<()> {
+    let changes = AxSetAttr {
+        atime: accessed,
+        mtime: modified,
+        ..Default::default()
+    };
+    arceos_api::fs::ax_set_symlink_attr(path, &changes)
+}
+
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
     ReadDir::new(path)
@@ -75,3 +168,32 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     arceos_api::fs::ax_rename(old, new)
 }
+
+/// Returns the attributes of the filesystem containing the given path, such
+/// as the total and free blocks.
+pub fn statfs(path: &str) -> io::Result<FileSystemInfo> {
+    arceos_api::fs::ax_statfs(path)
+}
+
+/// Returns the paths of all mount points, starting with the root `/`.
+#[cfg(feature = "alloc")]
+pub fn mount_points() -> Vec<String> {
+    arceos_api::fs::ax_mount_points()
+}
+
+/// Mounts a new filesystem of `fs_type` (e.g., `tmpfs` or `vfat`) from
+/// `source` on the existing directory `target`.
+pub fn mount(source: &str, target: &str, fs_type: &str) -> io::Result<()> {
+    arceos_api::fs::ax_mount(source, target, fs_type)
+}
+
+/// Mounts the directory `source` on the directory `target` as well, like
+/// `mount --bind`.
+pub fn bind_mount(source: &str, target: &str) -> io::Result<()> {
+    arceos_api::fs::ax_bind_mount(source, target)
+}
+
+/// Unmounts the filesystem mounted on the directory `target`.
+pub fn umount(target: &str) -> io::Result<()> {
+    arceos_api::fs::ax_umount(target)
+}
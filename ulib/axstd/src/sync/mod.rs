@@ -1,4 +1,7 @@
 //! Useful synchronization primitives.
+//!
+//! Without the `multitask` feature, `Mutex` is a raw spinlock, `Condvar` is
+//! not available, and the others spin instead of blocking the current task.
 
 #[doc(no_inline)]
 pub use core::sync::atomic;
@@ -7,12 +10,15 @@ pub use core::sync::atomic;
 #[doc(no_inline)]
 pub use alloc::sync::{Arc, Weak};
 
-#[cfg(feature = "multitask")]
-mod mutex;
+#[doc(no_inline)]
+pub use arceos_api::sync::{Barrier, BarrierWaitResult, Semaphore, SemaphoreGuard};
+#[doc(no_inline)]
+pub use arceos_api::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
-pub use self::mutex::{Mutex, MutexGuard};
+#[doc(no_inline)]
+pub use arceos_api::sync::{Condvar, Mutex, MutexGuard, WaitTimeoutResult};
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]